@@ -0,0 +1,99 @@
+//! Paths in pods as the command line names them, `POD:PATH`, and how they
+//! are told apart from local ones
+
+use std::fmt;
+
+/// A path in a pod, `POD:PATH` on the command line
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub pod: String,
+    pub path: String,
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.pod, self.path)
+    }
+}
+
+/// Parses `POD:PATH`, or `pod/POD:PATH` for a pod named with a single
+/// letter, which is otherwise taken for the drive of a Windows path like
+/// `C:\data` and rejected, as are UNC paths like `\\server\share`, or
+/// `sts/NAME:ORDINAL:PATH` for a pod of a StatefulSet
+pub fn parse(s: &str) -> Result<Target, String> {
+    if let Some((name, Some(rest))) = statefulset(s) {
+        return match rest.split_once(':') {
+            Some((ordinal, path)) if !path.is_empty() => Ok(Target {
+                pod: ordinal_pod(name, ordinal)?,
+                path: path.to_string(),
+            }),
+            _ => Err("expected sts/NAME:ORDINAL:PATH, e.g. `sts/db:0:/var/lib/db`".to_string()),
+        };
+    }
+    let explicit = s.strip_prefix("pod/");
+    if explicit.is_none() && is_windows_path(s) {
+        return Err(format!(
+            "{} is a local path, expected POD:PATH, e.g. `web-0:/srv/app`, or pod/POD:PATH for a pod named with a single letter",
+            s
+        ));
+    }
+    match explicit.unwrap_or(s).split_once(':') {
+        Some((pod, path)) if is_pod_name(pod) && !path.is_empty() => Ok(Target {
+            pod: pod.to_string(),
+            path: path.to_string(),
+        }),
+        Some((pod, _)) if !pod.is_empty() && !is_pod_name(pod) => Err(format!(
+            "{} isn't a pod name, those are lowercase letters, digits, `-` and `.`",
+            pod
+        )),
+        _ => Err("expected POD:PATH, e.g. `web-0:/srv/app`".to_string()),
+    }
+}
+
+/// Whether `s` is a Windows path, with a drive letter, `C:\data`, `C:data`
+/// or `C:/data`, or UNC or with a long-path prefix, `\\server\share` or
+/// `\\?\C:\data`
+pub fn is_windows_path(s: &str) -> bool {
+    let mut chars = s.chars();
+    let drive = matches!(
+        (chars.next(), chars.next()),
+        (Some(letter), Some(':')) if letter.is_ascii_alphabetic()
+    );
+    drive || s.starts_with("\\\\")
+}
+
+/// The name of the StatefulSet of `sts/NAME` or `statefulset/NAME`, and
+/// what follows a `:` after it
+pub fn statefulset(s: &str) -> Option<(&str, Option<&str>)> {
+    let rest = ["sts/", "statefulset/", "statefulsets/"]
+        .iter()
+        .find_map(|prefix| s.strip_prefix(prefix))?;
+    Some(match rest.split_once(':') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (rest, None),
+    })
+}
+
+/// The pod of `ordinal` of the StatefulSet `name`, the pods of a
+/// StatefulSet are named after it and their ordinal
+pub fn ordinal_pod(name: &str, ordinal: &str) -> Result<String, String> {
+    if !is_pod_name(name) {
+        return Err(format!(
+            "{} isn't the name of a StatefulSet, those are lowercase letters, digits, `-` and `.`",
+            name
+        ));
+    }
+    match ordinal.parse::<u32>() {
+        Ok(ordinal) => Ok(format!("{}-{}", name, ordinal)),
+        Err(_) => Err(format!("{} isn't the ordinal of a pod, like 0", ordinal)),
+    }
+}
+
+/// Whether `name` is a valid name of a pod, a DNS subdomain
+pub fn is_pod_name(name: &str) -> bool {
+    let alnum = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+    name.len() <= 253
+        && name.starts_with(alnum)
+        && name.ends_with(alnum)
+        && name.chars().all(|c| alnum(c) || c == '-' || c == '.')
+}
@@ -0,0 +1,175 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::{bail, Context as _};
+use indicatif::HumanBytes;
+use k8s_openapi::api::core::v1::Pod;
+use kube::Api;
+use tokio::io::AsyncWrite;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    exec::{self, quote},
+    transport::{tar_attrs, IdMap, Owners},
+};
+
+use crate::{
+    cp,
+    target::{self, Target},
+    term, Global,
+};
+
+/// Download a path in a pod as a tar archive, without unpacking it
+#[derive(clap::Args, Debug)]
+pub struct ArchiveArgs {
+    /// File or directory to archive, `POD:PATH`
+    #[arg(value_parser = target::parse)]
+    target: Target,
+
+    /// Archive to write, a .tar, .tar.gz, .tgz, .tar.bz2, .tbz2, .tar.xz or .txz, compressed in the pod as the name says; `-` writes the tar to stdout
+    #[arg(value_name = "ARCHIVE")]
+    archive: PathBuf,
+
+    /// Store the extended attributes of the files, SELinux labels and file capabilities among them, through GNU `tar`
+    #[arg(long)]
+    xattrs: bool,
+
+    /// Store the POSIX ACLs of the files, through GNU `tar`
+    #[arg(long)]
+    acls: bool,
+
+    /// Store the owners and groups of the files by their ids only, rather than their names too
+    #[arg(long)]
+    numeric_ids: bool,
+
+    /// Files of the container's user `NAME:ID` belong to the local user `NAME:ID` in the archive, like `me:1000=app:1001`; repeat to map several
+    #[arg(long, value_name = "LOCAL=REMOTE", value_parser = cp::parse_id_map)]
+    owner_map: Vec<IdMap>,
+
+    /// Like --owner-map, for groups
+    #[arg(long, value_name = "LOCAL=REMOTE", value_parser = cp::parse_id_map)]
+    group_map: Vec<IdMap>,
+}
+
+pub async fn run(global: &Global, args: &ArchiveArgs) -> anyhow::Result<ExitCode> {
+    let stdout = args.archive == Path::new("-");
+    let compression = if stdout {
+        ""
+    } else {
+        compression(&args.archive)?
+    };
+    let api = global.session().await?.pods(global.namespace());
+    let targets = target::resolve(global, &api, std::slice::from_ref(&args.target)).await?;
+    let path = targets[0].path.trim_end_matches('/');
+    let (dir, name) = match path.rsplit_once('/') {
+        _ if path.is_empty() => ("/", "."),
+        Some(("", name)) => ("/", name),
+        Some((dir, name)) => (dir, name),
+        None => (".", path),
+    };
+    let owners = Owners {
+        numeric: args.numeric_ids,
+        users: args.owner_map.clone(),
+        groups: args.group_map.clone(),
+    };
+    // the maps are removed when the shell exits, it can't be replaced
+    let setup = owners.pack_setup();
+    let command = format!(
+        "{}{}tar {}{}-C {} -c{}f - -- {}",
+        setup,
+        if setup.is_empty() { "exec " } else { "" },
+        tar_attrs(args.xattrs, args.acls, false),
+        owners.pack_options(),
+        quote(dir),
+        compression,
+        quote(name)
+    );
+
+    let cancel = cp::interrupt();
+    Ok(target::each(global, &api, &targets, |target| {
+        let (api, command, cancel) = (&api, &command, &cancel);
+        async move {
+            if stdout {
+                let out = &mut tokio::io::stdout();
+                return Ok(tar(global, api, &target.pod, command, out, cancel).await?);
+            }
+
+            // only a complete archive gets the name
+            let part = part(&args.archive);
+            let mut file = tokio::fs::File::create(&part)
+                .await
+                .with_context(|| format!("failed to create {}", part.display()))?;
+            let written = tar(global, api, &target.pod, command, &mut file, cancel).await;
+            drop(file);
+            if let Err(err) = written {
+                let _ = std::fs::remove_file(&part);
+                return Err(err.into());
+            }
+            std::fs::rename(&part, &args.archive)
+                .with_context(|| format!("failed to write {}", args.archive.display()))?;
+            if !global.quiet {
+                let size = std::fs::metadata(&args.archive).map_or(0, |meta| meta.len());
+                term::success(format!(
+                    "archived {} to {}, {}",
+                    target,
+                    args.archive.display(),
+                    HumanBytes(size)
+                ));
+            }
+            Ok(())
+        }
+    })
+    .await)
+}
+
+/// Runs the `tar` `command` in `pod`, writing the archive to `out`
+async fn tar(
+    global: &Global,
+    api: &Api<Pod>,
+    pod: &str,
+    command: &str,
+    out: &mut (dyn AsyncWrite + Send + Unpin),
+    cancel: &CancellationToken,
+) -> k8scp::Result<()> {
+    exec::stream(
+        api,
+        pod,
+        &global.container,
+        command,
+        out,
+        &mut tokio::io::stderr(),
+        global.connect_timeout,
+        cancel,
+    )
+    .await
+}
+
+/// The `tar` flag compressing an archive named like `path`
+fn compression(path: &Path) -> anyhow::Result<&'static str> {
+    const FORMATS: [(&[&str], &str); 4] = [
+        (&[".tar"], ""),
+        (&[".tar.gz", ".tgz"], "z"),
+        (&[".tar.bz2", ".tbz2"], "j"),
+        (&[".tar.xz", ".txz"], "J"),
+    ];
+    let name = path.to_string_lossy();
+    match FORMATS
+        .iter()
+        .find(|(extensions, _)| extensions.iter().any(|ext| name.ends_with(ext)))
+    {
+        Some((_, flag)) => Ok(flag),
+        None => bail!(
+            "{} is not a tar archive, name it .tar, .tar.gz, .tgz, .tar.bz2, .tbz2, .tar.xz or .txz",
+            path.display()
+        ),
+    }
+}
+
+/// Where the archive `path` is written until it is complete
+fn part(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
@@ -0,0 +1,79 @@
+use std::time::SystemTime;
+
+use k8s_openapi::{
+    api::core::v1::{Event, EventSource, ObjectReference, Pod},
+    apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time},
+    chrono::Utc,
+};
+use kube::{
+    api::{Api, Patch, PatchParams, PostParams},
+    Client, ResourceExt,
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+/// The annotation `--annotate` sets on the pods, `<digest>@<time>`
+pub const LAST_COPY: &str = "k8scp.io/last-copy";
+
+/// The digest of a copy to a pod, of the SHA-256 digests of its files and
+/// their destinations, so the same files to the same places give the same
+/// digest
+pub fn digest(files: &[(String, String)]) -> String {
+    let mut files = files.to_vec();
+    files.sort();
+    let mut digest = Sha256::new();
+    for (dst, sha256) in &files {
+        digest.update(format!("{}  {}\n", sha256, dst));
+    }
+    format!("{:x}", digest.finalize())
+}
+
+/// Sets the [`LAST_COPY`] annotation of `pod` to `digest` and the time
+pub async fn annotate(api: &Api<Pod>, pod: &str, digest: &str) -> kube::Result<()> {
+    let value = format!(
+        "{}@{}",
+        digest,
+        humantime::format_rfc3339_seconds(SystemTime::now())
+    );
+    let patch = json!({ "metadata": { "annotations": { LAST_COPY: value } } });
+    api.patch(pod, &PatchParams::default(), &Patch::Merge(patch))
+        .await?;
+    Ok(())
+}
+
+/// Records a `FilesCopied` Event with `message` on `pod`, which `kubectl
+/// describe` and `kubectl get events` show
+pub async fn event(client: Client, pod: &Pod, message: String) -> kube::Result<()> {
+    let namespace = pod.namespace().unwrap_or_default();
+    let now = Time(Utc::now());
+    let event = Event {
+        metadata: ObjectMeta {
+            generate_name: Some(format!("{}.", pod.name_any())),
+            namespace: Some(namespace.clone()),
+            ..ObjectMeta::default()
+        },
+        involved_object: ObjectReference {
+            api_version: Some("v1".to_string()),
+            kind: Some("Pod".to_string()),
+            name: Some(pod.name_any()),
+            namespace: Some(namespace.clone()),
+            uid: pod.uid(),
+            ..ObjectReference::default()
+        },
+        reason: Some("FilesCopied".to_string()),
+        message: Some(message),
+        type_: Some("Normal".to_string()),
+        source: Some(EventSource {
+            component: Some("k8scp".to_string()),
+            host: None,
+        }),
+        first_timestamp: Some(now.clone()),
+        last_timestamp: Some(now),
+        count: Some(1),
+        ..Event::default()
+    };
+    Api::<Event>::namespaced(client, &namespace)
+        .create(&PostParams::default(), &event)
+        .await?;
+    Ok(())
+}
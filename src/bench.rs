@@ -0,0 +1,203 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use clap_complete::ArgValueCompleter;
+use indicatif::HumanBytes;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    copy_from_pod, copy_to_pod, diagnose,
+    exec::{self, quote},
+    files, hash,
+    progress::Progress,
+    transport::{Registry, Transport},
+    ExecOptions,
+};
+
+use crate::{complete, term, Global};
+
+/// Measure how fast every transport copies to and from a pod
+#[derive(clap::Args, Debug)]
+pub struct BenchArgs {
+    /// Pod to measure with
+    #[arg(short, long, add = ArgValueCompleter::new(complete::pods))]
+    pod: String,
+
+    /// Directory in the pod the test file is written to and removed from again
+    #[arg(short, long, default_value = "/tmp")]
+    dst: String,
+
+    /// Size of the test file, e.g. `512KiB` or `1GB`
+    #[arg(long, value_parser = parse_size, default_value = "16MiB")]
+    size: u64,
+}
+
+/// How long the copies through a transport took
+struct Timing {
+    upload: Duration,
+    download: Duration,
+}
+
+/// Measures the transports, just the one of `--transport` if given
+pub async fn run(global: &Global, args: &BenchArgs) -> anyhow::Result<()> {
+    let transports: Vec<Arc<dyn Transport>> = match &global.transport {
+        Some(transport) => vec![transport.clone()],
+        None => {
+            let registry = Registry::default();
+            registry
+                .names()
+                .filter_map(|name| registry.get(name))
+                .collect()
+        }
+    };
+
+    let api = global.session().await?.pods(global.namespace());
+
+    let dir = std::env::temp_dir();
+    let name = format!("k8scp-bench-{}", std::process::id());
+    let upload = dir.join(format!("{}.up", name));
+    let download = dir.join(format!("{}.down", name));
+    fill(&upload, args.size).with_context(|| format!("failed to write {}", upload.display()))?;
+    let remote = files::join(&args.dst, &name);
+
+    println!("{:<12} {:>14} {:>14}", "transport", "upload", "download");
+    for transport in &transports {
+        let timing = measure(global, &api, args, transport, &upload, &download, &remote).await;
+        // whatever a failed copy left behind
+        let _ = exec::run(
+            &api,
+            &args.pod,
+            &global.container,
+            &format!("rm -f -- {}", quote(&remote)),
+            global.connect_timeout,
+            global.idle_timeout,
+            &CancellationToken::new(),
+        )
+        .await;
+        match timing {
+            Ok(timing) => println!(
+                "{:<12} {:>14} {:>14}",
+                transport.name(),
+                rate(args.size, timing.upload),
+                rate(args.size, timing.download)
+            ),
+            Err(err) => term::error(format!("{}: {:#}", transport.name(), err)),
+        }
+    }
+    let _ = std::fs::remove_file(&upload);
+    let _ = std::fs::remove_file(&download);
+    Ok(())
+}
+
+/// Copies `local` to `remote` and back through `transport`
+async fn measure(
+    global: &Global,
+    api: &Api<Pod>,
+    args: &BenchArgs,
+    transport: &Arc<dyn Transport>,
+    local: &Path,
+    back: &Path,
+    remote: &str,
+) -> anyhow::Result<Timing> {
+    let opts = ExecOptions {
+        container: &global.container,
+        connect_timeout: global.connect_timeout,
+        idle_timeout: global.idle_timeout,
+        transport: &**transport,
+        ..ExecOptions::default()
+    };
+    let explain = |err| {
+        diagnose::explain(
+            api,
+            global.namespace(),
+            &args.pod,
+            &global.container,
+            global.connect_timeout,
+            err,
+        )
+    };
+    let progress = Arc::new(Progress::new());
+
+    let started = Instant::now();
+    let item = progress.item(&local.to_string_lossy(), &args.pod, remote, args.size);
+    let sent = match copy_to_pod(api, &args.pod, local, remote, &opts, item).await {
+        Ok(sent) => sent,
+        Err(err) => return Err(explain(err).await).context("upload failed"),
+    };
+    let upload = started.elapsed();
+
+    let started = Instant::now();
+    let item = progress.item(remote, &args.pod, &back.to_string_lossy(), args.size);
+    if let Err(err) = copy_from_pod(api, &args.pod, remote, back, &opts, item).await {
+        return Err(explain(err).await).context("download failed");
+    }
+    let download = started.elapsed();
+
+    let received = hash::file(&hash::Sha256, back).await?;
+    anyhow::ensure!(
+        received == sent,
+        "the file came back different, SHA-256 {} sent, {} received",
+        sent,
+        received
+    );
+    Ok(Timing { upload, download })
+}
+
+/// Writes `size` bytes of noise to `path`
+fn fill(path: &Path, size: u64) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    // xorshift, random enough that nothing on the way can compress it
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut left = size;
+    while left > 0 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let n = left.min(8) as usize;
+        file.write_all(&state.to_le_bytes()[..n])?;
+        left -= n as u64;
+    }
+    file.flush()
+}
+
+fn rate(bytes: u64, took: Duration) -> String {
+    format!(
+        "{}/s",
+        HumanBytes((bytes as f64 / took.as_secs_f64()) as u64)
+    )
+}
+
+/// Parses sizes like `100`, `512KiB`, `16MiB` or `1GB`
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| "expected a size like `16MiB`".to_string())?;
+    let unit = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KB" => 1000,
+        "KiB" => 1 << 10,
+        "M" | "MB" => 1000 * 1000,
+        "MiB" => 1 << 20,
+        "G" | "GB" => 1000 * 1000 * 1000,
+        "GiB" => 1 << 30,
+        unit => {
+            return Err(format!(
+                "unknown unit {}, one of B, KB, KiB, MB, MiB, GB, GiB",
+                unit
+            ))
+        }
+    };
+    number
+        .checked_mul(unit)
+        .ok_or_else(|| "size too large".to_string())
+}
@@ -0,0 +1,109 @@
+//! Synchronous copies for callers without an async runtime, like build
+//! scripts. A [`Client`] runs its own runtime and blocks on every copy.
+//!
+//! ```no_run
+//! use k8scp::{blocking::Client, CopyRequest, Location};
+//!
+//! # fn run() -> anyhow::Result<()> {
+//! let client = Client::try_default()?;
+//! let summary = client.copy(
+//!     CopyRequest::builder()
+//!         .source(Location::local("dist"))
+//!         .destination(Location::pod("web-0", "/srv")),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{path::Path, time::Duration};
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio::runtime::Runtime;
+
+use crate::{
+    client, progress::ItemProgress, CopyRequestBuilder, Error, ExecOptions, Result, Summary,
+};
+
+/// A cluster client with the runtime it runs on, must not be used from
+/// within an async runtime
+pub struct Client {
+    runtime: Runtime,
+    client: kube::Client,
+}
+
+impl Client {
+    /// Client for the current context of the kubeconfig at `path`, the API
+    /// server must answer within `connect_timeout`
+    pub fn new(kubeconfig: &str, connect_timeout: Duration) -> Result<Client> {
+        let runtime = runtime()?;
+        let client = runtime.block_on(async {
+            let config = client::config(client::load(kubeconfig)?, None, connect_timeout).await?;
+            Ok::<_, Error>(kube::Client::try_from(config)?)
+        })?;
+        Ok(Client { runtime, client })
+    }
+
+    /// Client configured from the environment like `kubectl` is, see
+    /// [`kube::Client::try_default`]
+    pub fn try_default() -> Result<Client> {
+        let runtime = runtime()?;
+        let client = runtime.block_on(kube::Client::try_default())?;
+        Ok(Client { runtime, client })
+    }
+
+    /// The underlying async client, only usable while this one is alive
+    pub fn kube(&self) -> kube::Client {
+        self.client.clone()
+    }
+
+    /// Runs `request` with this client and waits for it to finish, see
+    /// [`CopyRequest`](crate::CopyRequest)
+    pub fn copy(&self, request: CopyRequestBuilder) -> Result<Summary> {
+        let request = request.client(self.kube()).build()?;
+        self.runtime
+            .block_on(async { request.start().wait().await })
+    }
+
+    /// Blocking [`copy_to_pod`](crate::copy_to_pod) to `pod` in `namespace`
+    pub fn copy_to_pod(
+        &self,
+        namespace: &str,
+        pod: &str,
+        src: &Path,
+        dst: &str,
+        opts: &ExecOptions<'_>,
+        progress: ItemProgress,
+    ) -> Result<String> {
+        let pods = self.pods(namespace);
+        self.runtime
+            .block_on(crate::copy_to_pod(&pods, pod, src, dst, opts, progress))
+    }
+
+    /// Blocking [`copy_from_pod`](crate::copy_from_pod) from `pod` in
+    /// `namespace`
+    pub fn copy_from_pod(
+        &self,
+        namespace: &str,
+        pod: &str,
+        src: &str,
+        dst: &Path,
+        opts: &ExecOptions<'_>,
+        progress: ItemProgress,
+    ) -> Result<String> {
+        let pods = self.pods(namespace);
+        self.runtime
+            .block_on(crate::copy_from_pod(&pods, pod, src, dst, opts, progress))
+    }
+
+    fn pods(&self, namespace: &str) -> Api<Pod> {
+        Api::namespaced(self.kube(), namespace)
+    }
+}
+
+fn runtime() -> Result<Runtime> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| Error::Config(format!("failed to start a runtime: {}", err)))
+}
@@ -0,0 +1,101 @@
+use std::{io, process::ExitCode, process::Stdio};
+
+use anyhow::{bail, Context as _};
+use k8s_openapi::api::core::v1::Pod;
+use kube::Api;
+use tokio::io::AsyncWrite;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::exec;
+
+use crate::{
+    target::{self, Target},
+    Global,
+};
+
+/// Print files in pods
+#[derive(clap::Args, Debug)]
+pub struct CatArgs {
+    /// File to print, `POD:PATH`, files are printed in the order given
+    #[arg(required = true, value_parser = target::parse)]
+    target: Vec<Target>,
+
+    /// Encrypt the file with age for this recipient before it is printed, repeat for several recipients. Needs `age` locally.
+    #[arg(long, value_name = "RECIPIENT")]
+    encrypt_to: Vec<String>,
+}
+
+pub async fn run(global: &Global, args: &CatArgs) -> anyhow::Result<ExitCode> {
+    if !args.encrypt_to.is_empty() && args.target.len() > 1 {
+        bail!(
+            "--encrypt-to encrypts a single file, {} given",
+            args.target.len()
+        );
+    }
+    let api = global.session().await?.pods(global.namespace());
+    let targets = target::resolve(global, &api, &args.target).await?;
+    let cancel = CancellationToken::new();
+    Ok(target::each(global, &api, &targets, |target| {
+        let command = format!("exec cat -- {}", exec::quote(&target.path));
+        let (api, cancel) = (&api, &cancel);
+        async move {
+            if args.encrypt_to.is_empty() {
+                return show(
+                    global,
+                    api,
+                    target,
+                    command,
+                    &mut tokio::io::stdout(),
+                    cancel,
+                )
+                .await;
+            }
+            // what is printed is what `age` makes of the file
+            let mut age = tokio::process::Command::new("age")
+                .arg("--encrypt")
+                .args(args.encrypt_to.iter().flat_map(|r| ["-r", r.as_str()]))
+                .stdin(Stdio::piped())
+                .spawn()
+                .context("failed to run age, is it installed?")?;
+            let mut stdin = age.stdin.take().unwrap();
+            let shown = show(global, api, target, command, &mut stdin, cancel).await;
+            drop(stdin);
+            let status = age.wait().await.context("failed to run age")?;
+            shown?;
+            if !status.success() {
+                bail!("age failed with {}", status);
+            }
+            Ok(())
+        }
+    })
+    .await)
+}
+
+/// Runs `command` in the pod of `target`, writing what it prints to `out`
+/// as it does. A closed `out`, e.g. the stdout of `| head`, or `cancel`
+/// ends it without an error.
+pub async fn show(
+    global: &Global,
+    api: &Api<Pod>,
+    target: &Target,
+    command: String,
+    out: &mut (dyn AsyncWrite + Send + Unpin),
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    match exec::stream(
+        api,
+        &target.pod,
+        &global.container,
+        &command,
+        out,
+        &mut tokio::io::stderr(),
+        global.connect_timeout,
+        cancel,
+    )
+    .await
+    {
+        Ok(()) | Err(k8scp::Error::Cancelled) => Ok(()),
+        Err(k8scp::Error::Io { error, .. }) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
@@ -0,0 +1,129 @@
+use std::process::ExitCode;
+
+use anyhow::bail;
+use clap::ValueEnum;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    files,
+    hash::{Hasher, Sha256, Sha512},
+    remote,
+};
+
+use crate::{
+    target::{self, Target},
+    term, Global,
+};
+
+/// Print the digests of files in pods, of every file below a directory
+#[derive(clap::Args, Debug)]
+pub struct HashArgs {
+    /// File or directory to hash, `POD:PATH`
+    #[arg(required = true, value_parser = target::parse)]
+    target: Vec<Target>,
+
+    #[arg(long, value_enum, default_value_t = Algo::Sha256)]
+    algo: Algo,
+
+    #[arg(long, value_enum, default_value_t = HashFormat::Sums)]
+    format: HashFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Algo {
+    /// Computed with `sha256sum` in the container
+    Sha256,
+    /// Computed with `sha512sum` in the container
+    Sha512,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum HashFormat {
+    /// A line per file like `sha256sum` prints, the digest and the path in the pod
+    Sums,
+    /// A JSON object per file and line
+    Json,
+}
+
+pub async fn run(global: &Global, args: &HashArgs) -> anyhow::Result<ExitCode> {
+    let hasher: &dyn Hasher = match args.algo {
+        Algo::Sha256 => &Sha256,
+        Algo::Sha512 => &Sha512,
+    };
+    let api = global.session().await?.pods(global.namespace());
+    let targets = target::resolve(global, &api, &args.target).await?;
+    let cancel = CancellationToken::new();
+    Ok(target::each(global, &api, &targets, |target| {
+        let (api, cancel) = (&api, &cancel);
+        async move {
+            let entry = remote::stat(
+                api,
+                &target.pod,
+                &global.container,
+                &target.path,
+                global.connect_timeout,
+                cancel,
+            )
+            .await?;
+            // the files of a directory by their paths in the pod, sorted
+            let paths = match entry {
+                None => bail!("No such file or directory"),
+                Some(entry) if entry.kind == remote::Kind::Dir => remote::files(
+                    api,
+                    &target.pod,
+                    &global.container,
+                    &target.path,
+                    global.connect_timeout,
+                    cancel,
+                )
+                .await?
+                .iter()
+                .map(|file| files::join(&target.path, file))
+                .collect(),
+                Some(_) => vec![target.path.clone()],
+            };
+            let digests = remote::digests(
+                api,
+                &target.pod,
+                &global.container,
+                &paths,
+                hasher,
+                global.connect_timeout,
+                cancel,
+            )
+            .await?;
+
+            let mut failed = 0;
+            for (path, digest) in paths.iter().zip(digests) {
+                let Some(digest) = digest else {
+                    failed += 1;
+                    term::warn(format!("{}:{} couldn't be hashed", target.pod, path));
+                    continue;
+                };
+                match args.format {
+                    HashFormat::Sums => println!("{}  {}", digest, path),
+                    HashFormat::Json => println!(
+                        "{}",
+                        json!({
+                            "pod": target.pod,
+                            "path": path,
+                            "algorithm": hasher.name(),
+                            "digest": digest,
+                        })
+                    ),
+                }
+            }
+            if failed > 0 {
+                bail!(
+                    "{} of {} files couldn't be hashed with {}",
+                    failed,
+                    paths.len(),
+                    hasher.name()
+                );
+            }
+            Ok(())
+        }
+    })
+    .await)
+}
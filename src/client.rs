@@ -0,0 +1,78 @@
+use std::{fs, time::Duration};
+
+use kube::{
+    config::{KubeConfigOptions, Kubeconfig},
+    Config,
+};
+
+use crate::{redact, Error, Result};
+
+/// Reads and parses the kubeconfig at `path`
+pub fn load(path: &str) -> Result<Kubeconfig> {
+    let yaml = fs::read_to_string(path)
+        .map_err(|err| Error::Config(format!("failed to read kubeconfig {}: {}", path, err)))?;
+    Kubeconfig::from_yaml(&yaml).map_err(|err| {
+        Error::Config(format!(
+            "failed to parse kubeconfig {}: {}",
+            path,
+            redact::redact(&err.to_string())
+        ))
+    })
+}
+
+/// Reads the kubeconfig kubectl would use, the files of `$KUBECONFIG`
+/// merged or else `~/.kube/config`
+pub fn load_default() -> Result<Kubeconfig> {
+    Kubeconfig::read().map_err(|err| {
+        Error::Config(format!(
+            "failed to read the kubeconfig of $KUBECONFIG or ~/.kube/config: {}",
+            err
+        ))
+    })
+}
+
+/// Client configuration for `context` of `kubeconfig`, its current context
+/// if not given. The credentials of its users are registered as
+/// [`redact::secret`]s.
+pub async fn config(
+    kubeconfig: Kubeconfig,
+    context: Option<&str>,
+    connect_timeout: Duration,
+) -> Result<Config> {
+    secrets(&kubeconfig);
+    let mut config = Config::from_custom_kubeconfig(
+        kubeconfig,
+        &KubeConfigOptions {
+            context: context.map(str::to_string),
+            cluster: None,
+            user: None,
+        },
+    )
+    .await?;
+    config.connect_timeout = Some(connect_timeout);
+    Ok(config)
+}
+
+/// Registers the tokens, passwords, client keys and auth provider tokens of
+/// the users of `kubeconfig`
+fn secrets(kubeconfig: &Kubeconfig) {
+    for user in &kubeconfig.auth_infos {
+        let Ok(user) = serde_json::to_value(&user.auth_info) else {
+            continue;
+        };
+        let provider = &user["auth-provider"]["config"];
+        for value in ["token", "password", "client-key-data"]
+            .iter()
+            .map(|key| &user[key])
+            .chain(
+                ["access-token", "id-token", "refresh-token", "client-secret"]
+                    .iter()
+                    .map(|key| &provider[key]),
+            )
+        {
+            if let Some(secret) = value.as_str() {
+                redact::secret(secret);
+            }
+        }
+    }
+}
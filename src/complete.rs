@@ -0,0 +1,203 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use clap_complete::{env::Shells, CompletionCandidate};
+use k8s_openapi::api::core::v1::{Namespace, Pod};
+use kube::{
+    api::{Api, ListParams},
+    Client,
+};
+use tokio::time;
+
+use k8scp::client;
+
+/// Variable the shell sets when asking for completions
+pub const VAR: &str = "COMPLETE";
+
+/// Completion gives up on the cluster after this long, a shell waiting on
+/// a <TAB> shouldn't hang
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Print the shell setup for completions
+#[derive(clap::Args, Debug)]
+pub struct CompletionsArgs {
+    #[arg(value_parser = ["bash", "zsh", "fish", "elvish", "powershell"])]
+    shell: String,
+}
+
+/// Prints the script registering the binary as its own completer, the same
+/// script `COMPLETE=<shell> k8scp-rust` prints
+pub fn print(args: &CompletionsArgs, name: &str) -> anyhow::Result<()> {
+    let shells = Shells::builtins();
+    let shell = shells
+        .completer(&args.shell)
+        .with_context(|| format!("unsupported shell {}", args.shell))?;
+    let mut completer = PathBuf::from(std::env::args_os().next().unwrap_or(name.into()));
+    if completer.components().count() > 1 {
+        completer = std::env::current_dir()?.join(completer);
+    }
+    shell.write_registration(
+        VAR,
+        name,
+        name,
+        &completer.to_string_lossy(),
+        &mut io::stdout(),
+    )?;
+    Ok(())
+}
+
+pub fn namespaces(current: &OsStr) -> Vec<CompletionCandidate> {
+    query(current, |client, _| async move {
+        let api: Api<Namespace> = Api::all(client);
+        let list = api.list(&ListParams::default()).await?;
+        Ok(list
+            .items
+            .into_iter()
+            .filter_map(|ns| ns.metadata.name)
+            .map(|name| (name, None))
+            .collect())
+    })
+}
+
+pub fn pods(current: &OsStr) -> Vec<CompletionCandidate> {
+    // `--pod a,b` completes the part after the last comma
+    let current = current.to_string_lossy();
+    let (done, current) = match current.rsplit_once(',') {
+        Some((done, current)) => (Some(format!("{},", done)), current.to_string()),
+        None => (None, current.to_string()),
+    };
+    let candidates = query(OsStr::new(&current), |client, line| async move {
+        let api: Api<Pod> = Api::namespaced(client, &line.namespace);
+        let list = api.list(&ListParams::default()).await?;
+        Ok(list
+            .items
+            .into_iter()
+            .filter_map(|pod| {
+                let phase = pod.status.and_then(|status| status.phase);
+                Some((pod.metadata.name?, phase))
+            })
+            .collect())
+    });
+    match done {
+        Some(done) => candidates
+            .into_iter()
+            .map(|candidate| candidate.add_prefix(&done))
+            .collect(),
+        None => candidates,
+    }
+}
+
+pub fn containers(current: &OsStr) -> Vec<CompletionCandidate> {
+    query(current, |client, line| async move {
+        let Some(pod) = line.pod else {
+            return Ok(Vec::new());
+        };
+        let api: Api<Pod> = Api::namespaced(client, &line.namespace);
+        let pod = api.get(&pod).await?;
+        Ok(pod
+            .spec
+            .into_iter()
+            .flat_map(|spec| spec.containers)
+            .map(|c| (c.name, c.image))
+            .collect())
+    })
+}
+
+/// What the command line being completed says about the target
+struct Line {
+    kubeconfig: Option<String>,
+    namespace: String,
+    /// The first of the given pods
+    pod: Option<String>,
+}
+
+impl Line {
+    /// Reads the options from the words the shell passes after `--`
+    fn parse(args: impl IntoIterator<Item = OsString>) -> Line {
+        let mut line = Line {
+            kubeconfig: None,
+            namespace: "default".to_string(),
+            pod: None,
+        };
+        let words: Vec<_> = args
+            .into_iter()
+            .skip_while(|arg| arg != "--")
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let mut words = words.iter();
+        while let Some(word) = words.next() {
+            let (flag, value) = match word.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (word.as_str(), None),
+            };
+            if !matches!(
+                flag,
+                "-k" | "--kubeconfig" | "-n" | "--namespace" | "-p" | "--pod"
+            ) {
+                continue;
+            }
+            let Some(value) = value.or_else(|| words.next().cloned()) else {
+                break;
+            };
+            match flag {
+                "-k" | "--kubeconfig" => line.kubeconfig = Some(value),
+                "-n" | "--namespace" => line.namespace = value,
+                _ if line.pod.is_none() => line.pod = value.split(',').next().map(str::to_string),
+                _ => {}
+            }
+        }
+        line
+    }
+
+    /// The given kubeconfig, or the one kubectl would use
+    fn kubeconfig(&self) -> Option<String> {
+        self.kubeconfig
+            .clone()
+            .or_else(|| {
+                // `;` separates them on Windows
+                std::env::var_os("KUBECONFIG")
+                    .and_then(|paths| std::env::split_paths(&paths).next())
+                    .map(|path| path.to_string_lossy().into_owned())
+            })
+            .or_else(|| {
+                dirs::home_dir()
+                    .map(|home| home.join(".kube/config").to_string_lossy().into_owned())
+            })
+    }
+}
+
+/// Completes `current` with the names `list` finds in the cluster, any
+/// failure just means no candidates
+fn query<F, Fut>(current: &OsStr, list: F) -> Vec<CompletionCandidate>
+where
+    F: FnOnce(Client, Line) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<Vec<(String, Option<String>)>>>,
+{
+    let current = current.to_string_lossy();
+    let line = Line::parse(std::env::args_os());
+    let names = async {
+        let kubeconfig = client::load(&line.kubeconfig().context("no kubeconfig")?)?;
+        let config = client::config(kubeconfig, None, TIMEOUT).await?;
+        let client = Client::try_from(config)?;
+        time::timeout(TIMEOUT, list(client, line)).await?
+    };
+    // completion runs before `main` starts its runtime
+    let names = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(anyhow::Error::from)
+        .and_then(|runtime| runtime.block_on(names));
+    let mut names = names.unwrap_or_default();
+    names.sort();
+    names
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(current.as_ref()))
+        .map(|(name, help)| CompletionCandidate::new(name).help(help.map(Into::into)))
+        .collect()
+}
@@ -0,0 +1,68 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+
+/// The config file, named profiles of defaults for the command line
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// Defaults for the flags of the same names, flags given on the command
+/// line win
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    pub kubeconfig: Option<String>,
+    pub context: Option<String>,
+    pub namespace: Option<String>,
+    pub container: Option<String>,
+    pub transport: Option<String>,
+    pub connect_timeout: Option<String>,
+    pub idle_timeout: Option<String>,
+    pub progress: Option<String>,
+    pub umask: Option<String>,
+    pub yes: Option<bool>,
+    pub allow_system_paths: Option<bool>,
+    pub system_paths: Option<Vec<String>>,
+}
+
+/// `k8scp/config.toml` in the user config directory, `~/.config` on Linux
+pub fn default_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("k8scp").join("config.toml"))
+}
+
+/// The profile `name` of the config file at `path`, [`default_path`] if not
+/// given
+pub fn profile(path: Option<&Path>, name: &str) -> anyhow::Result<Profile> {
+    let path = path
+        .map(Path::to_path_buf)
+        .or_else(default_path)
+        .ok_or_else(|| anyhow!("no user config directory to find profile {} in", name))?;
+    let config = load(&path)?;
+    config.profiles.get(name).cloned().ok_or_else(|| {
+        let names: Vec<_> = config.profiles.keys().map(String::as_str).collect();
+        match names.as_slice() {
+            [] => anyhow!("no profile {} in {}, it has none", name, path.display()),
+            names => anyhow!(
+                "no profile {} in {}, one of {}",
+                name,
+                path.display(),
+                names.join(", ")
+            ),
+        }
+    })
+}
+
+fn load(path: &Path) -> anyhow::Result<Config> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("failed to parse config {}", path.display()))
+}
@@ -0,0 +1,187 @@
+use std::{collections::HashMap, process::ExitCode};
+
+use anyhow::{bail, Context as _};
+use futures::TryStreamExt;
+use k8s_openapi::{
+    api::{
+        apps::v1::{Deployment, StatefulSet},
+        core::v1::Pod,
+    },
+    apimachinery::pkg::apis::meta::v1::LabelSelector,
+};
+use kube::{
+    api::{Api, ListParams},
+    runtime::watcher::{self, Event},
+    ResourceExt,
+};
+use tracing::*;
+
+use crate::{
+    cp::{self, CpArgs, Selection},
+    print_error, Global,
+};
+
+/// Keep files in the pods of a workload, copying them into every pod as it
+/// becomes Ready
+#[derive(clap::Args, Debug)]
+pub struct ControllerArgs {
+    /// Workload whose pods get the files, `deployment/NAME` or `statefulset/NAME`
+    #[arg(short, long)]
+    workload: String,
+
+    /// Local file or directory, directories are copied recursively
+    #[arg(short, long)]
+    src: String,
+
+    #[arg(short, long)]
+    dst: String,
+
+    /// Don't record the transfers in the history
+    #[arg(long, conflicts_with = "history_file")]
+    no_history: bool,
+}
+
+pub async fn run(global: &Global, args: &ControllerArgs) -> anyhow::Result<()> {
+    let session = global.session().await?;
+    let selector = selector(global, session.client.clone(), &args.workload).await?;
+    let pods = session.pods(global.namespace());
+    let cancel = cp::interrupt();
+    info!("keeping {} in the pods of {}", args.src, args.workload);
+
+    // the pods that have the files, by uid, and how often their containers
+    // had restarted then, a restarted container lost what wasn't on a volume
+    let mut copied: HashMap<String, i32> = HashMap::new();
+    let mut events = Box::pin(watcher::watcher(
+        pods,
+        ListParams::default().labels(&selector),
+    ));
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            event = events.try_next() => event,
+        };
+        let ready = match event {
+            Ok(Some(Event::Applied(pod))) => vec![pod],
+            Ok(Some(Event::Deleted(pod))) => {
+                copied.remove(&uid(&pod));
+                continue;
+            }
+            Ok(Some(Event::Restarted(pods))) => {
+                copied.retain(|uid, _| pods.iter().any(|pod| self::uid(pod) == *uid));
+                pods
+            }
+            Ok(None) => bail!("stopped watching the pods of {}", args.workload),
+            // the watcher retries on the next poll
+            Err(err) => {
+                warn!("watching the pods of {}: {}", args.workload, err);
+                continue;
+            }
+        };
+        let due: Vec<_> = ready
+            .iter()
+            .filter(|pod| is_ready(pod))
+            .filter(|pod| copied.get(&uid(pod)) != Some(&restarts(pod)))
+            .collect();
+        if due.is_empty() {
+            continue;
+        }
+        let copy = CpArgs {
+            pod: due.iter().map(|pod| pod.name_any()).collect(),
+            src: Some(args.src.clone()),
+            dst: Some(args.dst.clone()),
+            no_history: args.no_history,
+            ..Default::default()
+        };
+        info!("{} ready", copy.pod.join(", "));
+        // files that are still there, e.g. on a volume, aren't copied again
+        match cp::transfer(global, &copy, &Selection::Changed, &cancel).await {
+            // a pod the copy failed on is tried again with the others
+            Ok(code) if code == ExitCode::SUCCESS => {
+                for pod in due {
+                    copied.insert(uid(pod), restarts(pod));
+                }
+            }
+            Ok(_) => {}
+            // tried again on the next change of the pods
+            Err(err) => print_error(&err),
+        }
+    }
+}
+
+/// The label selector of the pods of `workload`
+async fn selector(global: &Global, client: kube::Client, workload: &str) -> anyhow::Result<String> {
+    let Some((kind, name)) = workload.split_once('/') else {
+        bail!(
+            "invalid workload {}, expected `deployment/NAME` or `statefulset/NAME`",
+            workload
+        );
+    };
+    let selector = match kind.to_ascii_lowercase().as_str() {
+        "deployment" | "deployments" | "deploy" => {
+            let api: Api<Deployment> = Api::namespaced(client, global.namespace());
+            api.get(name)
+                .await
+                .map(|d| d.spec.map(|spec| spec.selector))
+        }
+        "statefulset" | "statefulsets" | "sts" => {
+            let api: Api<StatefulSet> = Api::namespaced(client, global.namespace());
+            api.get(name)
+                .await
+                .map(|s| s.spec.map(|spec| spec.selector))
+        }
+        kind => bail!(
+            "unsupported workload kind {}, one of deployment, statefulset",
+            kind
+        ),
+    }
+    .with_context(|| format!("failed to get {}", workload))?
+    .unwrap_or_default();
+    let selector = labels(&selector);
+    if selector.is_empty() {
+        bail!("{} has no pod selector", workload);
+    }
+    Ok(selector)
+}
+
+/// `selector` in the syntax of `ListParams::labels`
+fn labels(selector: &LabelSelector) -> String {
+    let mut terms: Vec<String> = selector
+        .match_labels
+        .iter()
+        .flatten()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    for expr in selector.match_expressions.iter().flatten() {
+        let values = expr.values.as_deref().unwrap_or_default().join(",");
+        terms.push(match expr.operator.as_str() {
+            "In" => format!("{} in ({})", expr.key, values),
+            "NotIn" => format!("{} notin ({})", expr.key, values),
+            "DoesNotExist" => format!("!{}", expr.key),
+            _ => expr.key.clone(),
+        });
+    }
+    terms.join(",")
+}
+
+fn uid(pod: &Pod) -> String {
+    pod.uid().unwrap_or_default()
+}
+
+/// Whether `pod` is Ready and not on its way out
+pub fn is_ready(pod: &Pod) -> bool {
+    pod.metadata.deletion_timestamp.is_none()
+        && pod
+            .status
+            .iter()
+            .flat_map(|status| status.conditions.iter().flatten())
+            .any(|c| c.type_ == "Ready" && c.status == "True")
+}
+
+/// How often the containers of `pod` restarted altogether
+fn restarts(pod: &Pod) -> i32 {
+    pod.status
+        .iter()
+        .flat_map(|status| status.container_statuses.iter().flatten())
+        .map(|c| c.restart_count)
+        .sum()
+}
@@ -0,0 +1,1017 @@
+use std::{
+    io,
+    ops::DerefMut,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::lock::Mutex;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, AttachParams, AttachedProcess};
+use tokio::{
+    io::{
+        AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt,
+        BufReader, ReadBuf,
+    },
+    time,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::{
+    endpoint::{Entry, Source},
+    exec::{self, quote, stalled},
+    files, guard,
+    hash::{self, Digest, Hasher},
+    progress::ItemProgress,
+    text::{self, Converted, TextMode},
+    transport::{self, Channel, Codec, Exec, Traced, Transport},
+    Error, Result,
+};
+
+/// Times a copy is checked before it is considered different, the first
+/// wait in between, doubling after every check
+const CHECKS: u32 = 5;
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// How fast the container is assumed to join the parts of a split file at
+/// least, which bounds how long that may take
+const JOIN_RATE: u64 = 20 << 20;
+
+/// How a transfer talks to the container
+#[derive(Debug, Clone)]
+pub struct ExecOptions<'a> {
+    /// Default container of the pod if empty
+    pub container: &'a str,
+    /// Timeout for establishing the exec stream
+    pub connect_timeout: Duration,
+    /// A transfer fails when the exec stream makes no progress for this long
+    pub idle_timeout: Duration,
+    /// Aborts the transfer with [`Error::Cancelled`] when cancelled
+    pub cancel: CancellationToken,
+    /// How the file is copied, `exec-cat` by default
+    pub transport: &'a dyn Transport,
+    /// What the copied file is hashed with, SHA-256 by default
+    pub hasher: &'a dyn Hasher,
+    /// The umask the container makes the directories and files of an upload
+    /// with, that of its shell if `None`
+    pub umask: Option<u32>,
+    /// How the line endings of the file are converted on the way, it is
+    /// copied as it is if `None`. The digest is that of what was sent or
+    /// received, a file of another size is sent than the local one.
+    pub text_mode: Option<TextMode>,
+    /// The directories uploads refuse to write right into, see [`guard`],
+    /// its [`TOP_DIRS`](guard::TOP_DIRS) and
+    /// [`SYSTEM_DIRS`](guard::SYSTEM_DIRS) if `None`
+    pub system_paths: Option<&'a [String]>,
+    /// Lets uploads write into [`ExecOptions::system_paths`] anyway
+    pub allow_system_paths: bool,
+    /// How many times [`copy_to_pod`] sends a file again when sending it
+    /// fails, a named pipe or a device is sent once
+    pub retries: u32,
+    /// How long to wait before the first retry, doubled for every one after
+    pub retry_backoff: Duration,
+}
+
+impl Default for ExecOptions<'_> {
+    fn default() -> Self {
+        ExecOptions {
+            container: "",
+            connect_timeout: Duration::from_secs(30),
+            idle_timeout: Duration::from_secs(60),
+            cancel: CancellationToken::new(),
+            transport: &Exec(transport::Cat),
+            hasher: &hash::Sha256,
+            umask: None,
+            text_mode: None,
+            system_paths: None,
+            allow_system_paths: false,
+            retries: 0,
+            retry_backoff: Duration::ZERO,
+        }
+    }
+}
+
+/// Copies the local file `src` to the path `dst` in `pod`, creating the
+/// directories leading to it, and returns the digest of what was sent as
+/// hex, see [`ExecOptions::hasher`].
+///
+/// A relative `dst` is resolved against the working directory of the
+/// container. What the container writes to stderr is reported through
+/// `progress` as it arrives. A cancelled copy removes what it wrote of
+/// `dst` before failing with [`Error::Cancelled`]. The copy goes through
+/// [`ExecOptions::transport`]; a `src` that is a named pipe or a device is
+/// sent as it is read, until it ends, by a transport that
+/// [streams](Transport::streams), and fails with [`Error::Config`] otherwise.
+/// So does a `dst` right in one of [`ExecOptions::system_paths`]. A failed
+/// copy is sent again up to [`ExecOptions::retries`] times.
+pub async fn copy_to_pod(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &Path,
+    dst: &str,
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+) -> Result<String> {
+    guard::check(dst, opts)?;
+    // what was read of a stream is gone
+    let retries = match tokio::fs::metadata(src).await {
+        Ok(meta) if files::is_stream(&meta) => 0,
+        _ => opts.retries,
+    };
+    attempts(dst, retries, opts, &progress, || {
+        send_file(pods, pod, src, dst, opts, progress.clone())
+    })
+    .await
+}
+
+/// Copies `src` to `dst` in `pod` once, see [`copy_to_pod`]
+async fn send_file(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &Path,
+    dst: &str,
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+) -> Result<String> {
+    let res = async {
+        let file = tokio::fs::File::open(src)
+            .await
+            .map_err(|err| Error::io(src, err))?;
+        let meta = file.metadata().await.map_err(|err| Error::io(src, err))?;
+        let size = if !files::is_stream(&meta) {
+            match opts.text_mode {
+                Some(mode) => text::converted_len(src, mode)
+                    .await
+                    .map_err(|err| Error::io(src, err))?,
+                None => meta.len(),
+            }
+        } else if opts.transport.streams() {
+            0
+        } else {
+            return Err(Error::Config(format!(
+                "the size of {} isn't known, which the {} transport needs",
+                src.display(),
+                opts.transport.name()
+            )));
+        };
+        let file: Box<dyn AsyncRead + Send + Unpin> = match opts.text_mode {
+            Some(mode) => Box::new(Converted::new(file, mode)),
+            None => Box::new(file),
+        };
+        let mut reader = Metered::new(file, opts.hasher.digest(), progress.clone()).path(src);
+        opts.transport
+            .upload(pods, pod, &mut reader, size, dst, opts, progress)
+            .await?;
+        Ok(reader.digest())
+    }
+    .await;
+    if let Err(Error::Cancelled) = &res {
+        remove(pods, pod, dst, opts).await;
+    }
+    res
+}
+
+/// Copies `entry` of `source` to the path `dst` in `pod` like
+/// [`copy_to_pod`] copies a local file, streaming it from the source as it
+/// is sent, and returns the digest of what was sent as hex.
+///
+/// The size of `entry` must be known, transports are told it up front.
+pub async fn copy_source_to_pod(
+    pods: &Api<Pod>,
+    pod: &str,
+    source: &dyn Source,
+    entry: &Entry,
+    dst: &str,
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+) -> Result<String> {
+    guard::check(dst, opts)?;
+    let Some(size) = entry.size else {
+        return Err(Error::Config(format!(
+            "the size of {} isn't known",
+            source.describe(entry)
+        )));
+    };
+    if opts.text_mode.is_some() {
+        return Err(Error::Config(format!(
+            "the line endings of {} can't be converted, only those of local files",
+            source.describe(entry)
+        )));
+    }
+    let (mut tx, rx) = tokio::io::duplex(64 * 1024);
+    let mut reader = Metered::new(rx, opts.hasher.digest(), progress.clone());
+    let read = async {
+        source.read(entry, &mut tx, progress.clone()).await?;
+        tx.shutdown()
+            .await
+            .map_err(|err| Error::stream("finishing the copy", err))
+    };
+    let sent = opts
+        .transport
+        .upload(pods, pod, &mut reader, size, dst, opts, progress.clone());
+    // whichever end fails first is the cause, the other one only sees the
+    // pipe break
+    let res = futures::future::try_join(read, sent).await.map(drop);
+    if let Err(Error::Cancelled) = &res {
+        remove(pods, pod, dst, opts).await;
+    }
+    res.map(|()| reader.digest())
+}
+
+/// Copies the local file `src` to `dst` in `pod` in parts of `part_size`
+/// bytes, each sent through an exec stream of its own, checked and sent
+/// again up to `retries` times if that fails. The container joins the parts
+/// and the result is checked against the digest of `src`, which is
+/// returned. A file that fits in one part is copied like [`copy_to_pod`]
+/// does, with the same retries.
+///
+/// The parts are kept next to `dst` in `.NAME.k8scp-parts` while they
+/// arrive, and removed if the copy fails.
+#[allow(clippy::too_many_arguments)]
+pub async fn copy_split_to_pod(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &Path,
+    dst: &str,
+    part_size: u64,
+    retries: u32,
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+) -> Result<String> {
+    if part_size == 0 {
+        return Err(Error::Config("the parts must be larger than 0".to_string()));
+    }
+    guard::check(dst, opts)?;
+    let meta = tokio::fs::metadata(src)
+        .await
+        .map_err(|err| Error::io(src, err))?;
+    if files::is_stream(&meta) {
+        return Err(Error::Config(format!(
+            "{} can't be split, its size isn't known",
+            src.display()
+        )));
+    }
+    let size = meta.len();
+    if size <= part_size {
+        let opts = ExecOptions {
+            retries,
+            ..opts.clone()
+        };
+        return copy_to_pod(pods, pod, src, dst, &opts, progress).await;
+    }
+    if opts.text_mode.is_some() {
+        return Err(Error::Config(format!(
+            "{} can't be split, its line endings are converted",
+            src.display()
+        )));
+    }
+    let (remote_dir, name) = match dst.rsplit_once('/') {
+        Some(("", name)) => ("", name),
+        Some((dir, name)) => (dir, name),
+        None => (".", dst),
+    };
+    let parts = format!("{}/.{}.k8scp-parts", remote_dir, name);
+    let joined = format!("{}/.{}.k8scp-part", remote_dir, name);
+    let count = size.div_ceil(part_size);
+    let width = count.to_string().len();
+
+    let sent = async {
+        for i in 0..count {
+            let offset = i * part_size;
+            let len = part_size.min(size - offset);
+            let part = format!("{}/{:0width$}", parts, i, width = width);
+            attempts(&part, retries, opts, &progress, || {
+                send_part(pods, pod, src, offset, len, &part, opts, progress.clone())
+            })
+            .await?;
+        }
+        Ok(())
+    };
+    let res = async {
+        // the parts are hashed on their own, and sent again if they fail
+        let (digest, ()) = futures::future::try_join(hash::file(opts.hasher, src), sent).await?;
+        let command = exec::umasked(
+            opts.umask,
+            &format!(
+                "cd {} && cat -- * > {} && cd / && rm -rf -- {}",
+                quote(&parts),
+                quote(&joined),
+                quote(&parts)
+            ),
+        );
+        // `cat` prints nothing while it joins
+        let timeout = opts.idle_timeout + Duration::from_secs(size / JOIN_RATE);
+        exec::run(
+            pods,
+            pod,
+            opts.container,
+            &command,
+            timeout,
+            timeout,
+            &opts.cancel,
+        )
+        .await?;
+        check_digest(
+            pods,
+            pod,
+            opts.container,
+            &joined,
+            opts.hasher,
+            &digest,
+            opts.idle_timeout,
+            &opts.cancel,
+        )
+        .await?;
+        let command = format!("mv -f -- {} {}", quote(&joined), quote(dst));
+        exec::run(
+            pods,
+            pod,
+            opts.container,
+            &command,
+            opts.connect_timeout,
+            opts.idle_timeout,
+            &opts.cancel,
+        )
+        .await?;
+        Ok(digest)
+    }
+    .await;
+    if res.is_err() {
+        let command = format!("rm -rf -- {} {}", quote(&parts), quote(&joined));
+        let cleanup = exec::run(
+            pods,
+            pod,
+            opts.container,
+            &command,
+            opts.connect_timeout,
+            opts.idle_timeout,
+            &CancellationToken::new(),
+        )
+        .await;
+        if let Err(err) = cleanup {
+            warn!(
+                "failed to remove the parts of {} from pod {}: {}",
+                dst, pod, err
+            );
+        }
+    }
+    res
+}
+
+/// Sends the `len` bytes of `src` at `offset` to `part`, checking its digest
+/// in the pod
+#[allow(clippy::too_many_arguments)]
+async fn send_part(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &Path,
+    offset: u64,
+    len: u64,
+    part: &str,
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+) -> Result<()> {
+    let mut file = tokio::fs::File::open(src)
+        .await
+        .map_err(|err| Error::io(src, err))?;
+    file.seek(io::SeekFrom::Start(offset))
+        .await
+        .map_err(|err| Error::io(src, err))?;
+    let mut reader = Metered::new(file.take(len), opts.hasher.digest(), progress.clone())
+        .path(src)
+        .offset(offset);
+    opts.transport
+        .upload(pods, pod, &mut reader, len, part, opts, progress)
+        .await?;
+    check_digest(
+        pods,
+        pod,
+        opts.container,
+        part,
+        opts.hasher,
+        &reader.digest(),
+        opts.idle_timeout,
+        &opts.cancel,
+    )
+    .await
+}
+
+/// Runs `copy` of `dst` until it succeeds or `retries` more attempts failed,
+/// waiting [`ExecOptions::retry_backoff`] before the first retry and twice
+/// as long before each one after, and counting each retry in the stats of
+/// `progress`
+pub(crate) async fn attempts<T, F, Fut>(
+    dst: &str,
+    retries: u32,
+    opts: &ExecOptions<'_>,
+    progress: &ItemProgress,
+    mut copy: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        match copy().await {
+            Ok(done) => return Ok(done),
+            Err(Error::Cancelled) => return Err(Error::Cancelled),
+            Err(err) if attempt < retries => {
+                let backoff = opts.retry_backoff.saturating_mul(1 << attempt.min(16));
+                attempt += 1;
+                warn!(
+                    "copy to {} failed, retrying ({}/{}): {}",
+                    dst, attempt, retries, err
+                );
+                progress.retrying();
+                tokio::select! {
+                    () = time::sleep(backoff) => {}
+                    () = opts.cancel.cancelled() => return Err(Error::Cancelled),
+                }
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Fails unless `path` in `pod` has the digest `digest` of `hasher`
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn check_digest(
+    pods: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    path: &str,
+    hasher: &dyn Hasher,
+    digest: &str,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let command = hasher.remote_command(path);
+    // an upload ends without the container confirming it, `cat` may still
+    // be writing the last of it when the first check runs, or `tar` not
+    // have created it yet
+    let mut wait = SETTLE;
+    for attempt in 1.. {
+        // the hash comes once the whole file is read
+        let output = exec::run(pods, pod, container, &command, timeout, timeout, cancel).await;
+        let stdout = match output {
+            Ok(output) => output.stdout,
+            Err(Error::RemoteCommand { .. }) if attempt < CHECKS => String::new(),
+            Err(err) => return Err(err),
+        };
+        let remote = stdout.split_whitespace().next().unwrap_or_default();
+        if remote == digest {
+            break;
+        }
+        if attempt == CHECKS {
+            return Err(Error::Verification {
+                pod: pod.to_string(),
+                path: path.to_string(),
+                algorithm: hasher.name().to_string(),
+                local: digest.to_string(),
+                remote: remote.to_string(),
+            });
+        }
+        time::sleep(wait).await;
+        wait *= 2;
+    }
+    Ok(())
+}
+
+/// Removes `path` from `pod` after a transfer to it broke off, through a new
+/// stream since that one is gone
+pub(crate) async fn remove(pods: &Api<Pod>, pod: &str, path: &str, opts: &ExecOptions<'_>) {
+    let command = format!("rm -f -- {}", quote(path));
+    let cleanup = exec::run(
+        pods,
+        pod,
+        opts.container,
+        &command,
+        opts.connect_timeout,
+        opts.idle_timeout,
+        &CancellationToken::new(),
+    )
+    .await;
+    if let Err(err) = cleanup {
+        warn!("failed to remove {} from pod {}: {}", path, pod, err);
+    }
+}
+
+/// Uploads what `src` reads, `size` bytes, through an exec stream running
+/// the write command of `codec`
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn upload(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &mut (dyn AsyncRead + Send + Unpin),
+    size: u64,
+    dst: &str,
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+    codec: &dyn Codec,
+) -> Result<()> {
+    let (remote_dir, name) = match dst.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((dir, name)) => (dir, name),
+        None => (".", dst),
+    };
+    if name.is_empty() {
+        return Err(Error::Config(format!(
+            "{} is a directory, the destination must name a file",
+            dst
+        )));
+    }
+    let mut encoder = codec.encoder(name, size);
+
+    // pod exec
+    let mut ap = AttachParams::default().stdin(true);
+    if !opts.container.is_empty() {
+        ap = ap.container(opts.container);
+    }
+
+    let exec = exec::umasked(
+        opts.umask,
+        &format!(
+            "mkdir -p {} && cd {} && {}",
+            quote(remote_dir),
+            quote(remote_dir),
+            codec.write_command(name, size)
+        ),
+    );
+
+    progress.connecting();
+    let connecting = Instant::now();
+    let mut attached = connect(pods, pod, &exec, &ap, opts).await?;
+    progress.connected();
+    transport::connected(pod, connecting);
+
+    // The received streams from `AttachedProcess`
+    let stdin = attached
+        .stdin()
+        .ok_or_else(|| Error::missing(pod, "stdin"))?;
+    let mut stdin_writer = Traced::new(stdin, pod, Channel::Stdin);
+    let stdout = attached
+        .stdout()
+        .ok_or_else(|| Error::missing(pod, "stdout"))?;
+    let mut stdout_reader = Traced::new(stdout, pod, Channel::Stdout);
+    let stderr = attached
+        .stderr()
+        .ok_or_else(|| Error::missing(pod, "stderr"))?;
+    let stderr_reader = Traced::new(stderr, pod, Channel::Stderr);
+
+    // stdout
+    let stdout = Arc::new(Mutex::new(StringWriter { str: String::new() }));
+    let out = stdout.clone();
+    let reading = format!("reading stdout of pod {}", pod);
+    tokio::spawn(async move {
+        // only logged, what matters is the status
+        let copied = tokio::io::copy(&mut stdout_reader, out.lock().await.deref_mut()).await;
+        if let Err(err) = copied {
+            debug!("{}", Error::stream(reading, err));
+        }
+    });
+
+    // stderr, shown as it arrives
+    let stderr = tokio::spawn(forward_stderr(stderr_reader, progress.clone()));
+
+    let status = attached
+        .take_status()
+        .ok_or_else(|| Error::missing(pod, "a status"))?;
+
+    // stdin, every write must make progress within the idle timeout
+    let idle_timeout = opts.idle_timeout;
+    let sent = async {
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut encoded = Vec::new();
+        loop {
+            let n = src.read(&mut buf).await.map_err(local)?;
+            encoded.clear();
+            match n {
+                0 => encoder.finish(&mut encoded),
+                n => encoder.encode(&buf[..n], &mut encoded),
+            }
+            .map_err(local)?;
+            if !encoded.is_empty() {
+                let write = time::timeout(idle_timeout, stdin_writer.write_all(&encoded));
+                tokio::select! {
+                    written = write => written
+                        .map_err(|_| Error::Timeout(stalled(idle_timeout)))?
+                        .map_err(|err| Error::stream(format!("sending to pod {}", pod), err))?,
+                    () = opts.cancel.cancelled() => return Err(Error::Cancelled),
+                }
+            }
+            if n == 0 {
+                return Ok(());
+            }
+        }
+    }
+    .await;
+    if sent.is_err() {
+        attached.abort();
+    }
+    sent?;
+    // closing stdin ends the stream, kube doesn't wait for the status then
+    let stdin_writer = codec.confirms().then_some(stdin_writer);
+    let status = time::timeout(idle_timeout, status)
+        .await
+        .map_err(|_| Error::Timeout(stalled(idle_timeout)))?;
+    drop(stdin_writer);
+    transport::status(pod, status.as_ref(), connecting);
+
+    if !stdout.lock().await.str.is_empty() {
+        info!("stdout:{}", stdout.lock().await.str);
+    }
+    // all of it has been shown by now
+    let stderr = stderr.await.unwrap_or_default();
+    match status {
+        Some(status) if status.status.as_deref() != Some("Success") => {
+            Err(Error::remote(status, stderr))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Copies the file `src` in `pod` to the local path `dst`, removing `dst`
+/// again if the copy fails, and returns the digest of what was received as
+/// hex, see [`ExecOptions::hasher`] and [`verify_from_pod`].
+///
+/// A relative `src` is resolved against the working directory of the
+/// container. `progress` counts the bytes received against the total it was
+/// created with, pass 0 if the size of `src` is unknown. The copy goes
+/// through [`ExecOptions::transport`].
+pub async fn copy_from_pod(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &str,
+    dst: &Path,
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+) -> Result<String> {
+    let res = async {
+        let mut file = tokio::fs::File::create(dst)
+            .await
+            .map_err(|err| Error::io(dst, err))?;
+        receive(pods, pod, src, &mut file, Some(dst), opts, progress).await
+    }
+    .await;
+    if res.is_err() {
+        let _ = tokio::fs::remove_file(dst).await;
+    }
+    res
+}
+
+/// [`copy_from_pod`] writing to `dst`, e.g. the stdin of a program that
+/// encrypts what it gets, rather than a file. `dst` is shut down at the end.
+pub async fn copy_from_pod_into(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &str,
+    dst: &mut (dyn AsyncWrite + Send + Unpin),
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+) -> Result<String> {
+    receive(pods, pod, src, dst, None, opts, progress).await
+}
+
+/// Copies `src` in `pod` to `dst`, the local file `path` if it is one, and
+/// returns the digest of what was received
+async fn receive(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &str,
+    dst: &mut (dyn AsyncWrite + Send + Unpin),
+    path: Option<&Path>,
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+) -> Result<String> {
+    let dst: Box<dyn AsyncWrite + Send + Unpin + '_> = match opts.text_mode {
+        Some(mode) => Box::new(Converted::new(dst, mode)),
+        None => Box::new(dst),
+    };
+    let mut writer = Metered::new(dst, opts.hasher.digest(), progress.clone());
+    if let Some(path) = path {
+        writer = writer.path(path);
+    }
+    opts.transport
+        .download(pods, pod, src, &mut writer, opts, progress)
+        .await?;
+    // the end of the file is what ends a CR held back
+    writer.shutdown().await.map_err(local)?;
+    Ok(writer.digest())
+}
+
+/// Fails with [`Error::Verification`] unless the file `src` in `pod` has
+/// the digest `digest` of [`ExecOptions::hasher`], the one
+/// [`copy_from_pod`] returned for a copy of it
+pub async fn verify_from_pod(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &str,
+    digest: &str,
+    opts: &ExecOptions<'_>,
+) -> Result<()> {
+    let command = opts.hasher.remote_command(src);
+    // the hash comes once the whole file is read
+    let output = exec::run(
+        pods,
+        pod,
+        opts.container,
+        &command,
+        opts.connect_timeout,
+        opts.connect_timeout,
+        &opts.cancel,
+    )
+    .await?;
+    let remote = output.stdout.split_whitespace().next().unwrap_or_default();
+    if remote != digest {
+        return Err(Error::Verification {
+            pod: pod.to_string(),
+            path: src.to_string(),
+            algorithm: opts.hasher.name().to_string(),
+            local: digest.to_string(),
+            remote: remote.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Fails with [`Error::Verification`] unless `dst` in `pod` has the digest
+/// `digest` of [`ExecOptions::hasher`], the one an upload to it returned,
+/// giving the container a moment to finish writing it
+pub async fn verify_to_pod(
+    pods: &Api<Pod>,
+    pod: &str,
+    dst: &str,
+    digest: &str,
+    opts: &ExecOptions<'_>,
+) -> Result<()> {
+    check_digest(
+        pods,
+        pod,
+        opts.container,
+        dst,
+        opts.hasher,
+        digest,
+        opts.idle_timeout,
+        &opts.cancel,
+    )
+    .await
+}
+
+/// Downloads into `dst` through an exec stream running the read command of
+/// `codec`
+pub(crate) async fn download(
+    pods: &Api<Pod>,
+    pod: &str,
+    src: &str,
+    dst: &mut (dyn AsyncWrite + Send + Unpin),
+    opts: &ExecOptions<'_>,
+    progress: ItemProgress,
+    codec: &dyn Codec,
+) -> Result<()> {
+    let mut ap = AttachParams::default();
+    if !opts.container.is_empty() {
+        ap = ap.container(opts.container);
+    }
+    let exec = codec.read_command(src);
+
+    progress.connecting();
+    let connecting = Instant::now();
+    let mut attached = connect(pods, pod, &exec, &ap, opts).await?;
+    progress.connected();
+    transport::connected(pod, connecting);
+
+    let stdout = attached
+        .stdout()
+        .ok_or_else(|| Error::missing(pod, "stdout"))?;
+    let mut stdout_reader = Traced::new(stdout, pod, Channel::Stdout);
+    let stderr = attached
+        .stderr()
+        .ok_or_else(|| Error::missing(pod, "stderr"))?;
+    let stderr_reader = Traced::new(stderr, pod, Channel::Stderr);
+    let status = attached
+        .take_status()
+        .ok_or_else(|| Error::missing(pod, "a status"))?;
+    let stderr = tokio::spawn(forward_stderr(stderr_reader, progress.clone()));
+
+    let mut decoder = codec.decoder();
+    let receiving = || format!("receiving from pod {}", pod);
+    let received = async {
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut decoded = Vec::new();
+        loop {
+            let read = time::timeout(opts.idle_timeout, stdout_reader.read(&mut buf));
+            let n = tokio::select! {
+                n = read => n
+                    .map_err(|_| Error::Timeout(stalled(opts.idle_timeout)))?
+                    .map_err(|err| Error::stream(receiving(), err))?,
+                () = opts.cancel.cancelled() => return Err(Error::Cancelled),
+            };
+            if n == 0 {
+                break;
+            }
+            decoded.clear();
+            decoder
+                .decode(&buf[..n], &mut decoded)
+                .map_err(|err| Error::stream(receiving(), err))?;
+            dst.write_all(&decoded).await.map_err(local)?;
+        }
+        dst.flush().await.map_err(local)?;
+        Ok(())
+    }
+    .await;
+    if received.is_err() {
+        attached.abort();
+    }
+    received?;
+
+    let status = time::timeout(opts.idle_timeout, status)
+        .await
+        .map_err(|_| Error::Timeout(stalled(opts.idle_timeout)))?;
+    transport::status(pod, status.as_ref(), connecting);
+    let stderr = stderr.await.unwrap_or_default();
+    match status {
+        Some(status) if status.status.as_deref() != Some("Success") => {
+            Err(Error::remote(status, stderr))
+        }
+        // a failed command explains a truncated stream better
+        _ => decoder
+            .finish()
+            .map_err(|err| Error::stream(receiving(), err)),
+    }
+}
+
+/// An error of the reader or writer on the local end of a transfer
+fn local(err: io::Error) -> Error {
+    // `-` like the unnamed streams of other tools
+    Error::unwrap_io(err, |err| Error::io("-", err))
+}
+
+/// Establishes the exec stream running `command` in `pod`
+async fn connect(
+    pods: &Api<Pod>,
+    pod: &str,
+    command: &str,
+    ap: &AttachParams,
+    opts: &ExecOptions<'_>,
+) -> Result<AttachedProcess> {
+    let exec = time::timeout(
+        opts.connect_timeout,
+        pods.exec(pod, vec!["sh", "-c", command], ap),
+    )
+    .instrument(info_span!("connect", pod = %pod));
+    let attached = tokio::select! {
+        attached = exec => attached,
+        () = opts.cancel.cancelled() => return Err(Error::Cancelled),
+    };
+    let attached = attached.map_err(|_| {
+        Error::Timeout(format!(
+            "timed out after {} establishing exec stream to pod {}",
+            humantime::format_duration(opts.connect_timeout),
+            pod
+        ))
+    })??;
+    Ok(attached)
+}
+
+/// Counts and hashes what goes through a reader or writer, reporting the
+/// position to `progress`
+pub(crate) struct Metered<S> {
+    inner: S,
+    pos: u64,
+    /// Where in the item `inner` starts, for a part of a file
+    offset: u64,
+    digest: Box<dyn Digest>,
+    progress: ItemProgress,
+    /// The local file `inner` reads or writes, named in its errors
+    path: Option<PathBuf>,
+}
+
+impl<S> Metered<S> {
+    pub(crate) fn new(inner: S, digest: Box<dyn Digest>, progress: ItemProgress) -> Metered<S> {
+        Metered {
+            inner,
+            pos: 0,
+            offset: 0,
+            digest,
+            progress,
+            path: None,
+        }
+    }
+
+    pub(crate) fn path(mut self, path: &Path) -> Metered<S> {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    pub(crate) fn offset(mut self, offset: u64) -> Metered<S> {
+        self.offset = offset;
+        self
+    }
+
+    /// The digest of what went through as hex
+    pub(crate) fn digest(&self) -> String {
+        self.digest.hex()
+    }
+
+    fn advance(&mut self, data: &[u8]) {
+        self.pos += data.len() as u64;
+        self.digest.update(data);
+        self.progress.set_position(self.offset + self.pos);
+    }
+
+    fn error<T>(&self, ret: Poll<io::Result<T>>) -> Poll<io::Result<T>> {
+        match (ret, &self.path) {
+            (Poll::Ready(Err(err)), Some(path)) => Poll::Ready(Err(Error::io(path, err).into())),
+            (ret, _) => ret,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Metered<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let ret = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = ret {
+            self.advance(&buf.filled()[filled..]);
+        }
+        self.error(ret)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Metered<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let ret = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = ret {
+            self.advance(&buf[..n]);
+        }
+        self.error(ret)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let ret = Pin::new(&mut self.inner).poll_flush(cx);
+        self.error(ret)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let ret = Pin::new(&mut self.inner).poll_shutdown(cx);
+        self.error(ret)
+    }
+}
+
+#[derive(Debug)]
+struct StringWriter {
+    str: String,
+}
+
+impl AsyncWrite for StringWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.str
+            .push_str(std::str::from_utf8(buf).unwrap_or("[not utf8]"));
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reports what the container writes to stderr line by line as it arrives,
+/// returning all of it once stderr is closed
+async fn forward_stderr(reader: impl AsyncRead + Unpin, progress: ItemProgress) -> String {
+    let mut reader = BufReader::new(reader);
+    let mut all = String::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&line);
+                progress.stderr(line.trim_end_matches(['\n', '\r']));
+                all.push_str(&line);
+            }
+        }
+    }
+    all
+}
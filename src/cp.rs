@@ -0,0 +1,1818 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{bail, Context as _};
+use clap::ArgGroup;
+use clap_complete::ArgValueCompleter;
+use futures::{future, stream, StreamExt};
+use indicatif::HumanBytes;
+use k8s_openapi::api::{apps::v1::StatefulSet, core::v1::Pod};
+use kube::api::Api;
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use k8scp::{
+    copy_source_to_pod, copy_split_to_pod, copy_to_pod, diagnose,
+    endpoint::{Entry, Object, Source},
+    exec,
+    files::{self, FileEntry, Walk},
+    guard,
+    progress::{Progress, ProgressMode, StderrObserver},
+    redact::redact,
+    remote,
+    stats::Stats,
+    text::{TextFiles, TextMode},
+    transport::{AgeDecrypt, Exec, Extract, Id, IdMap, Owners, Transport},
+    verify_to_pod, Error, ExecOptions,
+};
+
+use crate::{
+    audit, bench, complete,
+    history::{self, History},
+    journal::Journal,
+    manifest,
+    metrics::METRICS,
+    notify, overwrite,
+    pick::Pick,
+    preflight, print_error, relay,
+    report::{self, Report, ReportFormat},
+    rolling::{self, Wave},
+    sync, target, term,
+    then::Then,
+    Global,
+};
+
+/// Exit code of a copy where some transfers failed and others completed,
+/// other failures exit with 1 and usage errors with 2
+const EXIT_PARTIAL: u8 = 3;
+/// Exit code of a copy where no transfer completed
+const EXIT_FAILED: u8 = 4;
+
+/// Times a part of a `--split` file is sent again if not given
+const PART_RETRIES: u32 = 3;
+
+/// How long to wait before the first retry if not given
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How long `--pre-exec` and `--post-exec` may run if not given
+const EXEC_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long `--wait-ready` waits for a pod if not given
+const READY_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(clap::Args, Debug, Clone, Default)]
+#[command(group(ArgGroup::new("selectors").args(["manifest", "selector"])))]
+pub struct CpArgs {
+    /// Target pod, repeat or separate with commas to copy to several pods, `sts/NAME:ORDINAL` for a pod of a StatefulSet
+    #[arg(short, long, env = "K8SCP_POD", required_unless_present_any = ["manifest", "selector", "pick"], value_delimiter = ',', add = ArgValueCompleter::new(complete::pods))]
+    pub pod: Vec<String>,
+
+    /// Copy to the running pods this label selector matches, like `app=web`, in every namespace of --namespace
+    #[arg(short = 'l', long, conflicts_with_all = ["pod", "manifest"])]
+    pub selector: Option<String>,
+
+    /// The ordinal of the pod of the StatefulSets of --pod given as `sts/NAME`
+    #[arg(long, value_name = "ORDINAL")]
+    pub pod_index: Option<u32>,
+
+    /// Local file or directory, directories are copied recursively, or the URL of a file, `s3://BUCKET/KEY`, `gs://BUCKET/KEY` or `https://…`
+    #[arg(short, long, required_unless_present = "manifest")]
+    pub src: Option<String>,
+
+    #[arg(short, long, required_unless_present = "manifest")]
+    pub dst: Option<String>,
+
+    /// Decrypt the age encrypted files in the pods with this identity file, a path in the container, they are copied through its `age`
+    #[arg(long, value_name = "IDENTITY")]
+    pub decrypt: Option<String>,
+
+    /// Unpack the archive `--src` names in `--dst` instead, a .tar, .tar.gz, .tgz, .tar.bz2, .tar.xz or .zip, through the container's `tar` or `unzip`
+    #[arg(long, conflicts_with = "decrypt")]
+    pub extract: bool,
+
+    /// Restore the extended attributes of the archive --extract unpacks, SELinux labels and file capabilities among them, through GNU `tar`
+    #[arg(long, requires = "extract")]
+    pub xattrs: bool,
+
+    /// Restore the POSIX ACLs of the archive --extract unpacks, through GNU `tar`
+    #[arg(long, requires = "extract")]
+    pub acls: bool,
+
+    /// Give the files --extract unpacks the owners and groups of the archive by their ids, rather than the exec user
+    #[arg(long, requires = "extract")]
+    pub numeric_ids: bool,
+
+    /// Files --extract unpacks that the archive gives the local user `NAME:ID` belong to the user `NAME:ID` of the container, like `me:1000=app:1001`; repeat to map several
+    #[arg(long, value_name = "LOCAL=REMOTE", value_parser = parse_id_map, requires = "extract")]
+    pub owner_map: Vec<IdMap>,
+
+    /// Like --owner-map, for groups
+    #[arg(long, value_name = "LOCAL=REMOTE", value_parser = parse_id_map, requires = "extract")]
+    pub group_map: Vec<IdMap>,
+
+    /// Convert the line endings of text files on the way, `crlf->lf` or `lf->crlf`
+    #[arg(long, value_name = "MODE", value_parser = TextMode::parse, conflicts_with_all = ["decrypt", "extract", "split"])]
+    pub text_mode: Option<TextMode>,
+
+    /// Extensions of the files --text-mode takes as text, separated by commas [default: txt, md, csv, conf, cfg, ini, env, properties, yaml, yml, json, toml, xml, html, css, js, sh, sql]
+    #[arg(
+        long,
+        value_name = "EXT",
+        value_delimiter = ',',
+        requires = "text_mode"
+    )]
+    pub text: Vec<String>,
+
+    /// Send files larger than this in parts of this size, like `512MiB`, each checked and sent again on its own if it fails, which the container joins
+    #[arg(long, value_name = "SIZE", value_parser = bench::parse_size, conflicts_with_all = ["decrypt", "extract"])]
+    pub split: Option<u64>,
+
+    /// Times a part of a --split file is sent again when sending it fails [default: 3]
+    #[arg(long, value_name = "N", requires = "split")]
+    pub part_retries: Option<u32>,
+
+    /// Times a file is sent again when sending it fails, a --split file is retried by the part with --part-retries [default: 0]
+    #[arg(long, value_name = "N")]
+    pub retries: Option<u32>,
+
+    /// How long to wait before the first retry of a file or part, twice as long before each one after [default: 1s]
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub retry_backoff: Option<Duration>,
+
+    /// Shell command run in the container of every pod before its files are copied, a pod it fails in gets none of them
+    #[arg(long, value_name = "COMMAND")]
+    pub pre_exec: Option<String>,
+
+    /// Shell command run in the container of every pod once all its files were copied, a pod it fails in counts as failed
+    #[arg(long, value_name = "COMMAND")]
+    pub post_exec: Option<String>,
+
+    /// Once the files are copied, `signal:SIGNAL:PIDFILE` or `signal:SIGNAL:NAME` signals the process in every pod, `rollout-restart` restarts the workloads of the pods; repeat to do several
+    #[arg(long, value_name = "ACTION", value_parser = Then::parse)]
+    pub then: Vec<Then>,
+
+    /// How long --pre-exec, --post-exec and the signals of --then may run [default: 5m]
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pub exec_timeout: Option<Duration>,
+
+    /// Run the copies this YAML file describes instead, `--pod` is where those that name no pods go
+    #[arg(long, conflicts_with_all = ["src", "dst"])]
+    pub manifest: Option<PathBuf>,
+
+    /// Copy to the pods --selector or the selectors of the manifest match in every namespace, not only in --namespace
+    #[arg(long, requires = "selectors")]
+    pub all_namespaces: bool,
+
+    /// Copy to one of the pods --selector or the selectors of the manifest match rather than to all of them, to one of those of --namespace without either
+    #[arg(long, value_enum, value_name = "STRATEGY", conflicts_with = "pod")]
+    pub pick: Option<Pick>,
+
+    /// Print the transfer statistics as JSON on stdout when done
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Write a report of every file/pod pair to this file when done
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+
+    /// Format of the report [default: csv for `.csv` files, json otherwise]
+    #[arg(long, value_enum, requires = "report")]
+    pub report_format: Option<ReportFormat>,
+
+    /// Copy only the file/pod pairs a JSON report of --report lists as failed or skipped
+    #[arg(long, value_name = "REPORT", conflicts_with = "manifest")]
+    pub retry_from: Option<PathBuf>,
+
+    /// Don't record the transfers in the history
+    #[arg(long, conflicts_with = "history_file")]
+    pub no_history: bool,
+
+    /// Copy only the files at the paths below the directory `--src` this file lists, a line each, `-` reads the list from stdin
+    #[arg(long, value_name = "FILE", conflicts_with = "manifest")]
+    pub files_from: Option<PathBuf>,
+
+    /// The paths of --files-from are separated by NUL rather than newlines, like `find -print0` prints them
+    #[arg(long, requires = "files_from")]
+    pub from0: bool,
+
+    /// Copy only the files modified after this UTC date and time, like `2024-01-01T00:00:00Z`, or this long ago, like `24h`
+    #[arg(long, value_name = "TIME", value_parser = history::parse_time)]
+    pub newer_than: Option<SystemTime>,
+
+    /// Copy only what is this many levels below a directory `--src`, 1 for the files directly in it
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Don't go into directories on other file systems than the directory `--src`, like mounted volumes or network mounts
+    #[arg(long)]
+    pub one_file_system: bool,
+
+    /// Copy the `.git`, `.hg`, `.svn`, `target`, `node_modules` and `__pycache__` directories below a directory `--src` too, rather than leave them out
+    #[arg(long)]
+    pub no_vcs_exclude: bool,
+
+    /// Make the directories below `--src` that hold no files to copy in the pods too, rather than leave them out
+    #[arg(long)]
+    pub keep_empty_dirs: bool,
+
+    /// Copy every file of a directory, rather than skip those an interrupted run of the same copy already copied
+    #[arg(long)]
+    pub no_resume: bool,
+
+    /// Stop every transfer at the first that fails, rather than only the rest of the pod it failed in
+    #[arg(long, conflicts_with = "keep_going")]
+    pub fail_fast: bool,
+
+    /// Copy the rest of the files of a pod after one fails too, the failures are reported together
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// Copy to at most this many pods at a time, the others wait for one to be done [default: all at once]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_parallel_pods: Option<u16>,
+
+    /// Copy to the pods in waves of this many, `N` or a percentage like `10%`, each once the one before succeeded
+    #[arg(long, value_name = "SIZE", value_parser = Wave::parse, group = "waves")]
+    pub rolling: Option<Wave>,
+
+    /// Copy to this many pods first, and to the others only once that succeeded and --canary-check passed in them
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..), group = "waves")]
+    pub canary: Option<u16>,
+
+    /// Shell command checking the canary pods after the copy, the other pods get nothing if it fails in one
+    #[arg(long, value_name = "COMMAND", requires = "canary")]
+    pub canary_check: Option<String>,
+
+    /// Don't copy to the pods whose names match this glob, like `web-canary-*`, repeat for several
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude_pod: Vec<String>,
+
+    /// Copy to the first pod only, the others get the files from the pods that have them over the network of the cluster, with `nc` and `tar`
+    #[arg(long, conflicts_with_all = ["waves", "extract"])]
+    pub relay: bool,
+
+    /// Port the pods listen on for --relay [default: 7790]
+    #[arg(long, value_name = "PORT", requires = "relay")]
+    pub relay_port: Option<u16>,
+
+    /// Set the annotation k8scp.io/last-copy, `<digest>@<time>`, on the pods the copy succeeded to
+    #[arg(long)]
+    pub annotate: bool,
+
+    /// Record a Kubernetes Event on the pods the copy succeeded to
+    #[arg(long)]
+    pub event: bool,
+
+    /// Post how the run ended as JSON to this URL, a Slack or Teams incoming webhook takes it as it is
+    #[arg(long, value_name = "URL", env = "K8SCP_NOTIFY_URL")]
+    pub notify_url: Option<String>,
+
+    /// Wait for the pods of a wave of --rolling or --canary to be Ready after the copy before the next wave starts
+    #[arg(long, requires = "waves")]
+    pub wait_ready: bool,
+
+    /// How long --wait-ready waits for a pod [default: 5m]
+    #[arg(long, value_parser = humantime::parse_duration, requires = "wait_ready")]
+    pub ready_timeout: Option<Duration>,
+}
+
+impl CpArgs {
+    /// How a directory `--src` is walked
+    pub fn walk(&self) -> Walk {
+        Walk {
+            max_depth: self.max_depth,
+            one_file_system: self.one_file_system,
+            exclude: vcs_exclude(self.no_vcs_exclude),
+        }
+    }
+
+    /// The files whose line endings --text-mode converts
+    pub fn text_files(&self) -> Option<TextFiles> {
+        text_files(self.text_mode, &self.text)
+    }
+
+    /// Who the files --extract unpacks belong to
+    pub fn owners(&self) -> Owners {
+        Owners {
+            numeric: self.numeric_ids,
+            users: self.owner_map.clone(),
+            groups: self.group_map.clone(),
+        }
+    }
+}
+
+/// The files `--text-mode` converts as `mode` says, those with the
+/// `extensions` if any are given
+pub fn text_files(mode: Option<TextMode>, extensions: &[String]) -> Option<TextFiles> {
+    let mut text = TextFiles::new(mode?);
+    if !extensions.is_empty() {
+        text.extensions = extensions.to_vec();
+    }
+    Some(text)
+}
+
+/// A `NAME:ID=NAME:ID` map of a local user or group to one in the container
+pub fn parse_id_map(s: &str) -> Result<IdMap, String> {
+    let id = |s: &str| match s.rsplit_once(':') {
+        Some((name, id)) if !name.is_empty() && !name.contains(char::is_whitespace) => Ok(Id {
+            name: name.to_string(),
+            id: id
+                .parse()
+                .map_err(|_| format!("{} isn't a numeric id", id))?,
+        }),
+        _ => Err(format!("{} isn't NAME:ID", s)),
+    };
+    let Some((local, remote)) = s.split_once('=') else {
+        return Err(format!("{} isn't LOCAL=REMOTE", s));
+    };
+    Ok(IdMap {
+        local: id(local)?,
+        remote: id(remote)?,
+    })
+}
+
+/// The names a walk leaves out, [`files::VCS_EXCLUDES`] unless `--no-vcs-exclude`
+pub fn vcs_exclude(no_vcs_exclude: bool) -> Vec<String> {
+    if no_vcs_exclude {
+        Vec::new()
+    } else {
+        files::VCS_EXCLUDES
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+}
+
+/// Which of the files of `--src` a copy sends
+pub enum Selection {
+    /// All of them, once overwriting what exists is confirmed
+    All,
+    /// Those that differ from what is in a pod
+    Changed,
+    /// Those at these canonical paths, known to have changed
+    Paths(HashSet<PathBuf>),
+    /// Those at these destinations, by pod, that a report lists as not
+    /// copied
+    Retry(BTreeMap<String, HashSet<String>>),
+}
+
+pub async fn run(global: &Global, args: &CpArgs) -> anyhow::Result<ExitCode> {
+    let selection = match &args.retry_from {
+        Some(path) => {
+            let unfinished = report::unfinished(path)
+                .with_context(|| format!("failed to read report {}", path.display()))?;
+            if unfinished.is_empty() {
+                if !global.quiet {
+                    term::success(format!("every transfer in {} completed", path.display()));
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            Selection::Retry(unfinished)
+        }
+        None => Selection::All,
+    };
+    transfer(global, args, &selection, &interrupt()).await
+}
+
+/// A token Ctrl-C cancels, which stops the transfers and removes their
+/// partial files from the pods. A second Ctrl-C doesn't wait for that.
+pub fn interrupt() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let interrupted = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            interrupted.cancel();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+    cancel
+}
+
+/// Copies the `selection` of `args.src` to the pods until done or
+/// `cancel`led, reporting the outcome
+pub async fn transfer(
+    global: &Global,
+    args: &CpArgs,
+    selection: &Selection,
+    cancel: &CancellationToken,
+) -> anyhow::Result<ExitCode> {
+    let mode = global.progress_mode();
+    let mut progress = Progress::with_mode(mode, global.progress_template.clone())
+        .interval(global.progress_interval);
+    if mode == ProgressMode::None && !global.quiet {
+        progress = progress.observe(StderrObserver);
+    }
+    let progress = Arc::new(progress);
+    let report_to = args.report.clone().map(|path| {
+        let format = args
+            .report_format
+            .unwrap_or_else(|| ReportFormat::for_path(&path));
+        (path, format)
+    });
+    let report = Report::new();
+    let res = copy(global, args, selection, cancel, progress.clone(), &report).await;
+    match &res {
+        Ok(()) => {
+            progress.finish();
+            let stats = progress.stats();
+            if !global.quiet {
+                report.print_pods();
+                term::success(&stats);
+            }
+            if args.stats {
+                println!("{}", stats.to_json());
+            }
+        }
+        Err(err) => {
+            progress.fail(err);
+            if !global.quiet {
+                report.print_pods();
+            }
+        }
+    }
+
+    if let Some((path, format)) = report_to {
+        let written = report
+            .write(&path, format, res.as_ref().map(drop))
+            .with_context(|| format!("failed to write report {}", path.display()));
+        match (&res, written) {
+            (Ok(()), Err(err)) => return Err(err),
+            // the copy error is the one to return
+            (Err(_), Err(err)) => term::error(format!("{:#}", err)),
+            (_, Ok(())) => {}
+        }
+    }
+    if let Some(url) = &args.notify_url {
+        let payload = notification(global, args, &report, &progress.stats(), &res);
+        if let Err(err) = notify::post(url, &payload, global.connect_timeout).await {
+            term::warn(format!("failed to notify: {:#}", err));
+        }
+    }
+    let Err(err) = res else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    // a single transfer, or none because e.g. the kubeconfig couldn't be
+    // loaded, is a plain failure
+    let tally = report.tally(None);
+    if tally.total() <= 1 {
+        return Err(err);
+    }
+    print_error(&err);
+    Ok(ExitCode::from(if tally.completed > 0 {
+        EXIT_PARTIAL
+    } else {
+        EXIT_FAILED
+    }))
+}
+
+/// What `--notify-url` is sent once a run ended with `res`, its `text` for
+/// chat webhooks
+fn notification(
+    global: &Global,
+    args: &CpArgs,
+    report: &Report,
+    stats: &Stats,
+    res: &anyhow::Result<()>,
+) -> serde_json::Value {
+    let what = match &args.manifest {
+        Some(manifest) => format!("the manifest {}", manifest.display()),
+        None => format!(
+            "{} to {}",
+            args.src.as_deref().unwrap_or_default(),
+            args.dst.as_deref().unwrap_or_default()
+        ),
+    };
+    let pods = report.pods();
+    let tally = report.tally(None);
+    let whom = match pods.len() {
+        1 => format!("{} of {}", pods[0], global.namespace()),
+        n => format!("{} pods of {}", n, global.namespace()),
+    };
+    let text = match res {
+        Ok(()) => format!("k8scp copied {} in {}, {}", what, whom, stats),
+        Err(err) => format!(
+            "k8scp failed to copy {} in {}, {}: {:#}",
+            what, whom, tally, err
+        ),
+    };
+    let report_path = args
+        .report
+        .as_ref()
+        .map(|path| std::fs::canonicalize(path).unwrap_or_else(|_| path.clone()));
+    serde_json::json!({
+        "text": text,
+        "status": if res.is_ok() { "completed" } else { "failed" },
+        "error": res.as_ref().err().map(|err| redact(&format!("{:#}", err)).into_owned()),
+        "context": global.context,
+        "namespace": global.namespace(),
+        "pods": pods,
+        "src": args.src,
+        "dst": args.dst,
+        "manifest": args.manifest,
+        "files": {
+            "completed": tally.completed,
+            "failed": tally.failed,
+            "skipped": tally.skipped,
+        },
+        "bytes": stats.bytes(),
+        "elapsed": stats.elapsed().as_secs_f64(),
+        "retries": stats.retries(),
+        "verified": stats.verified(),
+        "report": report_path,
+    })
+}
+
+async fn copy(
+    global: &Global,
+    args: &CpArgs,
+    selection: &Selection,
+    cancel: &CancellationToken,
+    progress: Arc<Progress>,
+    report: &Report,
+) -> anyhow::Result<()> {
+    if let Some(path) = &args.manifest {
+        return manifest::copy(global, args, path, selection, cancel, &progress, report).await;
+    }
+    if args.selector.is_some() || args.pick.is_some() {
+        return manifest::copy_selected(global, args, selection, cancel, &progress, report).await;
+    }
+    let plan = plan(global, args, selection, &progress).await?;
+    progress.start(plan.count(), plan.size());
+    send(&plan, cancel, &progress, report).await
+}
+
+/// What a copy sends to which pod, worked out before anything is sent
+pub struct Plan<'a> {
+    global: &'a Global,
+    api: Api<Pod>,
+    history: History,
+    journal: Journal,
+    /// Where the files are streamed from if `--src` is a URL
+    source: Option<Object>,
+    transport: Arc<dyn Transport>,
+    /// Whether an upload is checked against the pod, what `--decrypt` and
+    /// `--extract` write there isn't what was sent
+    verify: bool,
+    pods: Vec<(String, Vec<FileEntry>)>,
+    /// The directories of `--keep-empty-dirs` made in every pod
+    dirs: Vec<String>,
+    /// The part size of `--split` and the retries of a part
+    split: Option<(u64, u32)>,
+    /// The retries of a file and how long to wait before the first
+    retry: (u32, Duration),
+    /// The files whose line endings are converted
+    text: Option<TextFiles>,
+    pre_exec: Option<String>,
+    post_exec: Option<String>,
+    then: Vec<Then>,
+    exec_timeout: Duration,
+    /// Stop everything at the first failure
+    fail_fast: bool,
+    /// Copy the rest of the files of a pod after one fails
+    keep_going: bool,
+    /// How many pods are copied to at a time
+    max_parallel_pods: usize,
+    /// How many pods are copied to before the next wave starts
+    wave: Option<Wave>,
+    /// How long the pods of a wave get to be Ready
+    wait_ready: Option<Duration>,
+    /// How many pods the first wave copies to
+    canary: Option<usize>,
+    canary_check: Option<String>,
+    /// The port of `--relay`
+    relay: Option<u16>,
+    annotate: bool,
+    event: bool,
+    /// The namespace shown with the pods, set when a run copies to the pods
+    /// of several namespaces
+    namespace: Option<String>,
+}
+
+impl Plan<'_> {
+    /// The pods in the waves they are copied to in, the canaries first. A
+    /// `--relay` sends to the first pod, then in every wave to as many pods
+    /// as have the files by then.
+    fn waves(&self) -> Vec<&[(String, Vec<FileEntry>)]> {
+        let mut waves = Vec::new();
+        let mut rest = &self.pods[..];
+        if self.relay.is_some() {
+            let mut size = 1;
+            while !rest.is_empty() {
+                let (wave, others) = rest.split_at(size.min(rest.len()));
+                waves.push(wave);
+                rest = others;
+                size = waves.iter().map(|wave| wave.len()).sum();
+            }
+            return waves;
+        }
+        if let Some(canary) = self.canary {
+            let (canaries, others) = rest.split_at(canary.min(rest.len()));
+            waves.push(canaries);
+            rest = others;
+        }
+        let size = self
+            .wave
+            .map_or(rest.len(), |wave| wave.size(self.pods.len()));
+        waves.extend(rest.chunks(size.max(1)));
+        waves
+    }
+
+    /// Shows the namespace with the pods in the report and progress, for a
+    /// run that copies to several namespaces and may find a pod name in more
+    /// than one
+    pub fn show_namespace(&mut self) {
+        self.namespace = Some(self.global.namespace().to_string());
+    }
+
+    /// `pod` as the report and progress show it, `namespace/pod` if the
+    /// namespace is shown
+    fn shown(&self, pod: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}/{}", namespace, pod),
+            None => pod.to_string(),
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        self.global.namespace()
+    }
+
+    /// The number of file/pod pairs
+    pub fn count(&self) -> usize {
+        self.pods.iter().map(|(_, files)| files.len()).sum()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.pods
+            .iter()
+            .flat_map(|(_, files)| files)
+            .map(|f| f.size)
+            .sum()
+    }
+}
+
+/// Works out what the copy of `args` sends, checking for existing
+/// destinations or comparing with the pods as `selection` needs
+pub async fn plan<'a>(
+    global: &'a Global,
+    args: &CpArgs,
+    selection: &Selection,
+    progress: &Progress,
+) -> anyhow::Result<Plan<'a>> {
+    // required unless a subcommand or manifest is given
+    let (Some(src), Some(dst)) = (&args.src, &args.dst) else {
+        unreachable!()
+    };
+    if target::is_windows_path(dst) {
+        bail!(
+            "--dst is a path in the pods, {} is a local Windows path",
+            dst
+        );
+    }
+    let pods = ordinals(global, &args.pod, args.pod_index).await?;
+    // the pods copied to
+    let targets: Vec<_> = pods
+        .iter()
+        .filter(|pod| {
+            !args
+                .exclude_pod
+                .iter()
+                .any(|pattern| target::glob(pattern, pod))
+        })
+        .cloned()
+        .collect();
+    if targets.is_empty() {
+        bail!("--exclude-pod leaves none of the {} pods", pods.len());
+    }
+    let namespace = global.namespace();
+    if namespace.contains(',') {
+        bail!(
+            "several namespaces, {}, are for --selector and the selectors of a manifest, the pods of --pod are in one",
+            namespace
+        );
+    }
+    let resolved;
+    let dst = if remote::expands(dst) || !dst.starts_with('/') {
+        resolved = resolve(global, &targets, dst).await?;
+        &resolved
+    } else {
+        dst
+    };
+    let walk = args.walk();
+    // a named pipe or a device is read once, as it comes
+    let stream =
+        !Object::is_url(src) && std::fs::metadata(src).is_ok_and(|meta| files::is_stream(&meta));
+    if stream {
+        if targets.len() > 1 {
+            bail!(
+                "{} can be read only once, it can't be copied to {} pods",
+                src,
+                targets.len()
+            );
+        }
+        if args.split.is_some() {
+            bail!("{} can't be split, its size isn't known", src);
+        }
+        if !matches!(selection, Selection::All) {
+            bail!(
+                "{} can't be compared with the pods, it can be read only once",
+                src
+            );
+        }
+    }
+    let (files, dirs, source, api, history, journal) = async {
+        // src files
+        let phase = progress.phase(format!("scanning {}", src));
+        let (files, source) = if Object::is_url(src) {
+            let (file, object) = object(global, src, dst).await?;
+            (vec![file], Some(object))
+        } else {
+            let skipped = |path: &Path| {
+                term::warn(format!("skipping {}, not a regular file", path.display()))
+            };
+            let files = match &args.files_from {
+                Some(list) => {
+                    files::listed(Path::new(src), dst, &listed(list, args.from0)?, skipped)?
+                }
+                None => files::collect_with(Path::new(src), dst, &walk, skipped)?,
+            };
+            (files, None)
+        };
+        drop(phase);
+        let files = match args.newer_than {
+            Some(_) if source.is_some() => {
+                bail!(
+                    "--newer-than goes by the times local files were modified, {} isn't one",
+                    src
+                )
+            }
+            Some(since) => newer(files, since)?,
+            None => files,
+        };
+        if args.split.is_some() && source.is_some() {
+            bail!("only local files can be split, not {}", src);
+        }
+        if args.text_mode.is_some() && source.is_some() {
+            bail!(
+                "only the line endings of local files can be converted, not those of {}",
+                src
+            );
+        }
+        let dirs = if args.keep_empty_dirs && source.is_none() {
+            empty_dirs(files::dirs(Path::new(src), dst, &walk)?, &files)
+        } else {
+            Vec::new()
+        };
+        if args.extract {
+            if let Some(file) = files.iter().find(|file| !Extract::unpacks(&file.name)) {
+                bail!("{} is not an archive --extract unpacks", file.name);
+            }
+            if args.xattrs || args.acls || args.owners().keeps() {
+                if let Some(file) = files.iter().find(|file| file.name.ends_with(".zip")) {
+                    bail!(
+                        "{} is a zip file, --xattrs, --acls and the owners need a tar archive",
+                        file.name
+                    );
+                }
+            }
+        }
+        let files = if args.decrypt.is_some() {
+            // what lands in the pods is the decrypted file
+            files
+                .into_iter()
+                .map(|mut file| {
+                    if let Some(name) = file.name.strip_suffix(".age") {
+                        file.name = name.to_string();
+                    }
+                    file
+                })
+                .collect()
+        } else {
+            files
+        };
+        // a mistyped destination can break the container
+        if !global.allow_system_paths {
+            let written = files.iter().map(FileEntry::remote_path).chain(dirs.clone());
+            for path in written {
+                if let Some(dir) = guard::system_path(&path, &global.system_paths) {
+                    bail!(
+                        "refusing to write {} into the system directory {}, pass --allow-system-paths if that is meant",
+                        path,
+                        dir
+                    );
+                }
+            }
+        }
+
+        // kube client
+        let phase = progress.phase(format!("loading kubeconfig {}", global.kubeconfig()?));
+        let session = global.session().await?;
+        drop(phase);
+        let api = session.pods(namespace);
+        let origin = session.origin(namespace, &global.container);
+
+        // a directory copy picks up where an interrupted run of it stopped
+        let journal =
+            if matches!(selection, Selection::All) && !args.no_resume && Path::new(src).is_dir() {
+                let mut pods = targets.clone();
+                pods.sort();
+                Journal::open(&[
+                    origin.context.as_deref().unwrap_or_default(),
+                    origin.cluster.as_deref().unwrap_or_default(),
+                    namespace,
+                    &global.container,
+                    &std::fs::canonicalize(src)?.to_string_lossy(),
+                    dst,
+                    &pods.join(","),
+                ])
+            } else {
+                Journal::disabled()
+            };
+        let history = if args.no_history {
+            History::disabled(origin)
+        } else {
+            History::open(global.history_file.as_deref(), origin)
+        };
+        anyhow::Ok((files, dirs, source, api, history, journal))
+    }
+    .instrument(info_span!("resolve-target", src = %src, namespace = %namespace))
+    .await?;
+
+    // what every pod gets
+    let pods: Vec<(String, Vec<FileEntry>)> = match selection {
+        Selection::All => {
+            let copied = |file: &FileEntry| targets.iter().all(|pod| journal.copied(pod, file));
+            let resumed = files.iter().filter(|file| copied(file)).count();
+            if resumed > 0 {
+                info!(
+                    "resuming an interrupted copy, {} of {} files are in the pods already, pass --no-resume to copy them again",
+                    resumed,
+                    files.len()
+                );
+            }
+            let remaining: Vec<_> = files.iter().filter(|file| !copied(file)).cloned().collect();
+            // what an archive holds isn't known before it is unpacked
+            if !args.extract {
+                let phase = progress.phase("checking destinations");
+                let pods: Vec<_> = targets
+                    .iter()
+                    .map(|pod| (pod.clone(), remaining.clone()))
+                    .collect();
+                let checked = preflight::check(
+                    &api,
+                    namespace,
+                    &pods,
+                    &global.container,
+                    global.connect_timeout,
+                )
+                .await;
+                let existing = match checked {
+                    Ok(()) if !global.yes => overwrite::check(
+                        &api,
+                        namespace,
+                        &targets,
+                        &global.container,
+                        &remaining,
+                        global.connect_timeout,
+                    )
+                    .await
+                    .context("failed to check for existing destinations"),
+                    Ok(()) => Ok(Vec::new()),
+                    Err(err) => Err(err),
+                };
+                drop(phase);
+                let existing = existing?;
+                if global.unattended && !existing.is_empty() {
+                    bail!(
+                        "{} already, overwriting needs yes",
+                        match existing.len() {
+                            1 => "1 destination exists".to_string(),
+                            n => format!("{} destinations exist", n),
+                        }
+                    );
+                }
+                overwrite::confirm(&existing)?;
+            }
+            targets
+                .iter()
+                .map(|pod| {
+                    let files = remaining
+                        .iter()
+                        .filter(|file| !journal.copied(pod, file))
+                        .cloned()
+                        .collect();
+                    (pod.clone(), files)
+                })
+                .collect()
+        }
+        Selection::Changed if args.decrypt.is_some() => {
+            bail!("encrypted files can't be compared with what they decrypt to in the pods")
+        }
+        Selection::Changed if args.extract => {
+            bail!("archives can't be compared with what they unpack to in the pods")
+        }
+        Selection::Changed if args.text_mode.is_some() => {
+            bail!("files whose line endings are converted can't be compared with the pods")
+        }
+        Selection::Changed if source.is_some() => {
+            bail!(
+                "{} can't be compared with the pods, only local files can",
+                src
+            )
+        }
+        Selection::Changed => {
+            let phase = progress.phase("comparing with the pods");
+            let changed = sync::changed(global, &api, &targets, &files).await;
+            drop(phase);
+            let changed = changed.context("failed to compare with the pods")?;
+            for (pod, changed) in &changed {
+                info!(
+                    "{}: {} of {} files to update",
+                    pod,
+                    changed.len(),
+                    files.len()
+                );
+            }
+            changed
+                .into_iter()
+                .map(|(pod, files)| (pod.clone(), files))
+                .collect()
+        }
+        Selection::Paths(paths) => {
+            let files: Vec<_> = files
+                .into_iter()
+                .filter(|file| {
+                    std::fs::canonicalize(&file.local).is_ok_and(|path| paths.contains(&path))
+                })
+                .collect();
+            targets
+                .iter()
+                .map(|pod| (pod.clone(), files.clone()))
+                .collect()
+        }
+        Selection::Retry(unfinished) => {
+            for pod in unfinished.keys().filter(|pod| !targets.contains(pod)) {
+                term::warn(format!(
+                    "{} has transfers to retry but isn't one of the pods copied to",
+                    pod
+                ));
+            }
+            targets
+                .iter()
+                .filter_map(|pod| {
+                    let dsts = unfinished.get(pod)?;
+                    let files = files
+                        .iter()
+                        .filter(|file| dsts.contains(&file.remote_path()))
+                        .cloned()
+                        .collect();
+                    Some((pod.clone(), files))
+                })
+                .collect()
+        }
+    };
+    // those of a full copy were checked before asking to overwrite them
+    if !matches!(selection, Selection::All) {
+        let phase = progress.phase("checking destinations");
+        let checked = preflight::check(
+            &api,
+            namespace,
+            &pods,
+            &global.container,
+            global.connect_timeout,
+        )
+        .await;
+        drop(phase);
+        checked?;
+    }
+    let transport: Arc<dyn Transport> = match &args.decrypt {
+        Some(identity) => Arc::new(Exec(AgeDecrypt::new(identity))),
+        None if args.extract => Arc::new(Exec(
+            Extract::default()
+                .xattrs(args.xattrs)
+                .acls(args.acls)
+                .owners(args.owners()),
+        )),
+        None => global.transport(),
+    };
+    if stream && !transport.streams() {
+        bail!(
+            "the size of {} isn't known, which the {} transport needs, exec-cat doesn't",
+            src,
+            transport.name()
+        );
+    }
+    Ok(Plan {
+        global,
+        api,
+        history,
+        journal,
+        source,
+        transport,
+        verify: args.decrypt.is_none() && !args.extract,
+        pods,
+        dirs,
+        split: args
+            .split
+            .map(|size| (size, args.part_retries.unwrap_or(PART_RETRIES))),
+        retry: (
+            args.retries.unwrap_or(0),
+            args.retry_backoff.unwrap_or(RETRY_BACKOFF),
+        ),
+        text: args.text_files(),
+        pre_exec: args.pre_exec.clone(),
+        post_exec: args.post_exec.clone(),
+        then: args.then.clone(),
+        exec_timeout: args.exec_timeout.unwrap_or(EXEC_TIMEOUT),
+        fail_fast: args.fail_fast,
+        keep_going: args.keep_going,
+        max_parallel_pods: args.max_parallel_pods.map_or(usize::MAX, usize::from),
+        wave: args.rolling,
+        wait_ready: args
+            .wait_ready
+            .then(|| args.ready_timeout.unwrap_or(READY_TIMEOUT)),
+        canary: args.canary.map(usize::from),
+        canary_check: args.canary_check.clone(),
+        relay: args.relay.then(|| args.relay_port.unwrap_or(relay::PORT)),
+        annotate: args.annotate,
+        event: args.event,
+        namespace: None,
+    })
+}
+
+/// `pods` with those of StatefulSets, `sts/NAME:ORDINAL` or `sts/NAME` and
+/// `--pod-index`, replaced by the names of their pods, once the StatefulSet
+/// is known to have the ordinal
+async fn ordinals(
+    global: &Global,
+    pods: &[String],
+    index: Option<u32>,
+) -> anyhow::Result<Vec<String>> {
+    if index.is_some() && !pods.iter().any(|pod| target::statefulset(pod).is_some()) {
+        bail!("--pod-index is the ordinal of a StatefulSet, give it as --pod sts/NAME");
+    }
+    let mut named = Vec::with_capacity(pods.len());
+    for pod in pods {
+        let Some((name, ordinal)) = target::statefulset(pod) else {
+            named.push(pod.clone());
+            continue;
+        };
+        let ordinal = match (ordinal, index) {
+            (Some(ordinal), _) => ordinal.to_string(),
+            (None, Some(index)) => index.to_string(),
+            (None, None) => bail!(
+                "{} is a StatefulSet, give the ordinal of its pod, sts/{}:0 or --pod-index 0",
+                pod,
+                name
+            ),
+        };
+        let pod = target::ordinal_pod(name, &ordinal).map_err(anyhow::Error::msg)?;
+        let api: Api<StatefulSet> =
+            Api::namespaced(global.session().await?.client.clone(), global.namespace());
+        let replicas = api
+            .get(name)
+            .await
+            .with_context(|| format!("failed to get StatefulSet {}", name))?
+            .spec
+            .and_then(|spec| spec.replicas)
+            .unwrap_or(1);
+        let ordinal: i32 = ordinal.parse().unwrap_or(i32::MAX);
+        match replicas {
+            0 => bail!("StatefulSet {} is scaled to 0, it has no pods", name),
+            _ if ordinal >= replicas => bail!(
+                "StatefulSet {} has no pod of ordinal {}, its ordinals are 0 to {}",
+                name,
+                ordinal,
+                replicas - 1
+            ),
+            _ => {}
+        }
+        named.push(pod);
+    }
+    Ok(named)
+}
+
+/// `dst` resolved in `pods`, see [`target::path`], which have to agree on
+/// what it is
+async fn resolve(global: &Global, pods: &[String], dst: &str) -> anyhow::Result<String> {
+    let api = global.session().await?.pods(global.namespace());
+    let cancel = CancellationToken::new();
+    let mut resolved: Option<(&str, String)> = None;
+    for pod in pods {
+        let path = target::path(global, &api, pod, dst, &cancel)
+            .await
+            .with_context(|| format!("failed to resolve {} in {}", dst, pod))?;
+        match &resolved {
+            Some((first, other)) if *other != path => bail!(
+                "{} is {} in {} but {} in {}, pass the path it is to copy to both",
+                dst,
+                other,
+                first,
+                path,
+                pod
+            ),
+            Some(_) => {}
+            None => resolved = Some((pod, path)),
+        }
+    }
+    Ok(resolved
+        .map(|(_, path)| path)
+        .unwrap_or_else(|| dst.to_string()))
+}
+
+/// The `dirs` that none of `files` are in, at any depth
+fn empty_dirs(dirs: Vec<String>, files: &[FileEntry]) -> Vec<String> {
+    let full: HashSet<&str> = files
+        .iter()
+        .flat_map(|file| {
+            // the directory of the file and those above it
+            std::iter::successors(Some(file.remote_dir.as_str()), |dir| {
+                dir.rsplit_once('/').map(|(parent, _)| parent)
+            })
+        })
+        .collect();
+    dirs.into_iter()
+        .filter(|dir| !full.contains(dir.as_str()))
+        .collect()
+}
+
+/// The `files` modified after `since`
+fn newer(files: Vec<FileEntry>, since: SystemTime) -> anyhow::Result<Vec<FileEntry>> {
+    let count = files.len();
+    let mut newer = Vec::new();
+    for file in files {
+        let modified = std::fs::metadata(&file.local)
+            .and_then(|meta| meta.modified())
+            .with_context(|| format!("failed to read the mtime of {}", file.local.display()))?;
+        if modified > since {
+            newer.push(file);
+        }
+    }
+    info!(
+        "{} of {} files were modified after {}",
+        newer.len(),
+        count,
+        humantime::format_rfc3339_seconds(since)
+    );
+    Ok(newer)
+}
+
+/// The paths the file `list` of `--files-from` holds, separated by newlines
+/// or else by NUL, `-` is stdin
+fn listed(list: &Path, from0: bool) -> anyhow::Result<Vec<PathBuf>> {
+    let data = if list == Path::new("-") {
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut data)
+            .context("failed to read the list of files from stdin")?;
+        data
+    } else {
+        std::fs::read(list).with_context(|| format!("failed to read {}", list.display()))?
+    };
+    let paths = if from0 {
+        data.split(|&b| b == 0).collect::<Vec<_>>()
+    } else {
+        data.split(|&b| b == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .collect()
+    };
+    Ok(paths
+        .into_iter()
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(String::from_utf8_lossy(path).into_owned()))
+        .collect())
+}
+
+/// The file at the URL `src` and where it goes in `dst`
+async fn object(global: &Global, src: &str, dst: &str) -> anyhow::Result<(FileEntry, Object)> {
+    let object = Object::new(src)?.connect_timeout(global.connect_timeout)?;
+    let Some(entry) = object.entries().await?.pop() else {
+        unreachable!()
+    };
+    let Some(size) = entry.size else {
+        bail!(
+            "the size of {} isn't known, the server sent no Content-Length",
+            src
+        );
+    };
+    let file = FileEntry {
+        // the URL, shown in the progress, the report and the history
+        local: PathBuf::from(src),
+        remote_dir: dst.to_string(),
+        name: entry.path,
+        size,
+    };
+    Ok((file, object))
+}
+
+/// Sends what `plan` has, the pods concurrently and the files of a pod one
+/// after another. With `--canary` and `--rolling` a wave of pods is done
+/// before the next starts, and none does after a wave that failed.
+pub async fn send(
+    plan: &Plan<'_>,
+    cancel: &CancellationToken,
+    progress: &Arc<Progress>,
+    report: &Report,
+) -> anyhow::Result<()> {
+    // what --fail-fast stops, Ctrl-C stops it too
+    let stop = cancel.child_token();
+    let first = std::sync::Mutex::new(None);
+    let waves = plan.waves();
+    // the wave that failed
+    let mut stopped = None;
+    // the pods a --relay sends from, those that have the files
+    let mut sources: Vec<&str> = Vec::new();
+    let mut results = Vec::with_capacity(plan.pods.len());
+    for (n, wave) in waves.iter().enumerate() {
+        if stopped.is_some() {
+            for (pod, files) in wave.iter() {
+                for file in files {
+                    report.skipped(&plan.shown(pod), file);
+                }
+                // skipped rather than failed, the wave that failed says why
+                results.push(Ok(()));
+            }
+            continue;
+        }
+        let sends: Vec<_> = wave
+            .iter()
+            .enumerate()
+            .map(|(i, (pod, files))| {
+                let (stop, first) = (&stop, &first);
+                let from = (!sources.is_empty()).then(|| sources[i % sources.len()]);
+                async move {
+                    let res = send_to(plan, pod, files, from, stop, progress, report).await;
+                    if res.is_err() && plan.fail_fast && !stop.is_cancelled() {
+                        *first.lock().unwrap() = Some(plan.shown(pod));
+                        stop.cancel();
+                    }
+                    res
+                }
+            })
+            .collect();
+        let mut wave_results: Vec<_> = stream::iter(sends)
+            .buffered(plan.max_parallel_pods)
+            .collect()
+            .await;
+        if let Some(timeout) = plan.wait_ready {
+            let _phase = progress.phase(format!("waiting for wave {} to be Ready", n + 1));
+            let stop = &stop;
+            let waits = wave
+                .iter()
+                .zip(&wave_results)
+                .map(|((pod, files), res)| async move {
+                    match res {
+                        Ok(()) if !files.is_empty() => {
+                            rolling::wait_ready(&plan.api, pod, timeout, stop).await
+                        }
+                        _ => Ok(()),
+                    }
+                });
+            let ready = future::join_all(waits).await;
+            for (res, ready) in wave_results.iter_mut().zip(ready) {
+                if res.is_ok() {
+                    *res = ready;
+                }
+            }
+        }
+        let canary = n == 0 && plan.canary.is_some();
+        if let Some(command) = plan.canary_check.as_ref().filter(|_| canary) {
+            let stop = &stop;
+            let checks = wave
+                .iter()
+                .zip(&wave_results)
+                .map(|((pod, _), res)| async move {
+                    match res {
+                        Ok(()) => hook(plan, pod, "canary-check", command, stop, progress).await,
+                        Err(_) => Ok(()),
+                    }
+                });
+            let checked = future::join_all(checks).await;
+            for (res, checked) in wave_results.iter_mut().zip(checked) {
+                if res.is_ok() {
+                    *res = checked;
+                }
+            }
+        }
+        let seed = n == 0 && plan.relay.is_some();
+        if (plan.wave.is_some() || canary || seed) && wave_results.iter().any(Result::is_err) {
+            stopped = Some(n + 1);
+        }
+        if plan.relay.is_some() {
+            let relayed = wave
+                .iter()
+                .zip(&wave_results)
+                .filter(|(_, res)| res.is_ok());
+            sources.extend(relayed.map(|((pod, _), _)| pod.as_str()));
+        }
+        results.extend(wave_results);
+    }
+
+    let failed = results.iter().filter(|res| res.is_err()).count();
+    if failed == 0 {
+        plan.journal.finish();
+        return rollout_restart(plan).await;
+    }
+    for ((pod, _), res) in plan.pods.iter().zip(&results) {
+        if let Err(err) = res {
+            report.pod_failed(&plan.shown(pod), err);
+        }
+    }
+    if plan.count() == 1 {
+        return results.into_iter().find(Result::is_err).unwrap();
+    }
+    for ((pod, _), res) in plan.pods.iter().zip(&results) {
+        if let Err(err) = res {
+            let pod = plan.shown(pod);
+            let tally = report.tally(Some(&pod));
+            progress.suspend(|| term::error(format!("{}: {:#} ({})", pod, err, tally)));
+        }
+    }
+    let tally = report.tally(None);
+    if let Some(pod) = first
+        .into_inner()
+        .unwrap()
+        .filter(|_| !cancel.is_cancelled())
+    {
+        bail!("copy stopped at the first failure, in {}, {}", pod, tally);
+    }
+    if stopped == Some(1) && waves.len() > 1 {
+        let others = match results.len() - waves[0].len() {
+            1 => "the other pod".to_string(),
+            n => format!("the other {} pods", n),
+        };
+        if plan.canary.is_some() {
+            bail!("canary failed, {} got nothing, {}", others, tally);
+        }
+        if plan.relay.is_some() {
+            let seed = plan.shown(&waves[0][0].0);
+            bail!(
+                "the copy to {}, which the others relay from, failed, {} got nothing, {}",
+                seed,
+                others,
+                tally
+            );
+        }
+    }
+    if let Some(wave) = stopped.filter(|&wave| wave < waves.len()) {
+        bail!(
+            "rollout stopped at wave {} of {}, copy failed on {} of {} pods, {}",
+            wave,
+            waves.len(),
+            failed,
+            results.len(),
+            tally
+        );
+    }
+    if results.len() == 1 {
+        bail!("copy failed, {}", tally);
+    }
+    bail!(
+        "copy failed on {} of {} pods, {}",
+        failed,
+        results.len(),
+        tally
+    );
+}
+
+/// Copies `files` to `pod` between the `--pre-exec` and `--post-exec`
+/// commands, neither runs in a pod that gets no files. They are relayed from
+/// the pod `from` if given.
+async fn send_to(
+    plan: &Plan<'_>,
+    pod: &str,
+    files: &[FileEntry],
+    from: Option<&str>,
+    cancel: &CancellationToken,
+    progress: &Arc<Progress>,
+    report: &Report,
+) -> anyhow::Result<()> {
+    if files.is_empty() && plan.dirs.is_empty() {
+        return Ok(());
+    }
+    // stopped while it waited for --max-parallel-pods
+    let shown = plan.shown(pod);
+    if cancel.is_cancelled() {
+        for file in files {
+            report.skipped(&shown, file);
+        }
+        return Err(Error::Cancelled.into());
+    }
+    if let Some(command) = &plan.pre_exec {
+        if let Err(err) = hook(plan, pod, "pre-exec", command, cancel, progress).await {
+            for file in files {
+                report.skipped(&shown, file);
+            }
+            return Err(err);
+        }
+    }
+    if !plan.dirs.is_empty() {
+        make_dirs(plan, pod, cancel).await?;
+    }
+    match (from, plan.relay) {
+        (Some(from), Some(port)) => {
+            relay_files(
+                plan, from, pod, &shown, files, port, cancel, progress, report,
+            )
+            .await?
+        }
+        _ => {
+            upload_files(
+                &plan.api,
+                pod,
+                &shown,
+                plan.source.as_ref(),
+                &*plan.transport,
+                plan.verify,
+                plan.split,
+                plan.retry,
+                plan.text.as_ref(),
+                plan.keep_going,
+                files,
+                plan.global,
+                progress,
+                &plan.history,
+                &plan.journal,
+                report,
+                cancel,
+            )
+            .await?;
+        }
+    }
+    if let Some(command) = &plan.post_exec {
+        hook(plan, pod, "post-exec", command, cancel, progress).await?;
+    }
+    for then in &plan.then {
+        if let Some(command) = then.command() {
+            hook(plan, pod, &then.to_string(), &command, cancel, progress).await?;
+        }
+    }
+    if plan.annotate || plan.event {
+        audit(plan, pod, &shown, files, progress, report).await;
+    }
+    Ok(())
+}
+
+/// Sets the annotation of `--annotate` and records the Event of `--event` on
+/// `pod` once `files` were copied to it. A failure is a warning, the files
+/// are there.
+async fn audit(
+    plan: &Plan<'_>,
+    pod: &str,
+    shown: &str,
+    files: &[FileEntry],
+    progress: &Progress,
+    report: &Report,
+) {
+    let dsts: HashSet<_> = files.iter().map(FileEntry::remote_path).collect();
+    let copied: Vec<_> = report
+        .completed(shown)
+        .into_iter()
+        .filter(|(dst, _)| dsts.contains(dst))
+        .collect();
+    if copied.is_empty() {
+        return;
+    }
+    let digest = audit::digest(&copied);
+    let res = async {
+        if plan.annotate {
+            audit::annotate(&plan.api, pod, &digest)
+                .await
+                .context("failed to annotate the pod")?;
+        }
+        if plan.event {
+            let what = match copied.len() {
+                1 => "1 file".to_string(),
+                n => format!("{} files", n),
+            };
+            let bytes: u64 = files.iter().map(|file| file.size).sum();
+            let message = format!(
+                "k8scp copied {} ({}), digest {}",
+                what,
+                HumanBytes(bytes),
+                digest
+            );
+            let client = plan.global.session().await?.client.clone();
+            let pod = plan.api.get(pod).await?;
+            audit::event(client, &pod, message)
+                .await
+                .context("failed to record an Event")?;
+        }
+        anyhow::Ok(())
+    }
+    .await;
+    if let Err(err) = res {
+        progress.suspend(|| term::warn(format!("{}: {:#}", shown, err)));
+    }
+}
+
+/// Makes the empty directories of the plan in `pod`
+async fn make_dirs(plan: &Plan<'_>, pod: &str, cancel: &CancellationToken) -> anyhow::Result<()> {
+    // a command line holds so many of them
+    for dirs in plan.dirs.chunks(256) {
+        let command = dirs.iter().fold("mkdir -p --".to_string(), |command, dir| {
+            format!("{} {}", command, exec::quote(dir))
+        });
+        let command = exec::umasked(plan.global.umask, &command);
+        exec::run(
+            &plan.api,
+            pod,
+            &plan.global.container,
+            &command,
+            plan.global.connect_timeout,
+            plan.global.idle_timeout,
+            cancel,
+        )
+        .await
+        .with_context(|| format!("failed to make the empty directories in {}", pod))?;
+    }
+    info!("{}: made {} empty directories", pod, plan.dirs.len());
+    Ok(())
+}
+
+/// Restarts the workloads of the pods that got files if `--then
+/// rollout-restart` says so
+async fn rollout_restart(plan: &Plan<'_>) -> anyhow::Result<()> {
+    if !plan.then.contains(&Then::RolloutRestart) {
+        return Ok(());
+    }
+    let pods: Vec<_> = plan
+        .pods
+        .iter()
+        .filter(|(_, files)| !files.is_empty())
+        .map(|(pod, _)| pod.as_str())
+        .collect();
+    if pods.is_empty() {
+        return Ok(());
+    }
+    let global = plan.global;
+    let client = global.session().await?.client.clone();
+    for workload in crate::then::rollout_restart(client, global.namespace(), &pods).await? {
+        if !global.quiet {
+            term::success(format!("restarted {}", workload));
+        }
+    }
+    Ok(())
+}
+
+/// Runs the `--pre-exec`, `--post-exec` or `--then` (`name`) `command` in
+/// `pod`, logging what it prints
+async fn hook(
+    plan: &Plan<'_>,
+    pod: &str,
+    name: &str,
+    command: &str,
+    cancel: &CancellationToken,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    let global = plan.global;
+    // a hook may print nothing until it is done
+    let output = exec::run(
+        &plan.api,
+        pod,
+        &global.container,
+        command,
+        plan.exec_timeout,
+        plan.exec_timeout,
+        cancel,
+    )
+    .instrument(info_span!("hook", pod = %pod, name = %name))
+    .await
+    .with_context(|| format!("{} failed", name))?;
+    progress.suspend(|| {
+        for line in output.stdout.lines().chain(output.stderr.lines()) {
+            info!("{}: {}: {}", pod, name, line);
+        }
+    });
+    Ok(())
+}
+
+/// Relays `files` to `pod`, which the report and progress show as `shown`,
+/// from the pod `from` that has them, see [`relay::relay`]
+#[allow(clippy::too_many_arguments)]
+async fn relay_files(
+    plan: &Plan<'_>,
+    from: &str,
+    pod: &str,
+    shown: &str,
+    files: &[FileEntry],
+    port: u16,
+    cancel: &CancellationToken,
+    progress: &Arc<Progress>,
+    report: &Report,
+) -> anyhow::Result<()> {
+    let items: Vec<_> = files
+        .iter()
+        .map(|file| {
+            let src = format!("{}:{}", from, file.remote_path());
+            progress.item(&src, shown, &file.remote_path(), file.size)
+        })
+        .collect();
+    let time = SystemTime::now();
+    let started = Instant::now();
+    let relayed = relay::relay(
+        &plan.api,
+        from,
+        pod,
+        &plan.global.container,
+        files,
+        port,
+        cancel,
+    )
+    .instrument(info_span!("relay", from = %from, pod = %pod, files = files.len()))
+    .await;
+    let relayed = match relayed {
+        Ok(relayed) => relayed,
+        Err(err) => {
+            for (file, item) in files.iter().zip(&items) {
+                item.fail(&err);
+                let res = Err(anyhow::anyhow!("{:#}", err));
+                report.record(shown, file, 0, started.elapsed(), &res);
+            }
+            return Err(err);
+        }
+    };
+    let mut failed: Vec<anyhow::Error> = Vec::new();
+    for ((file, item), res) in files.iter().zip(&items).zip(relayed) {
+        if res.is_ok() {
+            item.set_position(file.size);
+        }
+        plan.history.record(time, pod, file, item.position(), &res);
+        if let Ok(sha256) = &res {
+            plan.journal.record(pod, file, sha256);
+        }
+        report.record(shown, file, item.position(), started.elapsed(), &res);
+        let res = res.map(drop);
+        METRICS.record(&res, item.position(), started.elapsed());
+        match res {
+            Ok(()) => item.finish(),
+            Err(err) => {
+                item.fail(&err);
+                failed.push(err);
+            }
+        }
+    }
+    match failed.len() {
+        0 => Ok(()),
+        n => Err(failed.swap_remove(0).context(format!(
+            "{} of {} files failed to relay from {}, the first",
+            n,
+            files.len(),
+            from
+        ))),
+    }
+}
+
+/// Copies `files` to `pod`, which the report and progress show as `shown`,
+/// one after another, stopping at the first failure unless `keep_going`, or
+/// when `cancel`led. They are read from `source` if given, from their local paths otherwise,
+/// in parts if `split` gives their size and retries, sent again as `retry`
+/// says when that fails, the line endings of those `text` takes as text
+/// converted. With `verify` each upload is
+/// checked against the pod, parts are checked as they go either way.
+#[allow(clippy::too_many_arguments)]
+async fn upload_files(
+    pods: &Api<Pod>,
+    pod: &str,
+    shown: &str,
+    source: Option<&Object>,
+    transport: &dyn Transport,
+    verify: bool,
+    split: Option<(u64, u32)>,
+    retry: (u32, Duration),
+    text: Option<&TextFiles>,
+    keep_going: bool,
+    files: &[FileEntry],
+    global: &Global,
+    progress: &Arc<Progress>,
+    history: &History,
+    journal: &Journal,
+    report: &Report,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    let mut failed: Vec<anyhow::Error> = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        if cancel.is_cancelled() {
+            for file in &files[i..] {
+                report.skipped(shown, file);
+            }
+            return Err(failed.into_iter().next().unwrap_or(Error::Cancelled.into()));
+        }
+        let item = progress.item(
+            &file.local.to_string_lossy(),
+            shown,
+            &file.remote_path(),
+            file.size,
+        );
+        let time = SystemTime::now();
+        let started = Instant::now();
+        METRICS.active.inc();
+        let opts = ExecOptions {
+            container: &global.container,
+            connect_timeout: global.connect_timeout,
+            idle_timeout: global.idle_timeout,
+            cancel: cancel.clone(),
+            transport,
+            umask: global.umask,
+            text_mode: text.and_then(|text| text.mode_of(&file.name)),
+            system_paths: Some(&global.system_paths),
+            allow_system_paths: global.allow_system_paths,
+            retries: retry.0,
+            retry_backoff: retry.1,
+            ..ExecOptions::default()
+        };
+        let res = async {
+            let dst = file.remote_path();
+            let digest = match source {
+                Some(source) => {
+                    let entry = Entry {
+                        path: file.name.clone(),
+                        size: Some(file.size),
+                    };
+                    copy_source_to_pod(pods, pod, source, &entry, &dst, &opts, item.clone()).await?
+                }
+                None => match split {
+                    Some((part_size, retries)) => {
+                        copy_split_to_pod(
+                            pods,
+                            pod,
+                            &file.local,
+                            &file.remote_path(),
+                            part_size,
+                            retries,
+                            &opts,
+                            item.clone(),
+                        )
+                        .await?
+                    }
+                    None => copy_to_pod(pods, pod, &file.local, &dst, &opts, item.clone()).await?,
+                },
+            };
+            // a split copy checked its joined parts before moving them to `dst`
+            if verify {
+                if source.is_some() || split.is_none() {
+                    verify_to_pod(pods, pod, &dst, &digest, &opts).await?;
+                }
+                item.verified(&digest);
+            }
+            Ok(digest)
+        }
+        .instrument(info_span!(
+            "transfer",
+            pod = %pod,
+            src = %file.local.display(),
+            dst = %file.remote_path(),
+            bytes = file.size
+        ))
+        .await;
+        let res = match res {
+            Err(err) => Err(diagnose::explain(
+                pods,
+                global.namespace(),
+                pod,
+                &global.container,
+                global.connect_timeout,
+                err,
+            )
+            .await),
+            res => res,
+        }
+        .map_err(anyhow::Error::from);
+        METRICS.active.dec();
+        history.record(time, pod, file, item.position(), &res);
+        if let Ok(sha256) = &res {
+            journal.record(pod, file, sha256);
+        }
+        report.record(shown, file, item.position(), started.elapsed(), &res);
+        let res = res.map(drop);
+        METRICS.record(&res, item.position(), started.elapsed());
+        match &res {
+            Ok(()) => item.finish(),
+            Err(err) => item.fail(err),
+        }
+        match res {
+            Err(err) if keep_going && !cancel.is_cancelled() => failed.push(err),
+            Err(err) => {
+                for file in &files[i + 1..] {
+                    report.skipped(shown, file);
+                }
+                return Err(err);
+            }
+            Ok(()) => {}
+        }
+    }
+    match failed.len() {
+        0 => Ok(()),
+        n => Err(failed.swap_remove(0).context(format!(
+            "{} of {} files failed, the first",
+            n,
+            files.len()
+        ))),
+    }
+}
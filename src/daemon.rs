@@ -0,0 +1,627 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    convert::Infallible,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::{anyhow, bail, Context as _};
+use futures::future;
+use hyper::{
+    header::AUTHORIZATION, server::conn::Http, service::service_fn, Body, Method, Request,
+    Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::Notify,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use k8scp::{endpoint::Object, progress::Progress, redact};
+
+use crate::{
+    cp::{self, CpArgs, Selection},
+    manifest::{self, Entry},
+    pick::Pick,
+    report::Report,
+    Global,
+};
+
+/// Stay running and copy what other tools submit over a local HTTP API
+#[derive(clap::Args, Debug)]
+pub struct DaemonArgs {
+    /// Unix socket to listen on [default: k8scp.sock in the user runtime directory]
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Listen on this TCP address instead, requests copy with your credentials and must present --token
+    #[arg(long, conflicts_with = "socket", requires = "token")]
+    listen: Option<SocketAddr>,
+
+    /// Token every request must send as `Authorization: Bearer TOKEN`, required with --listen
+    #[arg(long, env = "K8SCP_DAEMON_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Where the transfers not done yet are kept for the next start [default: k8scp/queue.json in the user data directory]
+    #[arg(long)]
+    queue_file: Option<PathBuf>,
+
+    /// How many transfers run at once
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    concurrency: u16,
+}
+
+/// --token is at least this long, anything shorter is guessed
+const MIN_TOKEN: usize = 16;
+
+/// A transfer submitted to the daemon, a manifest entry with what the
+/// command line would otherwise give
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+struct Submission {
+    src: String,
+    dst: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pods: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selector: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transport: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pick: Option<Pick>,
+    /// Copy only what differs, like `sync`
+    #[serde(default)]
+    sync: bool,
+    /// Overwrite existing destinations, there is no one to ask, a transfer
+    /// without it fails on them
+    #[serde(default)]
+    yes: bool,
+    /// Queued transfers with a higher priority run first, those of the same
+    /// priority in the order they were submitted
+    #[serde(default)]
+    priority: i32,
+}
+
+/// What a `PATCH` of a queued transfer changes
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Change {
+    priority: i32,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum State {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+struct Job {
+    transfer: Submission,
+    state: State,
+    error: Option<String>,
+    cancel: CancellationToken,
+    /// Once running
+    progress: Option<Arc<Progress>>,
+    report: Arc<Report>,
+}
+
+/// What the queue file holds, the transfers that are queued or were
+/// running when the daemon stopped and the id the next one gets
+#[derive(Deserialize, Serialize, Default)]
+struct Queue {
+    next_id: u64,
+    transfers: Vec<Queued>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Queued {
+    id: u64,
+    transfer: Submission,
+}
+
+struct Daemon {
+    global: Global,
+    queue_file: PathBuf,
+    jobs: Mutex<BTreeMap<u64, Job>>,
+    /// The id of the next transfer, never one a transfer had before, even
+    /// one done and forgotten since
+    next_id: AtomicU64,
+    queued: Notify,
+    /// What requests authenticate with, if they have to
+    token: Option<String>,
+    /// Ctrl-C, running transfers stop and stay queued
+    shutdown: CancellationToken,
+}
+
+pub async fn run(global: &Global, args: &DaemonArgs) -> anyhow::Result<()> {
+    if let Some(token) = &args.token {
+        if token.len() < MIN_TOKEN {
+            bail!(
+                "--token is too short, give at least {} characters",
+                MIN_TOKEN
+            );
+        }
+        redact::secret(token.clone());
+    }
+    let queue_file = match &args.queue_file {
+        Some(path) => path.clone(),
+        None => default_queue_file()
+            .ok_or_else(|| anyhow!("no user data directory for the queue, pass --queue-file"))?,
+    };
+    let queue = load(&queue_file)?;
+    if !queue.transfers.is_empty() {
+        info!(
+            "{} transfers queued from the last run",
+            queue.transfers.len()
+        );
+    }
+    // connected once for all transfers
+    global.session().await?;
+    let shutdown = cp::interrupt();
+    let next_id = queue
+        .transfers
+        .iter()
+        .map(|queued| queued.id + 1)
+        .fold(queue.next_id.max(1), u64::max);
+    let jobs = queue
+        .transfers
+        .into_iter()
+        .map(|queued| (queued.id, Job::new(queued.transfer, &shutdown)))
+        .collect();
+    let daemon = Arc::new(Daemon {
+        global: global.clone(),
+        queue_file,
+        jobs: Mutex::new(jobs),
+        next_id: AtomicU64::new(next_id),
+        queued: Notify::new(),
+        token: args.token.clone(),
+        shutdown,
+    });
+
+    let workers: Vec<_> = (0..args.concurrency)
+        .map(|_| tokio::spawn(daemon.clone().work()))
+        .collect();
+    match args.listen {
+        Some(addr) => {
+            let listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("failed to listen on {}", addr))?;
+            info!("listening on http://{}", listener.local_addr()?);
+            loop {
+                tokio::select! {
+                    _ = daemon.shutdown.cancelled() => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => serve(daemon.clone(), stream),
+                        Err(err) => warn!("failed to accept connection: {}", err),
+                    },
+                }
+            }
+        }
+        None => listen_unix(&daemon, args.socket.clone()).await?,
+    }
+    future::join_all(workers).await;
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn listen_unix(daemon: &Arc<Daemon>, socket: Option<PathBuf>) -> anyhow::Result<()> {
+    use tokio::net::UnixStream;
+
+    let socket = socket.unwrap_or_else(|| {
+        dirs::runtime_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("k8scp.sock")
+    });
+    if socket.exists() {
+        if UnixStream::connect(&socket).await.is_ok() {
+            bail!("a daemon is already listening on {}", socket.display());
+        }
+        // left behind by one that didn't stop cleanly
+        fs::remove_file(&socket)
+            .with_context(|| format!("failed to remove {}", socket.display()))?;
+    }
+    let listener = bind_private(&socket)
+        .with_context(|| format!("failed to listen on {}", socket.display()))?;
+    info!("listening on {}", socket.display());
+    loop {
+        tokio::select! {
+            _ = daemon.shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => serve(daemon.clone(), stream),
+                Err(err) => warn!("failed to accept connection: {}", err),
+            },
+        }
+    }
+    let _ = fs::remove_file(&socket);
+    Ok(())
+}
+
+/// Binds `socket` in a directory of its own only we can enter, and moves
+/// it into place once only we can connect, whoever can connect copies with
+/// our credentials
+#[cfg(unix)]
+fn bind_private(socket: &Path) -> anyhow::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let dir = socket.with_file_name(format!(".k8scp-sock-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let staged = dir.join("sock");
+    let bound = tokio::net::UnixListener::bind(&staged)
+        .map_err(anyhow::Error::from)
+        .and_then(|listener| {
+            fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+            fs::rename(&staged, socket)?;
+            Ok(listener)
+        });
+    let _ = fs::remove_dir_all(&dir);
+    bound
+}
+
+#[cfg(not(unix))]
+async fn listen_unix(_: &Arc<Daemon>, _: Option<PathBuf>) -> anyhow::Result<()> {
+    bail!("no Unix sockets on this platform, pass --listen")
+}
+
+/// Answers the requests of a connection in the background
+fn serve<S>(daemon: Arc<Daemon>, stream: S)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let service = service_fn(move |req| {
+            let daemon = daemon.clone();
+            async move { Ok::<_, Infallible>(daemon.handle(req).await) }
+        });
+        if let Err(err) = Http::new()
+            .http1_only(true)
+            .serve_connection(stream, service)
+            .await
+        {
+            debug!("connection failed: {}", err);
+        }
+    });
+}
+
+impl Daemon {
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if !self.authorized(&req) {
+            return error(StatusCode::UNAUTHORIZED, "missing or wrong bearer token");
+        }
+        let path = req.uri().path().trim_end_matches('/').to_string();
+        let id = path
+            .strip_prefix("/transfers/")
+            .map(|id| id.parse::<u64>().ok());
+        match (req.method(), path.as_str(), id) {
+            (&Method::GET, "/transfers", _) => {
+                let jobs = self.jobs.lock().unwrap();
+                let all: Vec<_> = jobs.iter().map(|(id, job)| status(*id, job)).collect();
+                respond(StatusCode::OK, Value::Array(all))
+            }
+            (&Method::POST, "/transfers", _) => {
+                let body = match hyper::body::to_bytes(req.into_body()).await {
+                    Ok(body) => body,
+                    Err(err) => return error(StatusCode::BAD_REQUEST, err),
+                };
+                match serde_json::from_slice(&body) {
+                    Ok(transfer) => match self.submit(transfer) {
+                        Ok(status) => respond(StatusCode::CREATED, status),
+                        Err(err) => error(StatusCode::BAD_REQUEST, format!("{:#}", err)),
+                    },
+                    Err(err) => error(StatusCode::BAD_REQUEST, err),
+                }
+            }
+            (&Method::GET, _, Some(Some(id))) => match self.jobs.lock().unwrap().get(&id) {
+                Some(job) => respond(StatusCode::OK, status(id, job)),
+                None => error(StatusCode::NOT_FOUND, format!("no transfer {}", id)),
+            },
+            (&Method::PATCH, _, Some(Some(id))) => {
+                let body = match hyper::body::to_bytes(req.into_body()).await {
+                    Ok(body) => body,
+                    Err(err) => return error(StatusCode::BAD_REQUEST, err),
+                };
+                match serde_json::from_slice(&body) {
+                    Ok(change) => self.change(id, change),
+                    Err(err) => error(StatusCode::BAD_REQUEST, err),
+                }
+            }
+            (&Method::DELETE, _, Some(Some(id))) => self.cancel(id),
+            (_, "/transfers", _) | (_, _, Some(Some(_))) => {
+                error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+            }
+            _ => error(StatusCode::NOT_FOUND, format!("no such path {}", path)),
+        }
+    }
+
+    /// Whether `req` has the token, if one is needed, compared in constant
+    /// time so how long it takes doesn't give it away
+    fn authorized(&self, req: &Request<Body>) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let given = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        given.len() == token.len()
+            && given
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+
+    fn submit(&self, transfer: Submission) -> anyhow::Result<Value> {
+        if !Path::new(&transfer.src).is_absolute() && !Object::is_url(&transfer.src) {
+            bail!("src must be an absolute path or a URL");
+        }
+        if transfer.pods.is_empty() && transfer.selector.is_none() {
+            bail!("give pods or a selector");
+        }
+        if let Some(name) = &transfer.transport {
+            crate::transport(name).map_err(|err| anyhow!("{}: {}", name, err))?;
+        }
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        info!(id, src = %transfer.src, dst = %transfer.dst, "queued");
+        jobs.insert(id, Job::new(transfer, &self.shutdown));
+        self.persist(&jobs);
+        self.queued.notify_one();
+        Ok(status(id, &jobs[&id]))
+    }
+
+    fn change(&self, id: u64, change: Change) -> Response<Body> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(job) = jobs.get_mut(&id) else {
+            return error(StatusCode::NOT_FOUND, format!("no transfer {}", id));
+        };
+        if job.state != State::Queued {
+            return error(
+                StatusCode::CONFLICT,
+                format!("transfer {} isn't queued any more", id),
+            );
+        }
+        job.transfer.priority = change.priority;
+        info!(id, priority = change.priority, "reprioritized");
+        self.persist(&jobs);
+        respond(StatusCode::OK, status(id, &jobs[&id]))
+    }
+
+    fn cancel(&self, id: u64) -> Response<Body> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(job) = jobs.get_mut(&id) else {
+            return error(StatusCode::NOT_FOUND, format!("no transfer {}", id));
+        };
+        match job.state {
+            State::Queued => {
+                job.state = State::Cancelled;
+                self.persist(&jobs);
+            }
+            // stops and is marked cancelled once its partial files are removed
+            State::Running => job.cancel.cancel(),
+            _ => return error(StatusCode::CONFLICT, format!("transfer {} is done", id)),
+        }
+        info!(id, "cancelled");
+        respond(StatusCode::OK, status(id, &jobs[&id]))
+    }
+
+    /// Runs queued transfers one after another, by priority, until shut down
+    async fn work(self: Arc<Self>) {
+        loop {
+            let next = {
+                let mut jobs = self.jobs.lock().unwrap();
+                jobs.iter_mut()
+                    .filter(|(_, job)| job.state == State::Queued)
+                    .max_by_key(|(id, job)| (job.transfer.priority, Reverse(**id)))
+                    .map(|(id, job)| {
+                        let progress = Arc::new(Progress::new());
+                        job.state = State::Running;
+                        job.progress = Some(progress.clone());
+                        let run = (
+                            job.transfer.clone(),
+                            job.cancel.clone(),
+                            progress,
+                            job.report.clone(),
+                        );
+                        (*id, run)
+                    })
+            };
+            let Some((id, (transfer, cancel, progress, report))) = next else {
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => return,
+                    _ = self.queued.notified() => continue,
+                }
+            };
+            info!(id, "started");
+            let res = self.transfer(&transfer, &cancel, &progress, &report).await;
+            if self.shutdown.is_cancelled() {
+                // still in the queue file for the next start
+                return;
+            }
+            let mut jobs = self.jobs.lock().unwrap();
+            let job = jobs.get_mut(&id).unwrap();
+            match res {
+                Ok(()) => {
+                    progress.finish();
+                    info!(id, "completed");
+                    job.state = State::Completed;
+                }
+                Err(err) => {
+                    progress.fail(&err);
+                    warn!(id, "failed: {:#}", err);
+                    job.state = if cancel.is_cancelled() {
+                        State::Cancelled
+                    } else {
+                        State::Failed
+                    };
+                    job.error = Some(format!("{:#}", err));
+                }
+            }
+            self.persist(&jobs);
+        }
+    }
+
+    async fn transfer(
+        &self,
+        transfer: &Submission,
+        cancel: &CancellationToken,
+        progress: &Arc<Progress>,
+        report: &Report,
+    ) -> anyhow::Result<()> {
+        let mut global = self.global.clone();
+        global.yes |= transfer.yes;
+        // nobody answers a prompt in here
+        global.unattended = true;
+        let args = CpArgs::default();
+        let copies = manifest::resolve(&global, &args, Path::new(""), &transfer.entry()).await?;
+        let selection = match transfer.sync {
+            true => Selection::Changed,
+            false => Selection::All,
+        };
+        // a copy per namespace of the selector
+        let mut plans = Vec::with_capacity(copies.len());
+        for (global, args) in &copies {
+            plans.push(cp::plan(global, args, &selection, progress).await?);
+        }
+        manifest::show_namespaces(&mut plans);
+        progress.start(
+            plans.iter().map(cp::Plan::count).sum(),
+            plans.iter().map(cp::Plan::size).sum(),
+        );
+        for plan in &plans {
+            cp::send(plan, cancel, progress, report).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the transfers not done yet to the queue file, a failure is
+    /// logged rather than failing the request
+    fn persist(&self, jobs: &BTreeMap<u64, Job>) {
+        let queue = Queue {
+            next_id: self.next_id.load(Ordering::Relaxed),
+            transfers: jobs
+                .iter()
+                .filter(|(_, job)| matches!(job.state, State::Queued | State::Running))
+                .map(|(id, job)| Queued {
+                    id: *id,
+                    transfer: job.transfer.clone(),
+                })
+                .collect(),
+        };
+        if let Err(err) = save(&self.queue_file, &queue) {
+            warn!(
+                "failed to write queue {}: {:#}",
+                self.queue_file.display(),
+                err
+            );
+        }
+    }
+}
+
+impl Job {
+    fn new(transfer: Submission, shutdown: &CancellationToken) -> Job {
+        Job {
+            transfer,
+            state: State::Queued,
+            error: None,
+            cancel: shutdown.child_token(),
+            progress: None,
+            report: Arc::new(Report::new()),
+        }
+    }
+}
+
+impl Submission {
+    fn entry(&self) -> Entry {
+        Entry {
+            src: self.src.clone(),
+            dst: self.dst.clone(),
+            pods: self.pods.clone(),
+            selector: self.selector.clone(),
+            namespace: self.namespace.clone(),
+            container: self.container.clone(),
+            transport: self.transport.clone(),
+            pick: self.pick,
+            priority: self.priority,
+        }
+    }
+}
+
+fn status(id: u64, job: &Job) -> Value {
+    let tally = job.report.tally(None);
+    let mut status = json!({
+        "id": id,
+        "state": job.state,
+        "transfer": job.transfer,
+        "files": {
+            "completed": tally.completed,
+            "failed": tally.failed,
+            "skipped": tally.skipped,
+        },
+    });
+    if let Some(progress) = &job.progress {
+        status["bytes"] = progress.stats().bytes().into();
+    }
+    if let Some(error) = &job.error {
+        status["error"] = error.as_str().into();
+    }
+    status
+}
+
+fn respond(code: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error(code: StatusCode, err: impl ToString) -> Response<Body> {
+    respond(code, json!({ "error": err.to_string() }))
+}
+
+fn default_queue_file() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("k8scp").join("queue.json"))
+}
+
+fn load(path: &Path) -> anyhow::Result<Queue> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Queue::default()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read queue {}", path.display()))
+        }
+    };
+    serde_json::from_str(&text).with_context(|| format!("failed to parse queue {}", path.display()))
+}
+
+/// Replaces the queue file at once, a crash leaves the old or the new one
+fn save(path: &Path, queue: &Queue) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(queue)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
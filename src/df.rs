@@ -0,0 +1,94 @@
+use std::process::ExitCode;
+
+use anyhow::bail;
+use clap::ValueEnum;
+use indicatif::HumanBytes;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::remote;
+
+use crate::{
+    target::{self, Target},
+    Global,
+};
+
+/// Show the free space of the filesystems of paths in pods
+#[derive(clap::Args, Debug)]
+pub struct DfArgs {
+    /// Path whose filesystem to show, `POD:PATH`
+    #[arg(required = true, value_parser = target::parse)]
+    target: Vec<Target>,
+
+    #[arg(long, value_enum, default_value_t = DfFormat::Table)]
+    format: DfFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DfFormat {
+    /// A row per path with human-readable sizes
+    Table,
+    /// A JSON object per line, sizes in bytes
+    Json,
+}
+
+pub async fn run(global: &Global, args: &DfArgs) -> anyhow::Result<ExitCode> {
+    let api = global.session().await?.pods(global.namespace());
+    let targets = target::resolve(global, &api, &args.target).await?;
+    if args.format == DfFormat::Table {
+        println!(
+            "{:<24} {:>10} {:>10} {:>10} {:>5}  mounted on",
+            "path", "size", "used", "available", "use%"
+        );
+    }
+    Ok(target::each(global, &api, &targets, |target| {
+        let api = &api;
+        async move {
+            let Some(usage) = remote::df(
+                api,
+                &target.pod,
+                &global.container,
+                &target.path,
+                global.connect_timeout,
+                &CancellationToken::new(),
+            )
+            .await?
+            else {
+                bail!("couldn't read the output of df");
+            };
+            match args.format {
+                DfFormat::Table => {
+                    // what `df` shows, used of what non-root users can use
+                    let usable = usage.used + usage.available;
+                    let percent = match usable {
+                        0 => 0,
+                        usable => (usage.used * 100).div_ceil(usable),
+                    };
+                    println!(
+                        "{:<24} {:>10} {:>10} {:>10} {:>4}%  {}",
+                        target.to_string(),
+                        HumanBytes(usage.size).to_string(),
+                        HumanBytes(usage.used).to_string(),
+                        HumanBytes(usage.available).to_string(),
+                        percent,
+                        usage.mount
+                    );
+                }
+                DfFormat::Json => println!(
+                    "{}",
+                    json!({
+                        "pod": target.pod,
+                        "path": target.path,
+                        "filesystem": usage.filesystem,
+                        "size": usage.size,
+                        "used": usage.used,
+                        "available": usage.available,
+                        "mount": usage.mount,
+                    })
+                ),
+            }
+            Ok(())
+        }
+    })
+    .await)
+}
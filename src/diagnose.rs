@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use k8s_openapi::api::core::v1::{Namespace, Pod};
+use kube::api::{Api, ListParams};
+use tokio::time;
+
+use crate::Error;
+
+/// Closest matches named in a diagnostic
+const CLOSEST: usize = 3;
+
+/// Turns a failure to exec into `pod` into an [`Error::PodResolution`]
+/// naming the closest matches when the namespace, pod or container doesn't
+/// exist, other failures are returned as they are
+pub async fn explain(
+    pods: &Api<Pod>,
+    namespace: &str,
+    pod: &str,
+    container: &str,
+    timeout: Duration,
+    err: Error,
+) -> Error {
+    // only the API knows about targets, local and transfer errors aren't
+    // worth a round trip
+    if !matches!(err, Error::Connection(_) | Error::PodResolution(_)) {
+        return err;
+    }
+    let found = match time::timeout(timeout, pods.get(pod)).await {
+        Ok(Ok(found)) => found,
+        Ok(Err(kube::Error::Api(resp))) if resp.code == 404 => {
+            return Error::PodResolution(pod_not_found(pods, namespace, pod, timeout).await)
+        }
+        _ => return err,
+    };
+    let names: Vec<_> = found
+        .spec
+        .into_iter()
+        .flat_map(|spec| spec.containers)
+        .map(|c| c.name)
+        .collect();
+    if !container.is_empty() && !names.iter().any(|name| name == container) {
+        return Error::PodResolution(container_not_found(pod, container, &names));
+    }
+    err
+}
+
+/// Explains that `pod` doesn't exist in `namespace`, naming the closest
+/// pods, or the closest namespaces if `namespace` doesn't exist either
+pub async fn pod_not_found(
+    pods: &Api<Pod>,
+    namespace: &str,
+    pod: &str,
+    timeout: Duration,
+) -> String {
+    let namespaces: Api<Namespace> = Api::all(pods.clone().into_client());
+    if let Ok(Ok(list)) = time::timeout(timeout, namespaces.list(&ListParams::default())).await {
+        let names: Vec<_> = list
+            .items
+            .into_iter()
+            .filter_map(|ns| ns.metadata.name)
+            .collect();
+        if !names.iter().any(|name| name == namespace) {
+            return format!(
+                "namespace '{}' not found{}",
+                namespace,
+                closest(namespace, &names)
+            );
+        }
+    }
+    let names = match time::timeout(timeout, pods.list(&ListParams::default())).await {
+        Ok(Ok(list)) => list
+            .items
+            .into_iter()
+            .filter_map(|pod| pod.metadata.name)
+            .collect(),
+        _ => Vec::new(),
+    };
+    format!(
+        "pod '{}' not found in namespace {}{}",
+        pod,
+        namespace,
+        closest(pod, &names)
+    )
+}
+
+/// Explains that `pod` has no `container`, listing the ones it has
+pub fn container_not_found(pod: &str, container: &str, names: &[String]) -> String {
+    format!(
+        "container '{}' not found in pod {}; available: {}",
+        container,
+        pod,
+        names.join(", ")
+    )
+}
+
+/// The names most like `name`, as the end of a diagnostic
+fn closest(name: &str, names: &[String]) -> String {
+    // names containing `name` first, generated suffixes make them look far
+    // off by edit distance
+    let mut ranked: Vec<_> = names
+        .iter()
+        .map(|candidate| {
+            (
+                !candidate.contains(name),
+                strsim::levenshtein(name, candidate),
+                candidate,
+            )
+        })
+        .collect();
+    if ranked.is_empty() {
+        return String::new();
+    }
+    ranked.sort();
+    let ranked: Vec<_> = ranked
+        .into_iter()
+        .take(CLOSEST)
+        .map(|(_, _, candidate)| candidate.as_str())
+        .collect();
+    format!("; closest: {}", ranked.join(", "))
+}
@@ -0,0 +1,93 @@
+use std::{collections::HashSet, path::Path, process::ExitCode};
+
+use clap_complete::ArgValueCompleter;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    files::{self, Walk},
+    remote,
+};
+
+use crate::{
+    complete, cp,
+    sync::{self, State},
+    term, Global,
+};
+
+/// Show which files differ between a local path and the pods, exits with 1
+/// if any do
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// Pod to compare with, repeat or separate with commas to compare several pods
+    #[arg(short, long, env = "K8SCP_POD", required = true, value_delimiter = ',', add = ArgValueCompleter::new(complete::pods))]
+    pod: Vec<String>,
+
+    /// Local file or directory
+    #[arg(short, long)]
+    src: String,
+
+    /// Where `cp` would copy `--src` to
+    #[arg(short, long)]
+    dst: String,
+
+    /// Compare the `.git`, `.hg`, `.svn`, `target`, `node_modules` and `__pycache__` directories below a directory `--src` too, like `cp --no-vcs-exclude` copies them
+    #[arg(long)]
+    no_vcs_exclude: bool,
+}
+
+pub async fn run(global: &Global, args: &DiffArgs) -> anyhow::Result<ExitCode> {
+    let src = Path::new(&args.src);
+    let walk = Walk {
+        exclude: cp::vcs_exclude(args.no_vcs_exclude),
+        ..Walk::default()
+    };
+    let files = files::collect_with(src, &args.dst, &walk, |path| {
+        term::warn(format!("skipping {}, not a regular file", path.display()))
+    })?;
+    let local = sync::local_digests(&files).await?;
+    // the directory a copy of a directory ends up in
+    let base = src.is_dir().then(|| match src.file_name() {
+        Some(name) => files::join(&args.dst, &name.to_string_lossy()),
+        None => args.dst.clone(),
+    });
+
+    let api = global.session().await?.pods(global.namespace());
+
+    let mut differences = 0;
+    for pod in &args.pod {
+        let states = sync::compare(global, &api, pod, &files, &local).await?;
+        for (file, state) in files.iter().zip(states) {
+            let what = match state {
+                State::Same => continue,
+                State::Differs => "differs",
+                State::Missing => "only local",
+            };
+            differences += 1;
+            println!("{}:{} {}", pod, file.remote_path(), what);
+        }
+        let Some(base) = &base else {
+            continue;
+        };
+        let copied: HashSet<_> = files.iter().map(|file| file.remote_path()).collect();
+        let remote = remote::files(
+            &api,
+            pod,
+            &global.container,
+            base,
+            global.connect_timeout,
+            &CancellationToken::new(),
+        )
+        .await?;
+        for path in remote.iter().filter(|path| !walk.excludes(path)) {
+            let path = files::join(base, path);
+            if !copied.contains(&path) {
+                differences += 1;
+                println!("{}:{} only in the pod", pod, path);
+            }
+        }
+    }
+    Ok(match differences {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    })
+}
@@ -0,0 +1,366 @@
+use std::{fmt::Display, time::Duration};
+
+use anyhow::bail;
+use clap_complete::ArgValueCompleter;
+use console::style;
+use indicatif::HumanBytes;
+use k8s_openapi::api::{
+    authorization::v1::{ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec},
+    core::v1::Pod,
+};
+use kube::{
+    api::{Api, PostParams},
+    Client,
+};
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    client, diagnose,
+    exec::{self, quote},
+};
+
+use crate::{complete, term, Global};
+
+/// Tools the copy runs in the container
+const TOOLS: &[&str] = &["sh", "mkdir", "cat", "wc"];
+
+/// Check that a copy to the given pods can work
+#[derive(clap::Args, Debug)]
+pub struct DoctorArgs {
+    /// Pod to check, repeat or separate with commas to check several pods
+    #[arg(short, long, required = true, value_delimiter = ',', add = ArgValueCompleter::new(complete::pods))]
+    pod: Vec<String>,
+
+    /// Destination to check for write access and free space
+    #[arg(short, long)]
+    dst: Option<String>,
+}
+
+/// Prints the outcome of every check as it is made
+#[derive(Default)]
+struct Checklist {
+    checks: usize,
+    failed: usize,
+}
+
+impl Checklist {
+    fn pass(&mut self, msg: impl Display) {
+        self.checks += 1;
+        term::success(msg);
+    }
+
+    fn fail(&mut self, msg: impl Display) {
+        self.checks += 1;
+        self.failed += 1;
+        term::error(msg);
+    }
+
+    /// A check that can't be made because an earlier one failed
+    fn skip(&self, msg: impl Display) {
+        eprintln!("{} {}", style("-").dim(), style(msg).dim());
+    }
+
+    fn done(self) -> anyhow::Result<()> {
+        if self.failed > 0 {
+            bail!("{} of {} checks failed", self.failed, self.checks);
+        }
+        Ok(())
+    }
+}
+
+pub async fn run(args: &DoctorArgs, global: &Global) -> anyhow::Result<()> {
+    let mut checks = Checklist::default();
+    // the connect timeout goes for every check
+    let timeout = global.connect_timeout;
+    let namespace = global.namespace();
+
+    let path = global.kubeconfig()?;
+    let kubeconfig = match global.load_kubeconfig() {
+        Ok(kubeconfig) => kubeconfig,
+        Err(err) => {
+            checks.fail(format!("{:#}", err));
+            checks.skip("API server, permissions and pods not checked");
+            return checks.done();
+        }
+    };
+    let context = global
+        .context
+        .clone()
+        .or_else(|| kubeconfig.current_context.clone());
+    let config = match client::config(kubeconfig, global.context.as_deref(), timeout).await {
+        Ok(config) => {
+            checks.pass(format!(
+                "kubeconfig {} is valid, context {}",
+                path,
+                context.as_deref().unwrap_or("-")
+            ));
+            config
+        }
+        Err(err) => {
+            checks.fail(format!("kubeconfig {}: {:#}", path, err));
+            checks.skip("API server, permissions and pods not checked");
+            return checks.done();
+        }
+    };
+
+    let url = config.cluster_url.clone();
+    let reachable = async {
+        let client = Client::try_from(config)?;
+        let info = time::timeout(timeout, client.apiserver_version()).await??;
+        anyhow::Ok((client, info))
+    };
+    let client = match reachable.await {
+        Ok((client, info)) => {
+            checks.pass(format!(
+                "API server {} is reachable, {}",
+                url, info.git_version
+            ));
+            client
+        }
+        Err(err) => {
+            checks.fail(format!("API server {} is not reachable: {:#}", url, err));
+            checks.skip("permissions and pods not checked");
+            return checks.done();
+        }
+    };
+
+    for (verb, subresource) in [("get", None), ("create", Some("exec"))] {
+        let what = match subresource {
+            Some(sub) => format!("{} pods/{} in {}", verb, sub, namespace),
+            None => format!("{} pods in {}", verb, namespace),
+        };
+        match access(&client, namespace, verb, subresource, timeout).await {
+            Ok(true) => checks.pass(format!("allowed to {}", what)),
+            Ok(false) => checks.fail(format!("not allowed to {}", what)),
+            Err(err) => checks.fail(format!(
+                "failed to check whether allowed to {}: {:#}",
+                what, err
+            )),
+        }
+    }
+
+    let api: Api<Pod> = Api::namespaced(client, namespace);
+    for pod in &args.pod {
+        check_pod(&mut checks, &api, pod, args, global).await;
+    }
+    checks.done()
+}
+
+/// Asks the API server whether the current user may `verb` pods
+async fn access(
+    client: &Client,
+    namespace: &str,
+    verb: &str,
+    subresource: Option<&str>,
+    timeout: Duration,
+) -> anyhow::Result<bool> {
+    let review = SelfSubjectAccessReview {
+        spec: SelfSubjectAccessReviewSpec {
+            resource_attributes: Some(ResourceAttributes {
+                namespace: Some(namespace.to_string()),
+                verb: Some(verb.to_string()),
+                resource: Some("pods".to_string()),
+                subresource: subresource.map(str::to_string),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let api: Api<SelfSubjectAccessReview> = Api::all(client.clone());
+    let review = time::timeout(timeout, api.create(&PostParams::default(), &review)).await??;
+    Ok(review.status.is_some_and(|status| status.allowed))
+}
+
+async fn check_pod(
+    checks: &mut Checklist,
+    api: &Api<Pod>,
+    pod: &str,
+    args: &DoctorArgs,
+    global: &Global,
+) {
+    let timeout = global.connect_timeout;
+    let found = match time::timeout(timeout, api.get(pod)).await {
+        Ok(Ok(found)) => Ok(found),
+        Ok(Err(kube::Error::Api(err))) if err.code == 404 => {
+            Err(diagnose::pod_not_found(api, global.namespace(), pod, timeout).await)
+        }
+        Ok(Err(err)) => Err(format!(
+            "failed to get pod {}: {:#}",
+            pod,
+            anyhow::Error::from(err)
+        )),
+        Err(err) => Err(format!("failed to get pod {}: {}", pod, err)),
+    };
+    let found = match found {
+        Ok(found) => found,
+        Err(msg) => {
+            checks.fail(msg);
+            checks.skip(format!("container and destination in {} not checked", pod));
+            return;
+        }
+    };
+
+    let status = found.status.unwrap_or_default();
+    let phase = status.phase.as_deref().unwrap_or("Unknown");
+    let ready = status
+        .conditions
+        .iter()
+        .flatten()
+        .any(|c| c.type_ == "Ready" && c.status == "True");
+    if ready {
+        checks.pass(format!("pod {} is ready, {}", pod, phase));
+    } else {
+        checks.fail(format!("pod {} is not ready, {}", pod, phase));
+    }
+
+    // exec picks the first container when none is given
+    let containers = found.spec.map(|spec| spec.containers).unwrap_or_default();
+    let container = match global.container.as_str() {
+        "" => containers.first().map(|c| c.name.clone()),
+        name => containers
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.name.clone()),
+    };
+    let Some(container) = container else {
+        let names: Vec<_> = containers.iter().map(|c| c.name.clone()).collect();
+        checks.fail(diagnose::container_not_found(
+            pod,
+            &global.container,
+            &names,
+        ));
+        checks.skip(format!("tools and destination in {} not checked", pod));
+        return;
+    };
+    let running = status
+        .container_statuses
+        .iter()
+        .flatten()
+        .find(|s| s.name == container)
+        .is_some_and(|s| {
+            s.state
+                .as_ref()
+                .is_some_and(|state| state.running.is_some())
+        });
+    if running {
+        checks.pass(format!("container {}/{} is running", pod, container));
+    } else {
+        checks.fail(format!("container {}/{} is not running", pod, container));
+        checks.skip(format!("tools and destination in {} not checked", pod));
+        return;
+    }
+
+    let command = format!(
+        "for t in {}; do command -v \"$t\" > /dev/null || echo \"$t\"; done",
+        TOOLS.join(" ")
+    );
+    match exec::run(
+        api,
+        pod,
+        &global.container,
+        &command,
+        timeout,
+        timeout,
+        &CancellationToken::new(),
+    )
+    .await
+    {
+        Ok(output) => {
+            let missing: Vec<_> = output.stdout.split_whitespace().collect();
+            if missing.is_empty() {
+                checks.pass(format!(
+                    "{} found in {}/{}",
+                    TOOLS.join(", "),
+                    pod,
+                    container
+                ));
+            } else {
+                checks.fail(format!(
+                    "{} missing in {}/{}",
+                    missing.join(", "),
+                    pod,
+                    container
+                ));
+            }
+        }
+        Err(err) => {
+            checks.fail(format!(
+                "failed to exec into {}/{}: {:#}",
+                pod, container, err
+            ));
+            checks.skip(format!("destination in {} not checked", pod));
+            return;
+        }
+    }
+
+    if let Some(dst) = &args.dst {
+        check_destination(checks, api, pod, &global.container, dst, timeout).await;
+    }
+}
+
+/// Checks write access and free space at `dst`, or at its nearest existing
+/// parent when it doesn't exist yet
+async fn check_destination(
+    checks: &mut Checklist,
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    dst: &str,
+    timeout: Duration,
+) {
+    let command = format!(
+        "d={}; while [ ! -d \"$d\" ]; do d=$(dirname \"$d\"); done; echo \"$d\"; \
+         if [ -w \"$d\" ]; then echo w; else echo r; fi; df -Pk \"$d\" | tail -n 1",
+        quote(dst)
+    );
+    let output = match exec::run(
+        api,
+        pod,
+        container,
+        &command,
+        timeout,
+        timeout,
+        &CancellationToken::new(),
+    )
+    .await
+    {
+        Ok(output) => output,
+        Err(err) => {
+            checks.fail(format!("failed to check {}:{}: {:#}", pod, dst, err));
+            return;
+        }
+    };
+    let mut lines = output.stdout.lines();
+    let (Some(dir), Some(access)) = (lines.next(), lines.next()) else {
+        checks.fail(format!(
+            "failed to check {}:{}: unexpected output",
+            pod, dst
+        ));
+        return;
+    };
+    let at = if dir == dst.trim_end_matches('/') || dir == dst {
+        format!("{}:{}", pod, dir)
+    } else {
+        format!("{}:{} (nearest existing parent of {})", pod, dir, dst)
+    };
+    if access != "w" {
+        checks.fail(format!("{} is not writable", at));
+        return;
+    }
+    // POSIX df output: filesystem, blocks, used, available, capacity, mount
+    let available = lines
+        .next()
+        .and_then(|df| df.split_whitespace().nth(3))
+        .and_then(|kb| kb.parse::<u64>().ok());
+    match available {
+        Some(0) => checks.fail(format!("{} is writable but its filesystem is full", at)),
+        Some(kb) => checks.pass(format!(
+            "{} is writable, {} free",
+            at,
+            HumanBytes(kb * 1024)
+        )),
+        None => checks.pass(format!("{} is writable, free space unknown", at)),
+    }
+}
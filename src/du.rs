@@ -0,0 +1,83 @@
+use std::{cmp::Reverse, process::ExitCode};
+
+use clap::ValueEnum;
+use indicatif::HumanBytes;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::remote;
+
+use crate::{
+    target::{self, Target},
+    Global,
+};
+
+/// Show how much space paths in pods and the directories below them take up
+#[derive(clap::Args, Debug)]
+pub struct DuArgs {
+    /// Path to measure, `POD:PATH`
+    #[arg(required = true, value_parser = target::parse)]
+    target: Vec<Target>,
+
+    /// Show the directories this many levels below the path, 0 for just its total
+    #[arg(short = 'd', long, value_name = "N", default_value_t = 1)]
+    max_depth: usize,
+
+    #[arg(long, value_enum, default_value_t = DuFormat::Table)]
+    format: DuFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum DuFormat {
+    /// A row per directory, the largest first, with human-readable sizes
+    Table,
+    /// A JSON object per directory and line, sizes in bytes
+    Json,
+}
+
+pub async fn run(global: &Global, args: &DuArgs) -> anyhow::Result<ExitCode> {
+    let api = global.session().await?.pods(global.namespace());
+    let targets = target::resolve(global, &api, &args.target).await?;
+    Ok(target::each(global, &api, &targets, |target| {
+        let api = &api;
+        async move {
+            let mut usage = remote::du(
+                api,
+                &target.pod,
+                &global.container,
+                &target.path,
+                args.max_depth,
+                global.connect_timeout,
+                &CancellationToken::new(),
+            )
+            .await?;
+            match args.format {
+                DuFormat::Table => {
+                    usage.sort_by_key(|usage| Reverse(usage.bytes));
+                    for usage in usage {
+                        println!(
+                            "{:>10}  {}:{}",
+                            HumanBytes(usage.bytes).to_string(),
+                            target.pod,
+                            usage.path
+                        );
+                    }
+                }
+                DuFormat::Json => {
+                    for usage in usage {
+                        println!(
+                            "{}",
+                            json!({
+                                "pod": target.pod,
+                                "path": usage.path,
+                                "bytes": usage.bytes,
+                            })
+                        );
+                    }
+                }
+            }
+            Ok(())
+        }
+    })
+    .await)
+}
@@ -0,0 +1,243 @@
+use std::{fs, path::Path, process::ExitCode, sync::Arc};
+
+use anyhow::{bail, Context as _};
+use k8s_openapi::api::core::v1::Pod;
+use kube::Api;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    copy_from_pod, copy_to_pod, diagnose,
+    exec::{self, quote},
+    hash::{self, Sha256},
+    progress::Progress,
+    remote, ExecOptions,
+};
+
+use crate::{
+    target::{self, Target},
+    term, Global,
+};
+
+/// The file type bits of `st_mode`, and those of a regular file
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+
+/// Edit a file in a pod with `$EDITOR`, it is copied back when saved
+#[derive(clap::Args, Debug)]
+pub struct EditArgs {
+    /// File to edit, `POD:PATH`
+    #[arg(value_parser = target::parse)]
+    target: Target,
+
+    /// Keep the file as it was next to it in the pod, with this suffix appended to its name
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".bak"
+    )]
+    backup: Option<String>,
+}
+
+pub async fn run(global: &Global, args: &EditArgs) -> anyhow::Result<ExitCode> {
+    let api = global.session().await?.pods(global.namespace());
+    let target = &target::resolve(global, &api, std::slice::from_ref(&args.target))
+        .await?
+        .remove(0);
+    // the file keeps its name, editors go by it for the syntax
+    let dir = std::env::temp_dir().join(format!("k8scp-edit-{}", std::process::id()));
+    let name = target.path.rsplit('/').find(|name| !name.is_empty());
+    let local = dir.join(name.unwrap_or("file"));
+    let mut builder = fs::DirBuilder::new();
+    // configs have secrets, only the user gets to read the copy
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .recursive(true)
+        .create(&dir)
+        .with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let mut edited = false;
+    let res = edit(global, &api, args, target, &local, &mut edited).await;
+    // the edits are kept if they couldn't be copied back
+    if res.is_ok() || !edited {
+        let _ = fs::remove_dir_all(&dir);
+    }
+    if res.is_err() && edited {
+        term::warn(format!("the edited file is kept at {}", local.display()));
+    }
+    res.map(|()| ExitCode::SUCCESS)
+}
+
+/// Downloads the file `target` to `local`, opens it in the editor and
+/// copies it back if it changed, setting `edited` once it did
+async fn edit(
+    global: &Global,
+    api: &Api<Pod>,
+    args: &EditArgs,
+    target: &Target,
+    local: &Path,
+    edited: &mut bool,
+) -> anyhow::Result<()> {
+    let transport = global.transport();
+    let opts = ExecOptions {
+        container: &global.container,
+        connect_timeout: global.connect_timeout,
+        idle_timeout: global.idle_timeout,
+        transport: &*transport,
+        umask: global.umask,
+        system_paths: Some(&global.system_paths),
+        allow_system_paths: global.allow_system_paths,
+        ..ExecOptions::default()
+    };
+    let cancel = CancellationToken::new();
+    let progress = Arc::new(Progress::new());
+    let local_name = local.to_string_lossy();
+
+    let explain = |err| {
+        // why the pod couldn't be reached, if that is why
+        diagnose::explain(
+            api,
+            global.namespace(),
+            &target.pod,
+            &global.container,
+            global.connect_timeout,
+            err,
+        )
+    };
+    let found = remote::stat(
+        api,
+        &target.pod,
+        &global.container,
+        &target.path,
+        global.connect_timeout,
+        &cancel,
+    )
+    .await;
+    let entry = match found {
+        Ok(Some(entry)) if entry.kind == remote::Kind::File => entry,
+        Ok(Some(_)) => bail!("{} isn't a regular file", target),
+        Ok(None) => bail!("{} doesn't exist", target),
+        Err(err) => return Err(explain(err).await.into()),
+    };
+    let item = progress.item(
+        &target.path,
+        &target.pod,
+        &local_name,
+        entry.size.unwrap_or(0),
+    );
+    if let Err(err) = copy_from_pod(api, &target.pod, &target.path, local, &opts, item).await {
+        return Err(explain(err).await).with_context(|| format!("failed to download {}", target));
+    }
+    let opened = hash::file(&Sha256, local).await?;
+
+    let editor = editor();
+    let status = if cfg!(windows) {
+        tokio::process::Command::new("cmd")
+            .args(["/C", &editor])
+            .arg(local)
+            .status()
+            .await
+    } else {
+        // through the shell, so an `EDITOR` like `code --wait` works
+        tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", editor))
+            .arg("sh")
+            .arg(local)
+            .status()
+            .await
+    }
+    .with_context(|| format!("failed to run {}", editor))?;
+    if !status.success() {
+        bail!(
+            "{} exited with {}, {} is left as it was",
+            editor,
+            status,
+            target
+        );
+    }
+    if hash::file(&Sha256, local).await? == opened {
+        if !global.quiet {
+            term::success(format!("{} is unchanged", target));
+        }
+        return Ok(());
+    }
+    *edited = true;
+
+    let paths = [target.path.clone()];
+    let digest = || {
+        remote::digests(
+            api,
+            &target.pod,
+            &global.container,
+            &paths,
+            &Sha256,
+            global.connect_timeout,
+            &cancel,
+        )
+    };
+    // `--yes` copies it back regardless
+    if !global.yes && digest().await?[0].as_deref() != Some(opened.as_str()) {
+        bail!(
+            "{} changed in the pod while it was edited, pass --yes to overwrite it",
+            target
+        );
+    }
+    if let Some(suffix) = &args.backup {
+        let backup = format!("{}{}", target.path, suffix);
+        let command = format!("cp -p -- {} {}", quote(&target.path), quote(&backup));
+        exec::run(
+            api,
+            &target.pod,
+            &global.container,
+            &command,
+            global.connect_timeout,
+            global.idle_timeout,
+            &cancel,
+        )
+        .await
+        .with_context(|| format!("failed to back up {} to {}", target, backup))?;
+    }
+    let size = fs::metadata(local).map(|meta| meta.len()).unwrap_or(0);
+    let item = progress.item(&local_name, &target.pod, &target.path, size);
+    let sent = copy_to_pod(api, &target.pod, local, &target.path, &opts, item)
+        .await
+        .with_context(|| format!("failed to copy the edited file back to {}", target))?;
+    // a transport that writes a new file gives it the mode of the local one,
+    // the mode of a symlink is its own
+    if let Some(mode) = entry.mode.filter(|mode| mode & S_IFMT == S_IFREG) {
+        let command = format!("chmod {:o} -- {}", mode & 0o7777, quote(&target.path));
+        exec::run(
+            api,
+            &target.pod,
+            &global.container,
+            &command,
+            global.connect_timeout,
+            global.idle_timeout,
+            &cancel,
+        )
+        .await
+        .with_context(|| format!("failed to restore the mode of {}", target))?;
+    }
+    if digest().await?[0].as_deref() != Some(sent.as_str()) {
+        bail!(
+            "{} differs from the edited file after it was copied back",
+            target
+        );
+    }
+    if !global.quiet {
+        term::success(format!("saved {}", target));
+    }
+    Ok(())
+}
+
+/// The editor of `VISUAL` or `EDITOR`, `vi` if neither is set
+fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string())
+}
@@ -0,0 +1,185 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use futures::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tracing::warn;
+
+use super::{Entry, Sink, Source};
+use crate::{files, progress::ItemProgress, Error, Result};
+
+/// A local file or directory.
+///
+/// As a source a directory is copied recursively with its name, like
+/// `--src` is. As a sink it is an existing directory the files are copied
+/// into, or the path of a single file copied to it.
+#[derive(Debug, Clone)]
+pub struct LocalPath {
+    path: PathBuf,
+}
+
+impl LocalPath {
+    pub fn new(path: impl Into<PathBuf>) -> LocalPath {
+        LocalPath { path: path.into() }
+    }
+
+    /// The file the source `entry` is read from
+    fn source(&self, entry: &Entry) -> PathBuf {
+        if !self.path.is_dir() {
+            return self.path.clone();
+        }
+        // the entries of a directory start with its name, see `files::collect`
+        let rel = match self.path.file_name() {
+            Some(name) => entry
+                .path
+                .strip_prefix(&*name.to_string_lossy())
+                .and_then(|rel| rel.strip_prefix('/'))
+                .unwrap_or(&entry.path),
+            None => &entry.path,
+        };
+        self.path.join(rel)
+    }
+
+    /// The file the sink writes `entry` to
+    fn target(&self, entry: &Entry) -> PathBuf {
+        if !self.path.is_dir() && !entry.path.contains('/') {
+            return self.path.clone();
+        }
+        self.path.join(&entry.path)
+    }
+}
+
+impl Source for LocalPath {
+    fn entries(&self) -> BoxFuture<'_, Result<Vec<Entry>>> {
+        Box::pin(async {
+            let files = files::collect(&self.path, "", |path| {
+                warn!("skipping {}, not a regular file", path.display())
+            })?;
+            Ok(files
+                .into_iter()
+                .map(|file| Entry {
+                    // below the empty remote directory is relative once the
+                    // leading `/` is gone
+                    path: file.remote_path().trim_start_matches('/').to_string(),
+                    size: Some(file.size),
+                })
+                .collect())
+        })
+    }
+
+    fn read<'a>(
+        &'a self,
+        entry: &'a Entry,
+        to: &'a mut (dyn AsyncWrite + Send + Unpin),
+        _progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let path = self.source(entry);
+            let mut file = tokio::fs::File::open(&path)
+                .await
+                .map_err(|err| Error::io(&path, err))?;
+            copy(&mut file, to, &path).await
+        })
+    }
+
+    fn describe(&self, entry: &Entry) -> String {
+        self.source(entry).to_string_lossy().into_owned()
+    }
+}
+
+impl Sink for LocalPath {
+    fn write<'a>(
+        &'a self,
+        entry: &'a Entry,
+        from: &'a mut (dyn AsyncRead + Send + Unpin),
+        _progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let path = self.target(entry);
+            let res = async {
+                if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                    tokio::fs::create_dir_all(dir)
+                        .await
+                        .map_err(|err| Error::io(dir, err))?;
+                }
+                let mut file = tokio::fs::File::create(&path)
+                    .await
+                    .map_err(|err| Error::io(&path, err))?;
+                copy(from, &mut file, &path).await
+            }
+            .await;
+            if res.is_err() {
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+            res
+        })
+    }
+
+    fn describe(&self, entry: &Entry) -> String {
+        self.target(entry).to_string_lossy().into_owned()
+    }
+
+    fn remove<'a>(&'a self, entry: &'a Entry) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let _ = tokio::fs::remove_file(self.target(entry)).await;
+        })
+    }
+}
+
+/// Copies `from` to `to` and flushes it, errors of the other end are passed
+/// on, the local ones name `path`
+pub(super) async fn copy(
+    from: &mut (dyn AsyncRead + Send + Unpin),
+    to: &mut (dyn AsyncWrite + Send + Unpin),
+    path: &Path,
+) -> Result<()> {
+    tokio::io::copy(from, to)
+        .await
+        .map_err(|err| Error::unwrap_io(err, |err| Error::io(path, err)))?;
+    to.flush()
+        .await
+        .map_err(|err| Error::unwrap_io(err, |err| Error::io(path, err)))
+}
+
+/// A temporary local file holding a stream of unknown size, removed again
+/// when dropped
+#[derive(Debug)]
+pub(super) struct Spool {
+    pub(super) path: PathBuf,
+}
+
+impl Spool {
+    pub(super) fn new() -> Spool {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let name = format!(
+            "k8scp-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        Spool {
+            path: std::env::temp_dir().join(name),
+        }
+    }
+
+    /// Writes what `from` reads to the spool, returning it opened for reading
+    pub(super) async fn fill(
+        &self,
+        from: &mut (dyn AsyncRead + Send + Unpin),
+    ) -> Result<tokio::fs::File> {
+        let mut file = tokio::fs::File::create(&self.path)
+            .await
+            .map_err(|err| Error::io(&self.path, err))?;
+        copy(from, &mut file, &self.path).await?;
+        tokio::fs::File::open(&self.path)
+            .await
+            .map_err(|err| Error::io(&self.path, err))
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,117 @@
+//! The two ends of a copy.
+//!
+//! A [`Source`] lists files and reads them, a [`Sink`] writes them.
+//! [`CopyRequest`](crate::CopyRequest) pipes any source into any sink,
+//! counting, hashing and verifying what goes through, so local paths, pods,
+//! object storage, stdin and stdout combine freely and other endpoints only
+//! implement the two traits.
+
+mod local;
+mod object;
+mod pod;
+mod stdio;
+
+use std::fmt;
+
+use futures::future::{self, BoxFuture};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{hash::Hasher, progress::ItemProgress, Result};
+
+pub use local::LocalPath;
+pub use object::Object;
+pub use pod::{PodClient, PodPath};
+pub use stdio::{Stdin, Stdout};
+
+/// A file of a source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Where the file goes relative to the destination, `/` separated, e.g.
+    /// `dist/app.js` for a file of the directory `dist`
+    pub path: String,
+    /// Size in bytes if it is known before reading the file
+    pub size: Option<u64>,
+}
+
+/// Where the files of a copy come from
+pub trait Source: Send + Sync {
+    /// The files to copy
+    fn entries(&self) -> BoxFuture<'_, Result<Vec<Entry>>>;
+
+    /// Writes the contents of `entry` to `to`, `progress` only learns about
+    /// connections and remote stderr, the bytes are counted by the caller
+    fn read<'a>(
+        &'a self,
+        entry: &'a Entry,
+        to: &'a mut (dyn AsyncWrite + Send + Unpin),
+        progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Where `entry` is read from, shown in progress and summaries
+    fn describe(&self, entry: &Entry) -> String;
+
+    /// The pod the files are read from, if any
+    fn pod(&self) -> Option<&str> {
+        None
+    }
+
+    /// Fails if `entry` doesn't have the digest `digest` (hex) of `hasher`
+    /// at the source, for sources that can tell. Nothing to check by default.
+    fn check<'a>(
+        &'a self,
+        _entry: &'a Entry,
+        _hasher: &'a dyn Hasher,
+        _digest: &'a str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(future::ready(Ok(())))
+    }
+}
+
+/// Where the files of a copy go
+pub trait Sink: Send + Sync {
+    /// Writes what `from` reads as `entry`, cleaning up after itself if that
+    /// fails
+    fn write<'a>(
+        &'a self,
+        entry: &'a Entry,
+        from: &'a mut (dyn AsyncRead + Send + Unpin),
+        progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Where `entry` is written to, shown in progress and summaries
+    fn describe(&self, entry: &Entry) -> String;
+
+    /// The pod the files are written to, if any
+    fn pod(&self) -> Option<&str> {
+        None
+    }
+
+    /// Fails if the written `entry` doesn't have the digest `digest` (hex) of
+    /// `hasher`, for sinks that can tell. Nothing to check by default.
+    fn check<'a>(
+        &'a self,
+        _entry: &'a Entry,
+        _hasher: &'a dyn Hasher,
+        _digest: &'a str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(future::ready(Ok(())))
+    }
+
+    /// Removes what was written of `entry` after the source failed or the
+    /// copy didn't check out. Nothing to remove by default.
+    fn remove<'a>(&'a self, _entry: &'a Entry) -> BoxFuture<'a, ()> {
+        Box::pin(future::ready(()))
+    }
+}
+
+impl fmt::Debug for dyn Source + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Source").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for dyn Sink + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sink").finish_non_exhaustive()
+    }
+}
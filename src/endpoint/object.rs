@@ -0,0 +1,578 @@
+use std::{
+    env,
+    time::{Duration, SystemTime},
+};
+
+use futures::future::{self, BoxFuture};
+use hyper::{
+    body::{Bytes, HttpBody},
+    client::HttpConnector,
+    header::{self, HeaderValue},
+    Body, Method, Request, Response, StatusCode, Uri,
+};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{local::Spool, Entry, Sink, Source};
+use crate::{progress::ItemProgress, Error, Result};
+
+#[cfg(feature = "openssl")]
+type Connector = hyper_openssl::HttpsConnector<HttpConnector>;
+#[cfg(all(feature = "rustls", not(feature = "openssl")))]
+type Connector = hyper_rustls::HttpsConnector<HttpConnector>;
+
+/// How many redirects a download follows
+const REDIRECTS: usize = 10;
+
+/// Who makes the requests, some APIs like GitHub's reject them without one
+const USER_AGENT: &str = concat!("k8scp/", env!("CARGO_PKG_VERSION"));
+
+/// The hash of the payload that S3 doesn't compute, the payload is
+/// streamed and protected by TLS instead
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// An object in S3 or Google Cloud Storage, or a file at an HTTP(S) URL,
+/// streamed from and to the other end without a local copy.
+///
+/// `s3://BUCKET/KEY` requests are signed with `AWS_ACCESS_KEY_ID`,
+/// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN` for `AWS_REGION` (or
+/// `AWS_DEFAULT_REGION`, `us-east-1` if neither is set), `AWS_ENDPOINT_URL`
+/// sends them to another S3 compatible store like MinIO.
+/// `gs://BUCKET/KEY` requests carry the OAuth token `GOOGLE_OAUTH_ACCESS_TOKEN`,
+/// e.g. from `gcloud auth print-access-token`. Without credentials objects
+/// are read and written anonymously, as public buckets allow.
+///
+/// As a source it is a single file named like the last segment of its
+/// path. As a sink a URL ending in `/` is a prefix the files are written
+/// below, any other the object a single file is written to. S3 needs the
+/// size of an object up front, one from a source of unknown size, like a
+/// pod, is spooled to a temporary file first.
+#[derive(Clone)]
+pub struct Object {
+    url: String,
+    /// `url` as an HTTP(S) URL
+    location: String,
+    /// The file name of a source, the last segment of its path
+    name: Option<String>,
+    auth: Auth,
+    client: hyper::Client<Connector>,
+}
+
+/// How the requests for an [`Object`] are authenticated
+#[derive(Clone)]
+enum Auth {
+    /// Plain HTTP(S), without credentials
+    None,
+    /// S3, signed if credentials are given
+    S3 {
+        region: String,
+        credentials: Option<Credentials>,
+    },
+    /// Google Cloud Storage, with an OAuth token if given
+    Bearer(Option<String>),
+}
+
+#[derive(Clone)]
+struct Credentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl Object {
+    /// The object or file at `url`, `s3://BUCKET/KEY`, `gs://BUCKET/KEY`,
+    /// `http://…` or `https://…`, with the credentials of the environment
+    pub fn new(url: impl Into<String>) -> Result<Object> {
+        let url = url.into();
+        let invalid = || {
+            Error::Config(format!(
+                "invalid URL {}, expected s3://BUCKET/KEY, gs://BUCKET/KEY or http(s)://…",
+                url
+            ))
+        };
+        let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+        let scheme = scheme.to_ascii_lowercase();
+        let (location, name, auth) = match scheme.as_str() {
+            "s3" | "gs" => {
+                let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+                if bucket.is_empty() {
+                    return Err(invalid());
+                }
+                let name = key.rsplit('/').next().filter(|name| !name.is_empty());
+                let key = encode(key);
+                let (location, auth) = if scheme == "gs" {
+                    let location = format!("https://storage.googleapis.com/{}/{}", bucket, key);
+                    (location, Auth::Bearer(var("GOOGLE_OAUTH_ACCESS_TOKEN")))
+                } else {
+                    let region = var("AWS_REGION")
+                        .or_else(|| var("AWS_DEFAULT_REGION"))
+                        .unwrap_or_else(|| "us-east-1".to_string());
+                    let location = match var("AWS_ENDPOINT_URL") {
+                        Some(endpoint) => {
+                            format!("{}/{}/{}", endpoint.trim_end_matches('/'), bucket, key)
+                        }
+                        // the certificate of the virtual host doesn't cover
+                        // buckets with dots
+                        None if bucket.contains('.') => {
+                            format!("https://s3.{}.amazonaws.com/{}/{}", region, bucket, key)
+                        }
+                        None => format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region, key),
+                    };
+                    let credentials = match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY"))
+                    {
+                        (Some(access_key), Some(secret_key)) => Some(Credentials {
+                            access_key,
+                            secret_key,
+                            session_token: var("AWS_SESSION_TOKEN"),
+                        }),
+                        _ => None,
+                    };
+                    (
+                        location,
+                        Auth::S3 {
+                            region,
+                            credentials,
+                        },
+                    )
+                };
+                (location, name.map(str::to_string), auth)
+            }
+            "http" | "https" => {
+                let path = rest.split(['?', '#']).next().unwrap_or_default();
+                let name = path
+                    .split_once('/')
+                    .and_then(|(_, path)| path.rsplit('/').next())
+                    .filter(|name| !name.is_empty())
+                    .map(decode);
+                (url.clone(), name, Auth::None)
+            }
+            _ => return Err(invalid()),
+        };
+        location.parse::<Uri>().map_err(|_| invalid())?;
+        Ok(Object {
+            url,
+            location,
+            name,
+            auth,
+            client: hyper::Client::builder().build(connector(Duration::from_secs(30))?),
+        })
+    }
+
+    /// Whether `s` is a URL [`Object::new`] takes rather than a local path
+    pub fn is_url(s: &str) -> bool {
+        let scheme = s
+            .split_once("://")
+            .map(|(scheme, _)| scheme.to_ascii_lowercase());
+        matches!(scheme.as_deref(), Some("s3" | "gs" | "http" | "https"))
+    }
+
+    /// Timeout for connecting to the server, 30 seconds if not given
+    pub fn connect_timeout(mut self, timeout: Duration) -> Result<Object> {
+        self.client = hyper::Client::builder().build(connector(timeout)?);
+        Ok(self)
+    }
+
+    /// Writes the object to `to`, for reading it outside of a copy
+    pub async fn download(&self, to: &mut (dyn AsyncWrite + Send + Unpin)) -> Result<()> {
+        let response = self.fetch(Method::GET).await?;
+        let mut body = check(&Method::GET, &self.url, response).await?.into_body();
+        let received = |err| Error::Connection(format!("receiving {}: {}", self.url, err));
+        let passed = |err| Error::unwrap_io(err, |err| Error::stream("copying", err));
+        while let Some(chunk) = body.data().await {
+            to.write_all(&chunk.map_err(received)?)
+                .await
+                .map_err(passed)?;
+        }
+        to.flush().await.map_err(passed)
+    }
+
+    /// The URL of the object the sink writes `entry` to, and how it is shown
+    fn target(&self, entry: &Entry) -> (String, String) {
+        if !self.url.ends_with('/') {
+            return (self.location.clone(), self.url.clone());
+        }
+        (
+            format!("{}{}", self.location, encode(&entry.path)),
+            format!("{}{}", self.url, entry.path),
+        )
+    }
+
+    /// Sends a request for `url` with the credentials of the object, the
+    /// body of a `PUT` is `size` bytes if known
+    async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        size: Option<u64>,
+        body: Body,
+    ) -> Result<Response<Body>> {
+        let uri: Uri = url
+            .parse()
+            .map_err(|err| Error::Config(format!("invalid URL {}: {}", url, err)))?;
+        let mut request = Request::builder()
+            .method(method.clone())
+            .uri(&uri)
+            .header(header::USER_AGENT, USER_AGENT);
+        if let Some(authority) = uri.authority() {
+            request = request.header(header::HOST, authority.as_str());
+        }
+        if let Some(size) = size {
+            request = request.header(header::CONTENT_LENGTH, size);
+        }
+        let mut request = request
+            .body(body)
+            .map_err(|err| Error::Config(format!("invalid request for {}: {}", url, err)))?;
+        match &self.auth {
+            Auth::None
+            | Auth::S3 {
+                credentials: None, ..
+            }
+            | Auth::Bearer(None) => {}
+            Auth::S3 {
+                region,
+                credentials: Some(credentials),
+            } => sign(&mut request, region, credentials, SystemTime::now()),
+            Auth::Bearer(Some(token)) => {
+                let value = HeaderValue::from_str(&format!("Bearer {}", token))
+                    .map_err(|_| Error::Auth("invalid GOOGLE_OAUTH_ACCESS_TOKEN".to_string()))?;
+                request.headers_mut().insert(header::AUTHORIZATION, value);
+            }
+        }
+        self.client
+            .request(request)
+            .await
+            .map_err(|err| Error::Connection(format!("{} {}: {}", method, url, err)))
+    }
+
+    /// A `GET` or `HEAD` of the object, following the redirects of plain
+    /// URLs
+    async fn fetch(&self, method: Method) -> Result<Response<Body>> {
+        let mut url = self.location.clone();
+        for _ in 0..=REDIRECTS {
+            let response = self.send(method.clone(), &url, None, Body::empty()).await?;
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok());
+            match location {
+                Some(location)
+                    if response.status().is_redirection() && matches!(self.auth, Auth::None) =>
+                {
+                    url = redirect(&url, location);
+                }
+                _ => return Ok(response),
+            }
+        }
+        Err(Error::Connection(format!(
+            "{} {}: more than {} redirects",
+            method, self.url, REDIRECTS
+        )))
+    }
+}
+
+impl Source for Object {
+    fn entries(&self) -> BoxFuture<'_, Result<Vec<Entry>>> {
+        Box::pin(async {
+            let Some(name) = &self.name else {
+                return Err(Error::Config(format!("{} names no file", self.url)));
+            };
+            // the size is known without downloading it, some servers only
+            // answer a GET though
+            let response = self.fetch(Method::HEAD).await?;
+            let size = match response.status() {
+                status if status.is_success() => response
+                    .headers()
+                    .get(header::CONTENT_LENGTH)
+                    .and_then(|len| len.to_str().ok()?.parse().ok()),
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => {
+                    check(&Method::HEAD, &self.url, response).await?;
+                    None
+                }
+                _ => None,
+            };
+            Ok(vec![Entry {
+                path: name.clone(),
+                size,
+            }])
+        })
+    }
+
+    fn read<'a>(
+        &'a self,
+        _entry: &'a Entry,
+        to: &'a mut (dyn AsyncWrite + Send + Unpin),
+        _progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.download(to))
+    }
+
+    fn describe(&self, _entry: &Entry) -> String {
+        self.url.clone()
+    }
+}
+
+impl Sink for Object {
+    fn write<'a>(
+        &'a self,
+        entry: &'a Entry,
+        from: &'a mut (dyn AsyncRead + Send + Unpin),
+        _progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let (url, shown) = self.target(entry);
+            // S3 rejects uploads of unknown length, other stores and servers
+            // take them chunked
+            let spool;
+            let mut spooled;
+            let (from, size): (&mut (dyn AsyncRead + Send + Unpin), _) =
+                match (entry.size, &self.auth) {
+                    (None, Auth::S3 { .. }) => {
+                        spool = Spool::new();
+                        spooled = spool.fill(from).await?;
+                        let size = spooled
+                            .metadata()
+                            .await
+                            .map_err(|err| Error::io(&spool.path, err))?
+                            .len();
+                        (&mut spooled, Some(size))
+                    }
+                    (size, _) => (from, size),
+                };
+
+            let (mut sender, body) = Body::channel();
+            let sending = shown.clone();
+            let sent = async move {
+                let mut buf = vec![0u8; 64 * 1024];
+                loop {
+                    let n = from.read(&mut buf).await.map_err(|err| {
+                        Error::unwrap_io(err, |err| Error::stream("copying", err))
+                    })?;
+                    if n == 0 {
+                        return Ok(());
+                    }
+                    sender
+                        .send_data(Bytes::copy_from_slice(&buf[..n]))
+                        .await
+                        .map_err(|err| {
+                            Error::Connection(format!("sending {}: {}", sending, err))
+                        })?;
+                }
+            };
+            let (sent, response) =
+                future::join(sent, self.send(Method::PUT, &url, size, body)).await;
+            // a rejected upload breaks off sending it, the response tells why
+            check(&Method::PUT, &shown, response?).await?;
+            sent
+        })
+    }
+
+    fn describe(&self, entry: &Entry) -> String {
+        self.target(entry).1
+    }
+}
+
+/// A connector through the TLS stack the API server is talked to with
+fn connector(timeout: Duration) -> Result<Connector> {
+    let mut http = HttpConnector::new();
+    http.set_connect_timeout(Some(timeout));
+    http.enforce_http(false);
+    #[cfg(feature = "openssl")]
+    let connector = {
+        let tls = openssl::ssl::SslConnector::builder(openssl::ssl::SslMethod::tls())
+            .map_err(|err| Error::Config(format!("failed to set up TLS: {}", err)))?;
+        hyper_openssl::HttpsConnector::with_connector(http, tls)
+            .map_err(|err| Error::Config(format!("failed to set up TLS: {}", err)))?
+    };
+    #[cfg(all(feature = "rustls", not(feature = "openssl")))]
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(http);
+    Ok(connector)
+}
+
+/// Fails unless `response` to `method` of `url` is a success, with what
+/// the server says went wrong
+async fn check(method: &Method, url: &str, response: Response<Body>) -> Result<Response<Body>> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .unwrap_or_default();
+    let body = String::from_utf8_lossy(&body);
+    // S3 and GCS explain errors in XML
+    let message = match body.split_once("<Message>") {
+        Some((_, rest)) => rest.split("</Message>").next().unwrap_or_default(),
+        None => body.trim(),
+    };
+    let message = match message.chars().count() {
+        0 => status.to_string(),
+        n if n > 200 => format!(
+            "{}: {}…",
+            status,
+            message.chars().take(200).collect::<String>()
+        ),
+        _ => format!("{}: {}", status, message),
+    };
+    let message = format!("{} {}: {}", method, url, message);
+    Err(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Error::Auth(message),
+        StatusCode::NOT_FOUND => Error::Storage(format!("{} not found", url)),
+        _ => Error::Storage(message),
+    })
+}
+
+/// Signs `request` with AWS Signature Version 4, leaving its payload
+/// unsigned
+fn sign(request: &mut Request<Body>, region: &str, credentials: &Credentials, now: SystemTime) {
+    let time: String = humantime::format_rfc3339_seconds(now)
+        .to_string()
+        .chars()
+        .filter(|c| *c != '-' && *c != ':')
+        .collect();
+    let date = &time[..8];
+    let headers = request.headers_mut();
+    let mut set = |name: &'static str, value: &str| {
+        if let Ok(value) = HeaderValue::from_str(value) {
+            headers.insert(name, value);
+        }
+    };
+    set("x-amz-date", &time);
+    set("x-amz-content-sha256", UNSIGNED_PAYLOAD);
+    if let Some(token) = &credentials.session_token {
+        set("x-amz-security-token", token);
+    }
+
+    let mut signed: Vec<(&str, &str)> = request
+        .headers()
+        .iter()
+        .filter(|(name, _)| *name == header::HOST || name.as_str().starts_with("x-amz-"))
+        .map(|(name, value)| (name.as_str(), value.to_str().unwrap_or_default().trim()))
+        .collect();
+    signed.sort();
+    let names = signed
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method(),
+        request.uri().path(),
+        request.uri().query().unwrap_or_default(),
+        signed
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect::<String>(),
+        names,
+        UNSIGNED_PAYLOAD
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        time,
+        scope,
+        Sha256::digest(canonical.as_bytes())
+    );
+    let key = [region, "s3", "aws4_request"].iter().fold(
+        hmac(
+            format!("AWS4{}", credentials.secret_key).as_bytes(),
+            date.as_bytes(),
+        ),
+        |key, part| hmac(&key, part.as_bytes()),
+    );
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key,
+        scope,
+        names,
+        hex(&hmac(&key, to_sign.as_bytes()))
+    );
+    if let Ok(value) = HeaderValue::from_str(&authorization) {
+        request.headers_mut().insert(header::AUTHORIZATION, value);
+    }
+}
+
+/// HMAC-SHA256 of `data` with `key`
+fn hmac(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x36))
+        .chain_update(data)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Percent-encodes `key` for the path of a request, keeping its `/`
+fn encode(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for b in key.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(b as char)
+            }
+            b => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
+
+/// Decodes the percent-encoding of the URL path segment `segment`
+fn decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = match (bytes[i], segment.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) => u8::from_str_radix(hex, 16).ok(),
+            _ => None,
+        };
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Where the redirect to `location` of a request for `url` goes
+fn redirect(url: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let (scheme, rest) = url.split_once("://").unwrap_or(("https", url));
+    let authority = rest.split('/').next().unwrap_or_default();
+    match location.strip_prefix('/') {
+        Some(path) => format!("{}://{}/{}", scheme, authority, path),
+        None => {
+            let dir = url.rsplit_once('/').map_or(url, |(dir, _)| dir);
+            format!("{}/{}", dir, location)
+        }
+    }
+}
+
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
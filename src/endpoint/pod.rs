@@ -0,0 +1,305 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::future::BoxFuture;
+use k8s_openapi::api::core::v1::Pod;
+use kube::{api::Api, Client};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::sync::CancellationToken;
+
+use super::{local::Spool, Entry, Sink, Source};
+use crate::{
+    copy::{self, ExecOptions},
+    diagnose, files,
+    hash::Hasher,
+    progress::ItemProgress,
+    transport::{self, Exec, Transport},
+    Error, Result,
+};
+
+/// How paths in the pods of a namespace are reached, the defaults are those
+/// of [`ExecOptions`]
+#[derive(Debug, Clone)]
+pub struct PodClient {
+    pods: Api<Pod>,
+    namespace: String,
+    container: String,
+    connect_timeout: Duration,
+    idle_timeout: Duration,
+    transport: Arc<dyn Transport>,
+    cancel: CancellationToken,
+    system_paths: Option<Vec<String>>,
+    allow_system_paths: bool,
+}
+
+impl PodClient {
+    pub fn new(client: Client, namespace: impl Into<String>) -> PodClient {
+        let namespace = namespace.into();
+        let exec = ExecOptions::default();
+        PodClient {
+            pods: Api::namespaced(client, &namespace),
+            namespace,
+            container: String::new(),
+            connect_timeout: exec.connect_timeout,
+            idle_timeout: exec.idle_timeout,
+            transport: Arc::new(Exec(transport::Cat)),
+            cancel: exec.cancel,
+            system_paths: None,
+            allow_system_paths: exec.allow_system_paths,
+        }
+    }
+
+    /// Container of the pods, their default container if not given
+    pub fn container(mut self, container: impl Into<String>) -> PodClient {
+        self.container = container.into();
+        self
+    }
+
+    /// Timeout for establishing each exec stream
+    pub fn connect_timeout(mut self, timeout: Duration) -> PodClient {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// A transfer fails when its exec stream makes no progress for this long
+    pub fn idle_timeout(mut self, timeout: Duration) -> PodClient {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// How the files are copied, `exec-cat` if not given
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> PodClient {
+        self.transport = transport;
+        self
+    }
+
+    /// Aborts transfers with [`Error::Cancelled`] when `cancel` is cancelled
+    pub fn cancel(mut self, cancel: CancellationToken) -> PodClient {
+        self.cancel = cancel;
+        self
+    }
+
+    /// The directories uploads refuse to write right into, see
+    /// [`ExecOptions::system_paths`]
+    pub fn system_paths(mut self, paths: Vec<String>) -> PodClient {
+        self.system_paths = Some(paths);
+        self
+    }
+
+    /// Lets uploads write into the system paths anyway
+    pub fn allow_system_paths(mut self, allow: bool) -> PodClient {
+        self.allow_system_paths = allow;
+        self
+    }
+
+    /// `path` in `pod`
+    pub fn path(&self, pod: impl Into<String>, path: impl Into<String>) -> PodPath {
+        PodPath {
+            client: self.clone(),
+            pod: pod.into(),
+            path: path.into(),
+        }
+    }
+
+    fn exec(&self) -> ExecOptions<'_> {
+        ExecOptions {
+            container: &self.container,
+            connect_timeout: self.connect_timeout,
+            idle_timeout: self.idle_timeout,
+            cancel: self.cancel.clone(),
+            transport: &*self.transport,
+            system_paths: self.system_paths.as_deref(),
+            allow_system_paths: self.allow_system_paths,
+            ..ExecOptions::default()
+        }
+    }
+
+    /// Explains a failure to reach `pod` like the command line does
+    async fn explain(&self, pod: &str, err: Error) -> Error {
+        diagnose::explain(
+            &self.pods,
+            &self.namespace,
+            pod,
+            &self.container,
+            self.connect_timeout,
+            err,
+        )
+        .await
+    }
+}
+
+/// A path in a pod.
+///
+/// As a source it is a single file, as a sink the directory the files are
+/// copied into.
+#[derive(Debug, Clone)]
+pub struct PodPath {
+    client: PodClient,
+    pod: String,
+    path: String,
+}
+
+impl PodPath {
+    /// Where the sink writes `entry`
+    fn target(&self, entry: &Entry) -> String {
+        files::join(&self.path, &entry.path)
+    }
+
+    /// Fails unless `path` has the digest `digest`
+    async fn remote_digest(&self, path: &str, hasher: &dyn Hasher, digest: &str) -> Result<()> {
+        copy::check_digest(
+            &self.client.pods,
+            &self.pod,
+            &self.client.container,
+            path,
+            hasher,
+            digest,
+            self.client.idle_timeout,
+            &self.client.cancel,
+        )
+        .await
+    }
+}
+
+impl Source for PodPath {
+    fn entries(&self) -> BoxFuture<'_, Result<Vec<Entry>>> {
+        Box::pin(async {
+            let name = self.path.trim_end_matches('/').rsplit('/').next();
+            match name {
+                Some(name) if !name.is_empty() => Ok(vec![Entry {
+                    path: name.to_string(),
+                    // the size isn't known without asking the pod
+                    size: None,
+                }]),
+                _ => Err(Error::Config(format!("{} has no file name", self.path))),
+            }
+        })
+    }
+
+    fn read<'a>(
+        &'a self,
+        _entry: &'a Entry,
+        to: &'a mut (dyn AsyncWrite + Send + Unpin),
+        progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let opts = self.client.exec();
+            let res = opts
+                .transport
+                .download(
+                    &self.client.pods,
+                    &self.pod,
+                    &self.path,
+                    to,
+                    &opts,
+                    progress,
+                )
+                .await;
+            match res {
+                Ok(()) => Ok(()),
+                Err(err) => Err(self.client.explain(&self.pod, err).await),
+            }
+        })
+    }
+
+    fn describe(&self, _entry: &Entry) -> String {
+        self.path.clone()
+    }
+
+    fn pod(&self) -> Option<&str> {
+        Some(&self.pod)
+    }
+
+    fn check<'a>(
+        &'a self,
+        _entry: &'a Entry,
+        hasher: &'a dyn Hasher,
+        digest: &'a str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.remote_digest(&self.path, hasher, digest))
+    }
+}
+
+impl Sink for PodPath {
+    fn write<'a>(
+        &'a self,
+        entry: &'a Entry,
+        from: &'a mut (dyn AsyncRead + Send + Unpin),
+        progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let opts = self.client.exec();
+            let dst = self.target(entry);
+            let res = match entry.size {
+                Some(size) => {
+                    opts.transport
+                        .upload(
+                            &self.client.pods,
+                            &self.pod,
+                            from,
+                            size,
+                            &dst,
+                            &opts,
+                            progress,
+                        )
+                        .await
+                }
+                // transports are told the size up front
+                None => {
+                    let spool = Spool::new();
+                    let mut file = spool.fill(from).await?;
+                    let size = file
+                        .metadata()
+                        .await
+                        .map_err(|err| Error::io(&spool.path, err))?
+                        .len();
+                    opts.transport
+                        .upload(
+                            &self.client.pods,
+                            &self.pod,
+                            &mut file,
+                            size,
+                            &dst,
+                            &opts,
+                            progress,
+                        )
+                        .await
+                }
+            };
+            match res {
+                Ok(()) => Ok(()),
+                Err(Error::Cancelled) => {
+                    copy::remove(&self.client.pods, &self.pod, &dst, &opts).await;
+                    Err(Error::Cancelled)
+                }
+                Err(err) => Err(self.client.explain(&self.pod, err).await),
+            }
+        })
+    }
+
+    fn describe(&self, entry: &Entry) -> String {
+        self.target(entry)
+    }
+
+    fn pod(&self) -> Option<&str> {
+        Some(&self.pod)
+    }
+
+    fn check<'a>(
+        &'a self,
+        entry: &'a Entry,
+        hasher: &'a dyn Hasher,
+        digest: &'a str,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.remote_digest(&self.target(entry), hasher, digest)
+                .await
+        })
+    }
+
+    fn remove<'a>(&'a self, entry: &'a Entry) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let opts = self.client.exec();
+            copy::remove(&self.client.pods, &self.pod, &self.target(entry), &opts).await
+        })
+    }
+}
@@ -0,0 +1,90 @@
+use std::{path::Path, sync::Arc};
+
+use futures::future::{self, BoxFuture};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::Mutex,
+};
+
+use super::{
+    local::{self, Spool},
+    Entry, Sink, Source,
+};
+use crate::{progress::ItemProgress, Error, Result};
+
+/// The standard input of the process as a single file.
+///
+/// It is read once and kept in a temporary file, every destination and
+/// retry gets the same contents.
+#[derive(Debug, Clone)]
+pub struct Stdin {
+    name: String,
+    spool: Arc<Mutex<Option<Spool>>>,
+}
+
+impl Stdin {
+    /// `name` is what the file is called at the destination
+    pub fn new(name: impl Into<String>) -> Stdin {
+        Stdin {
+            name: name.into(),
+            spool: Arc::default(),
+        }
+    }
+}
+
+impl Source for Stdin {
+    fn entries(&self) -> BoxFuture<'_, Result<Vec<Entry>>> {
+        Box::pin(future::ready(Ok(vec![Entry {
+            path: self.name.clone(),
+            size: None,
+        }])))
+    }
+
+    fn read<'a>(
+        &'a self,
+        _entry: &'a Entry,
+        to: &'a mut (dyn AsyncWrite + Send + Unpin),
+        _progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut file = {
+                let mut spool = self.spool.lock().await;
+                match &*spool {
+                    Some(spool) => tokio::fs::File::open(&spool.path)
+                        .await
+                        .map_err(|err| Error::io(&spool.path, err))?,
+                    None => {
+                        let filled = Spool::new();
+                        let file = filled.fill(&mut tokio::io::stdin()).await?;
+                        *spool = Some(filled);
+                        file
+                    }
+                }
+            };
+            local::copy(&mut file, to, Path::new("-")).await
+        })
+    }
+
+    fn describe(&self, _entry: &Entry) -> String {
+        "-".to_string()
+    }
+}
+
+/// The standard output of the process, files are written one after another
+#[derive(Debug, Clone, Default)]
+pub struct Stdout;
+
+impl Sink for Stdout {
+    fn write<'a>(
+        &'a self,
+        _entry: &'a Entry,
+        from: &'a mut (dyn AsyncRead + Send + Unpin),
+        _progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { local::copy(from, &mut tokio::io::stdout(), Path::new("-")).await })
+    }
+
+    fn describe(&self, _entry: &Entry) -> String {
+        "-".to_string()
+    }
+}
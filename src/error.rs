@@ -0,0 +1,165 @@
+use std::{fmt, io, path::PathBuf, sync::Arc};
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use kube::client::UpgradeConnectionError;
+
+/// Result of the library operations
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Why a library operation failed, for callers that handle some causes
+/// differently than others
+#[derive(Debug, Clone, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The cluster rejected the credentials, or they couldn't be obtained
+    #[error("{0}")]
+    Auth(String),
+    /// The API server or an exec stream couldn't be reached or broke off
+    #[error("{0}")]
+    Connection(String),
+    /// Connecting, a stalled exec stream or a command took too long
+    #[error("{0}")]
+    Timeout(String),
+    /// The namespace, pod or container doesn't exist
+    #[error("{0}")]
+    PodResolution(String),
+    /// A command in the container failed, `stderr` is what it wrote there
+    /// and `message` what Kubernetes reported
+    #[error("{}", match stderr.trim() { "" => message.as_str(), stderr => stderr })]
+    RemoteCommand {
+        stderr: String,
+        exit_code: Option<i32>,
+        message: String,
+    },
+    /// A copy has a different digest on the two ends
+    #[error(
+        "verification of {path} in pod {pod} failed: {algorithm} {local} locally, {remote} in the pod"
+    )]
+    Verification {
+        pod: String,
+        path: String,
+        /// The name of the [`Hasher`](crate::hash::Hasher) the digests are of
+        algorithm: String,
+        local: String,
+        remote: String,
+    },
+    /// Reading or writing a local file failed
+    #[error("{}: {error}", path.display())]
+    Io {
+        path: PathBuf,
+        error: Arc<io::Error>,
+    },
+    /// An object store or HTTP server failed a request, see
+    /// [`endpoint::Object`](crate::endpoint::Object)
+    #[error("{0}")]
+    Storage(String),
+    /// The kubeconfig or the request is invalid
+    #[error("{0}")]
+    Config(String),
+    /// The operation was cancelled through its
+    /// [`CancellationToken`](tokio_util::sync::CancellationToken)
+    #[error("cancelled")]
+    Cancelled,
+}
+
+impl Error {
+    pub(crate) fn io(path: impl Into<PathBuf>, error: io::Error) -> Error {
+        Error::Io {
+            path: path.into(),
+            error: Arc::new(error),
+        }
+    }
+
+    /// The failure of a command that ended with `status`
+    pub(crate) fn remote(status: Status, stderr: String) -> Error {
+        let exit_code = status
+            .details
+            .as_ref()
+            .and_then(|details| details.causes.as_ref())
+            .into_iter()
+            .flatten()
+            .find(|cause| cause.reason.as_deref() == Some("ExitCode"))
+            .and_then(|cause| cause.message.as_deref()?.parse().ok());
+        Error::RemoteCommand {
+            stderr,
+            exit_code,
+            message: status
+                .message
+                .unwrap_or_else(|| "command failed".to_string()),
+        }
+    }
+
+    /// `err` on an exec stream while doing `what`
+    pub(crate) fn stream(what: impl fmt::Display, err: io::Error) -> Error {
+        Error::Connection(format!("{}: {}", what, err))
+    }
+
+    /// The exec stream to `pod` came back without its `channel`, like one
+    /// with a TTY, which has no stderr, or one the server closed early
+    pub(crate) fn missing(pod: &str, channel: &str) -> Error {
+        Error::Connection(format!(
+            "the exec stream to pod {} came back without {}",
+            pod, channel
+        ))
+    }
+
+    /// The [`Error`] `err` of a reader or writer passed in carries, see
+    /// `From<Error> for io::Error`, or what `otherwise` makes of it
+    pub(crate) fn unwrap_io(err: io::Error, otherwise: impl FnOnce(io::Error) -> Error) -> Error {
+        match err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<Error>())
+        {
+            Some(inner) => inner.clone(),
+            None => otherwise(err),
+        }
+    }
+}
+
+/// For readers and writers handed to a transport or an endpoint, it gets
+/// the [`Error`] back unchanged
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        io::Error::other(err)
+    }
+}
+
+impl From<kube::Error> for Error {
+    fn from(err: kube::Error) -> Error {
+        let message = chain(&err);
+        let code = match &err {
+            kube::Error::Api(resp) => Some(resp.code),
+            kube::Error::UpgradeConnection(UpgradeConnectionError::ProtocolSwitch(status)) => {
+                Some(status.as_u16())
+            }
+            kube::Error::Auth(_) => Some(401),
+            _ => None,
+        };
+        match code {
+            Some(401 | 403) => Error::Auth(message),
+            Some(404) => Error::PodResolution(message),
+            _ => Error::Connection(message),
+        }
+    }
+}
+
+impl From<kube::config::KubeconfigError> for Error {
+    fn from(err: kube::config::KubeconfigError) -> Error {
+        Error::Config(chain(&err))
+    }
+}
+
+/// `err` and its causes, like `{:#}` shows an `anyhow::Error`
+fn chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        // some errors repeat their source in their own message
+        if !message.ends_with(&cause_message) {
+            message = format!("{}: {}", message, cause_message);
+        }
+        source = cause.source();
+    }
+    message
+}
@@ -0,0 +1,281 @@
+use std::time::{Duration, Instant};
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, AttachParams};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    time,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    transport::{self, Channel, Traced},
+    Error, Result,
+};
+
+/// Output of a command run in a pod
+#[derive(Debug)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs `command` with `sh -c` in `pod` and collects its output, failing if
+/// it doesn't exit successfully within `timeout` or `cancel` is cancelled.
+/// The stream stalled when neither stdout nor stderr has anything for
+/// `idle_timeout`, or the status doesn't follow their end within it; pass
+/// `timeout` for a command that prints nothing until it is done.
+pub async fn run(
+    pods: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    command: &str,
+    timeout: Duration,
+    idle_timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Output> {
+    run_with_input(
+        pods,
+        pod,
+        container,
+        command,
+        None,
+        timeout,
+        idle_timeout,
+        cancel,
+    )
+    .await
+}
+
+/// [`run`] with `input` written to the stdin of `command`, for what mustn't
+/// be on its command line where any process in the pod can read it. Stdin
+/// stays open until the command exits.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_with_input(
+    pods: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    command: &str,
+    input: Option<&[u8]>,
+    timeout: Duration,
+    idle_timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Output> {
+    let mut ap = AttachParams::default().stdin(input.is_some());
+    if !container.is_empty() {
+        ap = ap.container(container);
+    }
+
+    let run = async {
+        let connecting = Instant::now();
+        let mut attached = pods.exec(pod, vec!["sh", "-c", command], &ap).await?;
+        transport::connected(pod, connecting);
+        // dropping stdin closes the stream, it's kept until the status is in
+        let _stdin = match input {
+            Some(input) => {
+                let stdin = attached
+                    .stdin()
+                    .ok_or_else(|| Error::missing(pod, "stdin"))?;
+                let mut stdin_writer = Traced::new(stdin, pod, Channel::Stdin);
+                let writing = format!("writing to a command in pod {}", pod);
+                stdin_writer
+                    .write_all(input)
+                    .await
+                    .map_err(|err| Error::stream(&writing, err))?;
+                stdin_writer
+                    .flush()
+                    .await
+                    .map_err(|err| Error::stream(&writing, err))?;
+                Some(stdin_writer)
+            }
+            None => None,
+        };
+        let stdout = attached
+            .stdout()
+            .ok_or_else(|| Error::missing(pod, "stdout"))?;
+        let mut stdout_reader = Traced::new(stdout, pod, Channel::Stdout);
+        let stderr = attached
+            .stderr()
+            .ok_or_else(|| Error::missing(pod, "stderr"))?;
+        let mut stderr_reader = Traced::new(stderr, pod, Channel::Stderr);
+        let status = attached
+            .take_status()
+            .ok_or_else(|| Error::missing(pod, "a status"))?;
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let (mut stdout_done, mut stderr_done) = (false, false);
+        let mut stdout_buf = vec![0u8; 16 * 1024];
+        let mut stderr_buf = vec![0u8; 16 * 1024];
+        // each read is cancelled by the other one arriving first, which loses
+        // nothing
+        while !stdout_done || !stderr_done {
+            let read = async {
+                tokio::select! {
+                    n = stdout_reader.read(&mut stdout_buf), if !stdout_done => {
+                        n.map(|n| (Channel::Stdout, n))
+                    }
+                    n = stderr_reader.read(&mut stderr_buf), if !stderr_done => {
+                        n.map(|n| (Channel::Stderr, n))
+                    }
+                }
+            };
+            let (channel, n) = time::timeout(idle_timeout, read)
+                .await
+                .map_err(|_| Error::Timeout(stalled(idle_timeout)))?
+                .map_err(|err| Error::stream(format!("running a command in pod {}", pod), err))?;
+            match (channel, n) {
+                (Channel::Stdout, 0) => stdout_done = true,
+                (Channel::Stdout, n) => stdout.extend_from_slice(&stdout_buf[..n]),
+                (_, 0) => stderr_done = true,
+                (_, n) => stderr.extend_from_slice(&stderr_buf[..n]),
+            }
+        }
+        let output = Output {
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        };
+        let status = time::timeout(idle_timeout, status)
+            .await
+            .map_err(|_| Error::Timeout(stalled(idle_timeout)))?;
+        transport::status(pod, status.as_ref(), connecting);
+        match status {
+            Some(status) if status.status.as_deref() != Some("Success") => {
+                Err(Error::remote(status, output.stderr))
+            }
+            _ => Ok(output),
+        }
+    };
+    let run = time::timeout(timeout, run);
+    let output = tokio::select! {
+        output = run => output,
+        () = cancel.cancelled() => return Err(Error::Cancelled),
+    };
+    output.map_err(|_| {
+        Error::Timeout(format!(
+            "timed out after {} running a command in pod {}",
+            humantime::format_duration(timeout),
+            pod
+        ))
+    })?
+}
+
+/// Runs `command` with `sh -c` in `pod`, writing what it prints to `stdout`
+/// and `stderr` as it arrives rather than collecting it, for commands that
+/// run for as long as they are wanted, like `tail -f`.
+///
+/// Only establishing the stream is bound by `timeout`, the command runs
+/// until it exits or `cancel` is cancelled. Its stdin is kept open and
+/// closes when this returns, which a command can wait for to know nobody
+/// reads it anymore. Failing to write `stdout` or `stderr` is an
+/// [`Error::Io`] of the path `-`, and as what the command wrote to stderr
+/// was written to `stderr` already, its failure has only its status.
+#[allow(clippy::too_many_arguments)]
+pub async fn stream(
+    pods: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    command: &str,
+    stdout: &mut (dyn AsyncWrite + Send + Unpin),
+    stderr: &mut (dyn AsyncWrite + Send + Unpin),
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let mut ap = AttachParams::default().stdin(true);
+    if !container.is_empty() {
+        ap = ap.container(container);
+    }
+
+    let connecting = Instant::now();
+    let exec = time::timeout(timeout, pods.exec(pod, vec!["sh", "-c", command], &ap));
+    let attached = tokio::select! {
+        attached = exec => attached,
+        () = cancel.cancelled() => return Err(Error::Cancelled),
+    };
+    let mut attached = attached.map_err(|_| {
+        Error::Timeout(format!(
+            "timed out after {} establishing exec stream to pod {}",
+            humantime::format_duration(timeout),
+            pod
+        ))
+    })??;
+    transport::connected(pod, connecting);
+    let _stdin = attached.stdin();
+    let remote_stdout = attached
+        .stdout()
+        .ok_or_else(|| Error::missing(pod, "stdout"))?;
+    let stdout_reader = Traced::new(remote_stdout, pod, Channel::Stdout);
+    let remote_stderr = attached
+        .stderr()
+        .ok_or_else(|| Error::missing(pod, "stderr"))?;
+    let stderr_reader = Traced::new(remote_stderr, pod, Channel::Stderr);
+    let status = attached
+        .take_status()
+        .ok_or_else(|| Error::missing(pod, "a status"))?;
+
+    let forwarded = async {
+        tokio::try_join!(
+            forward(stdout_reader, stdout, pod),
+            forward(stderr_reader, stderr, pod),
+        )
+    };
+    let forwarded = tokio::select! {
+        forwarded = forwarded => forwarded,
+        () = cancel.cancelled() => Err(Error::Cancelled),
+    };
+    if forwarded.is_err() {
+        attached.abort();
+    }
+    forwarded?;
+    let status = status.await;
+    transport::status(pod, status.as_ref(), connecting);
+    match status {
+        Some(status) if status.status.as_deref() != Some("Success") => {
+            Err(Error::remote(status, String::new()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Copies `reader` to `writer` until the end
+async fn forward(
+    mut reader: impl AsyncRead + Unpin,
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+    pod: &str,
+) -> Result<()> {
+    let local = |err| Error::io("-", err);
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .await
+            .map_err(|err| Error::stream(format!("running a command in pod {}", pod), err))?;
+        if n == 0 {
+            return writer.flush().await.map_err(local);
+        }
+        writer.write_all(&buf[..n]).await.map_err(local)?;
+        // what arrives in pieces, like lines, is written as it arrives
+        writer.flush().await.map_err(local)?;
+    }
+}
+
+/// What a stream that made no progress for `idle_timeout` fails with
+pub(crate) fn stalled(idle_timeout: Duration) -> String {
+    format!(
+        "exec stream stalled, no progress for {}",
+        humantime::format_duration(idle_timeout)
+    )
+}
+
+/// Quotes `s` as a single word for `sh`
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// `command` run with the file mode creation mask `umask` if given
+pub fn umasked(umask: Option<u32>, command: &str) -> String {
+    match umask {
+        Some(umask) => format!("umask {:03o} && {}", umask, command),
+        None => command.to_string(),
+    }
+}
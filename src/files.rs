@@ -0,0 +1,305 @@
+use std::{
+    collections::HashSet,
+    fs::Metadata,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+use walkdir::WalkDir;
+
+use crate::{Error, Result};
+
+/// A local file and where it is written in the container
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub local: PathBuf,
+    /// Remote directory the file is written to
+    pub remote_dir: String,
+    pub name: String,
+    pub size: u64,
+}
+
+impl FileEntry {
+    pub fn remote_path(&self) -> String {
+        format!("{}/{}", self.remote_dir.trim_end_matches('/'), self.name)
+    }
+}
+
+/// The names of the version control directories, build output and caches
+/// that a copy of a source tree rarely wants, for [`Walk::exclude`]
+pub const VCS_EXCLUDES: &[&str] = &[
+    ".git",
+    ".hg",
+    ".svn",
+    "target",
+    "node_modules",
+    "__pycache__",
+];
+
+/// How [`collect_with`] and [`dirs`] walk a directory
+#[derive(Debug, Clone, Default)]
+pub struct Walk {
+    /// How many levels below the directory to go, 1 for just what is in it,
+    /// all of them if `None`
+    pub max_depth: Option<usize>,
+    /// Don't go into directories on other file systems than the directory,
+    /// like mounted volumes or network mounts
+    pub one_file_system: bool,
+    /// Names of the files and directories below the directory to leave out,
+    /// with everything below them
+    pub exclude: Vec<String>,
+}
+
+impl Walk {
+    /// Whether the path `rel` below the directory, `/` separated, is left out
+    pub fn excludes(&self, rel: &str) -> bool {
+        rel.split('/')
+            .any(|name| self.exclude.iter().any(|n| n == name))
+    }
+
+    fn entries<'a>(
+        &'a self,
+        dir: &Path,
+    ) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> + 'a {
+        let mut walk = WalkDir::new(dir)
+            .sort_by_file_name()
+            .same_file_system(self.one_file_system);
+        if let Some(depth) = self.max_depth {
+            walk = walk.max_depth(depth);
+        }
+        // the directory itself is copied whatever its name
+        walk.into_iter().filter_entry(move |entry| {
+            entry.depth() == 0
+                || !self
+                    .exclude
+                    .iter()
+                    .any(|name| entry.file_name() == name.as_str())
+        })
+    }
+}
+
+/// Lists the files to copy from `src` into the remote directory `dst`. A directory
+/// is copied recursively into `dst/<directory name>`, like `cp -r` does, anything
+/// in it that is neither a file nor a directory is passed to `skipped`. A `src`
+/// that is a named pipe or a device is copied as it is read, its size is 0,
+/// see [`is_stream`].
+pub fn collect(src: &Path, dst: &str, skipped: impl FnMut(&Path)) -> Result<Vec<FileEntry>> {
+    collect_with(src, dst, &Walk::default(), skipped)
+}
+
+/// [`collect`], walking a directory `src` as `walk` says
+pub fn collect_with(
+    src: &Path,
+    dst: &str,
+    walk: &Walk,
+    mut skipped: impl FnMut(&Path),
+) -> Result<Vec<FileEntry>> {
+    let meta = std::fs::metadata(src).map_err(|err| Error::io(src, err))?;
+    if !meta.is_dir() {
+        let name = file_name(src)?;
+        return Ok(vec![FileEntry {
+            local: src.to_path_buf(),
+            remote_dir: dst.to_string(),
+            name,
+            size: meta.len(),
+        }]);
+    }
+
+    let base = base(src, dst);
+    let mut files = Vec::new();
+    for entry in walk.entries(src) {
+        let entry = entry.map_err(|err| {
+            let path = err.path().unwrap_or(src).to_path_buf();
+            Error::io(path, err.into())
+        })?;
+        if !entry.file_type().is_file() {
+            if !entry.file_type().is_dir() {
+                skipped(entry.path());
+            }
+            continue;
+        }
+        let rel = entry.path().strip_prefix(src).unwrap_or(entry.path());
+        files.push(FileEntry {
+            local: entry.path().to_path_buf(),
+            remote_dir: remote_dir(&base, rel),
+            name: file_name(entry.path())?,
+            size: entry
+                .metadata()
+                .map_err(|err| Error::io(entry.path(), err.into()))?
+                .len(),
+        });
+    }
+    Ok(files)
+}
+
+/// Lists the remote paths of the directory `src` and the directories below
+/// it `walk` goes to, where [`collect`] would put them in `dst`, none if `src` isn't a
+/// directory
+pub fn dirs(src: &Path, dst: &str, walk: &Walk) -> Result<Vec<String>> {
+    let meta = std::fs::metadata(src).map_err(|err| Error::io(src, err))?;
+    if !meta.is_dir() {
+        return Ok(Vec::new());
+    }
+    let base = base(src, dst);
+    let mut dirs = Vec::new();
+    for entry in walk.entries(src) {
+        let entry = entry.map_err(|err| {
+            let path = err.path().unwrap_or(src).to_path_buf();
+            Error::io(path, err.into())
+        })?;
+        if entry.file_type().is_dir() {
+            let rel = entry.path().strip_prefix(src).unwrap_or(entry.path());
+            dirs.push(below(&base, rel));
+        }
+    }
+    Ok(dirs)
+}
+
+/// Lists the files at the relative paths `listed` below the directory `src`,
+/// in that order, where [`collect`] would put them in `dst`. Paths listed
+/// twice are copied once, those that aren't regular files are passed to
+/// `skipped`.
+pub fn listed(
+    src: &Path,
+    dst: &str,
+    listed: &[PathBuf],
+    mut skipped: impl FnMut(&Path),
+) -> Result<Vec<FileEntry>> {
+    let meta = std::fs::metadata(src).map_err(|err| Error::io(src, err))?;
+    if !meta.is_dir() {
+        return Err(Error::Config(format!(
+            "{} isn't a directory to list files below",
+            src.display()
+        )));
+    }
+    let base = base(src, dst);
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for rel in listed {
+        // `./a` is what `find .` prints for `a`
+        let rel: PathBuf = rel
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect();
+        if !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(Error::Config(format!(
+                "{} isn't a relative path below {}",
+                rel.display(),
+                src.display()
+            )));
+        }
+        if rel.as_os_str().is_empty() || !seen.insert(rel.clone()) {
+            continue;
+        }
+        let local = src.join(&rel);
+        let meta = std::fs::symlink_metadata(&local).map_err(|err| Error::io(&local, err))?;
+        if !meta.is_file() {
+            skipped(&local);
+            continue;
+        }
+        files.push(FileEntry {
+            remote_dir: remote_dir(&base, &rel),
+            name: file_name(&local)?,
+            size: meta.len(),
+            local,
+        });
+    }
+    Ok(files)
+}
+
+/// The remote directory the contents of the directory `src` go to in `dst`
+fn base(src: &Path, dst: &str) -> String {
+    match src.file_name() {
+        Some(name) => join(dst, &name.to_string_lossy()),
+        // `.` and friends copy the directory contents
+        None => dst.to_string(),
+    }
+}
+
+/// The remote directory below `base` of the file at `rel` below the source
+fn remote_dir(base: &str, rel: &Path) -> String {
+    below(base, rel.parent().unwrap_or(Path::new("")))
+}
+
+/// The remote path below `base` of `rel` below the source
+fn below(base: &str, rel: &Path) -> String {
+    rel.components().fold(base.to_string(), |dir, c| {
+        join(&dir, &c.as_os_str().to_string_lossy())
+    })
+}
+
+/// Whether a file with the metadata `meta` is read as a stream whose size
+/// isn't known up front, like a named pipe or a device, rather than a
+/// regular file or a directory
+pub fn is_stream(meta: &Metadata) -> bool {
+    !meta.is_file() && !meta.is_dir()
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| Error::Config(format!("{} has no file name", path.display())))
+}
+
+/// Joins `name` to the remote directory `dir`
+pub fn join(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+/// The local path below `dir` of `rel`, a path relative to a remote
+/// directory, separated by `/` whatever the local separator is. Fails
+/// unless every part of `rel` is a plain name locally too, not `..` nor
+/// anything the local system reads as a root or a drive, since `rel` comes
+/// from a pod that could otherwise have files written outside `dir`.
+pub fn local_path(dir: &Path, rel: &str) -> Result<PathBuf> {
+    let mut path = dir.to_path_buf();
+    for name in rel
+        .split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+    {
+        let mut components = Path::new(name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => path.push(name),
+            _ => {
+                return Err(Error::Config(format!(
+                    "{} isn't a relative path below {}",
+                    rel,
+                    dir.display()
+                )))
+            }
+        }
+    }
+    Ok(path)
+}
+
+/// The hidden file next to `path` a download is written to before it is
+/// renamed to `path`, so nothing watching `path` sees a partial file
+pub fn part_path(path: &Path) -> PathBuf {
+    path.with_file_name(format!(
+        ".{}.k8scp-part",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ))
+}
+
+/// Gives the local file `path` the permission bits of `mode`, an `st_mode`
+/// whose type bits are ignored, and the modification time `modified`, those
+/// that are `Some`. The permissions are left as they are off Unix.
+pub fn preserve(path: &Path, mode: Option<u32>, modified: Option<SystemTime>) -> Result<()> {
+    // before the permissions, which may not let the file be opened to write
+    if let Some(modified) = modified {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(modified))
+            .map_err(|err| Error::io(path, err))?;
+    }
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))
+            .map_err(|err| Error::io(path, err))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(())
+}
@@ -0,0 +1,116 @@
+//! Directories of a container uploads refuse to write into, so a mistyped
+//! destination doesn't break it, see [`ExecOptions::system_paths`], and the
+//! paths not to remove from it, see [`dangerous`].
+//!
+//! [`ExecOptions::system_paths`]: crate::ExecOptions::system_paths
+
+use crate::{copy::ExecOptions, Error, Result};
+
+/// Directories removing which breaks the container, on top of `/` and every
+/// directory right below it
+pub const SYSTEM_DIRS: &[&str] = &[
+    "/usr/bin",
+    "/usr/lib",
+    "/usr/lib64",
+    "/usr/libexec",
+    "/usr/local",
+    "/usr/sbin",
+    "/usr/share",
+    "/var/lib",
+    "/var/log",
+    "/var/run",
+];
+
+/// The directories right below `/` that copies don't write into by default
+pub const TOP_DIRS: &[&str] = &[
+    "/", "/bin", "/boot", "/dev", "/etc", "/lib", "/lib32", "/lib64", "/proc", "/sbin", "/sys",
+    "/usr",
+];
+
+/// The directories copies don't write into by default, [`TOP_DIRS`] and
+/// [`SYSTEM_DIRS`]
+pub fn system_paths() -> Vec<String> {
+    TOP_DIRS
+        .iter()
+        .chain(SYSTEM_DIRS)
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// The one of `denied` that `path` is or is right in, if any. What is in a
+/// directory below one, like `/etc/app/app.conf`, is fine. Paths are
+/// normalized first and relative ones never match.
+pub fn system_path<'a, S: AsRef<str>>(path: &str, denied: &'a [S]) -> Option<&'a str> {
+    if !path.starts_with('/') {
+        return None;
+    }
+    let path = normal(path);
+    let parent = match path.rfind('/') {
+        Some(0) => "/",
+        Some(i) => &path[..i],
+        None => "/",
+    };
+    denied.iter().map(AsRef::as_ref).find(|dir| {
+        let dir = normal(&format!("/{}", dir.trim_start_matches('/')));
+        dir == path || dir == parent
+    })
+}
+
+/// Fails with [`Error::Config`] if `opts` don't let an upload write `dst`
+pub(crate) fn check(dst: &str, opts: &ExecOptions<'_>) -> Result<()> {
+    if opts.allow_system_paths {
+        return Ok(());
+    }
+    let dir = match opts.system_paths {
+        Some(denied) => system_path(dst, denied),
+        None => system_path(dst, TOP_DIRS).or_else(|| system_path(dst, SYSTEM_DIRS)),
+    };
+    match dir {
+        Some(dir) => Err(Error::Config(format!(
+            "refusing to write {} into the system directory {}, system paths aren't allowed",
+            dst, dir
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Why removing `path` from a container would be a mistake, if it would:
+/// `/`, a directory right below it, one of [`SYSTEM_DIRS`], the working
+/// directory or a path going up with `..`
+pub fn dangerous(path: &str) -> Option<&'static str> {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return Some("it goes up with `..`"),
+            part => parts.push(part),
+        }
+    }
+    if !path.starts_with('/') {
+        return parts
+            .is_empty()
+            .then_some("it is the working directory of the container");
+    }
+    let normal = format!("/{}", parts.join("/"));
+    match parts.len() {
+        0 => Some("it is the root directory"),
+        1 => Some("it is a top-level directory"),
+        _ if SYSTEM_DIRS.contains(&normal.as_str()) => Some("it is a system directory"),
+        _ => None,
+    }
+}
+
+/// `path` without `.`, `..` and repeated or trailing slashes
+fn normal(path: &str) -> String {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
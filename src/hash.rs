@@ -0,0 +1,116 @@
+//! The digests copies are verified with.
+//!
+//! A [`Hasher`] hashes a file locally as it goes through a copy and names
+//! the command computing the same digest in a container. [`Sha256`] is the
+//! default and [`Sha512`] built in too, embedders bring their own for other
+//! algorithms or implementations, e.g. a FIPS-certified one.
+
+use std::{fmt, path::Path};
+
+use sha2::Digest as _;
+use tokio::io::AsyncReadExt;
+
+use crate::{exec::quote, Error, Result};
+
+/// An algorithm copies are hashed and verified with
+pub trait Hasher: Send + Sync {
+    /// The name of the algorithm in messages, e.g. `SHA-256`
+    fn name(&self) -> &str;
+
+    /// Starts hashing a file
+    fn digest(&self) -> Box<dyn Digest>;
+
+    /// Shell command printing the digest of the file `path` as hex, the
+    /// first word of its output like with `sha256sum`
+    fn remote_command(&self, path: &str) -> String;
+}
+
+/// The digest of a file being hashed
+pub trait Digest: Send {
+    /// Hashes the next `data` of the file
+    fn update(&mut self, data: &[u8]);
+
+    /// The digest of the data so far as lowercase hex
+    fn hex(&self) -> String;
+}
+
+impl fmt::Debug for dyn Hasher + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The digest of the local file `path`
+pub async fn file(hasher: &dyn Hasher, path: &Path) -> Result<String> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|err| Error::io(path, err))?;
+    let mut digest = hasher.digest();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .await
+            .map_err(|err| Error::io(path, err))?;
+        if n == 0 {
+            return Ok(digest.hex());
+        }
+        digest.update(&buf[..n]);
+    }
+}
+
+/// SHA-256, checked with `sha256sum` in the container
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256;
+
+impl Hasher for Sha256 {
+    fn name(&self) -> &str {
+        "SHA-256"
+    }
+
+    fn digest(&self) -> Box<dyn Digest> {
+        Box::new(sha2::Sha256::new())
+    }
+
+    fn remote_command(&self, path: &str) -> String {
+        format!("sha256sum -- {}", quote(path))
+    }
+}
+
+impl Digest for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+
+    fn hex(&self) -> String {
+        format!("{:x}", self.clone().finalize())
+    }
+}
+
+/// SHA-512, checked with `sha512sum` in the container
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha512;
+
+impl Hasher for Sha512 {
+    fn name(&self) -> &str {
+        "SHA-512"
+    }
+
+    fn digest(&self) -> Box<dyn Digest> {
+        Box::new(sha2::Sha512::new())
+    }
+
+    fn remote_command(&self, path: &str) -> String {
+        format!("sha512sum -- {}", quote(path))
+    }
+}
+
+impl Digest for sha2::Sha512 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+
+    fn hex(&self) -> String {
+        format!("{:x}", self.clone().finalize())
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use console::style;
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+use tracing::*;
+
+use k8scp::files::FileEntry;
+
+use crate::{term, Global};
+
+/// Show the transfers recorded in the local history
+#[derive(clap::Args, Debug)]
+pub struct HistoryArgs {
+    #[arg(short, long)]
+    pod: Option<String>,
+
+    /// Only transfers whose source or destination contains this
+    #[arg(long)]
+    path: Option<String>,
+
+    /// Only transfers started after this, a UTC date/time like `2022-11-08 14:00` or a duration ago like `7d`
+    #[arg(long, value_parser = parse_time)]
+    since: Option<SystemTime>,
+
+    /// Only transfers started before this, same format as `--since`
+    #[arg(long, value_parser = parse_time)]
+    until: Option<SystemTime>,
+
+    /// Only failed transfers
+    #[arg(long)]
+    failed: bool,
+
+    /// Show at most this many of the most recent transfers
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Print the entries as JSON lines
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Completed,
+    Failed,
+}
+
+/// One line of the history file
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Entry {
+    /// Start of the transfer, RFC 3339 in UTC
+    pub time: String,
+    pub context: Option<String>,
+    pub cluster: Option<String>,
+    pub namespace: String,
+    pub pod: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    pub src: String,
+    pub dst: String,
+    pub bytes: u64,
+    /// Digest of the bytes sent, only known for completed transfers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    pub result: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Where the transfers of a run go, stored with every entry
+pub struct Origin {
+    pub context: Option<String>,
+    pub cluster: Option<String>,
+    pub namespace: String,
+    pub container: Option<String>,
+}
+
+/// Appends every transfer to the history file
+pub struct History {
+    file: Option<Mutex<File>>,
+    origin: Origin,
+}
+
+impl History {
+    /// Opens the history at `path` or the default location, a history that
+    /// can't be opened is reported and disabled rather than failing the copy
+    pub fn open(path: Option<&Path>, origin: Origin) -> History {
+        let file = match path.map(Path::to_path_buf).or_else(default_path) {
+            Some(path) => match append(&path) {
+                Ok(file) => Some(Mutex::new(file)),
+                Err(err) => {
+                    term::warn(format!("not recording history: {:#}", err));
+                    None
+                }
+            },
+            None => {
+                term::warn("not recording history: no user data directory");
+                None
+            }
+        };
+        History { file, origin }
+    }
+
+    pub fn disabled(origin: Origin) -> History {
+        History { file: None, origin }
+    }
+
+    /// Records a transfer of `file` to `pod` started at `time`, `res` holds
+    /// the checksum of a completed transfer
+    pub fn record(
+        &self,
+        time: SystemTime,
+        pod: &str,
+        file: &FileEntry,
+        bytes: u64,
+        res: &anyhow::Result<String>,
+    ) {
+        let Some(history) = &self.file else {
+            return;
+        };
+        let entry = Entry {
+            time: humantime::format_rfc3339_seconds(time).to_string(),
+            context: self.origin.context.clone(),
+            cluster: self.origin.cluster.clone(),
+            namespace: self.origin.namespace.clone(),
+            pod: pod.to_string(),
+            container: self.origin.container.clone(),
+            src: file.local.to_string_lossy().into_owned(),
+            dst: file.remote_path(),
+            bytes,
+            sha256: res.as_ref().ok().cloned(),
+            result: match res {
+                Ok(_) => Outcome::Completed,
+                Err(_) => Outcome::Failed,
+            },
+            error: res.as_ref().err().map(|err| format!("{:#}", err)),
+        };
+        let mut line = serde_json::to_string(&entry).unwrap();
+        line.push('\n');
+        // a single write per entry keeps lines of concurrent runs intact
+        if let Err(err) = history.lock().unwrap().write_all(line.as_bytes()) {
+            warn!("failed to record history: {}", err);
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("k8scp").join("history.jsonl"))
+}
+
+fn append(path: &Path) -> anyhow::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("{}", dir.display()))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("{}", path.display()))
+}
+
+/// Parses a UTC date, date and time, or a duration counted back from now
+pub fn parse_time(s: &str) -> Result<SystemTime, String> {
+    if let Ok(ago) = humantime::parse_duration(s) {
+        return SystemTime::now()
+            .checked_sub(ago)
+            .ok_or_else(|| format!("{} ago is out of range", s));
+    }
+    [
+        s.to_string(),
+        format!("{}:00", s),
+        format!("{} 00:00:00", s),
+    ]
+    .iter()
+    .find_map(|s| humantime::parse_rfc3339_weak(s).ok())
+    .ok_or_else(|| "expected a date like `2022-11-08 14:00` or a duration like `7d`".to_string())
+}
+
+impl HistoryArgs {
+    /// Whether `entry` is one to show, `--context` and `--namespace` filter
+    /// by kubeconfig context or cluster and namespace when given
+    fn matches(&self, entry: &Entry, global: &Global) -> bool {
+        let is = |filter: &Option<String>, value: Option<&str>| {
+            filter.as_deref().is_none_or(|f| value == Some(f))
+        };
+        let time = humantime::parse_rfc3339(&entry.time).ok();
+        (is(&global.context, entry.context.as_deref())
+            || is(&global.context, entry.cluster.as_deref()))
+            && is(&global.namespace, Some(&entry.namespace))
+            && is(&self.pod, Some(&entry.pod))
+            && self
+                .path
+                .as_deref()
+                .is_none_or(|p| entry.src.contains(p) || entry.dst.contains(p))
+            && self
+                .since
+                .is_none_or(|since| time.is_some_and(|t| t >= since))
+            && self
+                .until
+                .is_none_or(|until| time.is_some_and(|t| t < until))
+            && (!self.failed || entry.result == Outcome::Failed)
+    }
+}
+
+/// Prints the recorded transfers matching `args`, oldest first
+pub fn show(args: &HistoryArgs, global: &Global) -> anyhow::Result<()> {
+    let Some(path) = global.history_file.clone().or_else(default_path) else {
+        anyhow::bail!("no user data directory, pass --history-file");
+    };
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("{}", path.display())),
+    };
+
+    let mut entries = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("{}", path.display()))?;
+        match serde_json::from_str::<Entry>(&line) {
+            Ok(entry) if args.matches(&entry, global) => entries.push(entry),
+            Ok(_) => {}
+            Err(err) => debug!("skipping line {} of {}: {}", n + 1, path.display(), err),
+        }
+    }
+    let skip = args
+        .limit
+        .map_or(0, |limit| entries.len().saturating_sub(limit));
+
+    for entry in &entries[skip..] {
+        if args.json {
+            println!("{}", serde_json::to_string(entry).unwrap());
+        } else {
+            print_entry(entry);
+        }
+    }
+    Ok(())
+}
+
+fn print_entry(entry: &Entry) {
+    let mark = match entry.result {
+        Outcome::Completed => style("✔").green().bold(),
+        Outcome::Failed => style("✘").red().bold(),
+    };
+    println!(
+        "{} {} {} -> {}/{}/{}:{} {} {}",
+        style(&entry.time).dim(),
+        mark,
+        entry.src,
+        entry.context.as_deref().unwrap_or("-"),
+        entry.namespace,
+        entry.pod,
+        entry.dst,
+        style(HumanBytes(entry.bytes)).bold(),
+        match (&entry.sha256, &entry.error) {
+            (Some(sha256), _) => style(format!("sha256:{:.12}", sha256)).dim(),
+            (None, Some(err)) => style(err.clone()).red(),
+            (None, None) => style(String::new()),
+        },
+    );
+}
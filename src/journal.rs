@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::*;
+
+use k8scp::files::FileEntry;
+
+/// One line of a journal, a file that reached a pod
+#[derive(Serialize, Deserialize, Debug)]
+struct Line {
+    pod: String,
+    src: String,
+    dst: String,
+    size: u64,
+    /// Modification time of the local file, in nanoseconds since the epoch
+    modified: u64,
+    sha256: String,
+}
+
+/// The files of a copy that reached their pods, so running the copy again
+/// after it was interrupted or failed skips them. It is removed once the
+/// copy completes.
+pub struct Journal {
+    path: Option<PathBuf>,
+    file: Mutex<Option<File>>,
+    /// Size and modification time of the copied files, by pod and
+    /// destination
+    done: HashMap<(String, String), (u64, u64)>,
+}
+
+impl Journal {
+    /// The journal of the copy identified by `key`, with what an earlier
+    /// run of it copied. One that can't be read is ignored, the copy then
+    /// starts over.
+    pub fn open(key: &[&str]) -> Journal {
+        let Some(dir) = dirs::data_dir() else {
+            return Journal::disabled();
+        };
+        let id = format!("{:x}", Sha256::digest(key.join("\0")));
+        let path = dir
+            .join("k8scp")
+            .join("journal")
+            .join(format!("{}.jsonl", &id[..16]));
+        let done = match File::open(&path) {
+            Ok(file) => BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .filter_map(|line| serde_json::from_str::<Line>(&line).ok())
+                .map(|line| ((line.pod, line.dst), (line.size, line.modified)))
+                .collect(),
+            Err(_) => HashMap::new(),
+        };
+        Journal {
+            path: Some(path),
+            file: Mutex::new(None),
+            done,
+        }
+    }
+
+    pub fn disabled() -> Journal {
+        Journal {
+            path: None,
+            file: Mutex::new(None),
+            done: HashMap::new(),
+        }
+    }
+
+    /// Whether an earlier run copied `file` to `pod` and it hasn't changed
+    /// since, by its size and modification time
+    pub fn copied(&self, pod: &str, file: &FileEntry) -> bool {
+        self.done
+            .get(&(pod.to_string(), file.remote_path()))
+            .is_some_and(|done| Some(*done) == stamp(&file.local))
+    }
+
+    /// Records that `file` reached `pod` with the digest `sha256`, a failure
+    /// is logged rather than failing the copy
+    pub fn record(&self, pod: &str, file: &FileEntry, sha256: &str) {
+        let (Some(path), Some((size, modified))) = (&self.path, stamp(&file.local)) else {
+            return;
+        };
+        let line = Line {
+            pod: pod.to_string(),
+            src: file.local.to_string_lossy().into_owned(),
+            dst: file.remote_path(),
+            size,
+            modified,
+            sha256: sha256.to_string(),
+        };
+        let mut line = serde_json::to_string(&line).unwrap();
+        line.push('\n');
+        let mut journal = self.file.lock().unwrap();
+        if journal.is_none() {
+            match append(path) {
+                Ok(file) => *journal = Some(file),
+                Err(err) => {
+                    warn!("failed to write journal {}: {}", path.display(), err);
+                    return;
+                }
+            }
+        }
+        if let Some(Err(err)) = journal.as_mut().map(|file| file.write_all(line.as_bytes())) {
+            warn!("failed to write journal {}: {}", path.display(), err);
+        }
+    }
+
+    /// Removes the journal of a copy that completed
+    pub fn finish(&self) {
+        if let Some(path) = &self.path {
+            *self.file.lock().unwrap() = None;
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Size and modification time of the local file at `path`
+fn stamp(path: &Path) -> Option<(u64, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((meta.len(), modified.as_nanos() as u64))
+}
+
+fn append(path: &Path) -> std::io::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
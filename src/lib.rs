@@ -0,0 +1,79 @@
+//! Copies files between the local machine and containers of Kubernetes pods
+//! through the exec API, without `kubectl` or `tar` in the container, only
+//! `sh`, `mkdir` and `cat`.
+//!
+//! [`copy_to_pod`] and [`copy_from_pod`] copy a single file. Everything else
+//! the `k8scp-rust` binary does, copying directories to several pods, the
+//! overwrite check or the history, is built on top of them.
+//!
+//! ```no_run
+//! use std::{path::Path, sync::Arc};
+//!
+//! use k8s_openapi::api::core::v1::Pod;
+//! use k8scp::{copy_to_pod, progress::Progress, ExecOptions};
+//! use kube::{api::Api, Client};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let pods: Api<Pod> = Api::namespaced(Client::try_default().await?, "default");
+//! let progress = Arc::new(Progress::new());
+//! let item = progress.item("app.tar", "web-0", "/tmp/app.tar", 1024);
+//! let sha256 = copy_to_pod(
+//!     &pods,
+//!     "web-0",
+//!     Path::new("app.tar"),
+//!     "/tmp/app.tar",
+//!     &ExecOptions::default(),
+//!     item.clone(),
+//! )
+//! .await?;
+//! item.finish();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Progress is reported through the [`progress::ProgressObserver`]s added to
+//! the [`progress::Progress`] the transfer belongs to.
+//!
+//! [`CopyRequest`] does what a run of the binary does, directories, several
+//! pods, verification and retries, configured through a builder. It copies
+//! between any [`endpoint::Source`] and [`endpoint::Sink`], local paths,
+//! pods, object storage, stdin and stdout or endpoints of your own.
+//!
+//! Every operation that talks to a pod takes a
+//! [`CancellationToken`](tokio_util::sync::CancellationToken), cancelling it
+//! makes the operation fail with [`Error::Cancelled`].
+//!
+//! Failures are [`Error`]s, telling authentication, connection, missing
+//! pods, failed remote commands, verification mismatches and local IO apart.
+//!
+//! [`blocking`] has the same copies for callers that aren't async.
+
+pub mod address;
+pub mod blocking;
+pub mod client;
+mod copy;
+pub mod diagnose;
+pub mod endpoint;
+mod error;
+pub mod exec;
+pub mod files;
+pub mod guard;
+pub mod hash;
+pub mod merge;
+pub mod progress;
+pub mod redact;
+pub mod remote;
+mod request;
+pub mod stats;
+pub mod text;
+pub mod transport;
+
+pub use copy::{
+    copy_from_pod, copy_from_pod_into, copy_source_to_pod, copy_split_to_pod, copy_to_pod,
+    verify_from_pod, verify_to_pod, ExecOptions,
+};
+pub use error::{Error, Result};
+pub use request::{Copied, CopyRequest, CopyRequestBuilder, Location, Summary, Transfer};
+
+#[cfg(not(any(feature = "rustls", feature = "openssl")))]
+compile_error!("either the `rustls` or the `openssl` feature must be enabled");
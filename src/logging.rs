@@ -0,0 +1,80 @@
+use std::{fs::OpenOptions, path::Path, sync::Mutex};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use k8scp::redact::Redacting;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{filter::Targets, fmt::writer::BoxMakeWriter, prelude::*};
+
+/// Format of the log lines
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines
+    Pretty,
+    /// One JSON object per line
+    Json,
+}
+
+pub struct LogOptions<'a> {
+    pub level: LevelFilter,
+    pub format: LogFormat,
+    /// Logs go to stderr unless a file is given, so stdout only carries output
+    /// meant for scripts
+    pub file: Option<&'a Path>,
+    pub color: bool,
+    /// Log the exec streams chunk by chunk whatever the level
+    pub debug_transport: bool,
+    #[cfg(feature = "otel")]
+    pub otlp_endpoint: Option<&'a str>,
+}
+
+/// Keeps exporters alive until the end of the run
+pub struct LogGuard {
+    #[cfg(feature = "otel")]
+    _otel: Option<crate::otel::OtelGuard>,
+}
+
+/// Installs the global subscriber
+pub fn init(opts: LogOptions) -> anyhow::Result<LogGuard> {
+    let (writer, ansi) = match opts.file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("failed to open log file {}", path.display()))?;
+            (BoxMakeWriter::new(Mutex::new(Redacting::new(file))), false)
+        }
+        None => (
+            BoxMakeWriter::new(|| Redacting::new(std::io::stderr())),
+            opts.color,
+        ),
+    };
+
+    let fmt = tracing_subscriber::fmt::layer().with_writer(writer);
+    let fmt = match opts.format {
+        LogFormat::Pretty => fmt.with_ansi(ansi).boxed(),
+        LogFormat::Json => fmt.json().boxed(),
+    };
+    let mut filter = Targets::new().with_default(opts.level);
+    if opts.debug_transport {
+        filter = filter.with_target(k8scp::transport::TARGET, LevelFilter::TRACE);
+    }
+    let registry = tracing_subscriber::registry().with(fmt.with_filter(filter));
+
+    #[cfg(feature = "otel")]
+    {
+        // spans are exported independently of how verbose the console is
+        let (otel, guard) = match crate::otel::layer(opts.otlp_endpoint)? {
+            Some((layer, guard)) => (Some(layer.with_filter(LevelFilter::INFO)), Some(guard)),
+            None => (None, None),
+        };
+        registry.with(otel).init();
+        Ok(LogGuard { _otel: guard })
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        registry.init();
+        Ok(LogGuard {})
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::bail;
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    diagnose,
+    remote::{self, DirEntry, Kind},
+};
+
+use crate::{
+    target::{self, Target},
+    Global,
+};
+
+/// List a path in a pod
+#[derive(clap::Args, Debug)]
+pub struct LsArgs {
+    /// What to list, `POD:PATH`, the contents of a directory or a single entry
+    #[arg(value_parser = target::parse)]
+    target: Target,
+
+    #[arg(long, value_enum, default_value_t = LsFormat::Short)]
+    format: LsFormat,
+
+    /// Same as `--format long`
+    #[arg(short, long, conflicts_with = "format")]
+    long: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LsFormat {
+    /// Names, directories with a trailing `/`
+    Short,
+    /// Mode, size, modification time and name, like `ls -l`
+    Long,
+    /// A JSON array with an object per entry
+    Json,
+}
+
+pub async fn run(global: &Global, args: &LsArgs) -> anyhow::Result<()> {
+    let api = global.session().await?.pods(global.namespace());
+    let target = target::resolve(global, &api, std::slice::from_ref(&args.target))
+        .await?
+        .remove(0);
+    let Target { pod, path } = &target;
+    let cancel = CancellationToken::new();
+    let explain = |err| {
+        diagnose::explain(
+            &api,
+            global.namespace(),
+            pod,
+            &global.container,
+            global.connect_timeout,
+            err,
+        )
+    };
+
+    let entry = match remote::stat(
+        &api,
+        pod,
+        &global.container,
+        path,
+        global.connect_timeout,
+        &cancel,
+    )
+    .await
+    {
+        Ok(entry) => entry,
+        Err(err) => return Err(explain(err).await.into()),
+    };
+    let entries = match entry {
+        None => bail!("{}: no such file or directory", target),
+        Some(entry) if entry.kind == Kind::Dir => {
+            match remote::list(
+                &api,
+                pod,
+                &global.container,
+                path,
+                global.connect_timeout,
+                &cancel,
+            )
+            .await
+            {
+                Ok(listing) => listing.entries,
+                Err(err) => return Err(explain(err).await.into()),
+            }
+        }
+        Some(entry) => vec![entry],
+    };
+
+    let format = if args.long {
+        LsFormat::Long
+    } else {
+        args.format
+    };
+    match format {
+        LsFormat::Short => {
+            for entry in &entries {
+                println!("{}", name(entry));
+            }
+        }
+        LsFormat::Long => {
+            let width = entries
+                .iter()
+                .filter_map(|entry| entry.size)
+                .map(|size| size.to_string().len())
+                .max()
+                .unwrap_or(1);
+            for entry in &entries {
+                println!(
+                    "{} {:>width$} {:<20} {}",
+                    mode(entry),
+                    entry.size.map_or("-".to_string(), |size| size.to_string()),
+                    modified(entry).unwrap_or_else(|| "-".to_string()),
+                    name(entry),
+                    width = width
+                );
+            }
+        }
+        LsFormat::Json => {
+            let entries: Vec<_> = entries.iter().map(to_json).collect();
+            println!("{}", Value::Array(entries));
+        }
+    }
+    Ok(())
+}
+
+/// `entry` as `--format json` prints it
+pub fn to_json(entry: &DirEntry) -> Value {
+    json!({
+        "name": entry.name,
+        "kind": kind(entry),
+        "size": entry.size,
+        "mode": entry.mode.map(|_| mode(entry)),
+        "modified": modified(entry),
+    })
+}
+
+pub fn kind(entry: &DirEntry) -> &'static str {
+    match entry.kind {
+        Kind::Dir => "dir",
+        Kind::File => "file",
+        Kind::Other => "other",
+    }
+}
+
+fn name(entry: &DirEntry) -> String {
+    match entry.kind {
+        Kind::Dir => format!("{}/", entry.name.trim_end_matches('/')),
+        _ => entry.name.clone(),
+    }
+}
+
+pub fn modified(entry: &DirEntry) -> Option<String> {
+    entry
+        .modified
+        .map(|time| humantime::format_rfc3339_seconds(time).to_string())
+}
+
+/// The mode of `entry` the way `ls -l` shows it, `?` for the permissions if
+/// `stat` is missing in the container
+pub fn mode(entry: &DirEntry) -> String {
+    let Some(mode) = entry.mode else {
+        let kind = match entry.kind {
+            Kind::Dir => 'd',
+            Kind::File => '-',
+            Kind::Other => '?',
+        };
+        return format!("{}?????????", kind);
+    };
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o100000 => '-',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '?',
+    };
+    let mut s = String::from(kind);
+    // the permissions of owner, group and others, and the bit that turns
+    // their `x` into `s` or `t`
+    for (shift, special, letter) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        s.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => letter,
+            (false, true) => letter.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
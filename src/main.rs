@@ -1,195 +1,557 @@
+mod archive;
+mod audit;
+mod bench;
+mod cat;
+mod checksum;
+mod complete;
+mod config;
+mod controller;
+mod cp;
+mod daemon;
+mod df;
+mod diff;
+mod doctor;
+mod du;
+mod edit;
+mod history;
+mod journal;
+mod logging;
+mod ls;
+mod manifest;
+mod metrics;
+mod mkdir;
+mod notify;
+#[cfg(feature = "otel")]
+mod otel;
+mod overwrite;
+mod pick;
+mod preflight;
+mod pull;
+mod relay;
+mod report;
+mod rm;
+mod rolling;
+mod schedule;
+mod selfupdate;
+mod session;
+mod stat;
+mod sync;
+mod tail;
+mod target;
+mod term;
+mod then;
+#[cfg(feature = "tui")]
+mod tui;
+mod twoway;
+
 use std::{
-    fmt::Write,
-    fs,
-    io::Error,
-    ops::DerefMut,
-    path::Path,
-    pin::Pin,
-    sync::Arc,
-    task::{Context, Poll},
+    io::IsTerminal, net::SocketAddr, path::PathBuf, process::ExitCode, sync::Arc, time::Duration,
+};
+
+use anyhow::{bail, Context as _};
+
+use clap::{
+    builder::BoolishValueParser, error::ErrorKind, parser::ValueSource, ArgMatches, Args as _,
+    CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
 };
+use clap_complete::{ArgValueCompleter, CompleteEnv};
+use kube::config::Kubeconfig;
+use tokio::sync::OnceCell;
+use tracing::level_filters::LevelFilter;
 
-use clap::Parser;
-use futures::lock::Mutex;
-use indicatif::{ProgressBar, ProgressState, ProgressStyle};
-use k8s_openapi::api::core::v1::Pod;
-use kube::{
-    api::{Api, AttachParams},
-    config::{KubeConfigOptions, Kubeconfig},
-    Client, Config,
+use crate::{
+    archive::ArchiveArgs,
+    bench::BenchArgs,
+    cat::CatArgs,
+    checksum::HashArgs,
+    complete::CompletionsArgs,
+    config::Profile,
+    controller::ControllerArgs,
+    cp::CpArgs,
+    daemon::DaemonArgs,
+    df::DfArgs,
+    diff::DiffArgs,
+    doctor::DoctorArgs,
+    du::DuArgs,
+    edit::EditArgs,
+    history::HistoryArgs,
+    logging::{LogFormat, LogOptions},
+    ls::LsArgs,
+    mkdir::MkdirArgs,
+    pull::PullArgs,
+    rm::RmArgs,
+    selfupdate::SelfUpdateArgs,
+    session::Session,
+    stat::StatArgs,
+    sync::SyncArgs,
+    tail::TailArgs,
+};
+use k8scp::{
+    client,
+    progress::{BarTemplate, ProgressMode},
+    transport::{Cat, Exec, Registry, Transport},
 };
-use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-use tracing::*;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short, long)]
-    kubeconfig: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    global: Global,
+
+    /// Without a subcommand the flags of `cp` copy, as they did before
+    /// there were subcommands
+    #[command(flatten)]
+    cp: CpArgs,
+}
+
+// the flags every subcommand takes, not a doc comment since clap would
+// make it the about of the command
+#[derive(clap::Args, Debug, Clone)]
+struct Global {
+    /// Take the defaults of the flags not given from this profile of the config file
+    #[arg(long, global = true, env = "K8SCP_PROFILE")]
+    profile: Option<String>,
+
+    /// Config file with the profiles [default: k8scp/config.toml in the user config directory]
+    #[arg(long, global = true, env = "K8SCP_CONFIG", requires = "profile")]
+    config: Option<PathBuf>,
+
+    /// Required unless the profile has one
+    #[arg(short, long, global = true, env = "K8SCP_KUBECONFIG")]
+    kubeconfig: Option<String>,
 
-    #[arg(short, long, default_value = "default")]
-    namespace: String,
+    /// Context of the kubeconfig to use [default: its current context]
+    #[arg(long, global = true, env = "K8SCP_CONTEXT")]
+    context: Option<String>,
 
-    #[arg(short, long)]
-    pod: String,
+    /// [default: default]
+    #[arg(short, long, global = true, env = "K8SCP_NAMESPACE", add = ArgValueCompleter::new(complete::namespaces))]
+    namespace: Option<String>,
 
-    #[arg(short, long, default_value = "")]
+    #[arg(short, long, global = true, env = "K8SCP_CONTAINER", default_value = "", add = ArgValueCompleter::new(complete::containers))]
     container: String,
 
-    #[arg(short, long)]
-    src: String,
+    /// Timeout for connecting to the API server and establishing the exec stream
+    #[arg(long, global = true, env = "K8SCP_CONNECT_TIMEOUT", value_parser = humantime::parse_duration, default_value = "30s")]
+    connect_timeout: Duration,
+
+    /// Abort when the exec stream makes no progress for this long
+    #[arg(long, global = true, env = "K8SCP_IDLE_TIMEOUT", value_parser = humantime::parse_duration, default_value = "60s")]
+    idle_timeout: Duration,
+
+    /// How files get into the container: exec-cat, exec-tar or base64 [default: exec-cat]
+    #[arg(long, global = true, env = "K8SCP_TRANSPORT", value_parser = transport)]
+    transport: Option<Arc<dyn Transport>>,
+
+    /// The umask the container makes the files and directories of uploads with, like `027` [default: that of its shell]
+    #[arg(long, global = true, env = "K8SCP_UMASK", value_parser = parse_umask)]
+    umask: Option<u32>,
+
+    /// Overwrite existing destinations without asking
+    #[arg(short, long, global = true, env = "K8SCP_YES", value_parser = BoolishValueParser::new(), visible_alias = "force")]
+    yes: bool,
+
+    /// Copy into the directories of --system-paths too
+    #[arg(long, global = true, env = "K8SCP_ALLOW_SYSTEM_PATHS", value_parser = BoolishValueParser::new())]
+    allow_system_paths: bool,
+
+    /// Directories in the pods copies refuse to write files right into
+    #[arg(long, global = true, env = "K8SCP_SYSTEM_PATHS", value_name = "DIRS", value_delimiter = ',', default_values_t = k8scp::guard::system_paths())]
+    system_paths: Vec<String>,
+
+    /// Suppress the progress bar and informational logs
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// How to report progress, `bar` falls back to `none` when stderr is not a terminal
+    #[arg(long, global = true, env = "K8SCP_PROGRESS", value_enum, default_value_t = ProgressMode::Bar)]
+    progress: ProgressMode,
+
+    /// Layout of the progress bars: default, minimal, detailed or an indicatif template
+    #[arg(long, global = true, value_parser = BarTemplate::parse, default_value = "default")]
+    progress_template: BarTemplate,
+
+    /// Minimum time between two updates of a bar or two `progress` events of a transfer
+    #[arg(long, global = true, value_parser = humantime::parse_duration, default_value = "200ms")]
+    progress_interval: Duration,
+
+    /// Don't show the progress bar, same as `--progress none`
+    #[arg(long, global = true, conflicts_with = "progress")]
+    no_progress: bool,
 
-    #[arg(short, long)]
-    dst: String,
+    /// Disable colored output, also honors the NO_COLOR environment variable
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Log level: off, error, warn, info, debug or trace [default: info, warn with --quiet]
+    #[arg(long, global = true, env = "K8SCP_LOG_LEVEL")]
+    log_level: Option<LevelFilter>,
+
+    /// Format of the log lines
+    #[arg(long, global = true, env = "K8SCP_LOG_FORMAT", value_enum, default_value_t = LogFormat::Pretty)]
+    log_format: LogFormat,
+
+    /// Append logs to this file instead of writing them to stderr
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Log every chunk and stall of the exec streams with its channel, size and timing
+    #[arg(long, global = true)]
+    debug_transport: bool,
+
+    /// History file [default: k8scp/history.jsonl in the user data directory]
+    #[arg(long, global = true, env = "K8SCP_HISTORY_FILE")]
+    history_file: Option<PathBuf>,
+
+    /// Serve Prometheus metrics on this address, e.g. 127.0.0.1:9090, for as long as the tool runs
+    #[arg(long, global = true)]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Export spans to this OTLP/gRPC endpoint, OTEL_EXPORTER_OTLP_ENDPOINT works too
+    #[cfg(feature = "otel")]
+    #[arg(long, global = true)]
+    otlp_endpoint: Option<String>,
+
+    /// Whether this runs as the kubectl plugin, see [`plugin`]
+    #[arg(skip)]
+    plugin: bool,
+
+    /// Whether nobody is there to answer a prompt, like for a transfer of
+    /// the daemon, what would need confirming fails instead
+    #[arg(skip)]
+    unattended: bool,
+
+    #[arg(skip)]
+    session: OnceCell<Session>,
 }
 
-struct FileProcessReader {
-    file: tokio::fs::File,
-    cur: u64,
-    total: u64,
-    pb: Option<Arc<ProgressBar>>,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Copy local files and directories to pods
+    Cp(CpArgs),
+    Pull(PullArgs),
+    Sync(SyncArgs),
+    Diff(DiffArgs),
+    Bench(BenchArgs),
+    Controller(ControllerArgs),
+    Daemon(DaemonArgs),
+    Ls(LsArgs),
+    Rm(RmArgs),
+    Mkdir(MkdirArgs),
+    Stat(StatArgs),
+    Df(DfArgs),
+    Du(DuArgs),
+    Cat(CatArgs),
+    Edit(EditArgs),
+    Hash(HashArgs),
+    Tail(TailArgs),
+    Archive(ArchiveArgs),
+    Doctor(DoctorArgs),
+    History(HistoryArgs),
+    SelfUpdate(SelfUpdateArgs),
+    #[cfg(feature = "tui")]
+    Tui(tui::TuiArgs),
+    Completions(CompletionsArgs),
 }
 
-impl FileProcessReader {
-    async fn new(file_path: &str) -> FileProcessReader {
-        FileProcessReader {
-            file: tokio::fs::File::open(file_path).await.unwrap(),
-            cur: 0,
-            total: tokio::fs::metadata(file_path).await.unwrap().len(),
-            pb: None,
+impl Global {
+    /// The connection to the cluster, made on first use
+    async fn session(&self) -> anyhow::Result<&Session> {
+        self.session
+            .get_or_try_init(|| Session::connect(self))
+            .await
+    }
+
+    /// The kubeconfig given, or as the kubectl plugin the one kubectl uses
+    fn kubeconfig(&self) -> anyhow::Result<&str> {
+        match &self.kubeconfig {
+            Some(kubeconfig) => Ok(kubeconfig),
+            None if self.plugin
+                && std::env::var_os("KUBECONFIG").is_some_and(|v| !v.is_empty()) =>
+            {
+                Ok("$KUBECONFIG")
+            }
+            None if self.plugin => Ok("~/.kube/config"),
+            None => bail!("no kubeconfig given, pass --kubeconfig or use a profile that has one"),
         }
     }
-}
 
-impl AsyncRead for FileProcessReader {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
-    ) -> Poll<std::io::Result<()>> {
-        let ret = Pin::new(&mut self.file).poll_read(cx, buf);
-        if let Poll::Ready(Ok(())) = ret {
-            self.cur += buf.filled().len() as u64;
-            if let Some(pb) = self.pb.as_ref() {
-                pb.set_position(self.cur)
+    /// Reads the kubeconfig of [`Global::kubeconfig`]
+    fn load_kubeconfig(&self) -> anyhow::Result<Kubeconfig> {
+        let path = self.kubeconfig()?;
+        match &self.kubeconfig {
+            Some(_) => Ok(client::load(path)?),
+            None => Ok(client::load_default()?),
+        }
+    }
+
+    fn namespace(&self) -> &str {
+        self.namespace.as_deref().unwrap_or("default")
+    }
+
+    fn transport(&self) -> Arc<dyn Transport> {
+        self.transport
+            .clone()
+            .unwrap_or_else(|| Arc::new(Exec(Cat)))
+    }
+
+    fn progress_mode(&self) -> ProgressMode {
+        match self.progress {
+            ProgressMode::Json => ProgressMode::Json,
+            _ if self.quiet || self.no_progress || !std::io::stderr().is_terminal() => {
+                ProgressMode::None
             }
+            mode => mode,
+        }
+    }
+
+    /// As the kubectl plugin the namespace defaults to the one of the
+    /// context, like kubectl's does; a kubeconfig that can't be read fails
+    /// later, when connecting
+    fn kubectl_namespace(&mut self) {
+        if !self.plugin || self.namespace.is_some() {
+            return;
+        }
+        let Ok(kubeconfig) = self.load_kubeconfig() else {
+            return;
+        };
+        let context = self
+            .context
+            .clone()
+            .or_else(|| kubeconfig.current_context.clone());
+        self.namespace = kubeconfig
+            .contexts
+            .into_iter()
+            .find(|c| Some(&c.name) == context.as_ref())
+            .and_then(|c| c.context.namespace);
+    }
+
+    /// Takes what `profile` has for the flags `matches` didn't get
+    fn apply(&mut self, profile: Profile, matches: &ArgMatches) -> anyhow::Result<()> {
+        let unset = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                None | Some(ValueSource::DefaultValue)
+            )
+        };
+        if let Some(kubeconfig) = profile.kubeconfig.filter(|_| unset("kubeconfig")) {
+            self.kubeconfig = Some(kubeconfig);
+        }
+        if let Some(context) = profile.context.filter(|_| unset("context")) {
+            self.context = Some(context);
+        }
+        if let Some(namespace) = profile.namespace.filter(|_| unset("namespace")) {
+            self.namespace = Some(namespace);
+        }
+        if let Some(container) = profile.container.filter(|_| unset("container")) {
+            self.container = container;
+        }
+        if let Some(name) = profile.transport.filter(|_| unset("transport")) {
+            self.transport =
+                Some(transport(&name).map_err(|err| anyhow::anyhow!("transport: {}", err))?);
         }
-        ret
+        if let Some(timeout) = profile.connect_timeout.filter(|_| unset("connect_timeout")) {
+            self.connect_timeout =
+                humantime::parse_duration(&timeout).context("connect-timeout")?;
+        }
+        if let Some(timeout) = profile.idle_timeout.filter(|_| unset("idle_timeout")) {
+            self.idle_timeout = humantime::parse_duration(&timeout).context("idle-timeout")?;
+        }
+        if let Some(progress) = profile.progress.filter(|_| unset("progress")) {
+            self.progress = ProgressMode::from_str(&progress, false)
+                .map_err(|err| anyhow::anyhow!("progress: {}", err))?;
+        }
+        if let Some(umask) = profile.umask.filter(|_| unset("umask")) {
+            self.umask =
+                Some(parse_umask(&umask).map_err(|err| anyhow::anyhow!("umask: {}", err))?);
+        }
+        if let Some(yes) = profile.yes.filter(|_| unset("yes")) {
+            self.yes = yes;
+        }
+        if let Some(allow) = profile
+            .allow_system_paths
+            .filter(|_| unset("allow_system_paths"))
+        {
+            self.allow_system_paths = allow;
+        }
+        if let Some(paths) = profile.system_paths.filter(|_| unset("system_paths")) {
+            self.system_paths = paths;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
-struct StringWriter {
-    str: String,
+/// Exits with a usage error if any of the flags of the copy without a
+/// subcommand were given before the subcommand `name`. Unlike
+/// `args_conflicts_with_subcommands` this leaves the global flags alone.
+fn reject_copy_flags(matches: &ArgMatches, name: &str) {
+    let cp = CpArgs::augment_args(clap::Command::new("cp"));
+    for arg in cp.get_arguments() {
+        if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!(
+                        "--{} can't be used before the subcommand {}",
+                        arg.get_long().unwrap_or_default(),
+                        name
+                    ),
+                )
+                .exit();
+        }
+    }
 }
 
-impl AsyncWrite for StringWriter {
-    fn poll_write(
-        mut self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<Result<usize, Error>> {
-        self.str
-            .push_str(std::str::from_utf8(buf).unwrap_or("[not utf8]"));
-        Poll::Ready(Ok(buf.len()))
+/// Parses an octal umask like `027`
+fn parse_umask(s: &str) -> Result<u32, String> {
+    match u32::from_str_radix(s, 8) {
+        Ok(umask) if umask <= 0o777 => Ok(umask),
+        _ => Err("expected an octal umask like `027`".to_string()),
     }
+}
 
-    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
-        Poll::Ready(Ok(()))
+/// The registered transport called `name`
+fn transport(name: &str) -> Result<Arc<dyn Transport>, String> {
+    let registry = Registry::default();
+    registry.get(name).ok_or_else(|| {
+        let names: Vec<_> = registry.names().collect();
+        format!("unknown transport, one of {}", names.join(", "))
+    })
+}
+
+/// Whether this runs as `kubectl-cp2`, the kubectl plugin, which kubectl
+/// runs for `kubectl cp2`
+fn plugin() -> bool {
+    std::env::args_os()
+        .next()
+        .as_deref()
+        .map(std::path::Path::new)
+        .and_then(|path| path.file_stem())
+        .is_some_and(|name| name == "kubectl-cp2")
+}
+
+/// The command line, as the kubectl plugin named like kubectl shows it and
+/// with its defaults
+fn command() -> clap::Command {
+    let command = Args::command();
+    if !plugin() {
+        return command;
     }
+    command
+        .name("kubectl-cp2")
+        .bin_name("kubectl cp2")
+        .mut_arg("kubeconfig", |arg| {
+            arg.help("[default: the files of $KUBECONFIG merged, or ~/.kube/config]")
+        })
+        .mut_arg("namespace", |arg| {
+            arg.help("[default: the namespace of the context, or default]")
+        })
+}
 
-    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
-        Poll::Ready(Ok(()))
+fn main() -> ExitCode {
+    // answers the shell when it asks for completions, see `complete`
+    CompleteEnv::with_factory(command).complete();
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            print_error(&err);
+            ExitCode::FAILURE
+        }
     }
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
-
-    // src file
-    let mut f_reader = FileProcessReader::new(args.src.as_str()).await;
-
-    // process bar
-    let pb = Arc::new(ProgressBar::new(f_reader.total));
-    pb.set_style(ProgressStyle::with_template(
-        "{msg} {spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, {eta})")
-        .unwrap()
-        .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-        .progress_chars("#>-"));
-    f_reader.pb = Some(pb.clone());
-
-    // kube client
-    tracing_subscriber::fmt::init();
-    let client = Client::try_from(
-        Config::from_custom_kubeconfig(
-            Kubeconfig::from_yaml(fs::read_to_string(&args.kubeconfig)?.as_str())?,
-            &KubeConfigOptions {
-                context: None,
-                cluster: None,
-                user: None,
-            },
-        )
-        .await?,
-    )?;
-
-    // pod exec
-    let pods: Api<Pod> = Api::namespaced(client, args.namespace.as_str());
-    let mut ap = AttachParams::default().stdin(true);
-    if !args.container.is_empty() {
-        ap = ap.container(args.container);
-    }
-
-    let exec = format!(
-        "mkdir -p {} && cd {} && cat > {}",
-        args.dst,
-        args.dst,
-        Path::new(&args.src).file_name().unwrap().to_str().unwrap()
-    );
-
-    let mut attached = pods
-        .exec(args.pod.as_str(), vec!["sh", "-c", exec.as_str()], &ap)
-        .await?;
-
-    // The received streams from `AttachedProcess`
-    let mut stdin_writer = attached.stdin().unwrap();
-    let mut stdout_reader = attached.stdout().unwrap();
-    let mut stderr_reader = attached.stderr().unwrap();
-
-    // stdin
-    tokio::spawn(async move {
-        tokio::io::copy(&mut f_reader, &mut stdin_writer)
-            .await
-            .unwrap();
-    });
+async fn run() -> anyhow::Result<ExitCode> {
+    let matches = command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    args.global.plugin = plugin();
+    if let Some((name, _)) = matches.subcommand() {
+        reject_copy_flags(&matches, name);
+    }
+    if let Some(name) = args.global.profile.clone() {
+        // flags given after the subcommand are in its matches
+        let mut given = &matches;
+        while let Some((_, sub)) = given.subcommand() {
+            given = sub;
+        }
+        let profile = config::profile(args.global.config.as_deref(), &name)?;
+        args.global
+            .apply(profile, given)
+            .with_context(|| format!("invalid profile {}", name))?;
+    }
+    args.global.kubectl_namespace();
+    let global = &args.global;
 
-    // stdout
-    let stdout = Arc::new(Mutex::new(StringWriter { str: String::new() }));
-    let out = stdout.clone();
-    tokio::spawn(async move {
-        tokio::io::copy(&mut stdout_reader, out.lock().await.deref_mut())
-            .await
-            .unwrap();
-    });
+    let color = term::use_color(global.no_color);
+    term::set_color(color);
 
-    // stderr
-    let stderr = Arc::new(Mutex::new(StringWriter { str: String::new() }));
-    let err = stderr.clone();
-    tokio::spawn(async move {
-        tokio::io::copy(&mut stderr_reader, err.lock().await.deref_mut())
-            .await
-            .unwrap();
-    });
+    let done = match &args.command {
+        Some(Command::History(history)) => Some(history::show(history, global)),
+        Some(Command::Doctor(doctor)) => Some(doctor::run(doctor, global).await),
+        Some(Command::SelfUpdate(update)) => Some(selfupdate::run(update, global).await),
+        #[cfg(feature = "tui")]
+        Some(Command::Tui(tui)) => Some(tui::run(tui, global).await),
+        Some(Command::Completions(completions)) => {
+            Some(complete::print(completions, command().get_name()))
+        }
+        _ => None,
+    };
+    if let Some(done) = done {
+        return done.map(|()| ExitCode::SUCCESS);
+    }
 
-    attached.take_status().unwrap().await;
-    pb.abandon();
+    let level = global.log_level.unwrap_or(if global.quiet {
+        LevelFilter::WARN
+    } else {
+        LevelFilter::INFO
+    });
+    let _log_guard = logging::init(LogOptions {
+        level,
+        format: global.log_format,
+        file: global.log_file.as_deref(),
+        color,
+        debug_transport: global.debug_transport,
+        #[cfg(feature = "otel")]
+        otlp_endpoint: global.otlp_endpoint.as_deref(),
+    })?;
 
-    if !stdout.lock().await.str.is_empty() {
-        info!("stdout:{}", stdout.lock().await.str);
+    if let Some(addr) = global.metrics_addr {
+        metrics::serve(addr).await?;
     }
-    if !stderr.lock().await.str.is_empty() {
-        info!("stderr:{}", stderr.lock().await.str);
+
+    match &args.command {
+        None => cp::run(global, &args.cp).await,
+        Some(Command::Cp(cp)) => cp::run(global, cp).await,
+        Some(Command::Pull(pull)) => pull::run(global, pull).await,
+        Some(Command::Sync(sync)) => sync::run(global, sync).await,
+        Some(Command::Diff(diff)) => diff::run(global, diff).await,
+        Some(Command::Bench(bench)) => bench::run(global, bench).await.map(|()| ExitCode::SUCCESS),
+        Some(Command::Controller(controller)) => controller::run(global, controller)
+            .await
+            .map(|()| ExitCode::SUCCESS),
+        Some(Command::Daemon(daemon)) => daemon::run(global, daemon)
+            .await
+            .map(|()| ExitCode::SUCCESS),
+        Some(Command::Ls(ls)) => ls::run(global, ls).await.map(|()| ExitCode::SUCCESS),
+        Some(Command::Rm(rm)) => rm::run(global, rm).await,
+        Some(Command::Mkdir(mkdir)) => mkdir::run(global, mkdir).await,
+        Some(Command::Stat(stat)) => stat::run(global, stat).await,
+        Some(Command::Df(df)) => df::run(global, df).await,
+        Some(Command::Du(du)) => du::run(global, du).await,
+        Some(Command::Cat(cat)) => cat::run(global, cat).await,
+        Some(Command::Edit(edit)) => edit::run(global, edit).await,
+        Some(Command::Hash(hash)) => checksum::run(global, hash).await,
+        Some(Command::Tail(tail)) => tail::run(global, tail).await,
+        Some(Command::Archive(archive)) => archive::run(global, archive).await,
+        Some(_) => unreachable!("handled above"),
     }
+}
 
-    Ok(())
+/// Prints `err` the way returning it from `main` would
+fn print_error(err: &anyhow::Error) {
+    eprintln!("Error: {}", k8scp::redact::redact(&format!("{:?}", err)));
 }
@@ -0,0 +1,346 @@
+use std::{collections::BTreeMap, fs, path::Path, sync::Arc};
+
+use anyhow::{anyhow, bail, Context as _};
+use futures::{stream, StreamExt};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{
+    api::{Api, ListParams},
+    ResourceExt,
+};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{endpoint::Object, progress::Progress};
+
+use crate::{
+    cp::{self, CpArgs, Selection},
+    pick::Pick,
+    report::Report,
+    term, Global,
+};
+
+/// Many copies in one file, for runbooks that copy more than one thing
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    /// How many of the copies run at once
+    #[serde(default = "one")]
+    concurrency: usize,
+    transfers: Vec<Entry>,
+}
+
+/// One copy of a manifest, what it doesn't give comes from the command line
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    /// Relative to the directory of the manifest, or a URL
+    pub src: String,
+    pub dst: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pods: Vec<String>,
+    /// Label selector of the pods, those running are copied to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+    /// Which one of the pods of the selector to copy to, all by default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pick: Option<Pick>,
+    /// Copies with a higher priority run first, 0 by default
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
+}
+
+fn one() -> usize {
+    1
+}
+
+fn is_zero(priority: &i32) -> bool {
+    *priority == 0
+}
+
+/// Runs the copies of the manifest at `path`. They are all planned, and
+/// overwrites confirmed, before any is sent, and share `progress` and
+/// `report`.
+pub async fn copy(
+    global: &Global,
+    args: &CpArgs,
+    path: &Path,
+    selection: &Selection,
+    cancel: &CancellationToken,
+    progress: &Arc<Progress>,
+    report: &Report,
+) -> anyhow::Result<()> {
+    let manifest = load(path)?;
+    if manifest.concurrency == 0 {
+        bail!(
+            "invalid manifest {}, concurrency must be at least 1",
+            path.display()
+        );
+    }
+    let base = path.parent().unwrap_or(Path::new(""));
+    // those of the same priority in the order of the manifest
+    let mut transfers: Vec<_> = manifest.transfers.iter().enumerate().collect();
+    transfers.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.priority));
+
+    // connected before the copies take their copy of the flags
+    global.session().await?;
+    let mut copies = Vec::with_capacity(transfers.len());
+    for (i, entry) in transfers {
+        let what = || format!("entry {} of {}", i + 1, path.display());
+        let copy = resolve(global, args, base, entry)
+            .await
+            .with_context(what)?;
+        copies.extend(copy);
+    }
+    let runs = (manifest.concurrency, "entries of the manifest");
+    run(&copies, runs, selection, cancel, progress, report).await
+}
+
+/// Runs the copy of `args` to the pods of its `--selector`, or those its
+/// `--pick` picks from, like a manifest of that one transfer does, a copy
+/// per namespace its pods are in. `--pick` without a selector picks from
+/// all the pods of a namespace.
+pub async fn copy_selected(
+    global: &Global,
+    args: &CpArgs,
+    selection: &Selection,
+    cancel: &CancellationToken,
+    progress: &Arc<Progress>,
+    report: &Report,
+) -> anyhow::Result<()> {
+    let entry = Entry {
+        src: args.src.clone().unwrap_or_default(),
+        dst: args.dst.clone().unwrap_or_default(),
+        pods: Vec::new(),
+        selector: Some(args.selector.clone().unwrap_or_default()),
+        namespace: None,
+        container: None,
+        transport: None,
+        pick: None,
+        priority: 0,
+    };
+    let copies = resolve(global, args, Path::new(""), &entry).await?;
+    run(
+        &copies,
+        (1, "namespaces"),
+        selection,
+        cancel,
+        progress,
+        report,
+    )
+    .await
+}
+
+/// Plans `copies` and runs as many of them at once as `runs` says, failing
+/// with the count of those that failed, by what `runs` calls them, if more
+/// than one ran and any failed
+async fn run(
+    copies: &[(Global, CpArgs)],
+    (concurrency, what): (usize, &str),
+    selection: &Selection,
+    cancel: &CancellationToken,
+    progress: &Arc<Progress>,
+    report: &Report,
+) -> anyhow::Result<()> {
+    let mut plans = Vec::with_capacity(copies.len());
+    for (global, args) in copies {
+        let plan = cp::plan(global, args, selection, progress)
+            .await
+            .with_context(|| describe(args))?;
+        plans.push(plan);
+    }
+    show_namespaces(&mut plans);
+
+    progress.start(
+        plans.iter().map(cp::Plan::count).sum(),
+        plans.iter().map(cp::Plan::size).sum(),
+    );
+    let results: Vec<_> = stream::iter(&plans)
+        .map(|plan| cp::send(plan, cancel, progress, report))
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    let failed = results.iter().filter(|res| res.is_err()).count();
+    if failed == 0 {
+        return Ok(());
+    }
+    if results.len() == 1 {
+        return results.into_iter().find(Result::is_err).unwrap();
+    }
+    for ((_, args), res) in copies.iter().zip(&results) {
+        if let Err(err) = res {
+            progress.suspend(|| term::error(format!("{}: {:#}", describe(args), err)));
+        }
+    }
+    bail!(
+        "{} of {} {} failed, {}",
+        failed,
+        results.len(),
+        what,
+        report.tally(None)
+    );
+}
+
+/// Shows the namespaces of the pods if `plans` copy to more than one
+pub fn show_namespaces(plans: &mut [cp::Plan<'_>]) {
+    let several = plans
+        .iter()
+        .any(|plan| plan.namespace() != plans[0].namespace());
+    if several {
+        plans.iter_mut().for_each(cp::Plan::show_namespace);
+    }
+}
+
+fn load(path: &Path) -> anyhow::Result<Manifest> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read manifest {}", path.display()))?;
+    serde_yaml::from_str(&text)
+        .with_context(|| format!("failed to parse manifest {}", path.display()))
+}
+
+/// The flags and copy arguments of `entry`, a relative `src` is relative to
+/// `base`. A selector in several namespaces, separated with commas, or in
+/// all of them with `--all-namespaces`, is a copy per namespace its pods run
+/// in.
+pub async fn resolve(
+    global: &Global,
+    args: &CpArgs,
+    base: &Path,
+    entry: &Entry,
+) -> anyhow::Result<Vec<(Global, CpArgs)>> {
+    let mut global = global.clone();
+    if let Some(container) = &entry.container {
+        global.container = container.clone();
+    }
+    if let Some(name) = &entry.transport {
+        let transport = crate::transport(name).map_err(|err| anyhow!("{}: {}", name, err))?;
+        global.transport = Some(transport);
+    }
+
+    let namespaces: Vec<String> = entry
+        .namespace
+        .as_deref()
+        .unwrap_or(global.namespace())
+        .split(',')
+        .map(|namespace| namespace.trim().to_string())
+        .collect();
+    let all = args.all_namespaces && entry.namespace.is_none();
+    // the pods by namespace
+    let pods: BTreeMap<String, Vec<String>> = match (&entry.selector, entry.pods.is_empty()) {
+        (Some(_), false) => bail!("give either pods or a selector, not both"),
+        (Some(selector), true) => {
+            let session = global.session().await?;
+            let apis = match all {
+                true => vec![Api::all(session.client.clone())],
+                false => namespaces.iter().map(|ns| session.pods(ns)).collect(),
+            };
+            let mut matched: BTreeMap<_, Vec<_>> = BTreeMap::new();
+            for api in apis {
+                for pod in running(&api, selector).await? {
+                    matched
+                        .entry(pod.namespace().unwrap_or_default())
+                        .or_default()
+                        .push(pod);
+                }
+            }
+            let pick = entry.pick.or(args.pick);
+            // an empty selector, of --pick alone, matches every pod
+            let (matches, matching) = match selector.as_str() {
+                "" => (String::new(), String::new()),
+                selector => (
+                    format!(" {} matches", selector),
+                    format!(" match {}", selector),
+                ),
+            };
+            let mut pods = BTreeMap::new();
+            for (namespace, matched) in matched {
+                let names = match pick {
+                    Some(pick) => match pick.pick(matched) {
+                        Some(pod) => vec![pod.name_any()],
+                        None => bail!("none of the pods{} in {} is Ready", matches, namespace),
+                    },
+                    None => matched.iter().map(ResourceExt::name_any).collect(),
+                };
+                pods.insert(namespace, names);
+            }
+            if pods.is_empty() {
+                match all {
+                    true => bail!("no running pods{} in any namespace", matching),
+                    false => bail!("no running pods{} in {}", matching, namespaces.join(", ")),
+                }
+            }
+            pods
+        }
+        (None, _) if all || namespaces.len() > 1 => {
+            bail!("pods are in one namespace, give a selector to copy to several")
+        }
+        (None, false) => BTreeMap::from([(namespaces[0].clone(), entry.pods.clone())]),
+        (None, true) if !args.pod.is_empty() => {
+            BTreeMap::from([(namespaces[0].clone(), args.pod.clone())])
+        }
+        (None, true) => bail!("no pods, give pods, a selector or --pod"),
+    };
+    let src = if Object::is_url(&entry.src) {
+        entry.src.clone()
+    } else {
+        base.join(&entry.src).to_string_lossy().into_owned()
+    };
+    // the flags of the command line go for every entry, but those naming
+    // what to copy and where it is reported
+    let args = CpArgs {
+        pod: Vec::new(),
+        src: Some(src),
+        dst: Some(entry.dst.clone()),
+        stats: false,
+        report: None,
+        report_format: None,
+        retry_from: None,
+        manifest: None,
+        selector: None,
+        all_namespaces: false,
+        pick: None,
+        files_from: None,
+        from0: false,
+        notify_url: None,
+        ..args.clone()
+    };
+    let copies = pods
+        .into_iter()
+        .map(|(namespace, pods)| {
+            let mut global = global.clone();
+            global.namespace = Some(namespace);
+            let args = CpArgs {
+                pod: pods,
+                ..args.clone()
+            };
+            (global, args)
+        })
+        .collect();
+    Ok(copies)
+}
+
+/// The running pods of `api` that `selector` matches
+async fn running(api: &Api<Pod>, selector: &str) -> anyhow::Result<Vec<Pod>> {
+    let pods = api
+        .list(&ListParams::default().labels(selector))
+        .await
+        .with_context(|| format!("failed to list the pods of {}", selector))?
+        .into_iter()
+        .filter(|pod| pod.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running"))
+        .collect();
+    Ok(pods)
+}
+
+fn describe(args: &CpArgs) -> String {
+    format!(
+        "{} to {}",
+        args.src.as_deref().unwrap_or_default(),
+        args.dst.as_deref().unwrap_or_default()
+    )
+}
@@ -0,0 +1,36 @@
+//! What a two-way sync does with a file, from its digests on both sides and
+//! the one both sides agreed on after the last sync
+
+/// What is done with a file of either side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Push,
+    Pull,
+    RemoveLocal,
+    RemoveRemote,
+    Conflict,
+}
+
+/// What is done with a file whose digest is `local` locally and `remote` in
+/// the pod, `None` where it doesn't exist, and was `was` after the last
+/// sync, `None` if it wasn't synced then. The side that still has the digest
+/// of the last sync takes what the other one changed; a file changed on both
+/// is a conflict, even if one side removed it. Nothing is done with a file
+/// both sides agree on.
+pub fn action(local: Option<&str>, remote: Option<&str>, was: Option<&str>) -> Option<Action> {
+    Some(if local == remote {
+        return None;
+    } else if local == was {
+        match remote {
+            Some(_) => Action::Pull,
+            None => Action::RemoveLocal,
+        }
+    } else if remote == was {
+        match local {
+            Some(_) => Action::Push,
+            None => Action::RemoveRemote,
+        }
+    } else {
+        Action::Conflict
+    })
+}
@@ -0,0 +1,163 @@
+use std::{
+    net::SocketAddr,
+    sync::LazyLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::*;
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Transfer metrics, served in the Prometheus text format by [`serve`]
+pub struct Metrics {
+    registry: Registry,
+    bytes: IntCounter,
+    duration: Histogram,
+    failures: IntCounterVec,
+    pub active: IntGauge,
+    syncs: IntCounterVec,
+    last_sync: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let bytes =
+            IntCounter::new("k8scp_transferred_bytes_total", "Bytes written to pods").unwrap();
+        let duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "k8scp_transfer_duration_seconds",
+                "Duration of successful file transfers",
+            )
+            .buckets(exponential_buckets(0.05, 2.0, 14).unwrap()),
+        )
+        .unwrap();
+        let failures = IntCounterVec::new(
+            Opts::new("k8scp_transfer_failures_total", "Failed file transfers"),
+            &["reason"],
+        )
+        .unwrap();
+        let active = IntGauge::new("k8scp_active_transfers", "Transfers in flight").unwrap();
+        let syncs = IntCounterVec::new(
+            Opts::new("k8scp_syncs_total", "Scheduled syncs run"),
+            &["result"],
+        )
+        .unwrap();
+        let last_sync = IntGauge::new(
+            "k8scp_last_successful_sync_timestamp_seconds",
+            "When the last scheduled sync that succeeded ended, in seconds since the epoch",
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(bytes.clone())).unwrap();
+        registry.register(Box::new(duration.clone())).unwrap();
+        registry.register(Box::new(failures.clone())).unwrap();
+        registry.register(Box::new(active.clone())).unwrap();
+        registry.register(Box::new(syncs.clone())).unwrap();
+        registry.register(Box::new(last_sync.clone())).unwrap();
+        Metrics {
+            registry,
+            bytes,
+            duration,
+            failures,
+            active,
+            syncs,
+            last_sync,
+        }
+    }
+
+    /// Records the outcome of a transfer that moved `bytes` in `elapsed`
+    pub fn record(&self, res: &anyhow::Result<()>, bytes: u64, elapsed: Duration) {
+        self.bytes.inc_by(bytes);
+        match res {
+            Ok(()) => self.duration.observe(elapsed.as_secs_f64()),
+            Err(err) => self.failures.with_label_values(&[reason(err)]).inc(),
+        }
+    }
+
+    /// Records the outcome of a scheduled sync that just ended
+    pub fn sync(&self, succeeded: bool) {
+        let result = if succeeded { "success" } else { "failure" };
+        self.syncs.with_label_values(&[result]).inc();
+        if succeeded {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            self.last_sync.set(now.as_secs() as i64);
+        }
+    }
+
+    fn encode(&self) -> String {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
+
+/// Coarse failure cause used as the `reason` label
+fn reason(err: &anyhow::Error) -> &'static str {
+    match err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<k8scp::Error>())
+    {
+        Some(k8scp::Error::Timeout(_)) => "timeout",
+        Some(
+            k8scp::Error::Auth(_) | k8scp::Error::Connection(_) | k8scp::Error::PodResolution(_),
+        ) => "api",
+        Some(k8scp::Error::Io { .. }) => "io",
+        _ => "other",
+    }
+}
+
+/// Serves `/metrics` on `addr` in the background
+pub async fn serve(addr: SocketAddr) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(
+        "serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(async move {
+                        if let Err(err) = respond(stream).await {
+                            debug!("metrics request failed: {}", err);
+                        }
+                    });
+                }
+                Err(err) => warn!("failed to accept metrics connection: {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let (status, body) = if request.starts_with("GET /metrics ") {
+        ("200 OK", METRICS.encode())
+    } else {
+        ("404 Not Found", String::new())
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
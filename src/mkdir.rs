@@ -0,0 +1,86 @@
+use std::process::ExitCode;
+
+use tokio_util::sync::CancellationToken;
+
+use anyhow::bail;
+use k8scp::{
+    exec::{self, quote},
+    guard,
+};
+
+use crate::{
+    target::{self, Target},
+    term, Global,
+};
+
+/// Create directories in pods
+#[derive(clap::Args, Debug)]
+pub struct MkdirArgs {
+    /// Directory to create, `POD:PATH`
+    #[arg(required = true, value_parser = target::parse)]
+    target: Vec<Target>,
+
+    /// Create the missing parents too, and don't fail if the directory exists
+    #[arg(short, long)]
+    parents: bool,
+
+    /// Permissions of the new directories in octal, e.g. `750` [default: 777 less the umask]
+    #[arg(short, long, value_parser = parse_mode)]
+    mode: Option<String>,
+}
+
+pub async fn run(global: &Global, args: &MkdirArgs) -> anyhow::Result<ExitCode> {
+    let api = global.session().await?.pods(global.namespace());
+    let targets = target::resolve(global, &api, &args.target).await?;
+    // like copies, as given and as resolved
+    if !global.allow_system_paths {
+        for (given, target) in args.target.iter().zip(&targets) {
+            let denied = |path| guard::system_path(path, &global.system_paths);
+            if let Some(dir) = denied(&given.path).or_else(|| denied(&target.path)) {
+                bail!(
+                    "refusing to create {} in the system directory {}, pass --allow-system-paths if that is meant",
+                    given,
+                    dir
+                );
+            }
+        }
+    }
+    let mut flags = String::new();
+    if args.parents {
+        flags.push_str(" -p");
+    }
+    if let Some(mode) = &args.mode {
+        flags.push_str(&format!(" -m {}", mode));
+    }
+    Ok(target::each(global, &api, &targets, |target| {
+        let command = exec::umasked(
+            global.umask,
+            &format!("mkdir{} -- {}", flags, quote(&target.path)),
+        );
+        let api = &api;
+        async move {
+            exec::run(
+                api,
+                &target.pod,
+                &global.container,
+                &command,
+                global.connect_timeout,
+                global.idle_timeout,
+                &CancellationToken::new(),
+            )
+            .await?;
+            if !global.quiet {
+                term::success(format!("created {}", target));
+            }
+            Ok(())
+        }
+    })
+    .await)
+}
+
+fn parse_mode(s: &str) -> Result<String, String> {
+    match u32::from_str_radix(s, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(s.to_string()),
+        _ => Err("expected octal permissions like `755`".to_string()),
+    }
+}
@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context as _};
+use hyper::{
+    client::HttpConnector,
+    header::{CONTENT_TYPE, USER_AGENT},
+    Body, Client, Request, Uri,
+};
+use serde_json::Value;
+use tokio::time;
+
+/// Posts `payload` to `url` with the TLS stack the API server is talked to
+/// with, failing unless it answers with a success within `timeout`. Errors
+/// name only the host, the URL of a webhook is a secret.
+pub async fn post(url: &str, payload: &Value, timeout: Duration) -> anyhow::Result<()> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|_| anyhow!("--notify-url isn't a URL"))?;
+    let host = uri.host().unwrap_or_default().to_string();
+    let request = Request::post(uri)
+        .header(CONTENT_TYPE, "application/json")
+        .header(USER_AGENT, concat!("k8scp/", env!("CARGO_PKG_VERSION")))
+        .body(Body::from(payload.to_string()))?;
+    let client: Client<_, Body> = Client::builder().build(connector(timeout)?);
+    let response = time::timeout(timeout, client.request(request))
+        .await
+        .map_err(|_| anyhow!("{} didn't answer within {:?}", host, timeout))?
+        .with_context(|| format!("failed to post to {}", host))?;
+    if !response.status().is_success() {
+        bail!("{} answered {}", host, response.status());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "openssl")]
+fn connector(timeout: Duration) -> anyhow::Result<hyper_openssl::HttpsConnector<HttpConnector>> {
+    let tls = openssl::ssl::SslConnector::builder(openssl::ssl::SslMethod::tls())
+        .context("failed to set up TLS")?;
+    hyper_openssl::HttpsConnector::with_connector(http(timeout), tls)
+        .context("failed to set up TLS")
+}
+
+#[cfg(all(feature = "rustls", not(feature = "openssl")))]
+fn connector(timeout: Duration) -> anyhow::Result<hyper_rustls::HttpsConnector<HttpConnector>> {
+    Ok(hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(http(timeout)))
+}
+
+fn http(timeout: Duration) -> HttpConnector {
+    let mut http = HttpConnector::new();
+    http.set_connect_timeout(Some(timeout));
+    http.enforce_http(false);
+    http
+}
@@ -0,0 +1,47 @@
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+/// Flushes the exported spans when dropped
+pub struct OtelGuard(TracerProvider);
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.shutdown() {
+            eprintln!("failed to flush OpenTelemetry spans: {err}");
+        }
+    }
+}
+
+/// Builds a layer exporting spans over OTLP/gRPC to `endpoint`, or to the one
+/// configured through the standard `OTEL_EXPORTER_OTLP_*` variables. Returns
+/// `None` when neither is set.
+pub fn layer<S>(endpoint: Option<&str>) -> anyhow::Result<Option<(impl Layer<S>, OtelGuard)>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let configured = std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some()
+        || std::env::var_os("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_some();
+    if endpoint.is_none() && !configured {
+        return Ok(None);
+    }
+
+    let mut exporter = opentelemetry_otlp::SpanExporter::builder().with_tonic();
+    if let Some(endpoint) = endpoint {
+        exporter = exporter.with_endpoint(endpoint);
+    }
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter.build()?, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            env!("CARGO_PKG_NAME"),
+        )]))
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    Ok(Some((
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        OtelGuard(provider),
+    )))
+}
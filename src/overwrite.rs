@@ -0,0 +1,189 @@
+use std::{
+    io::{BufRead, IsTerminal, Write},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::bail;
+use console::style;
+use futures::future;
+use indicatif::HumanBytes;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    diagnose,
+    exec::{self, quote},
+    files::FileEntry,
+};
+
+use crate::term;
+
+/// Longest command sent to check destinations, bigger sets are split up
+const MAX_COMMAND: usize = 32 * 1024;
+
+/// A destination that already exists in a pod
+pub struct Existing<'a> {
+    pub pod: &'a str,
+    pub file: &'a FileEntry,
+    /// Size of the remote file, `None` for anything that isn't a regular file
+    pub size: Option<u64>,
+}
+
+/// Looks up which of the destinations of `files` already exist in `pods`
+pub async fn check<'a>(
+    api: &Api<Pod>,
+    namespace: &str,
+    pods: &'a [String],
+    container: &str,
+    files: &'a [FileEntry],
+    timeout: Duration,
+) -> anyhow::Result<Vec<Existing<'a>>> {
+    let checks = pods.iter().map(|pod| async move {
+        let mut existing = Vec::new();
+        for (offset, command) in commands(files) {
+            let output = match exec::run(
+                api,
+                pod,
+                container,
+                &command,
+                timeout,
+                timeout,
+                &CancellationToken::new(),
+            )
+            .await
+            {
+                Ok(output) => output,
+                Err(err) => {
+                    return Err(anyhow::Error::from(
+                        diagnose::explain(api, namespace, pod, container, timeout, err).await,
+                    ))
+                }
+            };
+            for line in output.stdout.lines() {
+                let mut fields = line.split(' ');
+                let (Some(Ok(i)), Some(kind)) =
+                    (fields.next().map(str::parse::<usize>), fields.next())
+                else {
+                    continue;
+                };
+                let Some(file) = files.get(offset + i) else {
+                    continue;
+                };
+                existing.push(Existing {
+                    pod: pod.as_str(),
+                    file,
+                    size: match kind {
+                        "f" => fields.next().and_then(|size| size.parse().ok()),
+                        _ => None,
+                    },
+                });
+            }
+        }
+        anyhow::Ok(existing)
+    });
+    Ok(future::try_join_all(checks)
+        .await?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+/// Commands printing `<index> f <size>` for existing files and `<index> o`
+/// for anything else in the way, with the offset of their first index
+fn commands(files: &[FileEntry]) -> Vec<(usize, String)> {
+    batches(files)
+        .into_iter()
+        .map(|(offset, paths)| {
+            let command = format!(
+                "i=0; for p in{}; do \
+                 if [ -f \"$p\" ]; then echo \"$i f $(wc -c < \"$p\")\"; \
+                 elif [ -e \"$p\" ] || [ -L \"$p\" ]; then echo \"$i o\"; fi; \
+                 i=$((i+1)); done",
+                paths
+            );
+            (offset, command)
+        })
+        .collect()
+}
+
+/// The quoted destinations of `files`, each preceded by a space, in batches
+/// short enough for a command, with the index of their first file
+pub fn batches(files: &[FileEntry]) -> Vec<(usize, String)> {
+    let mut batches = Vec::new();
+    let mut offset = 0;
+    while offset < files.len() {
+        let mut paths = String::new();
+        let mut end = offset;
+        while end < files.len() && (end == offset || paths.len() < MAX_COMMAND) {
+            paths.push(' ');
+            paths.push_str(&quote(&files[end].remote_path()));
+            end += 1;
+        }
+        batches.push((offset, paths));
+        offset = end;
+    }
+    batches
+}
+
+/// Asks whether `existing` may be overwritten, failing when the answer is no
+/// or there is no terminal to ask on
+pub fn confirm(existing: &[Existing]) -> anyhow::Result<()> {
+    if existing.is_empty() {
+        return Ok(());
+    }
+    term::warn(format!(
+        "{} {} already exist and would be overwritten:",
+        existing.len(),
+        if existing.len() == 1 { "file" } else { "files" }
+    ));
+    for e in existing {
+        let detail = match e.size {
+            Some(size) if size == e.file.size => format!("{}, same size", HumanBytes(size)),
+            Some(size) => format!("{}, local {}", HumanBytes(size), HumanBytes(e.file.size)),
+            None => "not a regular file".to_string(),
+        };
+        eprintln!(
+            "  {}:{} {}",
+            e.pod,
+            e.file.remote_path(),
+            style(format!("({})", detail)).dim()
+        );
+    }
+    ask()
+}
+
+/// Asks whether the local files `existing` may be overwritten by downloads,
+/// failing when the answer is no or there is no terminal to ask on
+pub fn confirm_local(existing: &[&Path]) -> anyhow::Result<()> {
+    if existing.is_empty() {
+        return Ok(());
+    }
+    term::warn(format!(
+        "{} already and would be overwritten:",
+        match existing.len() {
+            1 => "1 local file exists".to_string(),
+            n => format!("{} local files exist", n),
+        }
+    ));
+    for path in existing {
+        eprintln!("  {}", path.display());
+    }
+    ask()
+}
+
+/// Asks whether to overwrite what was listed
+fn ask() -> anyhow::Result<()> {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        bail!("refusing to overwrite without confirmation, pass --yes to overwrite");
+    }
+    eprint!("Overwrite? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    match answer.trim() {
+        "y" | "Y" | "yes" => Ok(()),
+        _ => bail!("not overwriting existing files"),
+    }
+}
@@ -0,0 +1,44 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use clap::ValueEnum;
+use k8s_openapi::api::core::v1::Pod;
+use kube::ResourceExt;
+use serde::{Deserialize, Serialize};
+
+use crate::controller;
+
+/// Which of the pods a selector matches gets the copy when one is enough
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pick {
+    /// The pod created last
+    Newest,
+    /// The pod created first
+    Oldest,
+    /// Any of them
+    Random,
+    /// The first of those that are Ready, in the order of their names
+    FirstReady,
+}
+
+impl Pick {
+    /// The one of `pods` to copy to, `None` if none fits, like none Ready.
+    /// Pods created at the same time go in the order of their names.
+    pub fn pick(self, mut pods: Vec<Pod>) -> Option<Pod> {
+        pods.sort_by_key(ResourceExt::name_any);
+        let created = |pod: &Pod| pod.creation_timestamp().map(|time| time.0);
+        match self {
+            Pick::Newest => pods.into_iter().rev().max_by_key(created),
+            Pick::Oldest => pods.into_iter().min_by_key(created),
+            Pick::Random if pods.is_empty() => None,
+            Pick::Random => {
+                let i = RandomState::new().build_hasher().finish() as usize % pods.len();
+                Some(pods.swap_remove(i))
+            }
+            Pick::FirstReady => pods.into_iter().find(controller::is_ready),
+        }
+    }
+}
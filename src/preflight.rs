@@ -0,0 +1,144 @@
+use std::{fmt, time::Duration};
+
+use anyhow::bail;
+use futures::future;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{diagnose, exec, files::FileEntry};
+
+use crate::overwrite;
+
+/// Problems listed in full, the rest are counted
+const SHOWN: usize = 10;
+
+/// Why a file can't be written to its destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    /// The destination is a directory
+    Dir,
+    /// The closest path above the destination that exists isn't a directory
+    NotDir(String),
+    /// The destination, or the directory it would be made in, can't be
+    /// written, a read-only mount or the permissions
+    ReadOnly(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Dir => write!(f, "is a directory"),
+            Problem::NotDir(path) => write!(f, "can't be made, {} isn't a directory", path),
+            Problem::ReadOnly(path) => write!(
+                f,
+                "can't be written, {} is on a read-only mount or not writable by the container user",
+                path
+            ),
+        }
+    }
+}
+
+/// Checks that the destinations of `pods` can be written before anything
+/// is sent, failing with what is in the way otherwise
+pub async fn check(
+    api: &Api<Pod>,
+    namespace: &str,
+    pods: &[(String, Vec<FileEntry>)],
+    container: &str,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let checks = pods.iter().map(|(pod, files)| async move {
+        let mut problems = Vec::new();
+        for (offset, command) in commands(files) {
+            let output = exec::run(
+                api,
+                pod,
+                container,
+                &command,
+                timeout,
+                timeout,
+                &CancellationToken::new(),
+            )
+            .await;
+            let output = match output {
+                Ok(output) => output,
+                Err(err) => {
+                    return Err(anyhow::Error::from(
+                        diagnose::explain(api, namespace, pod, container, timeout, err).await,
+                    ))
+                }
+            };
+            for line in output.stdout.lines() {
+                let mut fields = line.splitn(3, ' ');
+                let (Some(Ok(i)), Some(kind), path) = (
+                    fields.next().map(str::parse::<usize>),
+                    fields.next(),
+                    fields.next().unwrap_or_default().to_string(),
+                ) else {
+                    continue;
+                };
+                let problem = match kind {
+                    "dir" => Problem::Dir,
+                    "notdir" => Problem::NotDir(path),
+                    "ro" => Problem::ReadOnly(path),
+                    _ => continue,
+                };
+                if let Some(file) = files.get(offset + i) {
+                    problems.push(format!("{}:{} {}", pod, file.remote_path(), problem));
+                }
+            }
+        }
+        anyhow::Ok(problems)
+    });
+    let problems: Vec<_> = future::try_join_all(checks)
+        .await?
+        .into_iter()
+        .flatten()
+        .collect();
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!(
+        "{} {} can't be written:",
+        problems.len(),
+        if problems.len() == 1 {
+            "destination"
+        } else {
+            "destinations"
+        }
+    );
+    for problem in problems.iter().take(SHOWN) {
+        message += &format!("\n  {}", problem);
+    }
+    if problems.len() > SHOWN {
+        message += &format!("\n  and {} more", problems.len() - SHOWN);
+    }
+    bail!(message)
+}
+
+/// Commands printing `<index> dir` for destinations that are directories,
+/// `<index> notdir <path>` for those below something else and `<index> ro
+/// <path>` for those that can't be written, with the offset of their first
+/// index
+fn commands(files: &[FileEntry]) -> Vec<(usize, String)> {
+    overwrite::batches(files)
+        .into_iter()
+        .map(|(offset, paths)| {
+            // a file that exists is written over, one that doesn't is made
+            // in the closest directory above it that exists
+            let command = format!(
+                "i=0; for p in{}; do \
+                 if [ -d \"$p\" ]; then echo \"$i dir\"; \
+                 elif [ -e \"$p\" ]; then [ -w \"$p\" ] || echo \"$i ro $p\"; \
+                 else d=$(dirname -- \"$p\"); \
+                 while [ ! -e \"$d\" ] && [ \"$d\" != / ] && [ \"$d\" != . ]; do d=$(dirname -- \"$d\"); done; \
+                 if [ ! -d \"$d\" ]; then echo \"$i notdir $d\"; \
+                 elif [ ! -w \"$d\" ]; then echo \"$i ro $d\"; fi; fi; \
+                 i=$((i+1)); done",
+                paths
+            );
+            (offset, command)
+        })
+        .collect()
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use console::style;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+
+use super::{ProgressObserver, Transfer};
+use crate::stats::Stats;
+
+/// Redraw interval of spinners while nothing else moves the bars
+const SPINNER_TICK: Duration = Duration::from_millis(100);
+
+/// `minimal` preset, narrow enough for any terminal
+const MINIMAL: &str = "{msg} {percent:>3}%";
+
+/// `detailed` preset for transfer bars, the aggregate bar keeps the default
+const DETAILED: &str = "{src} -> {msg} {spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, {eta} left)";
+
+/// Layout of the progress bars, a preset or an indicatif template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BarTemplate {
+    /// A full bar for a single transfer, a compact bar per transfer of a
+    /// bigger run
+    Default,
+    Minimal,
+    Detailed,
+    /// Used for every bar, besides indicatif's keys `{src}`, `{pod}`,
+    /// `{dst}` and `{file}` describe the transfer, they are empty on the
+    /// aggregate bar
+    Custom(String),
+}
+
+impl BarTemplate {
+    /// Parses `--progress-template`, a preset name or a template
+    pub fn parse(s: &str) -> Result<BarTemplate, String> {
+        match s {
+            "default" => Ok(BarTemplate::Default),
+            "minimal" => Ok(BarTemplate::Minimal),
+            "detailed" => Ok(BarTemplate::Detailed),
+            template => {
+                ProgressStyle::with_template(template).map_err(|err| err.to_string())?;
+                Ok(BarTemplate::Custom(template.to_string()))
+            }
+        }
+    }
+
+    /// Style of the bar of `transfer`, or of the aggregate bar, `item` tells
+    /// whether the transfer is one of several
+    fn style(&self, transfer: Option<&Transfer>, item: bool) -> ProgressStyle {
+        let template = match (self, transfer) {
+            // a size of 0 is one that isn't known, of a pipe or a download
+            (BarTemplate::Default, Some(t)) if t.total == 0 => return stream_style(item),
+            (BarTemplate::Default, Some(_)) if item => return item_style(),
+            (BarTemplate::Default, _) | (BarTemplate::Detailed, None) => return bar_style(),
+            (BarTemplate::Minimal, _) => MINIMAL,
+            (BarTemplate::Detailed, Some(_)) => DETAILED,
+            (BarTemplate::Custom(template), _) => template,
+        };
+        // validated when parsed
+        let style = ProgressStyle::with_template(template)
+            .unwrap()
+            .progress_chars("#>-");
+        let style = with_eta(style);
+        let field = |value: &str| {
+            let value = value.to_string();
+            move |_: &ProgressState, w: &mut dyn Write| w.write_str(&value).unwrap()
+        };
+        let (src, pod, dst) = match transfer {
+            Some(t) => (t.src.as_str(), t.pod.as_str(), t.dst.as_str()),
+            None => ("", "", ""),
+        };
+        let file = Path::new(src)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        style
+            .with_key("src", field(src))
+            .with_key("pod", field(pod))
+            .with_key("dst", field(dst))
+            .with_key("file", field(&file))
+    }
+}
+
+/// Renders a run as indicatif bars on stderr
+pub struct BarObserver {
+    multi: MultiProgress,
+    template: BarTemplate,
+    /// Aggregate bar, only shown when there is more than one transfer
+    total: OnceLock<ProgressBar>,
+    bars: Mutex<HashMap<usize, ProgressBar>>,
+    phases: Mutex<HashMap<String, ProgressBar>>,
+}
+
+impl BarObserver {
+    pub fn new(template: BarTemplate) -> BarObserver {
+        BarObserver {
+            multi: MultiProgress::with_draw_target(ProgressDrawTarget::stderr()),
+            template,
+            total: OnceLock::new(),
+            bars: Mutex::default(),
+            phases: Mutex::default(),
+        }
+    }
+
+    fn bar(&self, transfer: &Transfer) -> Option<ProgressBar> {
+        self.bars.lock().unwrap().get(&transfer.id).cloned()
+    }
+}
+
+impl ProgressObserver for BarObserver {
+    fn run_started(&self, transfers: usize, total: u64) {
+        if transfers > 1 {
+            let pb = self.multi.add(ProgressBar::new(total));
+            pb.set_style(self.template.style(None, false));
+            pb.set_message(format!("total ({transfers} transfers)"));
+            let _ = self.total.set(pb);
+        }
+    }
+
+    fn phase_started(&self, phase: &str) {
+        let pb = self.multi.add(ProgressBar::new_spinner());
+        pb.set_style(spinner_style());
+        pb.set_message(phase.to_string());
+        pb.enable_steady_tick(SPINNER_TICK);
+        self.phases.lock().unwrap().insert(phase.to_string(), pb);
+    }
+
+    fn phase_finished(&self, phase: &str) {
+        if let Some(pb) = self.phases.lock().unwrap().remove(phase) {
+            pb.finish_and_clear();
+            self.multi.remove(&pb);
+        }
+    }
+
+    fn transfer_started(&self, transfer: &Transfer) {
+        let pb = match self.total.get() {
+            Some(aggregate) => {
+                let pb = self
+                    .multi
+                    .insert_before(aggregate, ProgressBar::new(transfer.total));
+                pb.set_style(self.template.style(Some(transfer), true));
+                pb
+            }
+            None => {
+                let pb = self.multi.add(ProgressBar::new(transfer.total));
+                pb.set_style(self.template.style(Some(transfer), false));
+                pb
+            }
+        };
+        pb.set_message(format!("{}:{}", transfer.pod, transfer.dst));
+        self.bars.lock().unwrap().insert(transfer.id, pb);
+    }
+
+    fn connecting(&self, transfer: &Transfer) {
+        if let Some(pb) = self.bar(transfer) {
+            pb.set_message(format!("{}:{} (connecting)", transfer.pod, transfer.dst));
+            pb.enable_steady_tick(SPINNER_TICK);
+        }
+    }
+
+    fn connected(&self, transfer: &Transfer) {
+        if let Some(pb) = self.bar(transfer) {
+            // the spinner of a transfer of unknown size keeps turning
+            if transfer.total > 0 {
+                pb.disable_steady_tick();
+            }
+            pb.set_message(format!("{}:{}", transfer.pod, transfer.dst));
+        }
+    }
+
+    fn progress(&self, transfer: &Transfer, bytes: u64) {
+        if let Some(pb) = self.bar(transfer) {
+            let delta = bytes.saturating_sub(pb.position());
+            pb.set_position(bytes);
+            if let Some(total) = self.total.get() {
+                total.inc(delta);
+            }
+        }
+    }
+
+    fn stderr(&self, transfer: &Transfer, line: &str) {
+        self.multi
+            .suspend(|| eprintln!("{} {}", style(format!("{}:", transfer.pod)).yellow(), line));
+    }
+
+    /// A single transfer keeps its bar on screen, transfers of a bigger run
+    /// make room for the ones still in flight
+    fn transfer_finished(
+        &self,
+        transfer: &Transfer,
+        _bytes: u64,
+        _result: Result<(), &anyhow::Error>,
+    ) {
+        if let Some(pb) = self.bars.lock().unwrap().remove(&transfer.id) {
+            if self.total.get().is_some() {
+                pb.finish_and_clear();
+                self.multi.remove(&pb);
+            } else {
+                pb.abandon();
+            }
+        }
+    }
+
+    fn run_finished(&self, _result: Result<&Stats, &anyhow::Error>) {
+        if let Some(pb) = self.total.get() {
+            pb.abandon();
+        }
+    }
+
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        self.multi.suspend(f)
+    }
+}
+
+fn bar_style() -> ProgressStyle {
+    with_eta(ProgressStyle::with_template(
+        "{msg} {spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, {eta})")
+        .unwrap()
+        .progress_chars("#>-"))
+}
+
+/// Shows `{eta}` in seconds with a decimal
+fn with_eta(style: ProgressStyle) -> ProgressStyle {
+    style.with_key("eta", |state: &ProgressState, w: &mut dyn Write| {
+        write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
+    })
+}
+
+fn item_style() -> ProgressStyle {
+    ProgressStyle::with_template("  {msg} [{bar:30.cyan/blue}] {bytes}/{total_bytes}")
+        .unwrap()
+        .progress_chars("#>-")
+}
+
+/// The bytes transferred so far and a spinner, for a transfer of unknown
+/// size
+fn stream_style(item: bool) -> ProgressStyle {
+    let template = if item {
+        "  {msg} {spinner:.green} {bytes}"
+    } else {
+        "{msg} {spinner:.green} [{elapsed_precise}] {bytes} ({binary_bytes_per_sec})"
+    };
+    ProgressStyle::with_template(template).unwrap()
+}
+
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::with_template("{spinner:.green} {msg}").unwrap()
+}
@@ -0,0 +1,112 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Stream,
+};
+
+use super::{ProgressObserver, Transfer};
+use crate::stats::Stats;
+
+/// What happened to a transfer, see [`events`]
+#[derive(Debug, Clone)]
+pub enum TransferEvent {
+    Started(Transfer),
+    /// `bytes` of the transfer have been copied so far, passed on at most
+    /// once per progress interval
+    Chunk {
+        transfer: Transfer,
+        bytes: u64,
+    },
+    /// The copy has the digest `sha256`, the same on both ends
+    Verified {
+        transfer: Transfer,
+        sha256: String,
+    },
+    Completed {
+        transfer: Transfer,
+        bytes: u64,
+    },
+    Failed {
+        transfer: Transfer,
+        bytes: u64,
+        /// The error with its causes
+        error: String,
+    },
+}
+
+/// Forwards the transfers of a run to an [`EventStream`]
+pub struct EventObserver {
+    tx: UnboundedSender<TransferEvent>,
+}
+
+/// The transfer events of a run, ending when the run finishes
+pub struct EventStream {
+    rx: UnboundedReceiver<TransferEvent>,
+}
+
+/// An observer and the stream the events it receives come out of, for
+/// consumers that would rather poll than be called back
+pub fn events() -> (EventObserver, EventStream) {
+    let (tx, rx) = mpsc::unbounded();
+    (EventObserver { tx }, EventStream { rx })
+}
+
+impl EventObserver {
+    fn send(&self, event: TransferEvent) {
+        // nobody listening anymore is fine
+        let _ = self.tx.unbounded_send(event);
+    }
+}
+
+impl ProgressObserver for EventObserver {
+    fn transfer_started(&self, transfer: &Transfer) {
+        self.send(TransferEvent::Started(transfer.clone()));
+    }
+
+    fn progress(&self, transfer: &Transfer, bytes: u64) {
+        self.send(TransferEvent::Chunk {
+            transfer: transfer.clone(),
+            bytes,
+        });
+    }
+
+    fn verified(&self, transfer: &Transfer, sha256: &str) {
+        self.send(TransferEvent::Verified {
+            transfer: transfer.clone(),
+            sha256: sha256.to_string(),
+        });
+    }
+
+    fn transfer_finished(
+        &self,
+        transfer: &Transfer,
+        bytes: u64,
+        result: Result<(), &anyhow::Error>,
+    ) {
+        let transfer = transfer.clone();
+        self.send(match result {
+            Ok(()) => TransferEvent::Completed { transfer, bytes },
+            Err(err) => TransferEvent::Failed {
+                transfer,
+                bytes,
+                error: format!("{:#}", err),
+            },
+        });
+    }
+
+    fn run_finished(&self, _result: Result<&Stats, &anyhow::Error>) {
+        self.tx.close_channel();
+    }
+}
+
+impl Stream for EventStream {
+    type Item = TransferEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<TransferEvent>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+use super::{ProgressObserver, Transfer};
+use crate::stats::Stats;
+
+/// Writes a run as newline-delimited JSON events on stdout
+pub struct JsonObserver {
+    transfers: Mutex<HashMap<usize, State>>,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+struct State {
+    started: Instant,
+}
+
+impl JsonObserver {
+    pub fn new() -> JsonObserver {
+        JsonObserver::writer(io::stdout())
+    }
+
+    /// Writes the events to `out` instead of stdout
+    pub fn writer(out: impl Write + Send + 'static) -> JsonObserver {
+        JsonObserver {
+            transfers: Mutex::default(),
+            out: Mutex::new(Box::new(out)),
+        }
+    }
+
+    fn emit(&self, event: Value) {
+        let mut out = self.out.lock().unwrap();
+        // a closed stdout stops the events, not the copy
+        let _ = writeln!(out, "{event}").and_then(|()| out.flush());
+    }
+}
+
+impl Default for JsonObserver {
+    fn default() -> Self {
+        JsonObserver::new()
+    }
+}
+
+impl ProgressObserver for JsonObserver {
+    fn transfer_started(&self, transfer: &Transfer) {
+        self.transfers.lock().unwrap().insert(
+            transfer.id,
+            State {
+                started: Instant::now(),
+            },
+        );
+        self.emit(json!({
+            "event": "started",
+            "src": transfer.src,
+            "pod": transfer.pod,
+            "dst": transfer.dst,
+            "total": transfer.total,
+        }));
+    }
+
+    fn progress(&self, transfer: &Transfer, bytes: u64) {
+        let transfers = self.transfers.lock().unwrap();
+        let Some(state) = transfers.get(&transfer.id) else {
+            return;
+        };
+        self.emit(json!({
+            "event": "progress",
+            "src": transfer.src,
+            "pod": transfer.pod,
+            "bytes": bytes,
+            "total": transfer.total,
+            "rate": rate(bytes, state.started.elapsed()),
+        }));
+    }
+
+    fn verified(&self, transfer: &Transfer, sha256: &str) {
+        self.emit(json!({
+            "event": "verified",
+            "src": transfer.src,
+            "pod": transfer.pod,
+            "dst": transfer.dst,
+            "sha256": sha256,
+        }));
+    }
+
+    fn stderr(&self, transfer: &Transfer, line: &str) {
+        self.emit(json!({
+            "event": "stderr",
+            "src": transfer.src,
+            "pod": transfer.pod,
+            "line": line,
+        }));
+    }
+
+    fn transfer_finished(
+        &self,
+        transfer: &Transfer,
+        bytes: u64,
+        result: Result<(), &anyhow::Error>,
+    ) {
+        let elapsed = self
+            .transfers
+            .lock()
+            .unwrap()
+            .remove(&transfer.id)
+            .map(|state| state.started.elapsed())
+            .unwrap_or_default();
+        match result {
+            Ok(()) => self.emit(json!({
+                "event": "completed",
+                "src": transfer.src,
+                "pod": transfer.pod,
+                "dst": transfer.dst,
+                "bytes": bytes,
+                "elapsed": elapsed.as_secs_f64(),
+                "rate": rate(bytes, elapsed),
+            })),
+            Err(err) => self.emit(json!({
+                "event": "failed",
+                "src": transfer.src,
+                "pod": transfer.pod,
+                "dst": transfer.dst,
+                "bytes": bytes,
+                "error": format!("{err:#}"),
+            })),
+        }
+    }
+
+    fn run_finished(&self, result: Result<&Stats, &anyhow::Error>) {
+        if let Err(err) = result {
+            self.emit(json!({
+                "event": "failed",
+                "error": format!("{err:#}"),
+            }));
+        }
+    }
+}
+
+/// Average bytes per second
+fn rate(bytes: u64, elapsed: Duration) -> u64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        (bytes as f64 / secs) as u64
+    } else {
+        0
+    }
+}
@@ -0,0 +1,337 @@
+#[cfg(feature = "cli")]
+mod bar;
+mod events;
+mod json;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+
+use crate::{stats::Stats, Error};
+
+#[cfg(feature = "cli")]
+pub use self::bar::{BarObserver, BarTemplate};
+pub use self::{
+    events::{events, EventObserver, EventStream, TransferEvent},
+    json::JsonObserver,
+};
+
+/// How transfer progress is reported
+#[cfg(feature = "cli")]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// Interactive progress bar on stderr
+    Bar,
+    /// Newline-delimited JSON events on stdout
+    Json,
+    /// No progress output
+    None,
+}
+
+/// A single file copied to a single pod
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    /// Unique within a run
+    pub id: usize,
+    pub src: String,
+    pub pod: String,
+    pub dst: String,
+    /// Size of the file in bytes
+    pub total: u64,
+}
+
+/// Receives the progress of a run. All methods default to doing nothing so an
+/// observer only implements the events it cares about.
+pub trait ProgressObserver: Send + Sync {
+    /// The run is made of `transfers` file copies summing up to `total` bytes
+    fn run_started(&self, _transfers: usize, _total: u64) {}
+
+    /// A setup step like loading the kubeconfig started
+    fn phase_started(&self, _phase: &str) {}
+
+    fn phase_finished(&self, _phase: &str) {}
+
+    fn transfer_started(&self, _transfer: &Transfer) {}
+
+    /// The exec stream of the transfer is being established
+    fn connecting(&self, _transfer: &Transfer) {}
+
+    fn connected(&self, _transfer: &Transfer) {}
+
+    /// `bytes` of the transfer have been sent so far
+    fn progress(&self, _transfer: &Transfer, _bytes: u64) {}
+
+    /// The container wrote `line` to stderr, reported as soon as it arrives
+    fn stderr(&self, _transfer: &Transfer, _line: &str) {}
+
+    /// The copy was verified to have the digest `sha256` on both ends,
+    /// SHA-256 unless the request has another [`Hasher`](crate::hash::Hasher)
+    fn verified(&self, _transfer: &Transfer, _sha256: &str) {}
+
+    fn transfer_finished(
+        &self,
+        _transfer: &Transfer,
+        _bytes: u64,
+        _result: Result<(), &anyhow::Error>,
+    ) {
+    }
+
+    fn run_finished(&self, _result: Result<&Stats, &anyhow::Error>) {}
+
+    /// Runs `f`, which prints to the terminal, with the observer's own output
+    /// out of the way
+    fn suspend(&self, f: &mut dyn FnMut()) {
+        f()
+    }
+}
+
+/// Prints what containers write to stderr when no progress is shown
+pub struct StderrObserver;
+
+impl ProgressObserver for StderrObserver {
+    fn stderr(&self, transfer: &Transfer, line: &str) {
+        eprintln!("{}: {}", transfer.pod, line);
+    }
+}
+
+/// Default minimum time between two position updates of a transfer passed
+/// on to the observers
+const INTERVAL: Duration = Duration::from_millis(200);
+
+/// Progress of a whole run, forwarded to the registered observers
+pub struct Progress {
+    observers: Vec<Box<dyn ProgressObserver>>,
+    interval: Duration,
+    stats: Mutex<Stats>,
+    next_id: AtomicUsize,
+}
+
+/// A setup step, finished when dropped, see [`Progress::phase`]
+pub struct Phase<'a> {
+    progress: &'a Progress,
+    name: String,
+}
+
+impl Drop for Phase<'_> {
+    fn drop(&mut self) {
+        self.progress.each(|o| o.phase_finished(&self.name));
+    }
+}
+
+/// Progress of copying a single file to a single pod
+#[derive(Clone)]
+pub struct ItemProgress(Arc<Item>);
+
+struct Item {
+    progress: Arc<Progress>,
+    transfer: Transfer,
+    pos: Mutex<Position>,
+}
+
+#[derive(Default)]
+struct Position {
+    pos: u64,
+    /// Last position passed on to the observers and when
+    reported: u64,
+    reported_at: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new() -> Progress {
+        Progress {
+            observers: Vec::new(),
+            interval: INTERVAL,
+            stats: Mutex::new(Stats::new()),
+            next_id: AtomicUsize::new(0),
+        }
+    }
+
+    /// Progress reported through the built-in renderer for `mode`, bars are
+    /// laid out by `template`
+    #[cfg(feature = "cli")]
+    pub fn with_mode(mode: ProgressMode, template: BarTemplate) -> Progress {
+        let progress = Progress::new();
+        match mode {
+            ProgressMode::Bar => progress.observe(BarObserver::new(template)),
+            ProgressMode::Json => progress.observe(JsonObserver::new()),
+            ProgressMode::None => progress,
+        }
+    }
+
+    /// Adds an observer receiving every event of the run
+    pub fn observe(mut self, observer: impl ProgressObserver + 'static) -> Progress {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Passes position updates of a transfer on at most once per `interval`,
+    /// reads are much more frequent than anything needs to redraw
+    pub fn interval(mut self, interval: Duration) -> Progress {
+        self.interval = interval;
+        self
+    }
+
+    fn each(&self, f: impl Fn(&dyn ProgressObserver)) {
+        for observer in &self.observers {
+            f(observer.as_ref());
+        }
+    }
+
+    /// The run is made of `items` file copies summing up to `total` bytes
+    pub fn start(&self, items: usize, total: u64) {
+        *self.stats.lock().unwrap() = Stats::new();
+        self.each(|o| o.run_started(items, total));
+    }
+
+    /// Starts reporting the copy of `total` bytes from `src` to `pod:dst`
+    pub fn item(self: &Arc<Self>, src: &str, pod: &str, dst: &str, total: u64) -> ItemProgress {
+        let transfer = Transfer {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            src: src.to_string(),
+            pod: pod.to_string(),
+            dst: dst.to_string(),
+            total,
+        };
+        self.each(|o| o.transfer_started(&transfer));
+        ItemProgress(Arc::new(Item {
+            progress: self.clone(),
+            transfer,
+            pos: Mutex::default(),
+        }))
+    }
+
+    /// Reports the setup step `name` until the returned guard is dropped
+    pub fn phase(&self, name: impl Into<String>) -> Phase<'_> {
+        let name = name.into();
+        self.each(|o| o.phase_started(&name));
+        Phase {
+            progress: self,
+            name,
+        }
+    }
+
+    /// Runs `f`, which prints to the terminal, with the observers' output out
+    /// of the way
+    pub fn suspend(&self, f: impl FnOnce()) {
+        fn nested(observers: &[Box<dyn ProgressObserver>], f: &mut dyn FnMut()) {
+            match observers.split_first() {
+                Some((first, rest)) => first.suspend(&mut || nested(rest, f)),
+                None => f(),
+            }
+        }
+        let mut f = Some(f);
+        nested(&self.observers, &mut || {
+            if let Some(f) = f.take() {
+                f()
+            }
+        });
+    }
+
+    pub fn finish(&self) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.finish();
+        self.each(|o| o.run_finished(Ok(&stats)));
+    }
+
+    pub fn fail(&self, err: &anyhow::Error) {
+        self.stats.lock().unwrap().finish();
+        self.each(|o| o.run_finished(Err(err)));
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress::new()
+    }
+}
+
+impl ItemProgress {
+    /// The exec stream to the pod is being established
+    pub fn connecting(&self) {
+        let item = &self.0;
+        item.progress.each(|o| o.connecting(&item.transfer));
+    }
+
+    pub fn connected(&self) {
+        let item = &self.0;
+        item.progress.each(|o| o.connected(&item.transfer));
+    }
+
+    pub fn set_position(&self, pos: u64) {
+        let item = &self.0;
+        let mut cur = item.pos.lock().unwrap();
+        let delta = pos.saturating_sub(cur.pos);
+        cur.pos = pos;
+        item.progress.stats.lock().unwrap().add(delta);
+        let now = Instant::now();
+        let due = cur
+            .reported_at
+            .is_none_or(|at| now - at >= item.progress.interval);
+        if pos != cur.reported && (due || pos == item.transfer.total) {
+            cur.reported = pos;
+            cur.reported_at = Some(now);
+            item.progress.each(|o| o.progress(&item.transfer, pos));
+        }
+    }
+
+    /// Passes on the position the interval held back
+    fn flush(&self) {
+        let item = &self.0;
+        let mut cur = item.pos.lock().unwrap();
+        if cur.reported != cur.pos {
+            cur.reported = cur.pos;
+            let pos = cur.pos;
+            item.progress.each(|o| o.progress(&item.transfer, pos));
+        }
+    }
+
+    pub fn stderr(&self, line: &str) {
+        let item = &self.0;
+        item.progress.each(|o| o.stderr(&item.transfer, line));
+    }
+
+    pub fn verified(&self, sha256: &str) {
+        let item = &self.0;
+        item.progress.stats.lock().unwrap().verify(true);
+        item.progress.each(|o| o.verified(&item.transfer, sha256));
+    }
+
+    /// The copy failed and is tried again
+    pub fn retrying(&self) {
+        self.0.progress.stats.lock().unwrap().retry();
+    }
+
+    pub fn position(&self) -> u64 {
+        self.0.pos.lock().unwrap().pos
+    }
+
+    pub fn finish(&self) {
+        self.flush();
+        let item = &self.0;
+        let pos = self.position();
+        item.progress
+            .each(|o| o.transfer_finished(&item.transfer, pos, Ok(())));
+    }
+
+    pub fn fail(&self, err: &anyhow::Error) {
+        self.flush();
+        let item = &self.0;
+        if let Some(Error::Verification { .. }) = err.downcast_ref() {
+            item.progress.stats.lock().unwrap().verify(false);
+        }
+        let pos = self.position();
+        item.progress
+            .each(|o| o.transfer_finished(&item.transfer, pos, Err(err)));
+    }
+}
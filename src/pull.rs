@@ -0,0 +1,502 @@
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::{ExitCode, Stdio},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context as _};
+use k8s_openapi::api::core::v1::Pod;
+use kube::Api;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    copy_from_pod, copy_from_pod_into,
+    files::{self, Walk},
+    progress::{ItemProgress, Progress, ProgressMode, StderrObserver},
+    remote,
+    text::{TextFiles, TextMode},
+    verify_from_pod, ExecOptions,
+};
+
+use crate::{
+    cp, overwrite,
+    target::{self, Target},
+    term, Global,
+};
+
+/// A file to download by its path in the pod and the local path it goes to,
+/// with what is known of it in the pod
+type Download = ((String, PathBuf), Option<remote::DirEntry>);
+
+/// Copy files from a pod to local paths
+#[derive(clap::Args, Debug)]
+pub struct PullArgs {
+    /// Files or directories to download, `POD:PATH`, or the directories to search with --remote-find
+    #[arg(required = true, value_parser = target::parse)]
+    src: Vec<Target>,
+
+    /// Local path to download to, into it if it is a directory or ends in `/` as it must for several sources, the directory the matches of --remote-find go to
+    dst: PathBuf,
+
+    /// Download the regular files `find` matches below the directory of `src` with this expression, like `-name '*.hprof' -mtime -1`, by their paths below it
+    #[arg(long, value_name = "EXPRESSION", allow_hyphen_values = true)]
+    remote_find: Option<String>,
+
+    /// Download only the matches of --remote-find this many levels below the directory, 1 for the files directly in it
+    #[arg(long, value_name = "N", requires = "remote_find")]
+    max_depth: Option<usize>,
+
+    /// Don't search directories on other file systems than the directory, like mounted volumes, with --remote-find
+    #[arg(long, requires = "remote_find")]
+    one_file_system: bool,
+
+    /// Convert the line endings of text files on the way, `crlf->lf` or `lf->crlf`
+    #[arg(long, value_name = "MODE", value_parser = TextMode::parse)]
+    text_mode: Option<TextMode>,
+
+    /// Extensions of the files --text-mode takes as text, separated by commas [default: those of `cp --text`]
+    #[arg(
+        long,
+        value_name = "EXT",
+        value_delimiter = ',',
+        requires = "text_mode"
+    )]
+    text: Vec<String>,
+
+    /// Leave the local files with the permissions and modification times they are made with, rather than those of the files in the pod
+    #[arg(long)]
+    no_preserve: bool,
+
+    /// Fail before downloading anything if it would overwrite a local file, rather than ask
+    #[arg(long, conflicts_with_all = ["skip_existing", "update"])]
+    no_clobber: bool,
+
+    /// Leave the local files that exist as they are and download the others
+    #[arg(long, conflicts_with = "update")]
+    skip_existing: bool,
+
+    /// Overwrite a local file only if the one in the pod was modified after it
+    #[arg(long)]
+    update: bool,
+
+    /// Encrypt the files with age for this recipient before they touch the disk, as `NAME.age`, repeat for several recipients. Needs `age` locally.
+    #[arg(long, value_name = "RECIPIENT")]
+    encrypt_to: Vec<String>,
+}
+
+pub async fn run(global: &Global, args: &PullArgs) -> anyhow::Result<ExitCode> {
+    let api = global.session().await?.pods(global.namespace());
+    let cancel = cp::interrupt();
+    let targets = target::resolve(global, &api, &args.src).await?;
+    let several = targets.len() > 1;
+    if several && !into_dir(&args.dst) {
+        bail!(
+            "{} isn't a directory, end it in / to download several sources into it",
+            args.dst.display()
+        );
+    }
+    let known = if args.remote_find.is_none() {
+        kinds(global, &api, &targets, &cancel).await
+    } else {
+        HashMap::new()
+    };
+    let downloaded = Cell::new(0);
+    let code = target::each(global, &api, &targets, |src| {
+        let (api, cancel, known, downloaded) = (&api, &cancel, &known, &downloaded);
+        async move {
+            let mut files = select(global, api, args, src, known, cancel).await?;
+            if !args.encrypt_to.is_empty() {
+                for (_, dst) in &mut files {
+                    let mut name = dst.file_name().unwrap_or_default().to_os_string();
+                    name.push(".age");
+                    dst.set_file_name(name);
+                }
+            }
+            if files.is_empty() {
+                if !global.quiet {
+                    term::warn(format!("no file below {} matches", src));
+                }
+                return Ok(());
+            }
+            // the sizes give the progress a total to go by
+            let paths: Vec<_> = files.iter().map(|(src, _)| src.clone()).collect();
+            let entries = remote::stats(
+                api,
+                &src.pod,
+                &global.container,
+                &paths,
+                global.connect_timeout,
+                cancel,
+            )
+            .await?;
+            let files = overwrites(global, args, files.into_iter().zip(entries).collect())?;
+            if files.is_empty() {
+                if !global.quiet {
+                    term::success(format!("{}: every local file is up to date", src));
+                }
+                downloaded.set(downloaded.get() + 1);
+                return Ok(());
+            }
+            let text = cp::text_files(args.text_mode, &args.text);
+            download(
+                global,
+                api,
+                src,
+                &files,
+                text.as_ref(),
+                &args.encrypt_to,
+                !args.no_preserve,
+                several,
+                cancel,
+            )
+            .await?;
+            downloaded.set(downloaded.get() + 1);
+            Ok(())
+        }
+    })
+    .await;
+    if several && !global.quiet {
+        let summary = format!(
+            "{} of {} sources downloaded",
+            downloaded.get(),
+            targets.len()
+        );
+        match downloaded.get() == targets.len() {
+            true => term::success(summary),
+            false => term::error(summary),
+        }
+    }
+    Ok(code)
+}
+
+/// What each of `targets` is, looked up with one command per pod. Those of
+/// a pod the command failed in are left out, to be looked up on their own.
+async fn kinds(
+    global: &Global,
+    api: &Api<Pod>,
+    targets: &[Target],
+    cancel: &CancellationToken,
+) -> HashMap<(String, String), Option<remote::DirEntry>> {
+    let mut known = HashMap::new();
+    let mut pods: Vec<_> = targets.iter().map(|target| &target.pod).collect();
+    pods.sort();
+    pods.dedup();
+    for pod in pods {
+        let paths: Vec<_> = targets
+            .iter()
+            .filter(|target| &target.pod == pod)
+            .map(|target| target.path.clone())
+            .collect();
+        let Ok(entries) = remote::stats(
+            api,
+            pod,
+            &global.container,
+            &paths,
+            global.connect_timeout,
+            cancel,
+        )
+        .await
+        else {
+            continue;
+        };
+        for (path, entry) in paths.into_iter().zip(entries) {
+            known.insert((pod.clone(), path), entry);
+        }
+    }
+    known
+}
+
+/// The files to download, by their paths in the pod and the local paths
+/// they go to, with what `known` says the sources are
+async fn select(
+    global: &Global,
+    api: &Api<Pod>,
+    args: &PullArgs,
+    src: &Target,
+    known: &HashMap<(String, String), Option<remote::DirEntry>>,
+    cancel: &CancellationToken,
+) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let Some(expression) = &args.remote_find else {
+        let entry = match known.get(&(src.pod.clone(), src.path.clone())) {
+            Some(entry) => entry.clone(),
+            None => {
+                remote::stat(
+                    api,
+                    &src.pod,
+                    &global.container,
+                    &src.path,
+                    global.connect_timeout,
+                    cancel,
+                )
+                .await?
+            }
+        };
+        let dst = if into_dir(&args.dst) {
+            let name = src.path.trim_end_matches('/').rsplit('/').next();
+            match name {
+                Some(name) if !name.is_empty() && name != "." && name != ".." => {
+                    args.dst.join(name)
+                }
+                _ => bail!("{} has no file name", src),
+            }
+        } else {
+            args.dst.clone()
+        };
+        return match entry {
+            None => bail!("No such file or directory"),
+            // a directory is laid out below `dst` as it is in the pod
+            Some(entry) if entry.kind == remote::Kind::Dir => Ok(remote::files(
+                api,
+                &src.pod,
+                &global.container,
+                &src.path,
+                global.connect_timeout,
+                cancel,
+            )
+            .await?
+            .into_iter()
+            .map(|rel| {
+                let local = files::local_path(&dst, &rel)?;
+                Ok((files::join(&src.path, &rel), local))
+            })
+            .collect::<k8scp::Result<_>>()?),
+            Some(_) => Ok(vec![(src.path.clone(), dst)]),
+        };
+    };
+    let found = remote::find(
+        api,
+        &src.pod,
+        &global.container,
+        &src.path,
+        expression,
+        &Walk {
+            max_depth: args.max_depth,
+            one_file_system: args.one_file_system,
+            ..Walk::default()
+        },
+        global.connect_timeout,
+        cancel,
+    )
+    .await?;
+    Ok(found
+        .into_iter()
+        .map(|rel| {
+            let dst = files::local_path(&args.dst, &rel)?;
+            Ok((files::join(&src.path, &rel), dst))
+        })
+        .collect::<k8scp::Result<_>>()?)
+}
+
+/// `files` without those --skip-existing or --update leave as they are,
+/// failing with --no-clobber or an answer of no if the others would
+/// overwrite local files
+fn overwrites(
+    global: &Global,
+    args: &PullArgs,
+    mut files: Vec<Download>,
+) -> anyhow::Result<Vec<Download>> {
+    if args.skip_existing {
+        files.retain(|((_, dst), _)| dst.symlink_metadata().is_err());
+    } else if args.update {
+        files.retain(|((_, dst), entry)| {
+            let modified = entry.as_ref().and_then(|entry| entry.modified);
+            match (
+                std::fs::metadata(dst).and_then(|meta| meta.modified()),
+                modified,
+            ) {
+                (Ok(local), Some(remote)) => remote > local,
+                _ => true,
+            }
+        });
+    }
+    if global.yes {
+        return Ok(files);
+    }
+    let existing: Vec<_> = files
+        .iter()
+        .map(|((_, dst), _)| dst.as_path())
+        .filter(|dst| dst.symlink_metadata().is_ok())
+        .collect();
+    if args.no_clobber && !existing.is_empty() {
+        bail!(
+            "{} would be overwritten{}",
+            existing[0].display(),
+            match existing.len() {
+                1 => String::new(),
+                n => format!(" along with {} more local files", n - 1),
+            }
+        );
+    }
+    // what --update downloads replaces what is older on purpose
+    if !args.update {
+        overwrite::confirm_local(&existing)?;
+    }
+    Ok(files)
+}
+
+/// Downloads `files` from `pod` one after another, each to a part file
+/// renamed to its destination once it is complete and its digest matches
+/// that of the file in the pod. A file that fails is
+/// reported and the next one downloaded regardless. The line endings of
+/// those `text` takes as text are converted, with recipients in
+/// `encrypt_to` the files are written as `age` encrypts them for those, and
+/// with `preserve` the local files get the permissions and modification
+/// times of those in the pod.
+/// The summary names `src` when it is one of `several` sources.
+#[allow(clippy::too_many_arguments)]
+async fn download(
+    global: &Global,
+    api: &Api<Pod>,
+    src: &Target,
+    files: &[Download],
+    text: Option<&TextFiles>,
+    encrypt_to: &[String],
+    preserve: bool,
+    several: bool,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    let pod = &src.pod;
+    let mode = global.progress_mode();
+    let mut progress = Progress::with_mode(mode, global.progress_template.clone())
+        .interval(global.progress_interval);
+    if mode == ProgressMode::None && !global.quiet {
+        progress = progress.observe(StderrObserver);
+    }
+    let progress = Arc::new(progress);
+    let transport = global.transport();
+    let opts = ExecOptions {
+        container: &global.container,
+        connect_timeout: global.connect_timeout,
+        idle_timeout: global.idle_timeout,
+        cancel: cancel.clone(),
+        transport: &*transport,
+        ..ExecOptions::default()
+    };
+
+    let size = |entry: &Option<remote::DirEntry>| entry.as_ref().and_then(|entry| entry.size);
+    progress.start(
+        files.len(),
+        files
+            .iter()
+            .map(|(_, entry)| size(entry).unwrap_or(0))
+            .sum(),
+    );
+    let mut failed = 0;
+    for ((src, dst), entry) in files {
+        let item = progress.item(src, pod, &dst.to_string_lossy(), size(entry).unwrap_or(0));
+        let name = src.rsplit('/').next().unwrap_or(src);
+        let opts = ExecOptions {
+            text_mode: text.and_then(|text| text.mode_of(name)),
+            ..opts.clone()
+        };
+        let part = files::part_path(dst);
+        let res = async {
+            create_parent(dst)?;
+            let digest = match encrypt_to {
+                [] => copy_from_pod(api, pod, src, &part, &opts, item.clone()).await?,
+                _ => encrypt(api, pod, src, &part, encrypt_to, &opts, item.clone()).await?,
+            };
+            let renamed = async {
+                verify_from_pod(api, pod, src, &digest, &opts).await?;
+                item.verified(&digest);
+                if let Some(entry) = entry.as_ref().filter(|_| preserve) {
+                    files::preserve(&part, entry.mode, entry.modified)?;
+                }
+                anyhow::Ok(())
+            }
+            .await
+            .and_then(|()| {
+                std::fs::rename(&part, dst)
+                    .with_context(|| format!("failed to replace {}", dst.display()))
+            });
+            if renamed.is_err() {
+                let _ = std::fs::remove_file(&part);
+            }
+            renamed
+        }
+        .await;
+        match res {
+            Ok(()) => item.finish(),
+            Err(err) if cancel.is_cancelled() => {
+                progress.fail(&err);
+                return Err(err);
+            }
+            Err(err) => {
+                item.fail(&err);
+                failed += 1;
+                progress.suspend(|| term::error(format!("{}:{}: {:#}", pod, src, err)));
+            }
+        }
+    }
+    if failed == 0 {
+        progress.finish();
+        if !global.quiet && several {
+            term::success(format!("{}: {}", src, progress.stats()));
+        } else if !global.quiet {
+            term::success(progress.stats());
+        }
+        return Ok(());
+    }
+    let err = anyhow::anyhow!("{} of {} files failed to download", failed, files.len());
+    progress.fail(&err);
+    Err(err)
+}
+
+/// Copies `src` in `pod` through the local `age`, which encrypts it for
+/// `recipients` into `dst`, and returns the digest of what was received,
+/// removing `dst` again if that fails
+async fn encrypt(
+    api: &Api<Pod>,
+    pod: &str,
+    src: &str,
+    dst: &Path,
+    recipients: &[String],
+    opts: &ExecOptions<'_>,
+    item: ItemProgress,
+) -> anyhow::Result<String> {
+    let res = async {
+        let mut age = tokio::process::Command::new("age")
+            .arg("--encrypt")
+            .args(recipients.iter().flat_map(|r| ["-r", r.as_str()]))
+            .arg("-o")
+            .arg(dst)
+            .stdin(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("failed to run age, is it installed?")?;
+        let mut stdin = age.stdin.take().unwrap();
+        let received = copy_from_pod_into(api, pod, src, &mut stdin, opts, item).await;
+        drop(stdin);
+        let status = age.wait().await.context("failed to run age")?;
+        // writing to an `age` that gave up fails too, it says why
+        if !status.success() {
+            bail!("age failed with {}", status);
+        }
+        Ok(received?)
+    }
+    .await;
+    if res.is_err() {
+        let _ = std::fs::remove_file(dst);
+    }
+    res
+}
+
+/// Whether `dst` is a directory to download into, one that exists or a path
+/// ending in a separator that is made if it doesn't
+fn into_dir(dst: &Path) -> bool {
+    dst.is_dir()
+        || dst
+            .to_string_lossy()
+            .ends_with(['/', std::path::MAIN_SEPARATOR])
+}
+
+/// Creates the directories leading to `path`
+fn create_parent(path: &Path) -> anyhow::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display())),
+        _ => Ok(()),
+    }
+}
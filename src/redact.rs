@@ -0,0 +1,218 @@
+//! Credentials scrubbed from logs and error messages.
+//!
+//! The tokens, passwords and client keys of a kubeconfig are registered as
+//! [`secret`]s when [`client::config`](crate::client::config) builds a
+//! client from it, and [`redact`] replaces them wherever they turn up.
+//! Those only known later, like the token an exec plugin prints, are caught
+//! by their shape: what follows `Bearer ` or `Basic `, and the values of
+//! keys like `"token"` in JSON, which an error quoting the output of a
+//! plugin has.
+
+use std::{borrow::Cow, io, sync::RwLock};
+
+/// What credentials are replaced with
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Secrets shorter than this aren't registered, replacing them would mangle
+/// unrelated text
+const MIN_SECRET: usize = 6;
+
+/// The keys whose values are credentials in a kubeconfig, an
+/// `ExecCredential` or an OAuth response
+const KEYS: [&str; 11] = [
+    "token",
+    "password",
+    "client-key-data",
+    "clientKeyData",
+    "access-token",
+    "access_token",
+    "id-token",
+    "id_token",
+    "refresh-token",
+    "refresh_token",
+    "client-secret",
+];
+
+/// How long what follows `Bearer ` or `Basic ` is at least to be replaced
+const MIN_CREDENTIAL: usize = 16;
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Registers `secret`, which [`redact`] replaces from then on
+pub fn secret(secret: impl Into<String>) {
+    let secret = secret.into();
+    if secret.len() < MIN_SECRET {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    if !secrets.contains(&secret) {
+        secrets.push(secret);
+        // the longest first, one may hold another
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    }
+}
+
+/// `s` with the registered secrets, bearer and basic credentials and the
+/// values of credential keys replaced with [`REDACTED`]
+pub fn redact(s: &str) -> Cow<'_, str> {
+    let mut out = Cow::Borrowed(s);
+    for secret in SECRETS.read().unwrap().iter() {
+        if out.contains(secret.as_str()) {
+            out = Cow::Owned(out.replace(secret.as_str(), REDACTED));
+        }
+    }
+    for scheme in ["Bearer ", "Basic "] {
+        if out.contains(scheme) {
+            out = Cow::Owned(after(&out, scheme));
+        }
+    }
+    for key in KEYS {
+        if out.contains(key) {
+            out = Cow::Owned(values(&out, key));
+        }
+    }
+    out
+}
+
+/// `s` with the word after every `prefix` replaced, if long enough to be a
+/// credential rather than a word like in `Basic authentication`
+fn after(s: &str, prefix: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find(prefix) {
+        let (before, from) = rest.split_at(i + prefix.len());
+        out.push_str(before);
+        let end = from
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ',' | '}'))
+            .unwrap_or(from.len());
+        if end >= MIN_CREDENTIAL && !from.starts_with(REDACTED) {
+            out.push_str(REDACTED);
+        } else {
+            out.push_str(&from[..end]);
+        }
+        rest = &from[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `s` with the string values of `"key": "value"` replaced, also when the
+/// JSON is quoted with backslashes in the Debug output of something
+fn values(s: &str, key: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find(key) {
+        let (before, from) = rest.split_at(i + key.len());
+        out.push_str(before);
+        rest = from;
+        // the key has to be a string of its own
+        if !before[..i].ends_with('"') {
+            continue;
+        }
+        let quote = match from.starts_with('\\') {
+            true => "\\\"",
+            false => "\"",
+        };
+        let Some(value) = from
+            .strip_prefix(quote)
+            .map(|s| s.trim_start_matches(' '))
+            .and_then(|s| s.strip_prefix(':'))
+            .map(|s| s.trim_start_matches(' '))
+            .and_then(|s| s.strip_prefix(quote))
+        else {
+            continue;
+        };
+        let Some(end) = value.find(quote) else {
+            continue;
+        };
+        out.push_str(&from[..from.len() - value.len()]);
+        out.push_str(REDACTED);
+        rest = &value[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `s` without its ANSI escape sequences, the colours of a log line
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        // CSI sequences end with a letter, others are a single character
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+    }
+    out
+}
+
+/// A line of a log with [`redact`] applied. Colours can split what is
+/// matched, a line with credentials between them loses its colours.
+fn redact_line(line: &[u8]) -> Vec<u8> {
+    let line = String::from_utf8_lossy(line);
+    if !line.contains('\x1b') {
+        return redact(&line).into_owned().into_bytes();
+    }
+    let plain = strip_ansi(&line);
+    match redact(&plain) {
+        Cow::Borrowed(_) => line.into_owned().into_bytes(),
+        Cow::Owned(redacted) => redacted.into_bytes(),
+    }
+}
+
+/// Writes what goes through it to `W` with [`redact`] applied a line at a
+/// time, for the writer of a logger. What a line is written in doesn't
+/// matter, a credential split across writes is replaced all the same. Bytes
+/// that aren't UTF-8 come out as U+FFFD. The last line is written without
+/// its newline when dropped.
+#[derive(Debug)]
+pub struct Redacting<W: io::Write> {
+    inner: W,
+    line: Vec<u8>,
+}
+
+impl<W: io::Write> Redacting<W> {
+    pub fn new(inner: W) -> Redacting<W> {
+        Redacting {
+            inner,
+            line: Vec::new(),
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for Redacting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.line.extend_from_slice(buf);
+        if let Some(end) = self.line.iter().rposition(|&b| b == b'\n') {
+            let rest = self.line.split_off(end + 1);
+            let lines = std::mem::replace(&mut self.line, rest);
+            for line in lines.split_inclusive(|&b| b == b'\n') {
+                self.inner.write_all(&redact_line(line))?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// Flushes the lines written, not one still going on
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: io::Write> Drop for Redacting<W> {
+    fn drop(&mut self) {
+        if !self.line.is_empty() {
+            let line = redact_line(&std::mem::take(&mut self.line));
+            let _ = self.inner.write_all(&line);
+            let _ = self.inner.flush();
+        }
+    }
+}
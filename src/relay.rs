@@ -0,0 +1,218 @@
+use std::{fmt::Write as _, time::Duration};
+
+use anyhow::{anyhow, Context as _};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use k8scp::{
+    exec::{self, quote},
+    files::FileEntry,
+    hash::Sha256,
+    remote,
+};
+
+use crate::overwrite;
+
+/// Port the pod a `--relay` copies to listens on if not given
+pub const PORT: u16 = 7790;
+
+/// How long relaying the files of a pod may take
+const TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// How long setting up and cleaning up the directory received into may take
+const CLEANUP: Duration = Duration::from_secs(30);
+
+/// How many files are moved into place with one command
+const MOVE_BATCH: usize = 64;
+
+/// How many times a second the sending pod tries to connect to the one
+/// receiving before it gives up, it may not listen yet
+const CONNECT_TRIES: u32 = 30;
+
+/// Copies `files`, which `from` has already, from `from` to `to` over the
+/// network of the cluster rather than through this machine. `to` listens on
+/// `port` with `nc` and untars what `from` sends it into a directory only
+/// its user can read, so both need `nc` and `tar`. Whatever connects has to
+/// send a token only this run knows first, and the files are moved into
+/// place only once their digests match those in `from`. The digests are
+/// returned, or the error of the file, in the order of `files`. The
+/// listener is killed and the directory removed however it ends.
+pub async fn relay(
+    api: &Api<Pod>,
+    from: &str,
+    to: &str,
+    container: &str,
+    files: &[FileEntry],
+    port: u16,
+    cancel: &CancellationToken,
+) -> anyhow::Result<Vec<anyhow::Result<String>>> {
+    let ip = api
+        .get(to)
+        .await?
+        .status
+        .and_then(|status| status.pod_ip)
+        .ok_or_else(|| anyhow!("{} has no IP to relay to yet", to))?;
+    // `mkdir` fails on one made by someone else, and `random` is only ever
+    // [0-9a-f], nothing to quote
+    let staging = format!("/tmp/.k8scp-relay-{}", random()?);
+    exec::run(
+        api,
+        to,
+        container,
+        &format!("mkdir -m 700 {0} && mkdir {0}/files", staging),
+        CLEANUP,
+        CLEANUP,
+        cancel,
+    )
+    .await
+    .with_context(|| format!("failed to make a directory to receive in {}", to))?;
+
+    let relayed = receive(api, from, to, container, files, &ip, port, &staging, cancel).await;
+
+    // nc may still be listening, for a sender that failed or a cancelled run
+    let cleanup = format!(
+        "kill $(cat {0}/pid 2>/dev/null) 2>/dev/null; rm -rf {0}",
+        staging
+    );
+    let cleaned = exec::run(
+        api,
+        to,
+        container,
+        &cleanup,
+        CLEANUP,
+        CLEANUP,
+        &CancellationToken::new(),
+    )
+    .await;
+    if let Err(err) = cleaned {
+        warn!("failed to clean up {} in {}: {}", staging, to, err);
+    }
+    relayed
+}
+
+/// Sends `files` from `from` into `staging` in `to` and moves those that
+/// arrived intact into place
+#[allow(clippy::too_many_arguments)]
+async fn receive(
+    api: &Api<Pod>,
+    from: &str,
+    to: &str,
+    container: &str,
+    files: &[FileEntry],
+    ip: &str,
+    port: u16,
+    staging: &str,
+    cancel: &CancellationToken,
+) -> anyhow::Result<Vec<anyhow::Result<String>>> {
+    // both ends read the token from stdin, on a command line any process
+    // in the pod could read it
+    let token = format!("{}\n", random()?);
+    for (_, paths) in overwrite::batches(files) {
+        // busybox and the traditional nc take the port with -p, the
+        // OpenBSD one without. Its pid is kept to kill it, and a stream not
+        // starting with the token is dropped unread.
+        let listen = |port: &str| {
+            format!(
+                "sh -c 'echo $$ > {}/pid; exec nc -l {} < /dev/null'",
+                staging, port
+            )
+        };
+        let receive = format!(
+            "IFS= read -r want && {{ {} 2>/dev/null || {}; }} | \
+             {{ IFS= read -r token && [ \"$token\" = \"$want\" ] && tar -xof - -C {}/files; }}",
+            listen(&format!("-p {}", port)),
+            listen(&port.to_string()),
+            staging
+        );
+        let send = format!(
+            "IFS= read -r token || exit 1; i=0; until {{ echo \"$token\"; tar -cf -{}; }} | nc -w 5 {} {}; do \
+             i=$((i+1)); [ $i -lt {} ] || exit 1; sleep 1; done",
+            paths, ip, port, CONNECT_TRIES
+        );
+        // nothing connects to a pod that listens once sending failed, and
+        // neither end prints anything while they copy
+        let listening = cancel.child_token();
+        let receiving = async {
+            let token = Some(token.as_bytes());
+            exec::run_with_input(
+                api, to, container, &receive, token, TIMEOUT, TIMEOUT, &listening,
+            )
+            .await
+            .with_context(|| format!("failed to receive from {}", from))
+        };
+        let sending = async {
+            let token = Some(token.as_bytes());
+            let sent =
+                exec::run_with_input(api, from, container, &send, token, TIMEOUT, TIMEOUT, cancel)
+                    .await
+                    .with_context(|| format!("failed to send to {}", to));
+            if sent.is_err() {
+                listening.cancel();
+            }
+            sent
+        };
+        let (received, sent) = tokio::join!(receiving, sending);
+        sent?;
+        received?;
+    }
+
+    // tar leaves out the leading `/`
+    let staged = |file: &FileEntry| format!("{}/files{}", staging, file.remote_path());
+    let paths: Vec<_> = files.iter().map(FileEntry::remote_path).collect();
+    let staged_paths: Vec<_> = files.iter().map(staged).collect();
+    let (expected, relayed) = tokio::try_join!(
+        remote::digests(api, from, container, &paths, &Sha256, TIMEOUT, cancel),
+        remote::digests(api, to, container, &staged_paths, &Sha256, TIMEOUT, cancel),
+    )?;
+    let mut checked: Vec<_> = files
+        .iter()
+        .zip(expected.into_iter().zip(relayed))
+        .map(|(file, digests)| match digests {
+            (Some(expected), Some(relayed)) if expected == relayed => Ok(relayed),
+            (None, _) => Err(anyhow!("{} isn't in {} to relay", file.remote_path(), from)),
+            (Some(_), None) => Err(anyhow!("{} didn't arrive", file.remote_path())),
+            (Some(_), Some(_)) => Err(anyhow!(
+                "{} differs from the one in {}",
+                file.remote_path(),
+                from
+            )),
+        })
+        .collect();
+
+    let intact: Vec<_> = (0..files.len()).filter(|&i| checked[i].is_ok()).collect();
+    for batch in intact.chunks(MOVE_BATCH) {
+        let command = batch
+            .iter()
+            .map(|&i| {
+                let file = &files[i];
+                format!(
+                    "mkdir -p {} && mv -f {} {}",
+                    quote(&file.remote_dir),
+                    quote(&staged(file)),
+                    quote(&file.remote_path())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" && ");
+        if let Err(err) = exec::run(api, to, container, &command, TIMEOUT, TIMEOUT, cancel).await {
+            let err = format!("{:#}", err);
+            for &i in batch {
+                checked[i] = Err(anyhow!("failed to move into place: {}", err));
+            }
+        }
+    }
+    Ok(checked)
+}
+
+/// 128 random bits from the operating system in hex, for names and tokens
+/// nobody else can guess
+fn random() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).context("failed to get random bytes")?;
+    Ok(bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    }))
+}
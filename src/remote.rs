@@ -0,0 +1,527 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    exec::{self, quote},
+    files::Walk,
+    hash::Hasher,
+    Error, Result,
+};
+
+/// Longest command sent at once, bigger sets of paths are split up
+const MAX_COMMAND: usize = 32 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Dir,
+    File,
+    /// Symlinks to nowhere, devices, sockets and the like
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub kind: Kind,
+    /// Size of a regular file
+    pub size: Option<u64>,
+    /// `st_mode`, type and permission bits, of the entry itself rather than
+    /// what a symlink points to
+    pub mode: Option<u32>,
+    pub modified: Option<SystemTime>,
+}
+
+/// Contents of a directory in a container
+#[derive(Debug)]
+pub struct Listing {
+    /// Absolute path of the listed directory
+    pub dir: String,
+    /// Directories first, then by name
+    pub entries: Vec<DirEntry>,
+}
+
+/// Lists `dir` in `pod`, a relative `dir` is resolved against the working
+/// directory of the container
+pub async fn list(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    dir: &str,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Listing> {
+    let command = format!(
+        "cd {} && pwd && for f in * .[!.]* ..?*; do {}; done",
+        quote(dir),
+        describe("\"$f\"", "continue")
+    );
+    let output = exec::run(api, pod, container, &command, timeout, timeout, cancel).await?;
+    let mut lines = output.stdout.lines();
+    let dir = lines.next().unwrap_or(dir).to_string();
+    let mut entries: Vec<_> = lines.filter_map(parse).collect();
+    entries.sort_by(|a, b| (a.kind != Kind::Dir, &a.name).cmp(&(b.kind != Kind::Dir, &b.name)));
+    Ok(Listing { dir, entries })
+}
+
+/// The `workingDir` the spec of `pod` gives `container`, the first
+/// container if it is empty as exec picks it, `None` if it gives none and the
+/// image decides
+pub async fn working_dir(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    timeout: Duration,
+) -> Result<Option<String>> {
+    let found = tokio::time::timeout(timeout, api.get(pod))
+        .await
+        .map_err(|_| Error::Timeout(format!("getting pod {} took over {:?}", pod, timeout)))??;
+    let containers = found.spec.map(|spec| spec.containers).unwrap_or_default();
+    let container = match container {
+        "" => containers.into_iter().next(),
+        name => containers.into_iter().find(|c| c.name == name),
+    };
+    Ok(container
+        .and_then(|c| c.working_dir)
+        .filter(|dir| !dir.is_empty()))
+}
+
+/// The relative path `path` below the directory `dir`, `path` itself if it
+/// is absolute
+pub fn resolve(dir: &str, path: &str) -> String {
+    if path.starts_with('/') {
+        return path.to_string();
+    }
+    let rel = path.trim_start_matches("./");
+    match rel {
+        "" | "." => dir.to_string(),
+        rel => join(dir, rel),
+    }
+}
+
+/// Whether `path` starts with `~` or has a `$` the shell of a container
+/// expands, see [`expand`]
+pub fn expands(path: &str) -> bool {
+    path.starts_with('~') || path.contains('$')
+}
+
+/// `path` with a leading `~` or `~USER` and the variables in it, `$NAME` or
+/// `${NAME}`, expanded by the shell of the container in `pod`. Nothing else
+/// is, a `$(…)` or `*` stays as it is.
+///
+/// Fails for a variable that isn't set in the container, rather than expand
+/// it to nothing.
+pub async fn expand(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    path: &str,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<String> {
+    if !expands(path) {
+        return Ok(path.to_string());
+    }
+    let command = format!("set -u; printf '%s\\n' {}", word(path));
+    let output = exec::run(api, pod, container, &command, timeout, timeout, cancel).await?;
+    let stdout = output.stdout;
+    Ok(stdout.strip_suffix('\n').unwrap_or(&stdout).to_string())
+}
+
+/// `path` as a shell word that expands its tilde prefix and variables only
+fn word(path: &str) -> String {
+    // the tilde prefix expands unquoted only
+    let (tilde, rest) = match path.find('/') {
+        Some(i) => path.split_at(i),
+        None => (path, ""),
+    };
+    let user = tilde.strip_prefix('~').filter(|user| {
+        user.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    });
+    // and only up to a slash that isn't quoted either
+    let (mut word, rest) = match (user, rest.strip_prefix('/')) {
+        (Some(_), None) => return tilde.to_string(),
+        (Some(_), Some(rest)) => (format!("{}/", tilde), rest),
+        (None, _) => (String::new(), path),
+    };
+    word.push('"');
+    for (i, c) in rest.char_indices() {
+        match c {
+            '$' if is_variable(&rest[i + 1..]) => word.push('$'),
+            '$' | '"' | '\\' | '`' => {
+                word.push('\\');
+                word.push(c);
+            }
+            _ => word.push(c),
+        }
+    }
+    word.push('"');
+    word
+}
+
+/// Whether what follows a `$` is `NAME` or `{NAME}`
+fn is_variable(s: &str) -> bool {
+    let name = s.strip_prefix('{').unwrap_or(s);
+    let starts = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    if !starts || !s.starts_with('{') {
+        return starts;
+    }
+    let len = name
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(name.len());
+    name[len..].starts_with('}')
+}
+
+/// What is at `path` in `pod`, `None` if nothing is, the name of the entry is
+/// `path`
+pub async fn stat(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    path: &str,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Option<DirEntry>> {
+    let command = describe(&quote(path), "exit 0");
+    let output = exec::run(api, pod, container, &command, timeout, timeout, cancel).await?;
+    Ok(output.stdout.lines().next().and_then(parse))
+}
+
+/// What is at each of `paths` in `pod`, in the same order, `None` where
+/// nothing is
+pub async fn stats(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    paths: &[String],
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Vec<Option<DirEntry>>> {
+    let mut entries = vec![None; paths.len()];
+    let mut offset = 0;
+    while offset < paths.len() {
+        // prints `<index> ` and what `describe` does for every path there is
+        let mut command = String::new();
+        let mut end = offset;
+        while end < paths.len() && (end == offset || command.len() < MAX_COMMAND) {
+            command.push_str(&format!(
+                "for f in {}; do printf '{} '; {}; done; ",
+                quote(&paths[end]),
+                end - offset,
+                describe("\"$f\"", "echo; continue")
+            ));
+            end += 1;
+        }
+        let output = exec::run(api, pod, container, &command, timeout, timeout, cancel).await?;
+        for line in output.stdout.lines() {
+            let Some((Ok(i), line)) = line
+                .split_once(' ')
+                .map(|(i, line)| (i.parse::<usize>(), line))
+            else {
+                continue;
+            };
+            if let Some(slot) = entries.get_mut(offset + i) {
+                *slot = parse(line);
+            }
+        }
+        offset = end;
+    }
+    Ok(entries)
+}
+
+/// Shell that prints `<kind> <mode> <size> <mtime> <name>` for `path`, a
+/// quoted word, or runs `missing` if nothing is there. `stat` isn't POSIX,
+/// the fields it gives are `-` where it is missing but the size of a regular
+/// file, which `wc -c` counts then.
+fn describe(path: &str, missing: &str) -> String {
+    format!(
+        "if [ -d {0} ]; then k=d; elif [ -f {0} ]; then k=f; \
+         elif [ -e {0} ] || [ -L {0} ]; then k=o; else {1}; fi; \
+         echo \"$k $(stat -c '%f %s %Y' -- {0} 2>/dev/null || \
+         echo - $([ $k = f ] && wc -c < {0} 2>/dev/null | tr -d ' ' || echo -) -) \"{0}",
+        path, missing
+    )
+}
+
+/// Parses a line printed by [`describe`]
+fn parse(line: &str) -> Option<DirEntry> {
+    let mut fields = line.splitn(5, ' ');
+    let kind = match fields.next()? {
+        "d" => Kind::Dir,
+        "f" => Kind::File,
+        _ => Kind::Other,
+    };
+    let mode = u32::from_str_radix(fields.next()?, 16).ok();
+    let size = fields.next()?.parse().ok().filter(|_| kind == Kind::File);
+    let modified = fields
+        .next()?
+        .parse()
+        .ok()
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+    let name = fields.next()?.to_string();
+    Some(DirEntry {
+        name,
+        kind,
+        size,
+        mode,
+        modified,
+    })
+}
+
+/// Space on the filesystem of a path in a container, from `df`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub filesystem: String,
+    /// In bytes, as are `used` and `available`
+    pub size: u64,
+    pub used: u64,
+    pub available: u64,
+    /// Where the filesystem is mounted
+    pub mount: String,
+}
+
+/// The usage of the filesystem `path` is on in `pod`, `None` if `df` printed
+/// something that isn't POSIX output
+pub async fn df(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    path: &str,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Option<Usage>> {
+    // fails like `df` does for paths that don't exist
+    let command = format!(
+        "out=$(df -Pk -- {}) && echo \"$out\" | tail -n 1",
+        quote(path)
+    );
+    let output = exec::run(api, pod, container, &command, timeout, timeout, cancel).await?;
+    // filesystem, 1024-blocks, used, available, capacity, mount
+    let mut fields = output.stdout.split_whitespace();
+    let filesystem = fields.next();
+    let mut sizes = fields.by_ref().take(3).map(|kb| kb.parse::<u64>().ok());
+    let (Some(filesystem), Some(Some(size)), Some(Some(used)), Some(Some(available))) =
+        (filesystem, sizes.next(), sizes.next(), sizes.next())
+    else {
+        return Ok(None);
+    };
+    let mount = fields.skip(1).collect::<Vec<_>>().join(" ");
+    Ok(Some(Usage {
+        filesystem: filesystem.to_string(),
+        size: size * 1024,
+        used: used * 1024,
+        available: available * 1024,
+        mount,
+    }))
+}
+
+/// Space used by a path in a container and what is below it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// As `du` printed it, starting with the path given
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// How much space `path` and the directories below it take up in `pod`, at
+/// most `depth` levels down, sorted by path.
+///
+/// The sizes are those of `du -k`, the disk space used. Without `du` in the
+/// container they are the sizes of the files below the directories, found
+/// with `find`; files that can't be read are left out either way.
+pub async fn du(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    path: &str,
+    depth: usize,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Vec<DiskUsage>> {
+    let path = match path.trim_end_matches('/') {
+        "" if path.starts_with('/') => "/",
+        trimmed => trimmed,
+    };
+    // the first line says which of the two it is
+    let command = format!(
+        "[ -e {0} ] || {{ echo {0}: No such file or directory >&2; exit 1; }}; \
+         if command -v du >/dev/null 2>&1; then echo du; du -k -- {0} 2>/dev/null; \
+         else echo find; find {0} -type f -exec wc -c {{}} + 2>/dev/null; fi; exit 0",
+        quote(path)
+    );
+    let output = exec::run(api, pod, container, &command, timeout, timeout, cancel).await?;
+    let mut lines = output.stdout.lines();
+    let from_du = lines.next() == Some("du");
+    let mut sizes = BTreeMap::new();
+    for line in lines {
+        // `du` separates the size with a tab, `wc` pads it with spaces
+        let split = if from_du {
+            line.split_once('\t')
+        } else {
+            line.trim_start().split_once(' ')
+        };
+        let Some((Ok(size), name)) = split.map(|(size, name)| (size.parse::<u64>(), name)) else {
+            continue;
+        };
+        let Some(rel) = relative(path, name) else {
+            // the total `wc` prints for several files
+            continue;
+        };
+        let components: Vec<_> = rel.split('/').filter(|c| !c.is_empty()).collect();
+        if from_du {
+            if components.len() <= depth {
+                sizes.insert(name.to_string(), size * 1024);
+            }
+            continue;
+        }
+        // a file counts towards every directory it is in
+        let parents = components.len().saturating_sub(1);
+        *sizes.entry(path.to_string()).or_insert(0) += size;
+        let mut dir = path.to_string();
+        for component in components.iter().take(parents.min(depth)) {
+            dir = join(&dir, component);
+            *sizes.entry(dir.clone()).or_insert(0) += size;
+        }
+    }
+    Ok(sizes
+        .into_iter()
+        .map(|(path, bytes)| DiskUsage { path, bytes })
+        .collect())
+}
+
+/// `name` relative to `dir`, if it is `dir` or below it
+fn relative<'a>(dir: &str, name: &'a str) -> Option<&'a str> {
+    let rel = name.strip_prefix(dir)?;
+    (rel.is_empty() || rel.starts_with('/') || dir == "/").then_some(rel)
+}
+
+fn join(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+/// Parent of the remote directory `dir`
+pub fn parent(dir: &str) -> String {
+    match dir.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+    }
+}
+
+/// The regular files below `dir` in `pod`, relative to it and sorted, none
+/// if `dir` doesn't exist
+pub async fn files(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    dir: &str,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Vec<String>> {
+    let command = format!(
+        "if [ -d {0} ]; then cd {0} && find . -type f; fi",
+        quote(dir)
+    );
+    let output = exec::run(api, pod, container, &command, timeout, timeout, cancel).await?;
+    Ok(found(&output.stdout))
+}
+
+/// The regular files below `dir` in `pod` that the `find` expression
+/// `expression` matches, like `-name '*.log' -mtime -1`, relative to `dir`
+/// and sorted, those `walk` goes to. The expression is passed to the shell as
+/// it is.
+///
+/// Fails if `dir` doesn't exist and whenever `find` does, e.g. for an
+/// invalid expression or a directory below `dir` it can't read.
+#[allow(clippy::too_many_arguments)]
+pub async fn find(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    dir: &str,
+    expression: &str,
+    walk: &Walk,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Vec<String>> {
+    // `find` stops descending there rather than list everything below
+    let mut options = String::new();
+    if let Some(depth) = walk.max_depth {
+        options += &format!(" -maxdepth {}", depth);
+    }
+    if walk.one_file_system {
+        options += " -xdev";
+    }
+    let command = format!(
+        "cd {} && find .{} -type f \\( {} \\) -print",
+        quote(dir),
+        options,
+        expression
+    );
+    let output = exec::run(api, pod, container, &command, timeout, timeout, cancel).await?;
+    let mut files = found(&output.stdout);
+    files.retain(|file| !walk.excludes(file));
+    Ok(files)
+}
+
+/// The paths `find .` printed without the `./`, sorted
+fn found(stdout: &str) -> Vec<String> {
+    let mut files: Vec<_> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("./"))
+        .map(str::to_string)
+        .collect();
+    files.sort();
+    files
+}
+
+/// The digests of `paths` in `pod` by `hasher`, in the same order, `None`
+/// for the paths that aren't regular files or couldn't be hashed
+pub async fn digests(
+    api: &Api<Pod>,
+    pod: &str,
+    container: &str,
+    paths: &[String],
+    hasher: &dyn Hasher,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<Vec<Option<String>>> {
+    let mut digests = vec![None; paths.len()];
+    let mut offset = 0;
+    while offset < paths.len() {
+        // prints `<index> <digest>` for every file that could be hashed
+        let mut command = String::new();
+        let mut end = offset;
+        while end < paths.len() && (end == offset || command.len() < MAX_COMMAND) {
+            command.push_str(&format!(
+                "if [ -f {0} ]; then echo \"{1} $({2} 2>/dev/null)\"; fi; ",
+                quote(&paths[end]),
+                end - offset,
+                hasher.remote_command(&paths[end])
+            ));
+            end += 1;
+        }
+        let output = exec::run(api, pod, container, &command, timeout, timeout, cancel).await?;
+        for line in output.stdout.lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(Ok(i)), Some(digest)) =
+                (fields.next().map(str::parse::<usize>), fields.next())
+            else {
+                continue;
+            };
+            if let Some(slot) = digests.get_mut(offset + i) {
+                *slot = Some(digest.to_string());
+            }
+        }
+        offset = end;
+    }
+    Ok(digests)
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt, fs,
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use console::style;
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+
+use k8scp::{files::FileEntry, redact::redact};
+
+/// Format of the `--report` file
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    /// CSV for `.csv` files, JSON otherwise
+    pub fn for_path(path: &Path) -> ReportFormat {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => ReportFormat::Csv,
+            _ => ReportFormat::Json,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Completed,
+    Failed,
+    /// Not attempted because an earlier file of the pod failed
+    Skipped,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Status::Completed => "completed",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+/// Outcome of one file/pod pair
+#[derive(Serialize, Deserialize, Debug)]
+struct Row {
+    pod: String,
+    src: String,
+    dst: String,
+    status: Status,
+    bytes: u64,
+    /// Seconds
+    elapsed: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    transfers: &'a [Row],
+}
+
+/// A report as `--retry-from` reads it
+#[derive(Deserialize)]
+struct Written {
+    transfers: Vec<Row>,
+}
+
+/// The destinations, by pod, of the transfers the JSON report at `path`
+/// lists as failed or skipped
+pub fn unfinished(path: &Path) -> anyhow::Result<BTreeMap<String, HashSet<String>>> {
+    let written: Written = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|err| anyhow::anyhow!("not a JSON report written by --report: {}", err))?;
+    let mut unfinished: BTreeMap<_, HashSet<_>> = BTreeMap::new();
+    for row in written.transfers {
+        if row.status != Status::Completed {
+            unfinished.entry(row.pod).or_default().insert(row.dst);
+        }
+    }
+    Ok(unfinished)
+}
+
+/// Number of transfers by status
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Tally {
+    pub completed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl Tally {
+    pub fn total(&self) -> usize {
+        self.completed + self.failed + self.skipped
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} transfers completed",
+            self.completed,
+            self.total()
+        )?;
+        if self.failed > 0 {
+            write!(f, ", {} failed", self.failed)?;
+        }
+        if self.skipped > 0 {
+            write!(f, ", {} skipped", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the outcome of every transfer of a run for `--report`
+pub struct Report {
+    rows: Mutex<Vec<Row>>,
+    /// Why the copy to a pod failed, by pod
+    failures: Mutex<BTreeMap<String, String>>,
+}
+
+impl Report {
+    pub fn new() -> Report {
+        Report {
+            rows: Mutex::default(),
+            failures: Mutex::default(),
+        }
+    }
+
+    /// Records a transfer of `file` to `pod`, `res` holds the checksum of a
+    /// completed transfer
+    pub fn record(
+        &self,
+        pod: &str,
+        file: &FileEntry,
+        bytes: u64,
+        elapsed: Duration,
+        res: &anyhow::Result<String>,
+    ) {
+        self.push(Row {
+            pod: pod.to_string(),
+            src: file.local.to_string_lossy().into_owned(),
+            dst: file.remote_path(),
+            status: match res {
+                Ok(_) => Status::Completed,
+                Err(_) => Status::Failed,
+            },
+            bytes,
+            elapsed: elapsed.as_secs_f64(),
+            sha256: res.as_ref().ok().cloned(),
+            error: res
+                .as_ref()
+                .err()
+                .map(|err| redact(&format!("{:#}", err)).into_owned()),
+        });
+    }
+
+    /// Records that `file` wasn't copied to `pod`
+    pub fn skipped(&self, pod: &str, file: &FileEntry) {
+        self.push(Row {
+            pod: pod.to_string(),
+            src: file.local.to_string_lossy().into_owned(),
+            dst: file.remote_path(),
+            status: Status::Skipped,
+            bytes: 0,
+            elapsed: 0.0,
+            sha256: None,
+            error: None,
+        });
+    }
+
+    /// Records why the copy to `pod` failed, which may be before any of its
+    /// files, like a `--pre-exec` command that failed
+    pub fn pod_failed(&self, pod: &str, err: &anyhow::Error) {
+        self.failures
+            .lock()
+            .unwrap()
+            .insert(pod.to_string(), format!("{:#}", err));
+    }
+
+    fn push(&self, row: Row) {
+        self.rows.lock().unwrap().push(row);
+    }
+
+    /// The pods transfers were recorded for, or that failed, in order
+    pub fn pods(&self) -> Vec<String> {
+        let mut pods: BTreeSet<_> = self
+            .rows
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|row| row.pod.clone())
+            .collect();
+        pods.extend(self.failures.lock().unwrap().keys().cloned());
+        pods.into_iter().collect()
+    }
+
+    /// The destinations and SHA-256 digests of the transfers to `pod` that
+    /// completed
+    pub fn completed(&self, pod: &str) -> Vec<(String, String)> {
+        let rows = self.rows.lock().unwrap();
+        rows.iter()
+            .filter(|row| row.pod == pod && row.status == Status::Completed)
+            .filter_map(|row| Some((row.dst.clone(), row.sha256.clone()?)))
+            .collect()
+    }
+
+    /// Counts the transfers recorded so far, of `pod` or of all pods
+    pub fn tally(&self, pod: Option<&str>) -> Tally {
+        let mut tally = Tally::default();
+        let rows = self.rows.lock().unwrap();
+        for row in rows
+            .iter()
+            .filter(|row| pod.is_none_or(|pod| row.pod == pod))
+        {
+            match row.status {
+                Status::Completed => tally.completed += 1,
+                Status::Failed => tally.failed += 1,
+                Status::Skipped => tally.skipped += 1,
+            }
+        }
+        tally
+    }
+
+    /// Prints a row per pod to stderr, its status, bytes, time and error,
+    /// and how many pods completed, failed or were skipped. Nothing is
+    /// printed for a copy to a single pod.
+    pub fn print_pods(&self) {
+        let mut pods: BTreeMap<&str, (Tally, u64, f64, Option<&str>)> = BTreeMap::new();
+        let rows = self.rows.lock().unwrap();
+        let failures = self.failures.lock().unwrap();
+        for row in rows.iter() {
+            let (tally, bytes, elapsed, error) = pods.entry(&row.pod).or_default();
+            match row.status {
+                Status::Completed => tally.completed += 1,
+                Status::Failed => tally.failed += 1,
+                Status::Skipped => tally.skipped += 1,
+            }
+            *bytes += row.bytes;
+            *elapsed += row.elapsed;
+            if error.is_none() {
+                *error = row.error.as_deref();
+            }
+        }
+        if pods.len() <= 1 {
+            return;
+        }
+
+        let width = pods.keys().map(|pod| pod.len()).max().unwrap_or(0).max(3);
+        eprintln!(
+            "{:<width$}  {:<9}  {:>10}  {:>8}  error",
+            "pod",
+            "status",
+            "bytes",
+            "time",
+            width = width
+        );
+        let mut counts = Tally::default();
+        for (pod, (tally, bytes, elapsed, error)) in &pods {
+            let error = failures.get(*pod).map(String::as_str).or(*error);
+            // the status is padded before it is colored, the escape codes
+            // would count towards the width
+            let status = if tally.failed > 0 || error.is_some() {
+                counts.failed += 1;
+                style(format!("{:<9}", Status::Failed.as_str())).red()
+            } else if tally.completed == 0 {
+                counts.skipped += 1;
+                style(format!("{:<9}", Status::Skipped.as_str())).yellow()
+            } else {
+                counts.completed += 1;
+                style(format!("{:<9}", Status::Completed.as_str())).green()
+            };
+            let row = format!(
+                "{:<width$}  {}  {:>10}  {:>8}  {}",
+                pod,
+                status,
+                HumanBytes(*bytes).to_string(),
+                format!("{:.2}s", elapsed),
+                error.and_then(|error| error.lines().next()).unwrap_or(""),
+                width = width
+            );
+            eprintln!("{}", row.trim_end());
+        }
+        let mut line = vec![style(format!("{} completed", counts.completed)).green()];
+        if counts.failed > 0 {
+            line.push(style(format!("{} failed", counts.failed)).red());
+        }
+        if counts.skipped > 0 {
+            line.push(style(format!("{} skipped", counts.skipped)).yellow());
+        }
+        let line: Vec<_> = line.iter().map(ToString::to_string).collect();
+        eprintln!("{} pods: {}", pods.len(), line.join(", "));
+    }
+
+    /// Writes the report of a run that ended with `res` to `path`
+    pub fn write(
+        &self,
+        path: &Path,
+        format: ReportFormat,
+        res: Result<(), &anyhow::Error>,
+    ) -> anyhow::Result<()> {
+        let rows = self.rows.lock().unwrap();
+        let out = match format {
+            ReportFormat::Json => {
+                let summary = Summary {
+                    status: match res {
+                        Ok(()) => Status::Completed,
+                        Err(_) => Status::Failed,
+                    },
+                    error: res
+                        .err()
+                        .map(|err| redact(&format!("{:#}", err)).into_owned()),
+                    transfers: &rows,
+                };
+                serde_json::to_string_pretty(&summary)? + "\n"
+            }
+            ReportFormat::Csv => csv(&rows),
+        };
+        fs::write(path, out)?;
+        Ok(())
+    }
+}
+
+fn csv(rows: &[Row]) -> String {
+    let mut out = String::from("pod,src,dst,status,bytes,elapsed,sha256,error\n");
+    for row in rows {
+        let fields = [
+            row.pod.as_str(),
+            &row.src,
+            &row.dst,
+            row.status.as_str(),
+            &row.bytes.to_string(),
+            &format!("{:.3}", row.elapsed),
+            row.sha256.as_deref().unwrap_or(""),
+            row.error.as_deref().unwrap_or(""),
+        ];
+        let fields: Vec<_> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Quotes `s` if it contains a separator, quote or line break
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
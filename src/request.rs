@@ -0,0 +1,600 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use futures::future;
+use kube::Client;
+use tokio::{io::AsyncWriteExt, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    copy::{self, ExecOptions, Metered},
+    endpoint::{Entry, LocalPath, Object, PodClient, Sink, Source, Stdin, Stdout},
+    hash::{self, Hasher},
+    progress::{self, EventStream, ItemProgress, Progress, ProgressObserver},
+    stats::Stats,
+    transport::{self, Exec, Transport},
+    Error, Result,
+};
+
+/// One end of a copy
+#[derive(Debug, Clone)]
+pub enum Location {
+    /// A local file, or a directory copied recursively
+    Local(PathBuf),
+    /// The same path in each of the pods
+    Pods { pods: Vec<String>, path: String },
+    /// The standard input as the file `name`, only a source
+    Stdin { name: String },
+    /// The standard output, only a destination
+    Stdout,
+    /// An object in S3 or GCS or a file at an HTTP(S) URL, see
+    /// [`Object`](crate::endpoint::Object)
+    Url(String),
+    /// Any other source
+    Source(Arc<dyn Source>),
+    /// Any other destination
+    Sink(Arc<dyn Sink>),
+}
+
+impl Location {
+    pub fn local(path: impl Into<PathBuf>) -> Location {
+        Location::Local(path.into())
+    }
+
+    pub fn pod(pod: impl Into<String>, path: impl Into<String>) -> Location {
+        Location::Pods {
+            pods: vec![pod.into()],
+            path: path.into(),
+        }
+    }
+
+    pub fn pods<I, S>(pods: I, path: impl Into<String>) -> Location
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Location::Pods {
+            pods: pods.into_iter().map(Into::into).collect(),
+            path: path.into(),
+        }
+    }
+
+    pub fn stdin(name: impl Into<String>) -> Location {
+        Location::Stdin { name: name.into() }
+    }
+
+    pub fn url(url: impl Into<String>) -> Location {
+        Location::Url(url.into())
+    }
+
+    pub fn source(source: impl Source + 'static) -> Location {
+        Location::Source(Arc::new(source))
+    }
+
+    pub fn sink(sink: impl Sink + 'static) -> Location {
+        Location::Sink(Arc::new(sink))
+    }
+}
+
+/// A copy from any [`Source`] to any number of [`Sink`]s, the library
+/// counterpart of a `k8scp-rust` run, see [`CopyRequest::builder`]
+pub struct CopyRequest {
+    source: Arc<dyn Source>,
+    sinks: Vec<Arc<dyn Sink>>,
+    hasher: Arc<dyn Hasher>,
+    verify: bool,
+    retries: u32,
+    retry_backoff: Duration,
+    cancel: CancellationToken,
+    progress: Progress,
+}
+
+/// Configures a [`CopyRequest`], the defaults are those of the command line
+pub struct CopyRequestBuilder {
+    client: Option<Client>,
+    namespace: String,
+    source: Option<Location>,
+    destination: Option<Location>,
+    container: String,
+    connect_timeout: Duration,
+    idle_timeout: Duration,
+    transport: Arc<dyn Transport>,
+    hasher: Arc<dyn Hasher>,
+    verify: bool,
+    retries: u32,
+    retry_backoff: Duration,
+    cancel: CancellationToken,
+    progress: Progress,
+    system_paths: Option<Vec<String>>,
+    allow_system_paths: bool,
+}
+
+impl CopyRequest {
+    /// Starts configuring a copy. Uploads copy a local file or directory into
+    /// a remote directory of one or more pods like `--src` and `--dst` do,
+    /// downloads copy a file of a single pod to a local path. Any other
+    /// combination of [`Location`]s works the same way, e.g. from one pod to
+    /// others or from stdin.
+    ///
+    /// ```no_run
+    /// use k8scp::{progress::JsonObserver, CopyRequest, Location};
+    /// use kube::Client;
+    ///
+    /// # async fn run() -> anyhow::Result<()> {
+    /// let transfer = CopyRequest::builder()
+    ///     .client(Client::try_default().await?)
+    ///     .namespace("shop")
+    ///     .source(Location::local("dist"))
+    ///     .destination(Location::pods(["web-0", "web-1"], "/srv"))
+    ///     .verify(true)
+    ///     .retries(2)
+    ///     .observe(JsonObserver::new())
+    ///     .build()?
+    ///     .start();
+    /// let summary = transfer.wait().await?;
+    /// println!("{} files, {} bytes", summary.files.len(), summary.stats.bytes());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> CopyRequestBuilder {
+        let exec = ExecOptions::default();
+        CopyRequestBuilder {
+            client: None,
+            namespace: "default".to_string(),
+            source: None,
+            destination: None,
+            container: String::new(),
+            connect_timeout: exec.connect_timeout,
+            idle_timeout: exec.idle_timeout,
+            transport: Arc::new(Exec(transport::Cat)),
+            hasher: Arc::new(hash::Sha256),
+            verify: false,
+            retries: 0,
+            retry_backoff: exec.retry_backoff,
+            cancel: CancellationToken::new(),
+            progress: Progress::new(),
+            system_paths: None,
+            allow_system_paths: exec.allow_system_paths,
+        }
+    }
+
+    /// Runs the copy in the background, must be called within a Tokio
+    /// runtime
+    pub fn start(self) -> Transfer {
+        let (observer, events) = progress::events();
+        let progress = Arc::new(self.progress.observe(observer));
+        let request = Run {
+            hasher: self.hasher,
+            verify: self.verify,
+            retries: self.retries,
+            retry_backoff: self.retry_backoff,
+            cancel: self.cancel.clone(),
+            progress: progress.clone(),
+        };
+        let (source, sinks) = (self.source, self.sinks);
+        let task = tokio::spawn(async move {
+            let res = request.copy(&*source, &sinks).await;
+            match &res {
+                Ok(_) => request.progress.finish(),
+                Err(err) => request.progress.fail(&anyhow::Error::new(err.clone())),
+            }
+            res.map(|files| Summary {
+                files,
+                stats: request.progress.stats(),
+            })
+        });
+        Transfer {
+            progress,
+            cancel: self.cancel,
+            events: Some(events),
+            task,
+        }
+    }
+}
+
+impl CopyRequestBuilder {
+    /// Client for the cluster, required if a location is in pods
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+
+    /// Where to copy from, required
+    pub fn source(mut self, source: Location) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Where to copy to, required. A remote destination is the directory
+    /// the source is copied into, a local one an existing directory or the
+    /// path of the copied file.
+    pub fn destination(mut self, destination: Location) -> Self {
+        self.destination = Some(destination);
+        self
+    }
+
+    /// Container of the pods, their default container if not given
+    pub fn container(mut self, container: impl Into<String>) -> Self {
+        self.container = container.into();
+        self
+    }
+
+    /// Timeout for establishing each exec stream
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// A transfer fails when its exec stream makes no progress for this long
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// How the files are copied, `exec-cat` if not given, see
+    /// [`transport::Registry`] for picking one by name
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// What the copied files are hashed and verified with, SHA-256 if not
+    /// given
+    pub fn hasher(mut self, hasher: Arc<dyn Hasher>) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Compares the digest of every copied file with the one computed in the
+    /// pods it is copied from or to, by `sha256sum` unless another
+    /// [`hasher`](CopyRequestBuilder::hasher) is given, which their
+    /// containers must have
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// The directories in the pods the copy refuses to write right into,
+    /// those of [`guard::system_paths`](crate::guard::system_paths) if not
+    /// given
+    pub fn system_paths(mut self, paths: Vec<String>) -> Self {
+        self.system_paths = Some(paths);
+        self
+    }
+
+    /// Lets the copy write into the system paths anyway
+    pub fn allow_system_paths(mut self, allow: bool) -> Self {
+        self.allow_system_paths = allow;
+        self
+    }
+
+    /// Copies a file again up to `retries` times when a copy of it fails
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Waits this long before the first retry and twice as long before
+    /// each one after, none by default
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Aborts the copy when `cancel` is cancelled, see [`Transfer::cancel`]
+    pub fn cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Adds an observer receiving the progress of the copy
+    pub fn observe(mut self, observer: impl ProgressObserver + 'static) -> Self {
+        self.progress = self.progress.observe(observer);
+        self
+    }
+
+    /// Minimum time between two position updates of a transfer passed on to
+    /// the observers
+    pub fn progress_interval(mut self, interval: Duration) -> Self {
+        self.progress = self.progress.interval(interval);
+        self
+    }
+
+    /// Checks that the request is complete and its locations fit together,
+    /// nothing is contacted yet
+    pub fn build(self) -> Result<CopyRequest> {
+        let invalid = |message: &str| Err(Error::Config(message.to_string()));
+        let (system_paths, allow_system_paths) = (self.system_paths, self.allow_system_paths);
+        let client = self.client.map(|client| {
+            let client = PodClient::new(client, self.namespace)
+                .container(self.container)
+                .connect_timeout(self.connect_timeout)
+                .idle_timeout(self.idle_timeout)
+                .transport(self.transport)
+                .cancel(self.cancel.clone())
+                .allow_system_paths(allow_system_paths);
+            match system_paths {
+                Some(paths) => client.system_paths(paths),
+                None => client,
+            }
+        });
+        let object = |url| Object::new(url)?.connect_timeout(self.connect_timeout);
+        let pods = || {
+            client
+                .as_ref()
+                .ok_or_else(|| Error::Config("no client given".to_string()))
+        };
+        let source: Arc<dyn Source> = match self.source {
+            None => return invalid("no source given"),
+            Some(Location::Local(src)) => Arc::new(LocalPath::new(src)),
+            Some(Location::Pods { pods: names, path }) => {
+                if names.len() != 1 {
+                    return Err(Error::Config(format!(
+                        "copies are from a single pod, {} given",
+                        names.len()
+                    )));
+                }
+                Arc::new(pods()?.path(&names[0], path))
+            }
+            Some(Location::Stdin { name }) => Arc::new(Stdin::new(name)),
+            Some(Location::Url(url)) => Arc::new(object(url)?),
+            Some(Location::Source(source)) => source,
+            Some(Location::Stdout | Location::Sink(_)) => {
+                return invalid("the source is only a destination")
+            }
+        };
+        let sinks: Vec<Arc<dyn Sink>> = match self.destination {
+            None => return invalid("no destination given"),
+            Some(Location::Local(dst)) => vec![Arc::new(LocalPath::new(dst))],
+            Some(Location::Pods { pods: names, path }) => {
+                if names.is_empty() {
+                    return invalid("no destination pod given");
+                }
+                let client = pods()?;
+                names
+                    .iter()
+                    .map(|pod| Arc::new(client.path(pod, &path)) as Arc<dyn Sink>)
+                    .collect()
+            }
+            Some(Location::Stdout) => vec![Arc::new(Stdout)],
+            Some(Location::Url(url)) => vec![Arc::new(object(url)?)],
+            Some(Location::Sink(sink)) => vec![sink],
+            Some(Location::Stdin { .. } | Location::Source(_)) => {
+                return invalid("the destination is only a source")
+            }
+        };
+        Ok(CopyRequest {
+            source,
+            sinks,
+            hasher: self.hasher,
+            verify: self.verify,
+            retries: self.retries,
+            retry_backoff: self.retry_backoff,
+            cancel: self.cancel,
+            progress: self.progress,
+        })
+    }
+}
+
+/// A running [`CopyRequest`]
+pub struct Transfer {
+    progress: Arc<Progress>,
+    cancel: CancellationToken,
+    events: Option<EventStream>,
+    task: JoinHandle<Result<Summary>>,
+}
+
+impl Transfer {
+    /// Bytes copied so far and the rates
+    pub fn stats(&self) -> Stats {
+        self.progress.stats()
+    }
+
+    /// The events of every file copied, from the start of the copy on. Only
+    /// the first call gets them.
+    pub fn events(&mut self) -> Option<EventStream> {
+        self.events.take()
+    }
+
+    /// Aborts the copy, files being written to pods are removed again and
+    /// [`Transfer::wait`] fails with [`Error::Cancelled`]
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Waits for the copy to finish. When copying to several pods it fails
+    /// with the first failure of any of them, the others are still copied
+    /// to, [`Transfer::events`] has every failure.
+    pub async fn wait(self) -> Result<Summary> {
+        match self.task.await {
+            Ok(res) => res,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+    }
+}
+
+/// What a finished [`Transfer`] copied
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub files: Vec<Copied>,
+    pub stats: Stats,
+}
+
+/// A copied file
+#[derive(Debug, Clone)]
+pub struct Copied {
+    /// The pod copied to, or from if the destination isn't one, empty if
+    /// neither end is a pod
+    pub pod: String,
+    pub src: String,
+    pub dst: String,
+    pub bytes: u64,
+    /// The digest of the file as hex, SHA-256 unless the request has another
+    /// [`hasher`](CopyRequestBuilder::hasher)
+    pub sha256: Option<String>,
+}
+
+/// The settings of a started request
+struct Run {
+    hasher: Arc<dyn Hasher>,
+    verify: bool,
+    retries: u32,
+    retry_backoff: Duration,
+    cancel: CancellationToken,
+    progress: Arc<Progress>,
+}
+
+/// Where a failure of a copy came from
+enum Side {
+    Source,
+    Sink,
+    Check,
+}
+
+impl Run {
+    async fn copy(&self, source: &dyn Source, sinks: &[Arc<dyn Sink>]) -> Result<Vec<Copied>> {
+        let entries = source.entries().await?;
+        let size: u64 = entries.iter().filter_map(|e| e.size).sum();
+        self.progress
+            .start(entries.len() * sinks.len(), size * sinks.len() as u64);
+
+        // sinks are written concurrently, the files of a sink one after another
+        let results = future::join_all(
+            sinks
+                .iter()
+                .map(|sink| self.copy_entries(source, &**sink, &entries)),
+        )
+        .await;
+        let mut copied = Vec::new();
+        let mut first_err = None;
+        for res in results {
+            match res {
+                Ok(files) => copied.extend(files),
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                }
+            }
+        }
+        match first_err {
+            None => Ok(copied),
+            Some(err) => Err(err),
+        }
+    }
+
+    /// Copies `entries` to `sink` one after another, stopping at the first
+    /// failure
+    async fn copy_entries(
+        &self,
+        source: &dyn Source,
+        sink: &dyn Sink,
+        entries: &[Entry],
+    ) -> Result<Vec<Copied>> {
+        let pod = sink.pod().or(source.pod()).unwrap_or_default();
+        let mut copied = Vec::new();
+        for entry in entries {
+            if self.cancel.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let src = source.describe(entry);
+            let dst = sink.describe(entry);
+            let item = self.progress.item(&src, pod, &dst, entry.size.unwrap_or(0));
+            let opts = ExecOptions {
+                retry_backoff: self.retry_backoff,
+                cancel: self.cancel.clone(),
+                ..ExecOptions::default()
+            };
+            let res = copy::attempts(&dst, self.retries, &opts, &item, || {
+                self.copy_entry(source, sink, entry, item.clone())
+            })
+            .await;
+            match res {
+                Ok(sha256) => {
+                    item.finish();
+                    copied.push(Copied {
+                        pod: pod.to_string(),
+                        src,
+                        dst,
+                        bytes: item.position(),
+                        sha256: Some(sha256),
+                    });
+                }
+                Err(err) => {
+                    item.fail(&anyhow::Error::new(err.clone()));
+                    return Err(err);
+                }
+            }
+        }
+        Ok(copied)
+    }
+
+    /// Pipes `entry` from `source` into `sink`, returning the digest of what
+    /// went through as hex
+    async fn copy_entry(
+        &self,
+        source: &dyn Source,
+        sink: &dyn Sink,
+        entry: &Entry,
+        item: ItemProgress,
+    ) -> Result<String> {
+        let (mut tx, rx) = tokio::io::duplex(64 * 1024);
+        let mut rx = Metered::new(rx, self.hasher.digest(), item.clone());
+        let read = async {
+            source.read(entry, &mut tx, item.clone()).await?;
+            tx.shutdown()
+                .await
+                .map_err(|err| Error::stream("finishing the copy", err))
+        };
+        let written = async { sink.write(entry, &mut rx, item.clone()).await };
+        // whichever end fails first is the cause, the other one only sees the
+        // pipe break
+        let res = future::try_join(
+            async { read.await.map_err(|err| (Side::Source, err)) },
+            async { written.await.map_err(|err| (Side::Sink, err)) },
+        )
+        .await;
+        let digest = rx.digest();
+        let res = match res {
+            Ok(_) if self.verify => check(source, sink, entry, &*self.hasher, &digest)
+                .await
+                .map_err(|err| (Side::Check, err)),
+            Ok(_) => Ok(()),
+            Err(err) => Err(err),
+        };
+        match res {
+            Ok(()) => {
+                if self.verify {
+                    item.verified(&digest);
+                }
+                Ok(digest)
+            }
+            // the sink cleans up after its own failures, what didn't check out
+            // is removed like what broke off
+            Err((Side::Sink, err)) if !matches!(err, Error::Cancelled) => Err(err),
+            Err((Side::Source | Side::Sink | Side::Check, err)) => {
+                sink.remove(entry).await;
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Fails unless both ends have `entry` with the digest `digest`, as far as
+/// they can tell
+async fn check(
+    source: &dyn Source,
+    sink: &dyn Sink,
+    entry: &Entry,
+    hasher: &dyn Hasher,
+    digest: &str,
+) -> Result<()> {
+    source.check(entry, hasher, digest).await?;
+    sink.check(entry, hasher, digest).await
+}
@@ -0,0 +1,113 @@
+use std::process::ExitCode;
+
+use anyhow::bail;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    exec::{self, quote},
+    guard,
+    remote::{self, Kind},
+};
+
+use crate::{
+    target::{self, Target},
+    term, Global,
+};
+
+/// Remove files and directories from pods
+#[derive(clap::Args, Debug)]
+pub struct RmArgs {
+    /// What to remove, `POD:PATH`
+    #[arg(required = true, value_parser = target::parse)]
+    target: Vec<Target>,
+
+    /// Remove directories and what they contain
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Show what would be removed without removing it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+pub async fn run(global: &Global, args: &RmArgs) -> anyhow::Result<ExitCode> {
+    let api = global.session().await?.pods(global.namespace());
+    let targets = target::resolve(global, &api, &args.target).await?;
+    // the dangerous ones stop everything before anything is removed, as
+    // given and as resolved
+    for (given, target) in args.target.iter().zip(&targets) {
+        if let Some(why) = guard::dangerous(&given.path).or_else(|| guard::dangerous(&target.path))
+        {
+            bail!("refusing to remove {}, {}", given, why);
+        }
+    }
+    Ok(target::each(global, &api, &targets, |target| {
+        remove(global, &api, args, target)
+    })
+    .await)
+}
+
+async fn remove(
+    global: &Global,
+    api: &Api<Pod>,
+    args: &RmArgs,
+    target: &Target,
+) -> anyhow::Result<()> {
+    let cancel = CancellationToken::new();
+    let Some(entry) = remote::stat(
+        api,
+        &target.pod,
+        &global.container,
+        &target.path,
+        global.connect_timeout,
+        &cancel,
+    )
+    .await?
+    else {
+        bail!("no such file or directory");
+    };
+    // a symlink is removed rather than what it points to
+    let dir = entry.kind == Kind::Dir && entry.mode.is_none_or(|m| m & 0o170000 != 0o120000);
+    if dir && !args.recursive {
+        bail!("is a directory, pass -r to remove it");
+    }
+    if args.dry_run {
+        if dir {
+            let files = remote::files(
+                api,
+                &target.pod,
+                &global.container,
+                &target.path,
+                global.connect_timeout,
+                &cancel,
+            )
+            .await?;
+            println!(
+                "would remove {}:{}/ and the {} files in it",
+                target.pod,
+                target.path.trim_end_matches('/'),
+                files.len()
+            );
+        } else {
+            println!("would remove {}", target);
+        }
+        return Ok(());
+    }
+    let flags = if dir { "-rf" } else { "-f" };
+    exec::run(
+        api,
+        &target.pod,
+        &global.container,
+        &format!("rm {} -- {}", flags, quote(&target.path)),
+        global.connect_timeout,
+        global.idle_timeout,
+        &cancel,
+    )
+    .await?;
+    if !global.quiet {
+        term::success(format!("removed {}", target));
+    }
+    Ok(())
+}
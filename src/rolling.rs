@@ -0,0 +1,80 @@
+use std::{fmt, time::Duration};
+
+use anyhow::bail;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio::time::{self, Instant};
+use tokio_util::sync::CancellationToken;
+
+use k8scp::Error;
+
+use crate::controller;
+
+/// How often a pod is looked at while waiting for it to be Ready
+const READY_POLL: Duration = Duration::from_secs(2);
+
+/// How many pods a wave of `--rolling` copies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wave {
+    Pods(usize),
+    /// This percentage of the pods, at least one
+    Percent(u8),
+}
+
+impl Wave {
+    /// Parses `N` or `N%`
+    pub fn parse(s: &str) -> Result<Wave, String> {
+        let invalid = || format!("{} isn't a number of pods or a percentage like 10%", s);
+        let wave = match s.strip_suffix('%') {
+            Some(percent) => match percent.parse() {
+                Ok(percent @ 1..=100) => Wave::Percent(percent),
+                _ => return Err(invalid()),
+            },
+            None => match s.parse() {
+                Ok(pods @ 1..) => Wave::Pods(pods),
+                _ => return Err(invalid()),
+            },
+        };
+        Ok(wave)
+    }
+
+    /// The number of the `pods` a wave copies to
+    pub fn size(&self, pods: usize) -> usize {
+        match *self {
+            Wave::Pods(n) => n,
+            Wave::Percent(percent) => (pods * percent as usize).div_ceil(100),
+        }
+        .max(1)
+    }
+}
+
+impl fmt::Display for Wave {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Wave::Pods(n) => write!(f, "{}", n),
+            Wave::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// Waits until `pod` is Ready, failing if it isn't within `timeout`
+pub async fn wait_ready(
+    api: &Api<Pod>,
+    pod: &str,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if controller::is_ready(&api.get(pod).await?) {
+            return Ok(());
+        }
+        if Instant::now() + READY_POLL > deadline {
+            bail!("{} wasn't Ready within {:?} of the copy", pod, timeout);
+        }
+        tokio::select! {
+            () = time::sleep(READY_POLL) => {}
+            () = cancel.cancelled() => return Err(Error::Cancelled.into()),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{Datelike, Local, NaiveDateTime, TimeZone, Timelike};
+
+/// When a repeated command runs, every so often or at the times of a cron
+/// expression
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// Parses a duration like `5m` or a cron expression like `*/15 * * * *`
+    pub fn parse(s: &str) -> Result<Schedule, String> {
+        if s.split_whitespace().count() == 5 {
+            return Cron::parse(s).map(Schedule::Cron);
+        }
+        match humantime::parse_duration(s) {
+            Ok(interval) if !interval.is_zero() => Ok(Schedule::Every(interval)),
+            Ok(_) => Err("the interval must be longer than 0".to_string()),
+            Err(_) => Err(
+                "expected a duration like `5m` or a cron expression like `*/15 * * * *`"
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// When to run next, a run that started at `last` and ended by now. An
+    /// interval runs right away the first time, and again that long after
+    /// the last run started, or as soon as it ended if it took longer.
+    pub fn next(&self, last: Option<SystemTime>) -> SystemTime {
+        let now = SystemTime::now();
+        match self {
+            Schedule::Every(interval) => last.map_or(now, |last| (last + *interval).max(now)),
+            Schedule::Cron(cron) => cron.next(now),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Every(interval) => {
+                write!(f, "every {}", humantime::format_duration(*interval))
+            }
+            Schedule::Cron(cron) => write!(f, "at `{}`", cron.expression),
+        }
+    }
+}
+
+/// A cron expression of 5 fields, minute, hour, day of the month, month and
+/// day of the week, matched in local time. Fields are lists of numbers,
+/// ranges `a-b`, `*` and steps `*/n` or `a-b/n`; Sunday is 0 or 7.
+#[derive(Debug, Clone)]
+pub struct Cron {
+    expression: String,
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    /// Whether the day of the month, or of the week, isn't `*`. A day
+    /// matches either when both are restricted, like cron does.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    pub fn parse(s: &str) -> Result<Cron, String> {
+        let fields: Vec<_> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "expected 5 fields in the cron expression, got {}",
+                fields.len()
+            ));
+        };
+        let mut weekdays = field(weekday, 0, 7, "day of the week")?;
+        // 7 is Sunday too
+        weekdays[0] |= weekdays[7];
+        weekdays.truncate(7);
+        Ok(Cron {
+            expression: s.split_whitespace().collect::<Vec<_>>().join(" "),
+            minutes: field(minute, 0, 59, "minute")?,
+            hours: field(hour, 0, 23, "hour")?,
+            days: field(day, 1, 31, "day of the month")?,
+            months: field(month, 1, 12, "month")?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    /// The first minute after `after` the expression matches
+    pub fn next(&self, after: SystemTime) -> SystemTime {
+        let after = chrono::DateTime::<Local>::from(after).naive_local();
+        let mut time = after
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))
+            .unwrap_or(after);
+        // 4 years cover the 29th of February
+        for _ in 0..4 * 366 * 24 * 60 {
+            time += chrono::Duration::minutes(1);
+            if !self.matches(&time) {
+                continue;
+            }
+            // local times skipped by a DST change don't happen
+            if let Some(time) = Local.from_local_datetime(&time).earliest() {
+                return time.into();
+            }
+        }
+        // an expression like `0 0 31 2 *` never matches
+        SystemTime::now() + Duration::from_secs(u32::MAX as u64)
+    }
+
+    fn matches(&self, time: &NaiveDateTime) -> bool {
+        let day = self.days[time.day() as usize - 1];
+        let weekday = self.weekdays[time.weekday().num_days_from_sunday() as usize];
+        let day = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        };
+        self.minutes[time.minute() as usize]
+            && self.hours[time.hour() as usize]
+            && self.months[time.month0() as usize]
+            && day
+    }
+}
+
+/// Parses the cron field `s` of values `min..=max`, which of them match by
+/// their offset from `min`
+fn field(s: &str, min: u32, max: u32, name: &str) -> Result<Vec<bool>, String> {
+    let invalid = || {
+        format!(
+            "invalid {} `{}` in the cron expression, expected {}-{}",
+            name, s, min, max
+        )
+    };
+    let number = |s: &str| match s.parse::<u32>() {
+        Ok(n) if (min..=max).contains(&n) => Ok(n),
+        _ => Err(invalid()),
+    };
+    let mut matches = vec![false; (max - min + 1) as usize];
+    for part in s.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(invalid()),
+            },
+            None => (part, 1),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (number(first)?, number(last)?),
+            // `5/10` is 5 and every 10 after it
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        for n in (first..=last).step_by(step as usize) {
+            matches[(n - min) as usize] = true;
+        }
+    }
+    Ok(matches)
+}
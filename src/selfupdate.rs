@@ -0,0 +1,220 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context as _};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use k8scp::endpoint::Object;
+
+use crate::{term, Global};
+
+/// The version of this binary
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The release listing the digests of its binaries, in the format of
+/// `sha256sum`
+const CHECKSUMS: &str = "SHA256SUMS";
+
+/// Replace this binary with the latest release from GitHub
+#[derive(clap::Args, Debug)]
+pub struct SelfUpdateArgs {
+    /// Only show whether a newer release exists
+    #[arg(long)]
+    check: bool,
+
+    /// Install this release, like `v0.2.0`, even if it isn't newer
+    #[arg(long, value_name = "TAG")]
+    tag: Option<String>,
+
+    /// The GitHub API of the releases, to update from a fork or a mirror
+    #[arg(
+        long,
+        env = "K8SCP_RELEASES_URL",
+        value_name = "URL",
+        default_value = "https://api.github.com/repos/RTsien/k8scp-rust/releases"
+    )]
+    releases: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+pub async fn run(args: &SelfUpdateArgs, global: &Global) -> anyhow::Result<()> {
+    let releases = args.releases.trim_end_matches('/');
+    let url = match &args.tag {
+        Some(tag) => format!("{}/tags/{}", releases, tag),
+        None => format!("{}/latest", releases),
+    };
+    let release: Release = serde_json::from_slice(&get(global, &url).await?)
+        .with_context(|| format!("unexpected answer from {}", url))?;
+
+    let newer = newer(&release.tag_name, VERSION);
+    if args.check {
+        if newer {
+            println!("{} is available, this is {}", release.tag_name, VERSION);
+        } else {
+            println!("{} is the latest release", VERSION);
+        }
+        return Ok(());
+    }
+    if !newer && args.tag.is_none() {
+        if !global.quiet {
+            term::success(format!("{} is the latest release", VERSION));
+        }
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .with_context(|| {
+            format!(
+                "release {} has no binary for this platform, {}",
+                release.tag_name, name
+            )
+        })?;
+    // a binary that can't be verified isn't installed
+    let checksums = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == CHECKSUMS)
+        .with_context(|| {
+            format!(
+                "release {} has no {} to verify {} with",
+                release.tag_name, CHECKSUMS, name
+            )
+        })?;
+    let checksums = String::from_utf8(get(global, &checksums.browser_download_url).await?)
+        .with_context(|| format!("{} of release {}", CHECKSUMS, release.tag_name))?;
+    let Some(expected) = checksum(&checksums, &name) else {
+        bail!(
+            "{} of release {} lists no digest for {}",
+            CHECKSUMS,
+            release.tag_name,
+            name
+        );
+    };
+
+    let binary = get(global, &asset.browser_download_url).await?;
+    let digest = format!("{:x}", Sha256::digest(&binary));
+    if !digest.eq_ignore_ascii_case(&expected) {
+        bail!(
+            "{} of release {} has the digest {}, {} says {}, not installing it",
+            name,
+            release.tag_name,
+            digest,
+            CHECKSUMS,
+            expected
+        );
+    }
+
+    let exe = std::env::current_exe()
+        .and_then(fs::canonicalize)
+        .context("failed to find this binary")?;
+    replace(&exe, &binary)?;
+    if !global.quiet {
+        term::success(format!(
+            "updated {} from {} to {}",
+            exe.display(),
+            VERSION,
+            release.tag_name
+        ));
+    }
+    Ok(())
+}
+
+/// The body of a `GET` of `url`
+async fn get(global: &Global, url: &str) -> anyhow::Result<Vec<u8>> {
+    let object = Object::new(url)?.connect_timeout(global.connect_timeout)?;
+    let mut body = Vec::new();
+    object.download(&mut body).await?;
+    Ok(body)
+}
+
+/// The name of the binary of this platform in a release, like
+/// `k8scp-rust-x86_64-linux` or `k8scp-rust-aarch64-macos`
+fn asset_name() -> String {
+    format!(
+        "k8scp-rust-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+/// The digest `checksums` lists for `name`, lines of a digest and a file
+/// name with a `*` in front if it was hashed in binary mode
+fn checksum(checksums: &str, name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (digest, file) = line.trim().split_once(char::is_whitespace)?;
+        let file = file.trim_start();
+        (file.strip_prefix('*').unwrap_or(file) == name).then(|| digest.to_string())
+    })
+}
+
+/// Whether the release `tag` is newer than `version`, one that isn't a
+/// version number is taken to be newer unless it is the same
+fn newer(tag: &str, version: &str) -> bool {
+    let number = |s: &str| -> Option<Vec<u64>> {
+        let s = s.strip_prefix('v').unwrap_or(s);
+        let s = s.split(['-', '+']).next()?;
+        s.split('.').map(|part| part.parse().ok()).collect()
+    };
+    match (number(tag), number(version)) {
+        (Some(tag), Some(version)) => tag > version,
+        _ => tag.strip_prefix('v').unwrap_or(tag) != version,
+    }
+}
+
+/// Replaces the binary at `exe` with `binary`, through a file next to it
+/// that is renamed over it, so it is never half written
+fn replace(exe: &Path, binary: &[u8]) -> anyhow::Result<()> {
+    let part = sibling(exe, ".k8scp-part");
+    let written = fs::write(&part, binary).and_then(|()| {
+        let permissions = fs::metadata(exe)?.permissions();
+        fs::set_permissions(&part, permissions)
+    });
+    if let Err(err) = written {
+        let _ = fs::remove_file(&part);
+        return Err(err).with_context(|| {
+            format!(
+                "failed to write {}, is its directory writable?",
+                part.display()
+            )
+        });
+    }
+    // Windows doesn't replace a running binary but lets it be renamed
+    #[cfg(windows)]
+    {
+        let old = sibling(exe, ".old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old).with_context(|| format!("failed to move {}", exe.display()))?;
+    }
+    if let Err(err) = fs::rename(&part, exe) {
+        let _ = fs::remove_file(&part);
+        return Err(err).with_context(|| format!("failed to replace {}", exe.display()));
+    }
+    Ok(())
+}
+
+/// `path` with `suffix` appended to its file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
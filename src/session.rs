@@ -0,0 +1,82 @@
+use std::fmt;
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::{api::Api, config::Kubeconfig, Client};
+use tracing::*;
+
+use k8scp::client;
+
+use crate::{history::Origin, Global};
+
+/// The connection to the cluster, made once per run through
+/// [`Global::session`] and shared by everything the run does, so the
+/// kubeconfig is read, credentials are fetched and connections to the API
+/// server are opened once instead of per copy
+#[derive(Clone)]
+pub struct Session {
+    pub client: Client,
+    /// The context in use
+    pub context: Option<String>,
+    /// The cluster of `context`
+    pub cluster: Option<String>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("context", &self.context)
+            .field("cluster", &self.cluster)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Session {
+    pub async fn connect(global: &Global) -> anyhow::Result<Session> {
+        let path = global.kubeconfig()?;
+        let kubeconfig = global.load_kubeconfig()?;
+        let (context, cluster) = context(&kubeconfig, global.context.as_deref());
+        let config = client::config(
+            kubeconfig,
+            global.context.as_deref(),
+            global.connect_timeout,
+        )
+        .await?;
+        let client = Client::try_from(config)?;
+        debug!(kubeconfig = %path, ?context, ?cluster, "connected");
+        Ok(Session {
+            client,
+            context,
+            cluster,
+        })
+    }
+
+    /// The pods of `namespace`
+    pub fn pods(&self, namespace: &str) -> Api<Pod> {
+        Api::namespaced(self.client.clone(), namespace)
+    }
+
+    /// Where copies to `namespace` and `container` go, recorded in the
+    /// history
+    pub fn origin(&self, namespace: &str, container: &str) -> Origin {
+        Origin {
+            context: self.context.clone(),
+            cluster: self.cluster.clone(),
+            namespace: namespace.to_string(),
+            container: Some(container.to_string()).filter(|c| !c.is_empty()),
+        }
+    }
+}
+
+/// The context of `kubeconfig` in use, `context` or the current one, and
+/// its cluster
+fn context(kubeconfig: &Kubeconfig, context: Option<&str>) -> (Option<String>, Option<String>) {
+    let context = context
+        .map(str::to_string)
+        .or_else(|| kubeconfig.current_context.clone());
+    let cluster = kubeconfig
+        .contexts
+        .iter()
+        .find(|c| Some(&c.name) == context.as_ref())
+        .map(|c| c.context.cluster.clone());
+    (context, cluster)
+}
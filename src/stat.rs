@@ -0,0 +1,80 @@
+use std::process::ExitCode;
+
+use anyhow::bail;
+use clap::ValueEnum;
+use tokio_util::sync::CancellationToken;
+
+use k8scp::remote;
+
+use crate::{
+    ls,
+    target::{self, Target},
+    Global,
+};
+
+/// Show the kind, size, mode and modification time of paths in pods
+#[derive(clap::Args, Debug)]
+pub struct StatArgs {
+    /// Path to show, `POD:PATH`
+    #[arg(required = true, value_parser = target::parse)]
+    target: Vec<Target>,
+
+    #[arg(long, value_enum, default_value_t = StatFormat::Text)]
+    format: StatFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum StatFormat {
+    /// A block of lines per path
+    Text,
+    /// A JSON object per line, with the fields of `ls --format json` and `pod`
+    Json,
+}
+
+pub async fn run(global: &Global, args: &StatArgs) -> anyhow::Result<ExitCode> {
+    let api = global.session().await?.pods(global.namespace());
+    let targets = target::resolve(global, &api, &args.target).await?;
+    Ok(target::each(global, &api, &targets, |target| {
+        let api = &api;
+        async move {
+            let Some(entry) = remote::stat(
+                api,
+                &target.pod,
+                &global.container,
+                &target.path,
+                global.connect_timeout,
+                &CancellationToken::new(),
+            )
+            .await?
+            else {
+                bail!("no such file or directory");
+            };
+            match args.format {
+                StatFormat::Text => {
+                    println!("    Path: {}", target);
+                    println!("    Kind: {}", ls::kind(&entry));
+                    if let Some(size) = entry.size {
+                        println!("    Size: {}", size);
+                    }
+                    match entry.mode {
+                        Some(mode) => {
+                            println!("    Mode: {} ({:04o})", ls::mode(&entry), mode & 0o7777)
+                        }
+                        None => println!("    Mode: unknown"),
+                    }
+                    println!(
+                        "Modified: {}",
+                        ls::modified(&entry).unwrap_or_else(|| "unknown".to_string())
+                    );
+                }
+                StatFormat::Json => {
+                    let mut json = ls::to_json(&entry);
+                    json["pod"] = target.pod.as_str().into();
+                    println!("{}", json);
+                }
+            }
+            Ok(())
+        }
+    })
+    .await)
+}
@@ -0,0 +1,163 @@
+#[cfg(feature = "cli")]
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "cli")]
+use console::style;
+#[cfg(feature = "cli")]
+use indicatif::HumanBytes;
+use serde_json::{json, Value};
+
+/// Span over which throughput samples for the peak rate are taken
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Throughput statistics of a run
+#[derive(Clone, Debug)]
+pub struct Stats {
+    start: Instant,
+    end: Option<Instant>,
+    bytes: u64,
+    peak: f64,
+    window_start: Instant,
+    window_bytes: u64,
+    retries: u64,
+    verified: u64,
+    mismatched: u64,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        let now = Instant::now();
+        Stats {
+            start: now,
+            end: None,
+            bytes: 0,
+            peak: 0.0,
+            window_start: now,
+            window_bytes: 0,
+            retries: 0,
+            verified: 0,
+            mismatched: 0,
+        }
+    }
+
+    /// Another `bytes` have been transferred
+    pub fn add(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        let bytes = self.bytes;
+        let now = Instant::now();
+        let span = now - self.window_start;
+        if span >= WINDOW {
+            let rate = (bytes - self.window_bytes) as f64 / span.as_secs_f64();
+            self.peak = self.peak.max(rate);
+            self.window_start = now;
+            self.window_bytes = bytes;
+        }
+    }
+
+    /// A failed copy is tried again
+    pub fn retry(&mut self) {
+        self.retries += 1;
+    }
+
+    /// A copy was checked against the other end, `ok` if the digests matched
+    pub fn verify(&mut self, ok: bool) {
+        if ok {
+            self.verified += 1;
+        } else {
+            self.mismatched += 1;
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.end.get_or_insert_with(Instant::now);
+    }
+
+    /// Bytes transferred so far
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Copies tried again after they failed
+    pub fn retries(&self) -> u64 {
+        self.retries
+    }
+
+    /// Copies whose digests matched on both ends
+    pub fn verified(&self) -> u64 {
+        self.verified
+    }
+
+    /// Copies whose digests didn't
+    pub fn mismatched(&self) -> u64 {
+        self.mismatched
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.end.unwrap_or_else(Instant::now) - self.start
+    }
+
+    /// Average bytes per second
+    pub fn average_rate(&self) -> f64 {
+        let secs = self.elapsed().as_secs_f64();
+        if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Highest bytes per second seen over a one second window, transfers shorter
+    /// than that only have their average
+    pub fn peak_rate(&self) -> f64 {
+        self.peak.max(self.average_rate())
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "bytes": self.bytes,
+            "elapsed": self.elapsed().as_secs_f64(),
+            "average_rate": self.average_rate() as u64,
+            "peak_rate": self.peak_rate() as u64,
+            "retries": self.retries,
+            "verified": self.verified,
+            "mismatched": self.mismatched,
+        })
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats::new()
+    }
+}
+
+#[cfg(feature = "cli")]
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "transferred {} in {}, average {}, peak {}, {} {}, {} verified",
+            style(HumanBytes(self.bytes)).bold(),
+            style(format!("{:.2}s", self.elapsed().as_secs_f64())).bold(),
+            style(format!("{}/s", HumanBytes(self.average_rate() as u64))).cyan(),
+            style(format!("{}/s", HumanBytes(self.peak_rate() as u64))).cyan(),
+            self.retries,
+            if self.retries == 1 {
+                "retry"
+            } else {
+                "retries"
+            },
+            self.verified,
+        )?;
+        if self.mismatched > 0 {
+            write!(
+                f,
+                ", {} {}",
+                style(self.mismatched).red(),
+                style("mismatched").red()
+            )?;
+        }
+        Ok(())
+    }
+}
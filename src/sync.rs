@@ -0,0 +1,271 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    process::ExitCode,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{bail, Context as _};
+use futures::future;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::{sync::mpsc, time};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use k8scp::{diagnose, files::FileEntry, hash, remote};
+
+use crate::{
+    cp::{self, CpArgs, Selection},
+    metrics::METRICS,
+    print_error,
+    schedule::Schedule,
+    twoway::{self, Conflicts},
+    Global,
+};
+
+/// Copy only the files that differ from what is in the pods
+#[derive(clap::Args, Debug)]
+pub struct SyncArgs {
+    #[command(flatten)]
+    pub copy: CpArgs,
+
+    /// Keep running and copy the files that change locally as they do, until Ctrl-C
+    #[arg(long, conflicts_with = "manifest")]
+    pub watch: bool,
+
+    /// How long changes settle before they are copied, changes within that time are copied together
+    #[arg(long, value_parser = humantime::parse_duration, default_value = "300ms", requires = "watch")]
+    pub debounce: Duration,
+
+    /// Keep running and sync again on this schedule until Ctrl-C, an interval like `5m` from the start of one sync to the next or a cron expression like `*/15 * * * *` in local time
+    #[arg(long, value_name = "SCHEDULE", value_parser = Schedule::parse, conflicts_with = "watch")]
+    pub every: Option<Schedule>,
+
+    /// Sync both ways: pull what changed in the pod, push what changed locally and remove what was removed on the other side since the last two-way sync
+    #[arg(long, conflicts_with_all = ["manifest", "selector", "watch", "decrypt", "extract"])]
+    pub two_way: bool,
+
+    /// What to do with files changed on both sides with `--two-way`
+    #[arg(long, value_enum, default_value_t = Conflicts::Abort, requires = "two_way")]
+    pub conflicts: Conflicts,
+}
+
+/// How a local file compares with its destination in a pod
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Same,
+    Differs,
+    /// Not in the pod, or not a regular file there
+    Missing,
+}
+
+pub async fn run(global: &Global, args: &SyncArgs) -> anyhow::Result<ExitCode> {
+    let cancel = cp::interrupt();
+    if let Some(schedule) = &args.every {
+        every(global, args, schedule, &cancel).await;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let code = once(global, args, &cancel).await?;
+    if !args.watch {
+        return Ok(code);
+    }
+    watch(global, args, &cancel).await?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Syncs the way `args` say one time
+async fn once(
+    global: &Global,
+    args: &SyncArgs,
+    cancel: &CancellationToken,
+) -> anyhow::Result<ExitCode> {
+    if args.two_way {
+        return twoway::run(global, args, cancel).await;
+    }
+    cp::transfer(global, &args.copy, &Selection::Changed, cancel).await
+}
+
+/// Syncs on `schedule` until `cancel`led, a sync that fails is reported and
+/// the next one run regardless. Reports are written after every sync.
+async fn every(global: &Global, args: &SyncArgs, schedule: &Schedule, cancel: &CancellationToken) {
+    info!("syncing {}", schedule);
+    let mut last = None;
+    for run in 1.. {
+        let next = schedule.next(last);
+        if let Ok(wait) = next.duration_since(SystemTime::now()) {
+            info!("next sync at {}", humantime::format_rfc3339_seconds(next));
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = time::sleep(wait) => {}
+            }
+        }
+        let started = SystemTime::now();
+        last = Some(started);
+        let res = once(global, args, cancel).await;
+        if cancel.is_cancelled() {
+            return;
+        }
+        let elapsed = started.elapsed().unwrap_or_default();
+        let succeeded = match res {
+            Ok(code) if code == ExitCode::SUCCESS => true,
+            Ok(_) => false,
+            Err(err) => {
+                print_error(&err);
+                false
+            }
+        };
+        METRICS.sync(succeeded);
+        info!(
+            "sync {} {} after {}",
+            run,
+            if succeeded { "succeeded" } else { "failed" },
+            humantime::format_duration(Duration::from_secs(elapsed.as_secs()))
+        );
+    }
+}
+
+/// Copies the files of `--src` that change until `cancel`led, a failed copy
+/// is reported and the next change copied regardless
+async fn watch(global: &Global, args: &SyncArgs, cancel: &CancellationToken) -> anyhow::Result<()> {
+    // required unless a subcommand is given
+    let Some(src) = &args.copy.src else {
+        unreachable!()
+    };
+    let root = std::fs::canonicalize(src).with_context(|| format!("failed to watch {}", src))?;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })?;
+    // a file is watched through its directory, editors replace files
+    // rather than write them
+    match root.parent().filter(|_| !root.is_dir()) {
+        Some(dir) => watcher.watch(dir, RecursiveMode::NonRecursive),
+        None => watcher.watch(&root, RecursiveMode::Recursive),
+    }
+    .with_context(|| format!("failed to watch {}", src))?;
+    info!("watching {} for changes", src);
+
+    loop {
+        let mut event = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            event = rx.recv() => event,
+        };
+        let mut changed = HashSet::new();
+        loop {
+            match event {
+                Some(Ok(event))
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) =>
+                {
+                    changed.extend(
+                        event
+                            .paths
+                            .into_iter()
+                            .filter(|path| path.starts_with(&root)),
+                    );
+                }
+                Some(Ok(_)) => {}
+                Some(Err(err)) => warn!("watching {}: {}", src, err),
+                None => bail!("stopped watching {}", src),
+            }
+            match time::timeout(args.debounce, rx.recv()).await {
+                Ok(next) => event = next,
+                Err(_) => break,
+            }
+        }
+        // what was removed again or isn't a file is left alone
+        changed.retain(|path| Path::is_file(path));
+        if changed.is_empty() {
+            continue;
+        }
+        info!(
+            "{} {} changed",
+            changed.len(),
+            if changed.len() == 1 { "file" } else { "files" }
+        );
+        if let Err(err) = cp::transfer(global, &args.copy, &Selection::Paths(changed), cancel).await
+        {
+            print_error(&err);
+        }
+    }
+}
+
+/// The SHA-256 of every one of `files`
+pub async fn local_digests(files: &[FileEntry]) -> anyhow::Result<Vec<String>> {
+    let mut digests = Vec::with_capacity(files.len());
+    for file in files {
+        digests.push(hash::file(&hash::Sha256, &file.local).await?);
+    }
+    Ok(digests)
+}
+
+/// Compares `files` with their destinations in `pod`, `local` are their
+/// digests
+pub async fn compare(
+    global: &Global,
+    api: &Api<Pod>,
+    pod: &str,
+    files: &[FileEntry],
+    local: &[String],
+) -> anyhow::Result<Vec<State>> {
+    let paths: Vec<_> = files.iter().map(FileEntry::remote_path).collect();
+    let remote = match remote::digests(
+        api,
+        pod,
+        &global.container,
+        &paths,
+        &hash::Sha256,
+        global.connect_timeout,
+        &CancellationToken::new(),
+    )
+    .await
+    {
+        Ok(remote) => remote,
+        Err(err) => {
+            return Err(diagnose::explain(
+                api,
+                global.namespace(),
+                pod,
+                &global.container,
+                global.connect_timeout,
+                err,
+            )
+            .await
+            .into())
+        }
+    };
+    Ok(local
+        .iter()
+        .zip(remote)
+        .map(|(local, remote)| match remote {
+            Some(remote) if remote == *local => State::Same,
+            Some(_) => State::Differs,
+            None => State::Missing,
+        })
+        .collect())
+}
+
+/// The files of `files` every one of `pods` doesn't have yet
+pub async fn changed<'a>(
+    global: &Global,
+    api: &Api<Pod>,
+    pods: &'a [String],
+    files: &[FileEntry],
+) -> anyhow::Result<Vec<(&'a String, Vec<FileEntry>)>> {
+    let local = local_digests(files).await?;
+    future::try_join_all(pods.iter().map(|pod| {
+        let local = &local;
+        async move {
+            let states = compare(global, api, pod, files, local).await?;
+            let changed = files
+                .iter()
+                .zip(states)
+                .filter(|(_, state)| *state != State::Same)
+                .map(|(file, _)| file.clone())
+                .collect();
+            anyhow::Ok((pod, changed))
+        }
+    }))
+    .await
+}
@@ -0,0 +1,59 @@
+use std::process::ExitCode;
+
+use tokio_util::sync::CancellationToken;
+
+use k8scp::exec;
+
+use crate::{
+    cat, cp,
+    target::{self, Target},
+    Global,
+};
+
+/// Print the end of a file in a pod, and what is appended to it with `-f`
+#[derive(clap::Args, Debug)]
+pub struct TailArgs {
+    /// File to print, `POD:PATH`
+    #[arg(value_parser = target::parse)]
+    target: Target,
+
+    /// How many lines of the end to print
+    #[arg(long, default_value_t = 10)]
+    lines: u64,
+
+    /// Keep printing what is appended until Ctrl-C, following the file by
+    /// name when it is rotated or recreated, like `tail -F`
+    #[arg(short, long, visible_short_alias = 'F')]
+    follow: bool,
+}
+
+pub async fn run(global: &Global, args: &TailArgs) -> anyhow::Result<ExitCode> {
+    let api = global.session().await?.pods(global.namespace());
+    let targets = target::resolve(global, &api, std::slice::from_ref(&args.target)).await?;
+    let path = exec::quote(&targets[0].path);
+    let (command, cancel) = if args.follow {
+        // the remote tail is ended when its stdin closes, that is when we
+        // stop reading, rather than left running in the pod
+        let command = format!(
+            "tail -F -n {} -- {} & t=$!; cat >/dev/null; kill $t 2>/dev/null",
+            args.lines, path
+        );
+        (command, cp::interrupt())
+    } else {
+        let command = format!("exec tail -n {} -- {}", args.lines, path);
+        (command, CancellationToken::new())
+    };
+    Ok(target::each(global, &api, &targets, |target| async {
+        let command = command.clone();
+        cat::show(
+            global,
+            &api,
+            target,
+            command,
+            &mut tokio::io::stdout(),
+            &cancel,
+        )
+        .await
+    })
+    .await)
+}
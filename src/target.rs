@@ -0,0 +1,145 @@
+use std::{future::Future, process::ExitCode};
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+
+use tokio_util::sync::CancellationToken;
+
+pub use k8scp::address::{is_windows_path, ordinal_pod, parse, statefulset, Target};
+use k8scp::{diagnose, remote};
+
+use crate::{term, Global};
+
+/// Whether `name` matches the glob `pattern`, in which `*` stands for any
+/// run of characters and `?` for any one
+pub fn glob(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<_>, Vec<_>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // the last `*` and where in `name` what it stands for ends
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp + 1;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// `targets` with their paths resolved in their pods, see [`path`]
+pub async fn resolve(
+    global: &Global,
+    api: &Api<Pod>,
+    targets: &[Target],
+) -> anyhow::Result<Vec<Target>> {
+    let cancel = CancellationToken::new();
+    let mut resolved = Vec::with_capacity(targets.len());
+    for target in targets {
+        let path = match path(global, api, &target.pod, &target.path, &cancel).await {
+            Ok(path) => path,
+            Err(err) => {
+                let err = explain(global, api, target, err).await;
+                return Err(err.context(format!("failed to resolve {}", target)));
+            }
+        };
+        resolved.push(Target {
+            pod: target.pod.clone(),
+            path,
+        });
+    }
+    Ok(resolved)
+}
+
+/// `path` in `pod` with its `~` and variables expanded by the shell of the
+/// container, see [`remote::expand`], and resolved against the `workingDir`
+/// of the container if relative. A relative path stays as it is when the pod
+/// spec gives no `workingDir`, the image has it then.
+pub async fn path(
+    global: &Global,
+    api: &Api<Pod>,
+    pod: &str,
+    path: &str,
+    cancel: &CancellationToken,
+) -> anyhow::Result<String> {
+    let path = remote::expand(
+        api,
+        pod,
+        &global.container,
+        path,
+        global.connect_timeout,
+        cancel,
+    )
+    .await?;
+    if path.starts_with('/') {
+        return Ok(path);
+    }
+    let dir = remote::working_dir(api, pod, &global.container, global.connect_timeout).await?;
+    Ok(match dir {
+        Some(dir) => remote::resolve(&dir, &path),
+        None => path,
+    })
+}
+
+/// Runs `f` for every one of `targets` in turn, a failure is reported with
+/// its target and the next target handled regardless. Exits with 1 if any
+/// failed.
+pub async fn each<'a, F, Fut>(
+    global: &Global,
+    api: &Api<Pod>,
+    targets: &'a [Target],
+    mut f: F,
+) -> ExitCode
+where
+    F: FnMut(&'a Target) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut failed = 0;
+    for target in targets {
+        let Err(err) = f(target).await else {
+            continue;
+        };
+        let err = explain(global, api, target, err).await;
+        failed += 1;
+        term::error(format!("{}: {:#}", target, err));
+    }
+    match failed {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
+
+/// `err` of `target`, with why the pod couldn't be reached if that is why
+async fn explain(
+    global: &Global,
+    api: &Api<Pod>,
+    target: &Target,
+    err: anyhow::Error,
+) -> anyhow::Error {
+    match err.downcast::<k8scp::Error>() {
+        Ok(err) => diagnose::explain(
+            api,
+            global.namespace(),
+            &target.pod,
+            &global.container,
+            global.connect_timeout,
+            err,
+        )
+        .await
+        .into(),
+        Err(err) => err,
+    }
+}
@@ -0,0 +1,37 @@
+use std::{fmt::Display, io::IsTerminal};
+
+use console::style;
+use k8scp::redact::redact;
+
+/// Whether output should be colored, `NO_COLOR` and `--no-color` turn it off as
+/// does writing to something that isn't a terminal
+pub fn use_color(no_color: bool) -> bool {
+    !no_color
+        && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        && std::io::stderr().is_terminal()
+}
+
+/// Applies the color choice to everything styled through `console`, which
+/// includes the progress bars
+pub fn set_color(color: bool) {
+    console::set_colors_enabled(color);
+    console::set_colors_enabled_stderr(color);
+}
+
+pub fn success(msg: impl Display) {
+    eprintln!("{} {}", style("✔").green().bold(), msg);
+}
+
+pub fn warn(msg: impl Display) {
+    let msg = msg.to_string();
+    eprintln!(
+        "{} {}",
+        style("!").yellow().bold(),
+        style(redact(&msg)).yellow()
+    );
+}
+
+pub fn error(msg: impl Display) {
+    let msg = msg.to_string();
+    eprintln!("{} {}", style("✘").red().bold(), style(redact(&msg)).red());
+}
@@ -0,0 +1,242 @@
+//! Line endings of text files converted on their way between a Windows
+//! machine and a container, see [`ExecOptions::text_mode`].
+//!
+//! [`ExecOptions::text_mode`]: crate::ExecOptions::text_mode
+
+use std::{
+    io,
+    path::Path,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+/// The extensions of the files [`TextFiles::new`] takes as text
+pub const TEXT_EXTENSIONS: [&str; 18] = [
+    "txt",
+    "md",
+    "csv",
+    "conf",
+    "cfg",
+    "ini",
+    "env",
+    "properties",
+    "yaml",
+    "yml",
+    "json",
+    "toml",
+    "xml",
+    "html",
+    "css",
+    "js",
+    "sh",
+    "sql",
+];
+
+/// How the line endings of a text file are converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode {
+    /// Windows line endings become Unix ones, `crlf->lf`
+    CrlfToLf,
+    /// Unix line endings become Windows ones, `lf->crlf`
+    LfToCrlf,
+}
+
+impl TextMode {
+    /// Parses `crlf->lf` or `lf->crlf`
+    pub fn parse(s: &str) -> Result<TextMode, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "crlf->lf" => Ok(TextMode::CrlfToLf),
+            "lf->crlf" => Ok(TextMode::LfToCrlf),
+            _ => Err(format!("{} isn't crlf->lf or lf->crlf", s)),
+        }
+    }
+}
+
+/// Which files are text and how their line endings are converted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextFiles {
+    pub mode: TextMode,
+    /// Names ending in `.` and one of these, in any case, are text
+    pub extensions: Vec<String>,
+}
+
+impl TextFiles {
+    /// The files with one of the [`TEXT_EXTENSIONS`] are converted as `mode`
+    /// says
+    pub fn new(mode: TextMode) -> TextFiles {
+        TextFiles {
+            mode,
+            extensions: TEXT_EXTENSIONS.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// How the file `name` is converted, `None` if it isn't text
+    pub fn mode_of(&self, name: &str) -> Option<TextMode> {
+        let (_, extension) = name.rsplit_once('.')?;
+        self.extensions
+            .iter()
+            .any(|ext| ext.trim_start_matches('.').eq_ignore_ascii_case(extension))
+            .then_some(self.mode)
+    }
+}
+
+/// Converts line endings chunk by chunk, a CR at the end of a chunk is
+/// held back until the next one tells what it ends
+#[derive(Debug)]
+pub(crate) struct Converter {
+    mode: TextMode,
+    /// The last byte seen was a CR
+    cr: bool,
+}
+
+impl Converter {
+    pub(crate) fn new(mode: TextMode) -> Converter {
+        Converter { mode, cr: false }
+    }
+
+    /// Appends what `data` becomes to `out`
+    pub(crate) fn convert(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        for &b in data {
+            match self.mode {
+                TextMode::CrlfToLf => {
+                    if self.cr && b != b'\n' {
+                        out.push(b'\r');
+                    }
+                    if b != b'\r' {
+                        out.push(b);
+                    }
+                }
+                // a line that ends in CRLF already keeps it
+                TextMode::LfToCrlf => {
+                    if b == b'\n' && !self.cr {
+                        out.push(b'\r');
+                    }
+                    out.push(b);
+                }
+            }
+            self.cr = b == b'\r';
+        }
+    }
+
+    /// Appends what is held back at the end of the file to `out`
+    pub(crate) fn finish(&mut self, out: &mut Vec<u8>) {
+        if self.mode == TextMode::CrlfToLf && self.cr {
+            out.push(b'\r');
+        }
+        self.cr = false;
+    }
+}
+
+/// The size of the file `path` once its line endings are converted as
+/// `mode` says
+pub(crate) async fn converted_len(path: &Path, mode: TextMode) -> io::Result<u64> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut converter = Converter::new(mode);
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut out = Vec::new();
+    let mut len = 0;
+    loop {
+        out.clear();
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            converter.finish(&mut out);
+            return Ok(len + out.len() as u64);
+        }
+        converter.convert(&buf[..n], &mut out);
+        len += out.len() as u64;
+    }
+}
+
+/// Reads `inner` with its line endings converted
+pub(crate) struct Converted<S> {
+    inner: S,
+    converter: Converter,
+    /// Converted bytes not read yet
+    pending: Vec<u8>,
+    done: bool,
+}
+
+impl<S> Converted<S> {
+    pub(crate) fn new(inner: S, mode: TextMode) -> Converted<S> {
+        Converted {
+            inner,
+            converter: Converter::new(mode),
+            pending: Vec::new(),
+            done: false,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Converted<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        while this.pending.is_empty() && !this.done {
+            let mut raw = [0u8; 16 * 1024];
+            let mut raw = ReadBuf::new(&mut raw);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut raw))?;
+            if raw.filled().is_empty() {
+                this.converter.finish(&mut this.pending);
+                this.done = true;
+            } else {
+                this.converter.convert(raw.filled(), &mut this.pending);
+            }
+        }
+        let n = this.pending.len().min(buf.remaining());
+        buf.put_slice(&this.pending[..n]);
+        this.pending.drain(..n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> Converted<S> {
+    /// Writes what is converted but not written yet to `inner`
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Converted<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        this.converter.convert(data, &mut this.pending);
+        // what doesn't go now goes with the next write or flush
+        if let Poll::Ready(Err(err)) = this.poll_drain(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(data.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.done {
+            this.converter.finish(&mut this.pending);
+            this.done = true;
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::BTreeSet, fmt::Debug, time::SystemTime};
+
+use anyhow::{bail, Context as _};
+use k8s_openapi::{
+    api::{
+        apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
+        core::v1::Pod,
+    },
+    apimachinery::pkg::apis::meta::v1::OwnerReference,
+    NamespaceResourceScope,
+};
+use kube::{
+    api::{Api, Patch, PatchParams},
+    Client, Resource, ResourceExt,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use k8scp::exec::quote;
+
+/// The annotation `kubectl rollout restart` sets on the pod template
+const RESTARTED_AT: &str = "kubectl.kubernetes.io/restartedAt";
+
+/// What a copy does once it succeeded, given with `--then`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Then {
+    /// Send `signal` to a process in the container of every pod, the one
+    /// whose pid is in the file `process` if it is a path, otherwise those
+    /// named `process`
+    Signal { signal: String, process: String },
+    /// Restart the workloads of the pods, like `kubectl rollout restart`
+    RolloutRestart,
+}
+
+impl Then {
+    /// Parses `signal:SIGNAL:PIDFILE`, `signal:SIGNAL:NAME` or
+    /// `rollout-restart`
+    pub fn parse(s: &str) -> Result<Then, String> {
+        if s == "rollout-restart" {
+            return Ok(Then::RolloutRestart);
+        }
+        let expected =
+            || "expected signal:SIGNAL:PIDFILE, signal:SIGNAL:NAME or rollout-restart".to_string();
+        let Some(("signal", rest)) = s.split_once(':') else {
+            return Err(expected());
+        };
+        let Some((signal, process)) = rest.split_once(':') else {
+            return Err(expected());
+        };
+        let signal = signal.strip_prefix("SIG").unwrap_or(signal);
+        if signal.is_empty() || !signal.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("invalid signal `{}`, like HUP or USR1", signal));
+        }
+        if process.is_empty() {
+            return Err(expected());
+        }
+        Ok(Then::Signal {
+            signal: signal.to_ascii_uppercase(),
+            process: process.to_string(),
+        })
+    }
+
+    /// The shell command sending the signal of [`Then::Signal`], failing if
+    /// there is nothing to signal
+    pub fn command(&self) -> Option<String> {
+        let Then::Signal { signal, process } = self else {
+            return None;
+        };
+        if process.contains('/') {
+            return Some(format!(
+                "pid=$(cat {}) && kill -s {} \"$pid\"",
+                quote(process),
+                signal
+            ));
+        }
+        // without pkill in many images the names are looked up in /proc,
+        // where they are cut to 15 characters
+        let name: String = process.chars().take(15).collect();
+        Some(format!(
+            "found=; for p in /proc/[0-9]*; do \
+             [ \"$(cat \"$p/comm\" 2>/dev/null)\" = {name} ] && kill -s {signal} \"${{p#/proc/}}\" && found=1; \
+             done; [ -n \"$found\" ] || {{ echo no process named {name} >&2; exit 1; }}",
+            name = quote(&name),
+            signal = signal,
+        ))
+    }
+}
+
+impl std::fmt::Display for Then {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Then::Signal { signal, process } => write!(f, "signal {} to {}", signal, process),
+            Then::RolloutRestart => write!(f, "rollout restart"),
+        }
+    }
+}
+
+/// Restarts the workloads `pods` of `namespace` belong to, each once
+pub async fn rollout_restart(
+    client: Client,
+    namespace: &str,
+    pods: &[&str],
+) -> anyhow::Result<Vec<String>> {
+    let mut workloads = BTreeSet::new();
+    for pod in pods {
+        workloads.insert(workload(client.clone(), namespace, pod).await?);
+    }
+    let now = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+    let patch = json!({
+        "spec": {"template": {"metadata": {"annotations": {RESTARTED_AT: now}}}}
+    });
+    let mut restarted = Vec::new();
+    for (kind, name) in workloads {
+        let patched = match kind {
+            "Deployment" => restart::<Deployment>(&client, namespace, &name, &patch).await,
+            "StatefulSet" => restart::<StatefulSet>(&client, namespace, &name, &patch).await,
+            _ => restart::<DaemonSet>(&client, namespace, &name, &patch).await,
+        };
+        let workload = format!("{}/{}", kind.to_ascii_lowercase(), name);
+        patched.with_context(|| format!("failed to restart {}", workload))?;
+        restarted.push(workload);
+    }
+    Ok(restarted)
+}
+
+/// The kind and name of the workload that controls `pod`, through its
+/// ReplicaSet for a Deployment
+async fn workload(
+    client: Client,
+    namespace: &str,
+    pod: &str,
+) -> anyhow::Result<(&'static str, String)> {
+    let controller = |owners: &[OwnerReference]| {
+        owners
+            .iter()
+            .find(|owner| owner.controller == Some(true))
+            .map(|owner| (owner.kind.clone(), owner.name.clone()))
+    };
+    let found = Api::<Pod>::namespaced(client.clone(), namespace)
+        .get(pod)
+        .await
+        .with_context(|| format!("failed to get pod {}", pod))?;
+    let unsupported = |kind: &str, name: &str| {
+        anyhow::anyhow!(
+            "pod {} belongs to {} {}, only Deployments, StatefulSets and DaemonSets can be restarted",
+            pod,
+            kind,
+            name
+        )
+    };
+    match controller(found.owner_references()) {
+        None => bail!("pod {} belongs to no workload to restart", pod),
+        Some((kind, name)) if kind == "StatefulSet" => Ok(("StatefulSet", name)),
+        Some((kind, name)) if kind == "DaemonSet" => Ok(("DaemonSet", name)),
+        Some((kind, name)) if kind == "ReplicaSet" => {
+            let set = Api::<ReplicaSet>::namespaced(client, namespace)
+                .get(&name)
+                .await
+                .with_context(|| format!("failed to get replicaset {}", name))?;
+            match controller(set.owner_references()) {
+                Some((kind, name)) if kind == "Deployment" => Ok(("Deployment", name)),
+                _ => Err(unsupported("ReplicaSet", &name)),
+            }
+        }
+        Some((kind, name)) => Err(unsupported(&kind, &name)),
+    }
+}
+
+/// Patches the pod template of the workload `name` as `patch` says, which
+/// makes its controller replace the pods
+async fn restart<K>(
+    client: &Client,
+    namespace: &str,
+    name: &str,
+    patch: &serde_json::Value,
+) -> kube::Result<()>
+where
+    K: Resource<Scope = NamespaceResourceScope> + Clone + DeserializeOwned + Debug,
+    K::DynamicType: Default,
+{
+    Api::<K>::namespaced(client.clone(), namespace)
+        .patch(name, &PatchParams::default(), &Patch::Merge(patch))
+        .await
+        .map(drop)
+}
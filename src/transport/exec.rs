@@ -0,0 +1,537 @@
+use std::io;
+
+use futures::future::BoxFuture;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::Transport;
+use crate::{copy, exec::quote, progress::ItemProgress, ExecOptions, Result};
+
+/// What an [`Exec`] transport runs in the container and how the file is
+/// laid out on the exec stream
+pub trait Codec: Send + Sync {
+    /// The name of the transport, e.g. `exec-cat`
+    fn name(&self) -> &str;
+
+    /// Shell command writing what it reads from stdin to the file `name` of
+    /// `size` bytes, it runs in the directory the file goes to
+    fn write_command(&self, name: &str, size: u64) -> String;
+
+    /// Whether the write command exits by itself once it has the whole file.
+    /// Its exit status is then waited for, otherwise stdin is closed right
+    /// after the file was sent, which loses the status.
+    fn confirms(&self) -> bool {
+        false
+    }
+
+    /// Whether the write command takes whatever comes until stdin is
+    /// closed, so files of unknown size can be written, `size` is 0 then
+    fn streams(&self) -> bool {
+        false
+    }
+
+    /// Shell command writing the file `path` to stdout
+    fn read_command(&self, path: &str) -> String;
+
+    /// Turns the `size` bytes of a file into what [`write_command`] reads,
+    /// `name` is the one the command was made with
+    ///
+    /// [`write_command`]: Codec::write_command
+    fn encoder(&self, name: &str, size: u64) -> Box<dyn Encoder>;
+
+    /// Turns what [`read_command`](Codec::read_command) writes back into
+    /// the file
+    fn decoder(&self) -> Box<dyn Decoder>;
+}
+
+/// Encodes a file chunk by chunk for the stdin of a command
+pub trait Encoder: Send {
+    /// Appends what the next `data` of the file becomes to `out`
+    fn encode(&mut self, data: &[u8], out: &mut Vec<u8>) -> io::Result<()>;
+
+    /// Appends what goes after the end of the file to `out`
+    fn finish(&mut self, out: &mut Vec<u8>) -> io::Result<()>;
+}
+
+/// Decodes the stdout of a command chunk by chunk into a file
+pub trait Decoder: Send {
+    /// Appends the file contents in the next `data` of the stream to `out`
+    fn decode(&mut self, data: &[u8], out: &mut Vec<u8>) -> io::Result<()>;
+
+    /// Fails if the stream ended before the file did
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// A transport through the exec stream of a `sh -c` running the commands
+/// of the codec `C`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Exec<C>(pub C);
+
+impl<C: Codec> Transport for Exec<C> {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn streams(&self) -> bool {
+        self.0.streams()
+    }
+
+    fn upload<'a>(
+        &'a self,
+        pods: &'a Api<Pod>,
+        pod: &'a str,
+        src: &'a mut (dyn AsyncRead + Send + Unpin),
+        size: u64,
+        dst: &'a str,
+        opts: &'a ExecOptions<'a>,
+        progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(copy::upload(
+            pods, pod, src, size, dst, opts, progress, &self.0,
+        ))
+    }
+
+    fn download<'a>(
+        &'a self,
+        pods: &'a Api<Pod>,
+        pod: &'a str,
+        src: &'a str,
+        dst: &'a mut (dyn AsyncWrite + Send + Unpin),
+        opts: &'a ExecOptions<'a>,
+        progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(copy::download(pods, pod, src, dst, opts, progress, &self.0))
+    }
+}
+
+/// The file as it is, through `cat`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cat;
+
+impl Codec for Cat {
+    fn name(&self) -> &str {
+        "exec-cat"
+    }
+
+    fn write_command(&self, name: &str, _size: u64) -> String {
+        format!("cat > {}", quote(name))
+    }
+
+    fn streams(&self) -> bool {
+        true
+    }
+
+    fn read_command(&self, path: &str) -> String {
+        format!("cat -- {}", quote(path))
+    }
+
+    fn encoder(&self, _name: &str, _size: u64) -> Box<dyn Encoder> {
+        Box::new(Verbatim)
+    }
+
+    fn decoder(&self) -> Box<dyn Decoder> {
+        Box::new(Verbatim)
+    }
+}
+
+/// Files encrypted with [age](https://age-encryption.org), decrypted on the
+/// way into the container by its `age` with the identity file `identity`
+/// there, so the key never leaves the pod. Downloads are read as they are.
+#[derive(Debug, Clone)]
+pub struct AgeDecrypt {
+    identity: String,
+}
+
+impl AgeDecrypt {
+    /// `identity` is a path in the container
+    pub fn new(identity: impl Into<String>) -> AgeDecrypt {
+        AgeDecrypt {
+            identity: identity.into(),
+        }
+    }
+}
+
+impl Codec for AgeDecrypt {
+    fn name(&self) -> &str {
+        "age"
+    }
+
+    fn write_command(&self, name: &str, size: u64) -> String {
+        // `head` ends the input with the file, `age` fails on a wrong key
+        // or a damaged file only once it has all of it
+        format!(
+            "head -c {} | age --decrypt -i {} > {} || {{ s=$?; rm -f -- {}; exit $s; }}",
+            size,
+            quote(&self.identity),
+            quote(name),
+            quote(name)
+        )
+    }
+
+    fn confirms(&self) -> bool {
+        true
+    }
+
+    fn read_command(&self, path: &str) -> String {
+        format!("cat -- {}", quote(path))
+    }
+
+    fn encoder(&self, _name: &str, _size: u64) -> Box<dyn Encoder> {
+        Box::new(Verbatim)
+    }
+
+    fn decoder(&self) -> Box<dyn Decoder> {
+        Box::new(Verbatim)
+    }
+}
+
+/// Archives unpacked into the directory they are copied to as they arrive,
+/// by the container's `tar`, or its `unzip` for a zip file, which can't be
+/// unpacked from a pipe and is spooled there first. The format is told by
+/// the extension of the archive's name, see [`Extract::unpacks`]. Downloads
+/// are read as they are.
+#[derive(Debug, Clone, Default)]
+pub struct Extract {
+    xattrs: bool,
+    acls: bool,
+    owners: Owners,
+}
+
+impl Extract {
+    /// Whether an archive named `name` can be unpacked
+    pub fn unpacks(name: &str) -> bool {
+        name.ends_with(".zip") || tar_flags(name).is_some()
+    }
+
+    /// Restores the extended attributes of a tar archive, every namespace
+    /// and the SELinux labels, rather than leave them out. Those outside
+    /// `user.*`, like file capabilities, need `tar` to run as root.
+    pub fn xattrs(mut self, xattrs: bool) -> Extract {
+        self.xattrs = xattrs;
+        self
+    }
+
+    /// Restores the POSIX ACLs of a tar archive, rather than leave them out
+    pub fn acls(mut self, acls: bool) -> Extract {
+        self.acls = acls;
+        self
+    }
+
+    /// Gives the files of a tar archive the owners `owners` says, rather
+    /// than the exec user
+    pub fn owners(mut self, owners: Owners) -> Extract {
+        self.owners = owners;
+        self
+    }
+}
+
+/// A user or group, by the name and the id it has on one side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Id {
+    pub name: String,
+    pub id: u32,
+}
+
+/// A user or group that is `local` on the local machine and `remote` in
+/// the container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdMap {
+    pub local: Id,
+    pub remote: Id,
+}
+
+/// Who the files of a tar archive belong to once they crossed over. With
+/// neither ids nor maps they belong to whoever unpacks them.
+#[derive(Debug, Clone, Default)]
+pub struct Owners {
+    /// The owners by their ids, rather than their names, which are looked
+    /// up again on the other side
+    pub numeric: bool,
+    /// Users that become other users on the way, by their ids
+    pub users: Vec<IdMap>,
+    /// Like `users`, for groups
+    pub groups: Vec<IdMap>,
+}
+
+impl Owners {
+    /// Whether the archive's owners are kept, mapped or not
+    pub fn keeps(&self) -> bool {
+        self.numeric || !self.users.is_empty() || !self.groups.is_empty()
+    }
+
+    /// Shell command, followed by ` && `, writing the maps of GNU `tar`
+    /// packing files of the container to `$u` and `$g`, which are removed
+    /// when the shell exits. Nothing without maps.
+    pub fn pack_setup(&self) -> String {
+        if self.users.is_empty() && self.groups.is_empty() {
+            return String::new();
+        }
+        let map = |maps: &[IdMap], file: &str| {
+            let lines: String = maps
+                .iter()
+                .map(|map| {
+                    format!(
+                        " {}",
+                        quote(&format!(
+                            "+{} {}:{}",
+                            map.remote.id, map.local.name, map.local.id
+                        ))
+                    )
+                })
+                .collect();
+            format!("printf '%s\\n'{} > \"${}\"", lines, file)
+        };
+        format!(
+            "u=$(mktemp) && g=$(mktemp) && trap 'rm -f -- \"$u\" \"$g\"' EXIT && {} && {} && ",
+            map(&self.users, "u"),
+            map(&self.groups, "g")
+        )
+    }
+
+    /// Options of GNU `tar` packing files of the container, after
+    /// [`pack_setup`](Owners::pack_setup), each followed by a space
+    pub fn pack_options(&self) -> String {
+        let mut options = String::new();
+        if !self.users.is_empty() || !self.groups.is_empty() {
+            options += "--owner-map=\"$u\" --group-map=\"$g\" ";
+        }
+        if self.numeric {
+            options += "--numeric-owner ";
+        }
+        options
+    }
+}
+
+/// The options of GNU `tar` that store or, `extract`ing, restore extended
+/// attributes and SELinux labels with `xattrs` and POSIX ACLs with `acls`,
+/// each followed by a space
+pub fn tar_attrs(xattrs: bool, acls: bool, extract: bool) -> String {
+    let mut options = String::new();
+    if xattrs {
+        // `tar` restores only `user.*` unless told otherwise
+        options += if extract {
+            "--xattrs --xattrs-include='*' --selinux "
+        } else {
+            "--xattrs --selinux "
+        };
+    }
+    if acls {
+        options += "--acls ";
+    }
+    options
+}
+
+/// The flag of the compression `tar` extracts an archive named `name` with
+fn tar_flags(name: &str) -> Option<&'static str> {
+    const FORMATS: [(&[&str], &str); 4] = [
+        (&[".tar"], ""),
+        (&[".tar.gz", ".tgz"], "z"),
+        (&[".tar.bz2", ".tbz2"], "j"),
+        (&[".tar.xz", ".txz"], "J"),
+    ];
+    FORMATS
+        .iter()
+        .find(|(extensions, _)| extensions.iter().any(|ext| name.ends_with(ext)))
+        .map(|(_, flags)| *flags)
+}
+
+/// Shell command unpacking the `size` bytes of a tar archive compressed as
+/// `flags` says with the owners it has by their ids, those the maps of
+/// `owners` name changed to the ids in the container afterwards
+fn unpack_owned(size: u64, attrs: &str, flags: &str, owners: &Owners) -> String {
+    if owners.users.is_empty() && owners.groups.is_empty() {
+        return format!(
+            "head -c {} | tar {}--same-owner --numeric-owner -x{}f -",
+            size, attrs, flags
+        );
+    }
+    // every file is looked at once, two maps can swap ids
+    let cases = |maps: &[IdMap]| -> String {
+        maps.iter()
+            .map(|map| format!("{}) echo {};; ", map.local.id, map.remote.id))
+            .collect()
+    };
+    format!(
+        "t=$(mktemp) && trap 'rm -f -- \"$t\"' EXIT && \
+         head -c {} | tar {}--same-owner --numeric-owner --quoting-style=literal --index-file=\"$t\" -xv{}f - && \
+         while IFS= read -r f; do o=$(stat -c %u:%g -- \"$f\") || exit; \
+         u=$(case ${{o%:*}} in {}*) ;; esac); g=$(case ${{o#*:}} in {}*) ;; esac); \
+         if [ -n \"$u$g\" ]; then chown -h \"$u${{g:+:$g}}\" -- \"$f\" || exit; fi; done < \"$t\"",
+        size,
+        attrs,
+        flags,
+        cases(&owners.users),
+        cases(&owners.groups)
+    )
+}
+
+impl Codec for Extract {
+    fn name(&self) -> &str {
+        "extract"
+    }
+
+    fn write_command(&self, name: &str, size: u64) -> String {
+        match tar_flags(name) {
+            Some(flags) if !self.owners.keeps() => format!(
+                "head -c {} | tar {}-x{}of -",
+                size,
+                tar_attrs(self.xattrs, self.acls, true),
+                flags
+            ),
+            Some(flags) => unpack_owned(size, &tar_attrs(self.xattrs, self.acls, true), flags, &self.owners),
+            None => format!(
+                "t=$(mktemp) && trap 'rm -f -- \"$t\"' EXIT && head -c {} > \"$t\" && unzip -oq \"$t\"",
+                size
+            ),
+        }
+    }
+
+    fn confirms(&self) -> bool {
+        true
+    }
+
+    fn read_command(&self, path: &str) -> String {
+        format!("cat -- {}", quote(path))
+    }
+
+    fn encoder(&self, _name: &str, _size: u64) -> Box<dyn Encoder> {
+        Box::new(Verbatim)
+    }
+
+    fn decoder(&self) -> Box<dyn Decoder> {
+        Box::new(Verbatim)
+    }
+}
+
+struct Verbatim;
+
+impl Encoder for Verbatim {
+    fn encode(&mut self, data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        out.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn finish(&mut self, _out: &mut Vec<u8>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Decoder for Verbatim {
+    fn decode(&mut self, data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        out.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The file as base64 through the `base64` tool, for exec streams that
+/// don't pass arbitrary bytes through unharmed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Base64;
+
+/// Bytes encoded per line, 76 characters like `base64` wraps at
+const LINE: usize = 57;
+
+impl Codec for Base64 {
+    fn name(&self) -> &str {
+        "base64"
+    }
+
+    fn write_command(&self, name: &str, size: u64) -> String {
+        // `head` ends the input once the file is through, like `tar`'s
+        format!(
+            "head -c {} | base64 -d > {}",
+            encoded_len(size),
+            quote(name)
+        )
+    }
+
+    fn confirms(&self) -> bool {
+        true
+    }
+
+    fn read_command(&self, path: &str) -> String {
+        format!("base64 < {}", quote(path))
+    }
+
+    fn encoder(&self, _name: &str, _size: u64) -> Box<dyn Encoder> {
+        Box::new(Base64Encoder { rest: Vec::new() })
+    }
+
+    fn decoder(&self) -> Box<dyn Decoder> {
+        Box::new(Base64Decoder { rest: Vec::new() })
+    }
+}
+
+struct Base64Encoder {
+    /// The start of a line that isn't complete yet
+    rest: Vec<u8>,
+}
+
+impl Encoder for Base64Encoder {
+    fn encode(&mut self, data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        self.rest.extend_from_slice(data);
+        let whole = self.rest.len() - self.rest.len() % LINE;
+        for line in self.rest[..whole].chunks(LINE) {
+            encode_line(line, out);
+        }
+        self.rest.drain(..whole);
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        if !self.rest.is_empty() {
+            encode_line(&self.rest, out);
+            self.rest.clear();
+        }
+        Ok(())
+    }
+}
+
+/// What the `size` bytes of a file take up encoded
+fn encoded_len(size: u64) -> u64 {
+    let line = |len: u64| len.div_ceil(3) * 4 + 1;
+    let (lines, rest) = (size / LINE as u64, size % LINE as u64);
+    let mut len = lines * line(LINE as u64);
+    if rest > 0 {
+        len += line(rest);
+    }
+    len
+}
+
+fn encode_line(line: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(base64::encode(line).as_bytes());
+    out.push(b'\n');
+}
+
+struct Base64Decoder {
+    /// Characters of a quantum that isn't complete yet
+    rest: Vec<u8>,
+}
+
+impl Decoder for Base64Decoder {
+    fn decode(&mut self, data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        self.rest
+            .extend(data.iter().filter(|b| !b.is_ascii_whitespace()));
+        let whole = self.rest.len() - self.rest.len() % 4;
+        base64::decode_config_buf(&self.rest[..whole], base64::STANDARD, out)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        self.rest.drain(..whole);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.rest.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "base64 stream ended within a quantum",
+            ))
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! How the bytes of a file get into and out of a container.
+//!
+//! A [`Transport`] copies a single file in either direction. The built-in
+//! ones all run a shell command through an exec stream and differ in what
+//! they run and how the file is laid out on the stream, see [`Exec`] and
+//! [`Codec`]. Other mechanisms implement [`Transport`] directly and are
+//! picked by name through a [`Registry`].
+
+mod exec;
+mod tar;
+mod trace;
+
+use std::{fmt, sync::Arc};
+
+use futures::future::BoxFuture;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{progress::ItemProgress, ExecOptions, Result};
+
+pub use exec::{
+    tar_attrs, AgeDecrypt, Base64, Cat, Codec, Decoder, Encoder, Exec, Extract, Id, IdMap, Owners,
+};
+pub use tar::Tar;
+pub use trace::{connected, status, Channel, Traced, TARGET};
+
+/// A way of copying a file between the local machine and a container.
+///
+/// The local end is a reader or writer. Its errors are reported as they
+/// are when they were made from an [`Error`](crate::Error), counting and
+/// hashing is left to it, `progress` only learns about the connection and
+/// the container's stderr.
+pub trait Transport: Send + Sync {
+    /// The name it is registered and selected by, e.g. `exec-cat`
+    fn name(&self) -> &str;
+
+    /// Writes the `size` bytes `src` reads to the path `dst` in `pod`,
+    /// creating the directories leading to it, see
+    /// [`copy_to_pod`](crate::copy_to_pod)
+    #[allow(clippy::too_many_arguments)]
+    fn upload<'a>(
+        &'a self,
+        pods: &'a Api<Pod>,
+        pod: &'a str,
+        src: &'a mut (dyn AsyncRead + Send + Unpin),
+        size: u64,
+        dst: &'a str,
+        opts: &'a ExecOptions<'a>,
+        progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Whether [`upload`](Transport::upload) takes what `src` reads until it
+    /// ends, so a named pipe or another source of unknown size can be
+    /// uploaded with a `size` of 0
+    fn streams(&self) -> bool {
+        false
+    }
+
+    /// Writes the file `src` in `pod` to `dst`, see
+    /// [`copy_from_pod`](crate::copy_from_pod)
+    fn download<'a>(
+        &'a self,
+        pods: &'a Api<Pod>,
+        pod: &'a str,
+        src: &'a str,
+        dst: &'a mut (dyn AsyncWrite + Send + Unpin),
+        opts: &'a ExecOptions<'a>,
+        progress: ItemProgress,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+impl fmt::Debug for dyn Transport + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Transports by name, [`Registry::default`] has the built-in ones
+#[derive(Clone)]
+pub struct Registry {
+    transports: Vec<Arc<dyn Transport>>,
+}
+
+impl Registry {
+    /// A registry without any transport
+    pub fn empty() -> Registry {
+        Registry {
+            transports: Vec::new(),
+        }
+    }
+
+    /// Adds `transport`, replacing the one registered under the same name
+    pub fn register(&mut self, transport: impl Transport + 'static) -> &mut Registry {
+        self.transports.retain(|t| t.name() != transport.name());
+        self.transports.push(Arc::new(transport));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Transport>> {
+        self.transports.iter().find(|t| t.name() == name).cloned()
+    }
+
+    /// The names of the registered transports, in the order they were added
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.transports.iter().map(|t| t.name())
+    }
+}
+
+impl Default for Registry {
+    /// `exec-cat`, `exec-tar` and `base64`
+    fn default() -> Registry {
+        let mut registry = Registry::empty();
+        registry.register(Exec(Cat));
+        registry.register(Exec(Tar));
+        registry.register(Exec(Base64));
+        registry
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{Codec, Decoder, Encoder};
+use crate::exec::quote;
+
+const BLOCK: usize = 512;
+
+/// The file as a single entry tar archive through `tar`, for containers
+/// whose exec stream or shell mangles `cat`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tar;
+
+impl Codec for Tar {
+    fn name(&self) -> &str {
+        "exec-tar"
+    }
+
+    fn write_command(&self, name: &str, size: u64) -> String {
+        // the name is in the archive, ownership is the exec user's like with
+        // `cat`. `head` ends the input with the archive, not every `tar`
+        // stops reading at its end.
+        format!("head -c {} | tar -xof -", archive_len(name, size))
+    }
+
+    fn confirms(&self) -> bool {
+        true
+    }
+
+    fn read_command(&self, path: &str) -> String {
+        let (dir, name) = match path.rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((dir, name)) => (dir, name),
+            None => (".", path),
+        };
+        format!("cd {} && tar -chf - -- {}", quote(dir), quote(name))
+    }
+
+    fn encoder(&self, name: &str, size: u64) -> Box<dyn Encoder> {
+        Box::new(TarEncoder {
+            name: name.to_string(),
+            size,
+            left: size,
+            started: false,
+        })
+    }
+
+    fn decoder(&self) -> Box<dyn Decoder> {
+        Box::new(TarDecoder {
+            header: Vec::with_capacity(BLOCK),
+            state: State::Header,
+        })
+    }
+}
+
+struct TarEncoder {
+    name: String,
+    size: u64,
+    /// Bytes of the file still to come
+    left: u64,
+    started: bool,
+}
+
+impl TarEncoder {
+    fn start(&mut self, out: &mut Vec<u8>) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        let mtime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        // names that don't fit the header come in an entry of their own
+        if self.name.len() > 100 {
+            let mut name = self.name.as_bytes().to_vec();
+            name.push(0);
+            out.extend_from_slice(&header(b"././@LongLink", b'L', name.len() as u64, mtime));
+            out.extend_from_slice(&name);
+            pad(name.len() as u64, out);
+        }
+        let name = &self.name.as_bytes()[..self.name.len().min(100)];
+        out.extend_from_slice(&header(name, b'0', self.size, mtime));
+    }
+}
+
+impl Encoder for TarEncoder {
+    fn encode(&mut self, data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        self.start(out);
+        if data.len() as u64 > self.left {
+            return Err(changed());
+        }
+        self.left -= data.len() as u64;
+        out.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        self.start(out);
+        if self.left > 0 {
+            return Err(changed());
+        }
+        pad(self.size, out);
+        // the end of the archive
+        out.extend_from_slice(&[0; 2 * BLOCK]);
+        Ok(())
+    }
+}
+
+/// Size of the archive holding the `size` bytes of the file `name`
+fn archive_len(name: &str, size: u64) -> u64 {
+    let padded = |len: u64| len.div_ceil(BLOCK as u64) * BLOCK as u64;
+    let long_name = match name.len() {
+        0..=100 => 0,
+        len => BLOCK as u64 + padded(len as u64 + 1),
+    };
+    long_name + BLOCK as u64 + padded(size) + 2 * BLOCK as u64
+}
+
+fn changed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "the file changed size while it was being copied",
+    )
+}
+
+/// A GNU tar header of an entry of the type `kind`
+fn header(name: &[u8], kind: u8, size: u64, mtime: u64) -> [u8; BLOCK] {
+    let mut header = [0; BLOCK];
+    header[..name.len()].copy_from_slice(name);
+    octal(&mut header[100..108], 0o644);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    if size < 1 << 33 {
+        octal(&mut header[124..136], size);
+    } else {
+        // base-256 for what 11 octal digits can't hold
+        header[124] = 0x80;
+        header[128..136].copy_from_slice(&size.to_be_bytes());
+    }
+    octal(&mut header[136..148], mtime);
+    header[156] = kind;
+    header[257..265].copy_from_slice(b"ustar  \0");
+    // the checksum is taken with its own field as spaces
+    header[148..156].fill(b' ');
+    let sum: u32 = header.iter().map(|&b| b as u32).sum();
+    octal(&mut header[148..155], sum as u64);
+    header
+}
+
+/// Writes `value` as zero padded octal digits and a NUL into `field`
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+/// Pads the `len` bytes of an entry to the next block
+fn pad(len: u64, out: &mut Vec<u8>) {
+    let padding = (BLOCK - (len % BLOCK as u64) as usize) % BLOCK;
+    out.resize(out.len() + padding, 0);
+}
+
+struct TarDecoder {
+    /// The header being read
+    header: Vec<u8>,
+    state: State,
+}
+
+enum State {
+    Header,
+    /// Skipping the data of an entry that isn't the file
+    Skip(u64),
+    /// The bytes of the file still to come
+    Data(u64),
+    /// The file is complete, the rest of the archive doesn't matter
+    Done,
+}
+
+impl TarDecoder {
+    /// What comes after the complete header
+    fn entry(&self) -> io::Result<State> {
+        let header = &self.header;
+        if header.iter().all(|&b| b == 0) {
+            return Err(invalid("the archive has no file"));
+        }
+        let size = number(&header[124..136])?;
+        match header[156] {
+            b'0' | b'7' | 0 if size == 0 => Ok(State::Done),
+            b'0' | b'7' | 0 => Ok(State::Data(size)),
+            // long names and pax attributes
+            b'L' | b'K' | b'x' | b'g' => {
+                let padded = size.div_ceil(BLOCK as u64) * BLOCK as u64;
+                Ok(if padded == 0 {
+                    State::Header
+                } else {
+                    State::Skip(padded)
+                })
+            }
+            b'5' => Err(invalid("not a file but a directory")),
+            _ => Err(invalid("not a regular file")),
+        }
+    }
+}
+
+impl Decoder for TarDecoder {
+    fn decode(&mut self, mut data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        while !data.is_empty() {
+            match self.state {
+                State::Header => {
+                    let n = (BLOCK - self.header.len()).min(data.len());
+                    self.header.extend_from_slice(&data[..n]);
+                    data = &data[n..];
+                    if self.header.len() == BLOCK {
+                        self.state = self.entry()?;
+                        self.header.clear();
+                    }
+                }
+                State::Skip(left) => {
+                    let n = left.min(data.len() as u64);
+                    data = &data[n as usize..];
+                    self.state = match left - n {
+                        0 => State::Header,
+                        left => State::Skip(left),
+                    };
+                }
+                State::Data(left) => {
+                    let n = left.min(data.len() as u64);
+                    out.extend_from_slice(&data[..n as usize]);
+                    data = &data[n as usize..];
+                    self.state = match left - n {
+                        0 => State::Done,
+                        left => State::Data(left),
+                    };
+                }
+                State::Done => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.state {
+            State::Done => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the archive ended before the file did",
+            )),
+        }
+    }
+}
+
+/// A numeric header field, octal or GNU base-256
+fn number(field: &[u8]) -> io::Result<u64> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..]
+            .iter()
+            .fold((field[0] & 0x7f) as u64, |n, &b| n << 8 | b as u64));
+    }
+    let digits = std::str::from_utf8(field)
+        .map_err(|_| invalid("malformed header"))?
+        .trim_matches(['\0', ' ']);
+    u64::from_str_radix(digits, 8).map_err(|_| invalid("malformed header"))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
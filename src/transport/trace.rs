@@ -0,0 +1,213 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tracing::{debug, trace};
+
+/// Target of the events `--debug-transport` turns on
+pub const TARGET: &str = "k8scp_rust::transport";
+
+/// A stream that had to wait at least this long is logged as stalled
+const STALL: Duration = Duration::from_millis(100);
+
+/// Exec stream channels, numbered as in the Kubernetes channel protocol
+#[derive(Debug, Clone, Copy)]
+pub enum Channel {
+    Stdin = 0,
+    Stdout = 1,
+    Stderr = 2,
+}
+
+impl Channel {
+    fn name(self) -> &'static str {
+        match self {
+            Channel::Stdin => "stdin",
+            Channel::Stdout => "stdout",
+            Channel::Stderr => "stderr",
+        }
+    }
+}
+
+/// Logs every chunk going through one channel of an exec stream: its size,
+/// when it went through and how long the stream made it wait.
+///
+/// kube passes every channel through a small in-memory pipe between us and
+/// the websocket, frames are at most that pipe's size. A chunk is what went
+/// through the pipe in one go, and a stall means the stdin pipe stayed full,
+/// i.e. the websocket wasn't keeping up with what we write.
+pub struct Traced<S> {
+    inner: S,
+    pod: String,
+    channel: Channel,
+    started: Instant,
+    last: Instant,
+    /// Since when the stream hasn't accepted a write
+    waiting: Option<Instant>,
+    chunks: u64,
+    bytes: u64,
+    stalls: u64,
+    longest_stall: Duration,
+    done: bool,
+}
+
+impl<S> Traced<S> {
+    pub fn new(inner: S, pod: &str, channel: Channel) -> Traced<S> {
+        let now = Instant::now();
+        Traced {
+            inner,
+            pod: pod.to_string(),
+            channel,
+            started: now,
+            last: now,
+            waiting: None,
+            chunks: 0,
+            bytes: 0,
+            stalls: 0,
+            longest_stall: Duration::ZERO,
+            done: false,
+        }
+    }
+
+    fn pending(&mut self) {
+        self.waiting.get_or_insert_with(Instant::now);
+    }
+
+    fn ready(&mut self, len: usize) {
+        let now = Instant::now();
+        if let Some(since) = self.waiting.take() {
+            let waited = now - since;
+            if waited >= STALL {
+                self.stalls += 1;
+                self.longest_stall = self.longest_stall.max(waited);
+                debug!(
+                    target: TARGET,
+                    pod = %self.pod,
+                    channel = self.channel as u8,
+                    waited_ms = waited.as_millis() as u64,
+                    "{} stalled, the stream didn't accept more data",
+                    self.channel.name()
+                );
+            }
+        }
+        if len == 0 {
+            self.close();
+            return;
+        }
+        self.chunks += 1;
+        self.bytes += len as u64;
+        trace!(
+            target: TARGET,
+            pod = %self.pod,
+            channel = self.channel as u8,
+            len,
+            at_ms = (now - self.started).as_millis() as u64,
+            gap_ms = (now - self.last).as_millis() as u64,
+            "{} chunk",
+            self.channel.name()
+        );
+        self.last = now;
+    }
+
+    fn close(&mut self) {
+        if self.done {
+            return;
+        }
+        self.done = true;
+        debug!(
+            target: TARGET,
+            pod = %self.pod,
+            channel = self.channel as u8,
+            chunks = self.chunks,
+            bytes = self.bytes,
+            elapsed_ms = self.started.elapsed().as_millis() as u64,
+            stalls = self.stalls,
+            longest_stall_ms = self.longest_stall.as_millis() as u64,
+            "{} closed",
+            self.channel.name()
+        );
+    }
+}
+
+impl<S> Drop for Traced<S> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Traced<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let ret = Pin::new(&mut self.inner).poll_read(cx, buf);
+        match &ret {
+            Poll::Ready(Ok(())) => {
+                let len = buf.filled().len() - filled;
+                self.ready(len);
+            }
+            Poll::Ready(Err(_)) => self.close(),
+            // waiting on the container is no stall, `gap_ms` shows it
+            Poll::Pending => {}
+        }
+        ret
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Traced<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let ret = Pin::new(&mut self.inner).poll_write(cx, buf);
+        match &ret {
+            // a write of nothing doesn't close the stream
+            Poll::Ready(Ok(n)) if *n > 0 => self.ready(*n),
+            Poll::Ready(_) => {}
+            Poll::Pending => self.pending(),
+        }
+        ret
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let ret = Pin::new(&mut self.inner).poll_shutdown(cx);
+        if ret.is_ready() {
+            self.close();
+        }
+        ret
+    }
+}
+
+/// Logs how long establishing the exec stream to `pod` took
+pub fn connected(pod: &str, since: Instant) {
+    debug!(
+        target: TARGET,
+        pod = %pod,
+        connect_ms = since.elapsed().as_millis() as u64,
+        "exec stream established"
+    );
+}
+
+/// Logs the status the exec stream ended with, kube hands out none when the
+/// stream was closed before the container reported one
+pub fn status(pod: &str, status: Option<&Status>, since: Instant) {
+    debug!(
+        target: TARGET,
+        pod = %pod,
+        channel = 3,
+        status = status.and_then(|s| s.status.as_deref()).unwrap_or("none"),
+        at_ms = since.elapsed().as_millis() as u64,
+        "status received"
+    );
+}
@@ -0,0 +1,669 @@
+mod ui;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use clap_complete::ArgValueCompleter;
+use k8s_openapi::api::core::v1::{Namespace, Pod};
+use kube::{
+    api::{Api, ListParams},
+    Client,
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    widgets::ListState,
+};
+use tokio::{sync::mpsc, time};
+use tokio_util::sync::CancellationToken;
+
+use k8scp::{
+    copy_from_pod, copy_to_pod,
+    files::{self, FileEntry},
+    progress::{Progress, ProgressObserver, Transfer},
+    remote::{self, DirEntry, Kind, Listing},
+    transport::Transport,
+    ExecOptions,
+};
+
+use crate::{complete, Global};
+
+/// Browse a pod and copy files to and from it interactively
+#[derive(clap::Args, Debug)]
+pub struct TuiArgs {
+    /// Start in this pod instead of picking one, requires `--namespace`
+    #[arg(short, long, requires = "namespace", add = ArgValueCompleter::new(complete::pods))]
+    pod: Option<String>,
+}
+
+/// Results of the work done in the background
+enum Msg {
+    Namespaces(anyhow::Result<Vec<String>>),
+    /// Names and phases
+    Pods(anyhow::Result<Vec<(String, String)>>),
+    Remote(anyhow::Result<Listing>),
+    Started(Transfer),
+    Progress(usize, u64),
+    /// A line the container wrote to stderr, with the pod
+    Stderr(String, String),
+    Finished(usize, u64, Result<(), String>),
+}
+
+/// Forwards the progress of the transfers to the event loop
+struct Events(mpsc::UnboundedSender<Msg>);
+
+impl ProgressObserver for Events {
+    fn transfer_started(&self, transfer: &Transfer) {
+        let _ = self.0.send(Msg::Started(transfer.clone()));
+    }
+
+    fn progress(&self, transfer: &Transfer, bytes: u64) {
+        let _ = self.0.send(Msg::Progress(transfer.id, bytes));
+    }
+
+    fn stderr(&self, transfer: &Transfer, line: &str) {
+        let _ = self
+            .0
+            .send(Msg::Stderr(transfer.pod.clone(), line.to_string()));
+    }
+
+    fn transfer_finished(
+        &self,
+        transfer: &Transfer,
+        bytes: u64,
+        result: Result<(), &anyhow::Error>,
+    ) {
+        let result = result.map_err(|err| format!("{:#}", err));
+        let _ = self.0.send(Msg::Finished(transfer.id, bytes, result));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Screen {
+    Namespaces,
+    Pods,
+    Files,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Local,
+    Remote,
+}
+
+enum Status {
+    Info(String),
+    Error(String),
+}
+
+/// A transfer waiting for the overwrite confirmation
+enum Action {
+    Upload(PathBuf),
+    Download(String),
+}
+
+/// A list to pick an entry from
+struct Menu<T> {
+    items: Vec<T>,
+    state: ListState,
+}
+
+impl<T> Default for Menu<T> {
+    fn default() -> Self {
+        Menu {
+            items: Vec::new(),
+            state: ListState::default(),
+        }
+    }
+}
+
+impl<T> Menu<T> {
+    fn set(&mut self, items: Vec<T>) {
+        self.items = items;
+        let selected = self.state.selected().unwrap_or(0);
+        self.state
+            .select((!self.items.is_empty()).then(|| selected.min(self.items.len() - 1)));
+    }
+
+    fn reset(&mut self, items: Vec<T>) {
+        self.state.select(None);
+        self.set(items);
+    }
+
+    fn step(&mut self, delta: isize) {
+        if let Some(selected) = self.state.selected() {
+            let last = self.items.len().saturating_sub(1) as isize;
+            self.state
+                .select(Some((selected as isize + delta).clamp(0, last) as usize));
+        }
+    }
+
+    fn selected(&self) -> Option<&T> {
+        self.items.get(self.state.selected()?)
+    }
+}
+
+/// A transfer shown at the bottom of the screen
+struct TransferView {
+    id: usize,
+    transfer: Transfer,
+    bytes: u64,
+    result: Option<Result<(), String>>,
+}
+
+struct App {
+    client: Client,
+    context: Option<String>,
+    container: String,
+    connect_timeout: Duration,
+    idle_timeout: Duration,
+    umask: Option<u32>,
+    system_paths: Arc<[String]>,
+    allow_system_paths: bool,
+    transport: Arc<dyn Transport>,
+    tx: mpsc::UnboundedSender<Msg>,
+    progress: Arc<Progress>,
+
+    screen: Screen,
+    namespace: Option<String>,
+    pod: Option<String>,
+    namespaces: Menu<String>,
+    pods: Menu<(String, String)>,
+    local_dir: PathBuf,
+    local: Menu<DirEntry>,
+    remote_dir: String,
+    remote: Menu<DirEntry>,
+    focus: Side,
+    transfers: Vec<TransferView>,
+    confirm: Option<(String, Action)>,
+    status: Option<Status>,
+    loading: bool,
+    quit: bool,
+}
+
+/// Runs the TUI until it is quit, in `--namespace` if given instead of
+/// picking one
+pub async fn run(args: &TuiArgs, global: &Global) -> anyhow::Result<()> {
+    let session = global.session().await?;
+    let client = session.client.clone();
+    let context = session.context.clone();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut app = App {
+        client,
+        context,
+        container: global.container.clone(),
+        connect_timeout: global.connect_timeout,
+        idle_timeout: global.idle_timeout,
+        umask: global.umask,
+        system_paths: global.system_paths.clone().into(),
+        allow_system_paths: global.allow_system_paths,
+        transport: global.transport(),
+        progress: Arc::new(Progress::new().observe(Events(tx.clone()))),
+        tx,
+        screen: Screen::Namespaces,
+        namespace: global.namespace.clone(),
+        pod: args.pod.clone(),
+        namespaces: Menu::default(),
+        pods: Menu::default(),
+        local_dir: std::env::current_dir()?,
+        local: Menu::default(),
+        remote_dir: ".".to_string(),
+        remote: Menu::default(),
+        focus: Side::Local,
+        transfers: Vec::new(),
+        confirm: None,
+        status: None,
+        loading: false,
+        quit: false,
+    };
+    match (&app.namespace, &app.pod) {
+        (Some(_), Some(_)) => app.open_files(),
+        (Some(_), None) => app.open_pods(),
+        _ => app.open_namespaces(),
+    }
+
+    // crossterm only reads events blocking
+    let (keys_tx, mut keys) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if keys_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut terminal = ratatui::init();
+    let res = async {
+        while !app.quit {
+            terminal.draw(|frame| ui::draw(frame, &mut app))?;
+            tokio::select! {
+                Some(event) = keys.recv() => app.on_event(event),
+                Some(msg) = rx.recv() => app.on_msg(msg),
+            }
+            // progress comes in bursts, draw once for all of it
+            while let Ok(msg) = rx.try_recv() {
+                app.on_msg(msg);
+            }
+        }
+        anyhow::Ok(())
+    }
+    .await;
+    ratatui::restore();
+    res
+}
+
+impl App {
+    fn api(&self) -> Api<Pod> {
+        Api::namespaced(
+            self.client.clone(),
+            self.namespace.as_deref().unwrap_or("default"),
+        )
+    }
+
+    fn open_namespaces(&mut self) {
+        self.screen = Screen::Namespaces;
+        self.loading = true;
+        let api: Api<Namespace> = Api::all(self.client.clone());
+        let (tx, timeout) = (self.tx.clone(), self.connect_timeout);
+        tokio::spawn(async move {
+            let res = async {
+                let list = time::timeout(timeout, api.list(&ListParams::default())).await??;
+                let names = list.items.into_iter().filter_map(|ns| ns.metadata.name);
+                anyhow::Ok(names.collect())
+            };
+            let _ = tx.send(Msg::Namespaces(res.await));
+        });
+    }
+
+    fn open_pods(&mut self) {
+        self.screen = Screen::Pods;
+        self.loading = true;
+        let api = self.api();
+        let (tx, timeout) = (self.tx.clone(), self.connect_timeout);
+        tokio::spawn(async move {
+            let res = async {
+                let list = time::timeout(timeout, api.list(&ListParams::default())).await??;
+                let pods = list.items.into_iter().filter_map(|pod| {
+                    let phase = pod.status.and_then(|s| s.phase).unwrap_or_default();
+                    Some((pod.metadata.name?, phase))
+                });
+                anyhow::Ok(pods.collect())
+            };
+            let _ = tx.send(Msg::Pods(res.await));
+        });
+    }
+
+    fn open_files(&mut self) {
+        self.screen = Screen::Files;
+        self.remote_dir = ".".to_string();
+        self.remote.reset(Vec::new());
+        self.list_local(self.local_dir.clone());
+        self.list_remote(".".to_string());
+    }
+
+    fn list_local(&mut self, dir: PathBuf) {
+        match list_local(&dir) {
+            Ok(entries) => {
+                if dir != self.local_dir {
+                    self.local.reset(entries);
+                } else {
+                    self.local.set(entries);
+                }
+                self.local_dir = dir;
+            }
+            Err(err) => self.status = Some(Status::Error(format!("{}: {}", dir.display(), err))),
+        }
+    }
+
+    fn list_remote(&mut self, dir: String) {
+        let Some(pod) = self.pod.clone() else {
+            return;
+        };
+        self.loading = true;
+        let api = self.api();
+        let container = self.container.clone();
+        let (tx, timeout) = (self.tx.clone(), self.connect_timeout);
+        tokio::spawn(async move {
+            let res = remote::list(
+                &api,
+                &pod,
+                &container,
+                &dir,
+                timeout,
+                &CancellationToken::new(),
+            )
+            .await;
+            let _ = tx.send(Msg::Remote(res.map_err(Into::into)));
+        });
+    }
+
+    fn on_msg(&mut self, msg: Msg) {
+        match msg {
+            Msg::Namespaces(res) => {
+                self.loading = false;
+                match res {
+                    Ok(namespaces) => self.namespaces.set(namespaces),
+                    Err(err) => self.error("failed to list namespaces", &err),
+                }
+            }
+            Msg::Pods(res) => {
+                self.loading = false;
+                match res {
+                    Ok(pods) => self.pods.set(pods),
+                    Err(err) => self.error("failed to list pods", &err),
+                }
+            }
+            Msg::Remote(res) => {
+                self.loading = false;
+                match res {
+                    Ok(listing) => {
+                        if listing.dir != self.remote_dir {
+                            self.remote.reset(listing.entries);
+                        } else {
+                            self.remote.set(listing.entries);
+                        }
+                        self.remote_dir = listing.dir;
+                    }
+                    Err(err) => self.error("failed to list directory", &err),
+                }
+            }
+            Msg::Started(transfer) => self.transfers.push(TransferView {
+                id: transfer.id,
+                transfer,
+                bytes: 0,
+                result: None,
+            }),
+            Msg::Progress(id, bytes) => {
+                if let Some(view) = self.transfers.iter_mut().find(|v| v.id == id) {
+                    view.bytes = bytes;
+                }
+            }
+            Msg::Stderr(pod, line) => {
+                self.status = Some(Status::Error(format!("{}: {}", pod, line)));
+            }
+            Msg::Finished(id, bytes, result) => {
+                if let Some(view) = self.transfers.iter_mut().find(|v| v.id == id) {
+                    view.bytes = bytes;
+                    self.status = Some(match &result {
+                        Ok(()) => Status::Info(format!("copied {}", view.transfer.src)),
+                        Err(err) => Status::Error(err.clone()),
+                    });
+                    view.result = Some(result);
+                }
+                if self.screen == Screen::Files {
+                    self.list_local(self.local_dir.clone());
+                    self.list_remote(self.remote_dir.clone());
+                }
+            }
+        }
+    }
+
+    fn error(&mut self, what: &str, err: &anyhow::Error) {
+        self.status = Some(Status::Error(format!("{}: {:#}", what, err)));
+    }
+
+    fn on_event(&mut self, event: Event) {
+        let Event::Key(key) = event else {
+            return;
+        };
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        if let Some((_, action)) = self.confirm.take() {
+            if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                self.start(action);
+            }
+            return;
+        }
+        self.status = None;
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            _ => match self.screen {
+                Screen::Namespaces => self.on_namespaces_key(key),
+                Screen::Pods => self.on_pods_key(key),
+                Screen::Files => self.on_files_key(key),
+            },
+        }
+    }
+
+    fn on_namespaces_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.namespaces.step(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.namespaces.step(1),
+            KeyCode::Char('r') => self.open_namespaces(),
+            KeyCode::Enter => {
+                if let Some(namespace) = self.namespaces.selected().cloned() {
+                    self.namespace = Some(namespace);
+                    self.pods.reset(Vec::new());
+                    self.open_pods();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_pods_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.pods.step(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.pods.step(1),
+            KeyCode::Char('r') => self.open_pods(),
+            KeyCode::Esc => self.open_namespaces(),
+            KeyCode::Enter => {
+                if let Some((pod, _)) = self.pods.selected().cloned() {
+                    self.pod = Some(pod);
+                    self.open_files();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_files_key(&mut self, key: KeyEvent) {
+        let pane = match self.focus {
+            Side::Local => &mut self.local,
+            Side::Remote => &mut self.remote,
+        };
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => pane.step(-1),
+            KeyCode::Down | KeyCode::Char('j') => pane.step(1),
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Side::Local => Side::Remote,
+                    Side::Remote => Side::Local,
+                }
+            }
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                let Some(entry) = pane.selected().filter(|e| e.kind == Kind::Dir).cloned() else {
+                    return;
+                };
+                match self.focus {
+                    Side::Local => self.list_local(self.local_dir.join(&entry.name)),
+                    Side::Remote => self.list_remote(files::join(&self.remote_dir, &entry.name)),
+                }
+            }
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => match self.focus {
+                Side::Local => {
+                    if let Some(parent) = self.local_dir.parent() {
+                        self.list_local(parent.to_path_buf());
+                    }
+                }
+                Side::Remote => self.list_remote(remote::parent(&self.remote_dir)),
+            },
+            KeyCode::Char('r') => {
+                self.list_local(self.local_dir.clone());
+                self.list_remote(self.remote_dir.clone());
+            }
+            KeyCode::Char('u') => self.upload(),
+            KeyCode::Char('d') => self.download(),
+            KeyCode::Esc => {
+                self.pods.reset(Vec::new());
+                self.open_pods();
+            }
+            _ => {}
+        }
+    }
+
+    /// Uploads the selected local file into the remote directory
+    fn upload(&mut self) {
+        let Some(entry) = self.local.selected().cloned() else {
+            return;
+        };
+        if entry.kind != Kind::File {
+            self.status = Some(Status::Error(format!("{} is not a file", entry.name)));
+            return;
+        }
+        let action = Action::Upload(self.local_dir.join(&entry.name));
+        if self.remote.items.iter().any(|e| e.name == entry.name) {
+            let question = format!(
+                "overwrite {}? [y/N]",
+                files::join(&self.remote_dir, &entry.name)
+            );
+            self.confirm = Some((question, action));
+        } else {
+            self.start(action);
+        }
+    }
+
+    /// Downloads the selected remote file into the local directory
+    fn download(&mut self) {
+        let Some(entry) = self.remote.selected().cloned() else {
+            return;
+        };
+        if entry.kind != Kind::File {
+            self.status = Some(Status::Error(format!("{} is not a file", entry.name)));
+            return;
+        }
+        let action = Action::Download(entry.name.clone());
+        let local = self.local_dir.join(&entry.name);
+        if local.exists() {
+            self.confirm = Some((format!("overwrite {}? [y/N]", local.display()), action));
+        } else {
+            self.start(action);
+        }
+    }
+
+    fn start(&mut self, action: Action) {
+        let Some(pod) = self.pod.clone() else {
+            return;
+        };
+        let api = self.api();
+        let container = self.container.clone();
+        let (connect_timeout, idle_timeout) = (self.connect_timeout, self.idle_timeout);
+        let umask = self.umask;
+        let (system_paths, allow_system_paths) =
+            (self.system_paths.clone(), self.allow_system_paths);
+        let transport = self.transport.clone();
+        match action {
+            Action::Upload(local) => {
+                let file = match upload_entry(&local, &self.remote_dir) {
+                    Ok(file) => file,
+                    Err(err) => return self.error("failed to upload", &err),
+                };
+                let item = self.progress.item(
+                    &file.local.to_string_lossy(),
+                    &pod,
+                    &file.remote_path(),
+                    file.size,
+                );
+                tokio::spawn(async move {
+                    let opts = ExecOptions {
+                        container: &container,
+                        connect_timeout,
+                        idle_timeout,
+                        transport: &*transport,
+                        umask,
+                        system_paths: Some(&system_paths),
+                        allow_system_paths,
+                        ..ExecOptions::default()
+                    };
+                    let res = copy_to_pod(
+                        &api,
+                        &pod,
+                        &file.local,
+                        &file.remote_path(),
+                        &opts,
+                        item.clone(),
+                    )
+                    .await;
+                    match res {
+                        Ok(_) => item.finish(),
+                        Err(err) => item.fail(&err.into()),
+                    }
+                });
+            }
+            Action::Download(name) => {
+                let src = files::join(&self.remote_dir, &name);
+                let dst = self.local_dir.join(&name);
+                let size = self
+                    .remote
+                    .items
+                    .iter()
+                    .find(|e| e.name == name)
+                    .and_then(|e| e.size)
+                    .unwrap_or(0);
+                let item = self.progress.item(&src, &pod, &dst.to_string_lossy(), size);
+                tokio::spawn(async move {
+                    let opts = ExecOptions {
+                        container: &container,
+                        connect_timeout,
+                        idle_timeout,
+                        transport: &*transport,
+                        ..ExecOptions::default()
+                    };
+                    let res = copy_from_pod(&api, &pod, &src, &dst, &opts, item.clone()).await;
+                    match res {
+                        Ok(_) => item.finish(),
+                        Err(err) => item.fail(&err.into()),
+                    }
+                });
+            }
+        }
+    }
+}
+
+fn upload_entry(local: &Path, remote_dir: &str) -> anyhow::Result<FileEntry> {
+    let meta = fs::metadata(local)?;
+    Ok(FileEntry {
+        local: local.to_path_buf(),
+        remote_dir: remote_dir.to_string(),
+        name: local
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        size: meta.len(),
+    })
+}
+
+fn mode(meta: &fs::Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    return Some(std::os::unix::fs::MetadataExt::mode(meta));
+    #[cfg(not(unix))]
+    return None;
+}
+
+/// Lists the local directory `dir` like [`remote::list`] does
+fn list_local(dir: &Path) -> std::io::Result<Vec<DirEntry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = fs::metadata(entry.path()).ok();
+        let kind = match &meta {
+            Some(meta) if meta.is_dir() => Kind::Dir,
+            Some(meta) if meta.is_file() => Kind::File,
+            _ => Kind::Other,
+        };
+        entries.push(DirEntry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            kind,
+            size: meta.as_ref().filter(|m| m.is_file()).map(|m| m.len()),
+            mode: entry.metadata().ok().as_ref().and_then(mode),
+            modified: meta.and_then(|m| m.modified().ok()),
+        });
+    }
+    entries.sort_by(|a, b| (a.kind != Kind::Dir, &a.name).cmp(&(b.kind != Kind::Dir, &b.name)));
+    Ok(entries)
+}
@@ -0,0 +1,229 @@
+use indicatif::HumanBytes;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, LineGauge, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use super::{App, Menu, Screen, Side, Status};
+use k8scp::remote::{DirEntry, Kind};
+
+/// Transfers listed at the bottom, the most recent ones
+const TRANSFERS: usize = 5;
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let transfers = app.transfers.len().min(TRANSFERS);
+    let [header, body, bottom, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(3),
+        Constraint::Length(if transfers > 0 {
+            transfers as u16 + 2
+        } else {
+            0
+        }),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    draw_header(frame, header, app);
+    match app.screen {
+        Screen::Namespaces => {
+            let items = app
+                .namespaces
+                .items
+                .iter()
+                .map(|ns| ListItem::new(ns.as_str()));
+            draw_menu(
+                frame,
+                body,
+                "Namespaces",
+                items,
+                &mut app.namespaces.state,
+                app.loading,
+            );
+        }
+        Screen::Pods => {
+            let items = app.pods.items.iter().map(|(name, phase)| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{:<40} ", name)),
+                    Span::styled(phase.as_str(), phase_style(phase)),
+                ]))
+            });
+            draw_menu(frame, body, "Pods", items, &mut app.pods.state, app.loading);
+        }
+        Screen::Files => {
+            let [local, remote] =
+                Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .areas(body);
+            let title = format!(" local {} ", app.local_dir.display());
+            draw_pane(
+                frame,
+                local,
+                title,
+                &mut app.local,
+                app.focus == Side::Local,
+            );
+            let title = format!(
+                " {}:{} {}",
+                app.pod.as_deref().unwrap_or_default(),
+                app.remote_dir,
+                if app.loading { "(loading) " } else { "" }
+            );
+            draw_pane(
+                frame,
+                remote,
+                title,
+                &mut app.remote,
+                app.focus == Side::Remote,
+            );
+        }
+    }
+    if transfers > 0 {
+        draw_transfers(frame, bottom, app);
+    }
+    draw_footer(frame, footer, app);
+}
+
+fn draw_header(frame: &mut Frame, area: Rect, app: &App) {
+    let mut spans = vec![Span::styled("k8scp", Style::new().bold().fg(Color::Green))];
+    let fields = [
+        ("context", app.context.as_deref()),
+        ("namespace", app.namespace.as_deref()),
+        (
+            "pod",
+            app.pod.as_deref().filter(|_| app.screen == Screen::Files),
+        ),
+    ];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            spans.push(Span::raw(format!("  {} ", name)).dim());
+            spans.push(Span::raw(value.to_string()).bold());
+        }
+    }
+    frame.render_widget(Line::from(spans), area);
+}
+
+fn draw_menu<'a>(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    items: impl Iterator<Item = ListItem<'a>>,
+    state: &mut ListState,
+    loading: bool,
+) {
+    let title = format!(" {} {}", title, if loading { "(loading) " } else { "" });
+    let list = List::new(items)
+        .block(Block::bordered().title(title).border_style(Color::Cyan))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(list, area, state);
+}
+
+fn draw_pane(
+    frame: &mut Frame,
+    area: Rect,
+    title: String,
+    pane: &mut Menu<DirEntry>,
+    focused: bool,
+) {
+    let items = pane.items.iter().map(|entry| {
+        let name = match entry.kind {
+            Kind::Dir => Span::styled(
+                format!("{}/", entry.name),
+                Style::new().fg(Color::Blue).bold(),
+            ),
+            Kind::File => Span::raw(entry.name.clone()),
+            Kind::Other => Span::raw(entry.name.clone()).dim(),
+        };
+        let size = entry
+            .size
+            .map(|size| HumanBytes(size).to_string())
+            .unwrap_or_default();
+        ListItem::new(Line::from(vec![
+            name,
+            Span::raw("  "),
+            Span::raw(size).dim(),
+        ]))
+    });
+    let border = if focused {
+        Color::Cyan
+    } else {
+        Color::DarkGray
+    };
+    let list = List::new(items)
+        .block(Block::bordered().title(title).border_style(border))
+        .highlight_style(if focused {
+            Style::new().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::new().add_modifier(Modifier::BOLD)
+        })
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(list, area, &mut pane.state);
+}
+
+fn draw_transfers(frame: &mut Frame, area: Rect, app: &App) {
+    let block = Block::bordered()
+        .title(" transfers ")
+        .border_style(Color::DarkGray);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let recent = &app.transfers[app.transfers.len().saturating_sub(TRANSFERS)..];
+    let rows = Layout::vertical(recent.iter().map(|_| Constraint::Length(1))).split(inner);
+    for (view, row) in recent.iter().zip(rows.iter()) {
+        let t = &view.transfer;
+        let ratio = match t.total {
+            0 => 1.0,
+            total => (view.bytes as f64 / total as f64).min(1.0),
+        };
+        let (mark, color) = match &view.result {
+            None => ("…", Color::Cyan),
+            Some(Ok(())) => ("✔", Color::Green),
+            Some(Err(_)) => ("✘", Color::Red),
+        };
+        let label = format!(
+            "{} {} {} -> {} {}/{}",
+            mark,
+            t.pod,
+            t.src,
+            t.dst,
+            HumanBytes(view.bytes),
+            HumanBytes(t.total)
+        );
+        let gauge = LineGauge::default()
+            .ratio(ratio)
+            .label(label)
+            .filled_style(Style::new().fg(color));
+        frame.render_widget(gauge, *row);
+    }
+}
+
+fn draw_footer(frame: &mut Frame, area: Rect, app: &App) {
+    let line = if let Some((question, _)) = &app.confirm {
+        Line::styled(question.as_str(), Style::new().fg(Color::Yellow).bold())
+    } else {
+        match &app.status {
+            Some(Status::Error(msg)) => Line::styled(msg.as_str(), Style::new().fg(Color::Red)),
+            Some(Status::Info(msg)) => Line::styled(msg.as_str(), Style::new().fg(Color::Green)),
+            None => Line::raw(match app.screen {
+                Screen::Namespaces => "↑↓ move  enter select  r refresh  q quit",
+                Screen::Pods => "↑↓ move  enter select  esc namespaces  r refresh  q quit",
+                Screen::Files => {
+                    "tab switch  enter open  backspace up  u upload  d download  r refresh  esc pods  q quit"
+                }
+            })
+            .dim(),
+        }
+    };
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+fn phase_style(phase: &str) -> Style {
+    match phase {
+        "Running" | "Succeeded" => Style::new().fg(Color::Green),
+        "Pending" => Style::new().fg(Color::Yellow),
+        _ => Style::new().fg(Color::Red),
+    }
+}
@@ -0,0 +1,532 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    io::{BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context as _};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::Api;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio_util::sync::CancellationToken;
+use tracing::*;
+
+use k8scp::{
+    copy_from_pod,
+    exec::{self, quote},
+    files::{self, FileEntry, Walk},
+    hash,
+    merge::{self, Action},
+    progress::Progress,
+    remote, verify_from_pod, ExecOptions,
+};
+
+use crate::{
+    cp::{self, Selection},
+    sync::SyncArgs,
+    term, Global,
+};
+
+/// What a two-way sync does with a file changed on both sides
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflicts {
+    /// Copy nothing and list the conflicts
+    Abort,
+    /// Keep the side modified last
+    Newest,
+    /// Ask which side to keep for every conflict
+    Prompt,
+}
+
+/// The digests both sides agreed on after the last two-way sync, by path
+/// relative to the synced directory. A file changed since is one whose
+/// digest differs from its baseline.
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct Baseline {
+    src: String,
+    dst: String,
+    pod: String,
+    files: BTreeMap<String, String>,
+}
+
+/// Syncs the local directory `--src` and its copy in the pod both ways:
+/// what changed on one side since the last two-way sync is copied to the
+/// other, removals included, and files changed on both are resolved as
+/// `--conflicts` says
+pub async fn run(
+    global: &Global,
+    args: &SyncArgs,
+    cancel: &CancellationToken,
+) -> anyhow::Result<ExitCode> {
+    // required unless a subcommand is given
+    let (Some(src), Some(dst)) = (&args.copy.src, &args.copy.dst) else {
+        unreachable!()
+    };
+    let [pod] = args.copy.pod.as_slice() else {
+        bail!(
+            "--two-way syncs with a single pod, {} given",
+            args.copy.pod.len()
+        );
+    };
+    let root = Path::new(src);
+    if !root.is_dir() {
+        bail!("--two-way syncs a directory, {} isn't one", src);
+    }
+    // the directory a copy of the directory ends up in
+    let base = match root.file_name() {
+        Some(name) => files::join(dst, &name.to_string_lossy()),
+        None => dst.clone(),
+    };
+
+    let session = global.session().await?;
+    let api = session.pods(global.namespace());
+    let origin = session.origin(global.namespace(), &global.container);
+    let state = state_path(&[
+        origin.context.as_deref().unwrap_or_default(),
+        origin.cluster.as_deref().unwrap_or_default(),
+        global.namespace(),
+        pod,
+        &global.container,
+        &std::fs::canonicalize(root)?.to_string_lossy(),
+        &base,
+    ])?;
+    let baseline = load(&state)?;
+
+    // both sides, by relative path
+    // what a copy leaves out is left out on both sides
+    let walk = args.copy.walk();
+    let local = local_files(root, dst, &base, &walk, |path| {
+        term::warn(format!("skipping {}, not a regular file", path.display()))
+    })?;
+    let remote_files = remote::files(
+        &api,
+        pod,
+        &global.container,
+        &base,
+        global.connect_timeout,
+        cancel,
+    )
+    .await
+    .context("failed to list the files in the pod")?;
+    // the pod decides what these are, none may lead out of `root`
+    for file in &remote_files {
+        files::local_path(root, file)
+            .with_context(|| format!("{} lists a file outside of {}", pod, base))?;
+    }
+    let remote_files = remote_files.into_iter().filter(|file| !walk.excludes(file));
+    let paths: BTreeSet<_> = local.keys().cloned().chain(remote_files).collect();
+    let paths: Vec<_> = paths.into_iter().collect();
+    let (local_digests, remote_digests) =
+        digests(global, &api, pod, &base, &local, &paths, cancel).await?;
+
+    let mut actions = BTreeMap::new();
+    for (i, path) in paths.iter().enumerate() {
+        let (local, remote) = (&local_digests[i], &remote_digests[i]);
+        let was = baseline.files.get(path);
+        let action = merge::action(local.as_deref(), remote.as_deref(), was.map(String::as_str));
+        if let Some(action) = action {
+            actions.insert(path.clone(), action);
+        }
+    }
+    resolve(global, &api, args, pod, &base, &local, &mut actions, cancel).await?;
+
+    let with = |wanted: Action| {
+        actions
+            .iter()
+            .filter(move |(_, action)| **action == wanted)
+            .map(|(path, _)| path.clone())
+    };
+    let mut failed = 0;
+    failed += pull(global, &api, pod, &base, root, with(Action::Pull), cancel).await;
+    let removed_local: Vec<_> = with(Action::RemoveLocal).collect();
+    for path in &removed_local {
+        let local = files::local_path(root, path)?;
+        match std::fs::remove_file(&local) {
+            Ok(()) => info!("removed {}, it was removed in the pod", local.display()),
+            Err(err) => {
+                failed += 1;
+                term::error(format!("failed to remove {}: {}", local.display(), err));
+            }
+        }
+    }
+    let removed_remote: Vec<_> = with(Action::RemoveRemote)
+        .map(|path| files::join(&base, &path))
+        .collect();
+    if !removed_remote.is_empty() {
+        let command = removed_remote
+            .iter()
+            .fold("rm -f --".to_string(), |command, path| {
+                command + " " + &quote(path)
+            });
+        let removed = exec::run(
+            &api,
+            pod,
+            &global.container,
+            &command,
+            global.connect_timeout,
+            global.idle_timeout,
+            cancel,
+        )
+        .await;
+        match removed {
+            Ok(_) => info!(
+                "removed {} {} in {}, they were removed locally",
+                removed_remote.len(),
+                if removed_remote.len() == 1 {
+                    "file"
+                } else {
+                    "files"
+                },
+                pod
+            ),
+            Err(err) => {
+                failed += removed_remote.len();
+                term::error(format!("failed to remove files in {}: {}", pod, err));
+            }
+        }
+    }
+    let pushed: HashSet<PathBuf> = with(Action::Push)
+        .filter_map(|path| std::fs::canonicalize(&local.get(&path)?.local).ok())
+        .collect();
+    let pushed = if pushed.is_empty() {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        cp::transfer(global, &args.copy, &Selection::Paths(pushed), cancel).await
+    };
+
+    // the new baseline is what both sides agree on now
+    let touched: Vec<_> = actions.keys().cloned().collect();
+    let local_now = local_files(root, dst, &base, &walk, |_| {})?;
+    let (local_now, remote_now) =
+        digests(global, &api, pod, &base, &local_now, &touched, cancel).await?;
+    let mut files = BTreeMap::new();
+    for (i, path) in paths.iter().enumerate() {
+        if let Some(digest) = &local_digests[i] {
+            if !actions.contains_key(path) {
+                files.insert(path.clone(), digest.clone());
+            }
+        }
+    }
+    for (i, path) in touched.iter().enumerate() {
+        match (&local_now[i], &remote_now[i]) {
+            (Some(local), Some(remote)) if local == remote => {
+                files.insert(path.clone(), local.clone());
+            }
+            (None, None) => {}
+            // still differs, a conflict that was skipped or a copy that failed
+            _ => {
+                if let Some(digest) = baseline.files.get(path) {
+                    files.insert(path.clone(), digest.clone());
+                }
+            }
+        }
+    }
+    save(
+        &state,
+        &Baseline {
+            src: src.clone(),
+            dst: base.clone(),
+            pod: pod.clone(),
+            files,
+        },
+    )?;
+
+    let code = pushed?;
+    if !global.quiet {
+        let count = |action| actions.values().filter(|a| **a == action).count();
+        term::success(format!(
+            "synced {} with {}:{}, {} pulled, {} pushed, {} removed locally, {} removed in the pod",
+            src,
+            pod,
+            base,
+            count(Action::Pull),
+            count(Action::Push),
+            removed_local.len(),
+            removed_remote.len()
+        ));
+    }
+    if failed > 0 {
+        bail!("{} of {} changes couldn't be synced", failed, actions.len());
+    }
+    Ok(code)
+}
+
+/// The files below `root` a copy to `dst` takes, by path relative to
+/// `base`, the directory they end up in
+fn local_files(
+    root: &Path,
+    dst: &str,
+    base: &str,
+    walk: &Walk,
+    skipped: impl FnMut(&Path),
+) -> anyhow::Result<BTreeMap<String, FileEntry>> {
+    Ok(files::collect_with(root, dst, walk, skipped)?
+        .into_iter()
+        .map(|file| (relative(base, &file.remote_path()), file))
+        .collect())
+}
+
+/// The digests of `paths` in `local` and below `base` in `pod`, `None`
+/// where a side has no such file. Local files a copy leaves out, like
+/// symlinks or those below `--max-depth`, count as missing.
+async fn digests(
+    global: &Global,
+    api: &Api<Pod>,
+    pod: &str,
+    base: &str,
+    local_files: &BTreeMap<String, FileEntry>,
+    paths: &[String],
+    cancel: &CancellationToken,
+) -> anyhow::Result<(Vec<Option<String>>, Vec<Option<String>>)> {
+    let mut local = Vec::with_capacity(paths.len());
+    for path in paths {
+        local.push(match local_files.get(path) {
+            Some(file) => Some(hash::file(&hash::Sha256, &file.local).await?),
+            None => None,
+        });
+    }
+    let remote_paths: Vec<_> = paths.iter().map(|path| files::join(base, path)).collect();
+    let remote = remote::digests(
+        api,
+        pod,
+        &global.container,
+        &remote_paths,
+        &hash::Sha256,
+        global.connect_timeout,
+        cancel,
+    )
+    .await
+    .context("failed to compare with the pod")?;
+    Ok((local, remote))
+}
+
+/// Settles the conflicts of `actions` as `--conflicts` says
+#[allow(clippy::too_many_arguments)]
+async fn resolve(
+    global: &Global,
+    api: &Api<Pod>,
+    args: &SyncArgs,
+    pod: &str,
+    base: &str,
+    local: &BTreeMap<String, FileEntry>,
+    actions: &mut BTreeMap<String, Action>,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    let conflicts: Vec<_> = actions
+        .iter()
+        .filter(|(_, action)| **action == Action::Conflict)
+        .map(|(path, _)| path.clone())
+        .collect();
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    if args.conflicts == Conflicts::Abort {
+        term::warn(format!(
+            "{} {} changed both locally and in the pod since the last sync:",
+            conflicts.len(),
+            if conflicts.len() == 1 {
+                "file"
+            } else {
+                "files"
+            }
+        ));
+        for path in &conflicts {
+            eprintln!("  {}", path);
+        }
+        bail!("not syncing conflicting changes, pass --conflicts newest or prompt to resolve them");
+    }
+    if args.conflicts == Conflicts::Prompt
+        && (!std::io::stdin().is_terminal() || !std::io::stderr().is_terminal())
+    {
+        bail!(
+            "can't prompt for the conflicts without a terminal, pass --conflicts newest or abort"
+        );
+    }
+
+    for path in conflicts {
+        let remote = files::join(base, &path);
+        let local_modified = local
+            .get(&path)
+            .and_then(|file| std::fs::metadata(&file.local).ok())
+            .and_then(|meta| meta.modified().ok());
+        let remote_modified = remote::stat(
+            api,
+            pod,
+            &global.container,
+            &remote,
+            global.connect_timeout,
+            cancel,
+        )
+        .await?
+        .and_then(|entry| entry.modified);
+        let keep_local = match args.conflicts {
+            // a side the file was removed from is older than one it changed on
+            Conflicts::Newest => {
+                local_modified.unwrap_or(SystemTime::UNIX_EPOCH)
+                    >= remote_modified.unwrap_or(SystemTime::UNIX_EPOCH)
+            }
+            // a skipped conflict stays one, and is left as it is
+            _ => match ask(&path, local_modified, remote_modified)? {
+                Some(keep_local) => keep_local,
+                None => continue,
+            },
+        };
+        let action = match (
+            keep_local,
+            local_modified.is_some(),
+            remote_modified.is_some(),
+        ) {
+            (true, true, _) => Action::Push,
+            (true, false, _) => Action::RemoveRemote,
+            (false, _, true) => Action::Pull,
+            (false, _, false) => Action::RemoveLocal,
+        };
+        actions.insert(path, action);
+    }
+    Ok(())
+}
+
+/// Asks which side of the conflicting `path` to keep, `Some(true)` for the
+/// local one and `None` to skip it
+fn ask(
+    path: &str,
+    local: Option<SystemTime>,
+    remote: Option<SystemTime>,
+) -> anyhow::Result<Option<bool>> {
+    let when = |modified: Option<SystemTime>| match modified {
+        Some(time) => format!("modified {}", humantime::format_rfc3339_seconds(time)),
+        None => "removed".to_string(),
+    };
+    term::warn(format!(
+        "{} changed on both sides, locally {}, in the pod {}",
+        path,
+        when(local),
+        when(remote)
+    ));
+    loop {
+        eprint!("Keep [l]ocal, [r]emote or [s]kip? ");
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+            bail!("no answer for {}", path);
+        }
+        match answer.trim() {
+            "l" | "local" => return Ok(Some(true)),
+            "r" | "remote" => return Ok(Some(false)),
+            "s" | "skip" => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Copies `paths` from `base` in `pod` to `root`, returning how many
+/// failed. A file is replaced only once it was copied completely and
+/// verified.
+async fn pull(
+    global: &Global,
+    api: &Api<Pod>,
+    pod: &str,
+    base: &str,
+    root: &Path,
+    paths: impl Iterator<Item = String>,
+    cancel: &CancellationToken,
+) -> usize {
+    let progress = Arc::new(Progress::new());
+    let transport = global.transport();
+    let opts = ExecOptions {
+        container: &global.container,
+        connect_timeout: global.connect_timeout,
+        idle_timeout: global.idle_timeout,
+        cancel: cancel.clone(),
+        transport: &*transport,
+        ..ExecOptions::default()
+    };
+    let mut failed = 0;
+    for path in paths {
+        let remote = files::join(base, &path);
+        let local = match files::local_path(root, &path) {
+            Ok(local) => local,
+            Err(err) => {
+                failed += 1;
+                term::error(format!("failed to pull {}:{}: {}", pod, remote, err));
+                continue;
+            }
+        };
+        let part = files::part_path(&local);
+        let item = progress.item(&remote, pod, &local.to_string_lossy(), 0);
+        let res = async {
+            if let Some(dir) = local.parent() {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("failed to create {}", dir.display()))?;
+            }
+            let digest = copy_from_pod(api, pod, &remote, &part, &opts, item).await?;
+            let renamed = async {
+                verify_from_pod(api, pod, &remote, &digest, &opts).await?;
+                std::fs::rename(&part, &local)
+                    .with_context(|| format!("failed to replace {}", local.display()))
+            }
+            .await;
+            if renamed.is_err() {
+                let _ = std::fs::remove_file(&part);
+            }
+            renamed
+        }
+        .await;
+        match res {
+            Ok(()) => info!("pulled {}:{} to {}", pod, remote, local.display()),
+            Err(err) => {
+                failed += 1;
+                term::error(format!("failed to pull {}:{}: {:#}", pod, remote, err));
+            }
+        }
+    }
+    failed
+}
+
+/// `path` below the remote directory `base`, relative to it
+fn relative(base: &str, path: &str) -> String {
+    path.strip_prefix(base.trim_end_matches('/'))
+        .unwrap_or(path)
+        .trim_start_matches('/')
+        .to_string()
+}
+
+/// Where the baseline of the sync identified by `key` is kept
+fn state_path(key: &[&str]) -> anyhow::Result<PathBuf> {
+    let Some(dir) = dirs::data_dir() else {
+        bail!("no user data directory to keep the state of two-way syncs in");
+    };
+    let id = format!("{:x}", Sha256::digest(key.join("\0")));
+    Ok(dir
+        .join("k8scp")
+        .join("sync")
+        .join(format!("{}.json", &id[..16])))
+}
+
+fn load(path: &Path) -> anyhow::Result<Baseline> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .with_context(|| format!("failed to parse sync state {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Baseline::default()),
+        Err(err) => {
+            Err(err).with_context(|| format!("failed to read sync state {}", path.display()))
+        }
+    }
+}
+
+fn save(path: &Path, baseline: &Baseline) -> anyhow::Result<()> {
+    let write = || {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let part = path.with_extension("json.part");
+        std::fs::write(&part, serde_json::to_vec_pretty(baseline)?)?;
+        std::fs::rename(&part, path)?;
+        anyhow::Ok(())
+    };
+    write().with_context(|| format!("failed to write sync state {}", path.display()))
+}
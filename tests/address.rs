@@ -0,0 +1,64 @@
+use k8scp::address::{is_windows_path, parse};
+
+#[test]
+fn pod_paths_are_parsed() {
+    for (s, pod, path) in [
+        ("web-0:/srv/app", "web-0", "/srv/app"),
+        ("web-0:app.conf", "web-0", "app.conf"),
+        ("web-0:~/app.conf", "web-0", "~/app.conf"),
+        ("web-0.prod:/a:b", "web-0.prod", "/a:b"),
+        ("pod/x:/srv", "x", "/srv"),
+        ("pod/c:\\data", "c", "\\data"),
+        ("pod/web-0:/srv", "web-0", "/srv"),
+        ("sts/db:0:/var/lib/db", "db-0", "/var/lib/db"),
+        ("statefulset/db:12:/data", "db-12", "/data"),
+        ("statefulsets/db:1:data", "db-1", "data"),
+    ] {
+        let target = parse(s).unwrap_or_else(|err| panic!("{}: {}", s, err));
+        assert_eq!(
+            (target.pod.as_str(), target.path.as_str()),
+            (pod, path),
+            "{}",
+            s
+        );
+        assert_eq!(target.to_string(), format!("{}:{}", pod, path));
+    }
+}
+
+#[test]
+fn malformed_pod_paths_are_refused() {
+    for (s, message) in [
+        ("web-0", "expected POD:PATH"),
+        ("web-0:", "expected POD:PATH"),
+        (":/srv", "expected POD:PATH"),
+        ("Web-0:/srv", "Web-0 isn't a pod name"),
+        ("web-:/srv", "web- isn't a pod name"),
+        // there is no namespace in a target, see --namespace
+        ("prod/web-0:/srv", "prod/web-0 isn't a pod name"),
+        ("sts/db:/data", "expected sts/NAME:ORDINAL:PATH"),
+        ("sts/db:0:", "expected sts/NAME:ORDINAL:PATH"),
+        ("sts/db:first:/data", "first isn't the ordinal of a pod"),
+        ("sts/DB:0:/data", "DB isn't the name of a StatefulSet"),
+    ] {
+        let err = parse(s).map(|target| target.to_string()).unwrap_err();
+        assert!(err.contains(message), "{}: {}", s, err);
+    }
+}
+
+#[test]
+fn windows_paths_are_local() {
+    for s in [
+        "C:\\data",
+        "C:data",
+        "c:/data",
+        "\\\\server\\share",
+        "\\\\?\\C:\\data",
+    ] {
+        assert!(is_windows_path(s), "{}", s);
+        let err = parse(s).map(|target| target.to_string()).unwrap_err();
+        assert!(err.contains("is a local path"), "{}: {}", s, err);
+    }
+    for s in ["web-0:/srv", "/srv/app", "pod/c:/data", "1:/srv"] {
+        assert!(!is_windows_path(s), "{}", s);
+    }
+}
@@ -0,0 +1,417 @@
+//! A mock of the Kubernetes API for the integration tests.
+//!
+//! [`MockApi`] is a tower service answering what the copies ask the API
+//! server: pods, the pod list and the exec subresource. It is served on a
+//! local port since kube upgrades exec requests to websockets over a real
+//! connection. Execs are answered by scripted [`Exchange`]s and recorded, so
+//! tests check what was sent to a container as well as how its answers are
+//! handled.
+
+#![allow(dead_code)]
+
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::{
+    header,
+    server::conn::Http,
+    upgrade::{OnUpgrade, Upgraded},
+    Body, Request, Response, StatusCode,
+};
+use serde_json::{json, Value};
+use tokio_tungstenite::{
+    tungstenite::{handshake::derive_accept_key, protocol::Role, Message},
+    WebSocketStream,
+};
+
+/// An exec made in a pod
+#[derive(Debug, Clone)]
+pub struct Exec {
+    pub pod: String,
+    pub container: Option<String>,
+    /// The `sh -c` command
+    pub command: String,
+    /// What was sent to stdin
+    pub stdin: Vec<u8>,
+}
+
+/// How the container answers an exec
+#[derive(Debug, Clone, Default)]
+pub struct Exchange {
+    stdout: Vec<u8>,
+    stderr: String,
+    exit_code: Option<i32>,
+    /// Answers without reading stdin
+    eager: bool,
+    /// How long the container takes to answer
+    delay: Duration,
+}
+
+impl Exchange {
+    /// Exits successfully without output
+    pub fn success() -> Exchange {
+        Exchange::default()
+    }
+
+    pub fn stdout(stdout: impl Into<Vec<u8>>) -> Exchange {
+        Exchange {
+            stdout: stdout.into(),
+            ..Exchange::default()
+        }
+    }
+
+    /// Exits with `exit_code` after writing `stderr`
+    pub fn failure(exit_code: i32, stderr: &str) -> Exchange {
+        Exchange {
+            stderr: stderr.to_string(),
+            exit_code: Some(exit_code),
+            ..Exchange::default()
+        }
+    }
+
+    /// Answers right away like a command that doesn't read stdin, rather
+    /// than once stdin is closed
+    pub fn eager(self) -> Exchange {
+        Exchange {
+            eager: true,
+            ..self
+        }
+    }
+
+    /// Answers only after `delay`, like a wedged container
+    pub fn after(self, delay: Duration) -> Exchange {
+        Exchange { delay, ..self }
+    }
+
+    fn status(&self) -> Value {
+        match self.exit_code {
+            None | Some(0) => json!({"metadata": {}, "status": "Success"}),
+            Some(code) => json!({
+                "metadata": {},
+                "status": "Failure",
+                "message": format!("command terminated with non-zero exit code: {}", code),
+                "reason": "NonZeroExitCode",
+                "details": {"causes": [{"reason": "ExitCode", "message": code.to_string()}]},
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    pods: Vec<String>,
+    /// Answers by a part of the command they are for, used once each in
+    /// the order they were added
+    exchanges: VecDeque<(String, Exchange)>,
+    execs: Vec<Exec>,
+    /// Execs accepted and not answered yet
+    running: usize,
+    forbidden: bool,
+}
+
+/// The mock API server, running until dropped
+pub struct MockApi {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl MockApi {
+    /// Serves the namespace `default` with `pods` in it
+    pub async fn start<I, S>(pods: I) -> MockApi
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let state = Arc::new(Mutex::new(State {
+            pods: pods.into_iter().map(Into::into).collect(),
+            ..State::default()
+        }));
+        let service = {
+            let state = state.clone();
+            tower::service_fn(move |req| handle(state.clone(), req))
+        };
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let connection = Http::new()
+                    .http1_only(true)
+                    .serve_connection(stream, service.clone())
+                    .with_upgrades();
+                tokio::spawn(connection);
+            }
+        });
+        MockApi {
+            addr,
+            state,
+            server,
+        }
+    }
+
+    /// A client of the mock
+    pub fn client(&self) -> kube::Client {
+        let url = format!("http://{}", self.addr).parse().unwrap();
+        kube::Client::try_from(kube::Config::new(url)).unwrap()
+    }
+
+    /// Answers the next exec whose command contains `command` with
+    /// `exchange`. Execs nothing was scripted for succeed without output.
+    pub fn on(&self, command: &str, exchange: Exchange) -> &MockApi {
+        let mut state = self.state.lock().unwrap();
+        state.exchanges.push_back((command.to_string(), exchange));
+        self
+    }
+
+    /// Refuses every exec like RBAC without `pods/exec` does
+    pub fn forbid_exec(&self) {
+        self.state.lock().unwrap().forbidden = true;
+    }
+
+    /// Waits for the execs in flight to be answered. Copies through `cat`
+    /// return before the container got everything, see
+    /// `Codec::confirms`.
+    pub async fn settled(&self) {
+        for _ in 0..500 {
+            if self.state.lock().unwrap().running == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("execs still running");
+    }
+
+    /// The execs made so far
+    pub fn execs(&self) -> Vec<Exec> {
+        self.state.lock().unwrap().execs.clone()
+    }
+
+    /// The execs made so far whose command contains `command`
+    pub fn execs_of(&self, command: &str) -> Vec<Exec> {
+        let execs = self.execs();
+        execs
+            .into_iter()
+            .filter(|exec| exec.command.contains(command))
+            .collect()
+    }
+}
+
+impl Drop for MockApi {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let path = req.uri().path().to_string();
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let pods = state.lock().unwrap().pods.clone();
+    let response = match parts.as_slice() {
+        ["api", "v1", "namespaces"] => ok(json!({
+            "kind": "NamespaceList",
+            "apiVersion": "v1",
+            "metadata": {},
+            "items": [{"metadata": {"name": "default"}}],
+        })),
+        ["api", "v1", "namespaces", "default", "pods"] => ok(json!({
+            "kind": "PodList",
+            "apiVersion": "v1",
+            "metadata": {},
+            "items": pods.iter().map(|name| pod(name)).collect::<Vec<_>>(),
+        })),
+        ["api", "v1", "namespaces", "default", "pods", name] if pods.iter().any(|p| p == name) => {
+            ok(pod(name))
+        }
+        ["api", "v1", "namespaces", "default", "pods", name, "exec"]
+            if pods.iter().any(|p| p == name) =>
+        {
+            if state.lock().unwrap().forbidden {
+                failure(StatusCode::FORBIDDEN, "pods/exec is forbidden")
+            } else {
+                exec(state, name.to_string(), req)
+            }
+        }
+        ["api", "v1", "namespaces", "default", "pods", name, ..] => failure(
+            StatusCode::NOT_FOUND,
+            &format!("pods \"{}\" not found", name),
+        ),
+        _ => failure(StatusCode::NOT_FOUND, "not found"),
+    };
+    Ok(response)
+}
+
+fn pod(name: &str) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {"name": name, "namespace": "default"},
+        "spec": {"containers": [{"name": "app", "image": "busybox", "workingDir": "/srv"}]},
+        "status": {"phase": "Running"},
+    })
+}
+
+fn ok(body: Value) -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn failure(status: StatusCode, message: &str) -> Response<Body> {
+    let body = json!({
+        "kind": "Status",
+        "apiVersion": "v1",
+        "status": "Failure",
+        "message": message,
+        "code": status.as_u16(),
+    });
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// Accepts the websocket of an exec and answers it in the background
+fn exec(state: Arc<Mutex<State>>, pod: String, mut req: Request<Body>) -> Response<Body> {
+    let query = req.uri().query().unwrap_or_default().to_string();
+    let params: Vec<(String, String)> = url_params(&query);
+    let param = |key: &'static str| {
+        params
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    };
+    // `sh -c <command>`
+    let command = param("command").nth(2).unwrap_or_default();
+    let stdin = param("stdin").any(|v| v == "true");
+    let exec = Exec {
+        pod,
+        container: param("container").next(),
+        command,
+        stdin: Vec::new(),
+    };
+    let Some(key) = req.headers().get(header::SEC_WEBSOCKET_KEY).cloned() else {
+        return failure(StatusCode::BAD_REQUEST, "not a websocket request");
+    };
+    let upgrade = req.extensions_mut().remove::<OnUpgrade>().unwrap();
+    state.lock().unwrap().running += 1;
+    tokio::spawn(async move {
+        let upgraded = upgrade.await.unwrap();
+        let ws = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+        answer(state, ws, exec, stdin).await;
+    });
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "Upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(
+            header::SEC_WEBSOCKET_ACCEPT,
+            derive_accept_key(key.as_bytes()),
+        )
+        .header(header::SEC_WEBSOCKET_PROTOCOL, "v4.channel.k8s.io")
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Reads stdin like the command would, then sends the scripted answer
+async fn answer(
+    state: Arc<Mutex<State>>,
+    mut ws: WebSocketStream<Upgraded>,
+    mut exec: Exec,
+    stdin: bool,
+) {
+    let exchange = {
+        let mut state = state.lock().unwrap();
+        let found = state
+            .exchanges
+            .iter()
+            .position(|(command, _)| exec.command.contains(command.as_str()));
+        found
+            .and_then(|i| state.exchanges.remove(i))
+            .map(|(_, exchange)| exchange)
+            .unwrap_or_default()
+    };
+    if stdin && !exchange.eager {
+        // commands behind `head -c` answer once they have that much, others
+        // when stdin is closed
+        let expected = head_len(&exec.command);
+        while expected.is_none_or(|len| exec.stdin.len() < len) {
+            match ws.next().await {
+                Some(Ok(Message::Binary(data))) if data.first() == Some(&0) => {
+                    exec.stdin.extend_from_slice(&data[1..]);
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+    {
+        let mut state = state.lock().unwrap();
+        state.execs.push(exec);
+        state.running -= 1;
+    }
+    tokio::time::sleep(exchange.delay).await;
+
+    let frame = |channel: u8, data: &[u8]| {
+        let mut frame = vec![channel];
+        frame.extend_from_slice(data);
+        Message::Binary(frame)
+    };
+    let mut frames = Vec::new();
+    for chunk in exchange.stdout.chunks(16 * 1024) {
+        frames.push(frame(1, chunk));
+    }
+    if !exchange.stderr.is_empty() {
+        frames.push(frame(2, exchange.stderr.as_bytes()));
+    }
+    frames.push(frame(3, exchange.status().to_string().as_bytes()));
+    for frame in frames {
+        if ws.send(frame).await.is_err() {
+            return;
+        }
+    }
+    let _ = ws.close(None).await;
+}
+
+/// `N` of a `head -c N` in `command`
+fn head_len(command: &str) -> Option<usize> {
+    let rest = command.split("head -c ").nth(1)?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+fn url_params(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (decode(k), decode(v)))
+        .collect()
+}
+
+/// Percent-decodes a query component
+fn decode(s: &str) -> String {
+    let mut out = Vec::new();
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+                out.push(u8::from_str_radix(&hex, 16).unwrap_or(b'?'));
+            }
+            b => out.push(b),
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+use k8scp::{
+    files::{self, Walk},
+    Error,
+};
+use tempfile::TempDir;
+
+/// A directory `app` with a file at each of `paths`
+fn tree(paths: &[&str]) -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("app");
+    for path in paths {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, path.to_string_lossy().as_bytes()).unwrap();
+    }
+    (dir, root)
+}
+
+#[test]
+fn listed_files_go_where_collect_puts_them() {
+    let (_dir, root) = tree(&["a.txt", "conf/b.yaml", "conf/c.yaml"]);
+    let listed = ["./conf/b.yaml", "a.txt", "conf/b.yaml", "conf"].map(PathBuf::from);
+    let mut skipped = Vec::new();
+    let files = files::listed(&root, "/srv", &listed, |path| {
+        skipped.push(path.to_path_buf())
+    })
+    .unwrap();
+
+    let remote: Vec<_> = files.iter().map(|file| file.remote_path()).collect();
+    assert_eq!(remote, ["/srv/app/conf/b.yaml", "/srv/app/a.txt"]);
+    assert_eq!(skipped, [root.join("conf")]);
+    let collected = files::collect(&root, "/srv", |_| {}).unwrap();
+    assert!(collected
+        .iter()
+        .any(|file| file.remote_path() == "/srv/app/conf/b.yaml"));
+}
+
+#[test]
+fn listed_paths_stay_below_the_directory() {
+    let (_dir, root) = tree(&["a.txt"]);
+    for path in ["../app/a.txt", "/etc/passwd"] {
+        let err = files::listed(&root, "/srv", &[PathBuf::from(path)], |_| {}).unwrap_err();
+        assert!(matches!(err, Error::Config(_)), "{}: {:?}", path, err);
+    }
+    let err = files::listed(&root, "/srv", &[PathBuf::from("b.txt")], |_| {}).unwrap_err();
+    assert!(matches!(err, Error::Io { ref path, .. } if *path == root.join("b.txt")));
+}
+
+#[test]
+fn dirs_are_where_their_files_would_go() {
+    let (_dir, root) = tree(&["conf/b.yaml"]);
+    std::fs::create_dir_all(root.join("logs/old")).unwrap();
+    let dirs = files::dirs(&root, "/srv", &Walk::default()).unwrap();
+    assert_eq!(
+        dirs,
+        [
+            "/srv/app",
+            "/srv/app/conf",
+            "/srv/app/logs",
+            "/srv/app/logs/old"
+        ]
+    );
+    assert!(
+        files::dirs(&root.join("conf/b.yaml"), "/srv", &Walk::default())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn walk_stops_at_the_max_depth() {
+    let (_dir, root) = tree(&["a.txt", "conf/b.yaml", "conf/tls/c.pem"]);
+    let names = |max_depth| {
+        let walk = Walk {
+            max_depth,
+            ..Walk::default()
+        };
+        let files = files::collect_with(&root, "/srv", &walk, |_| {}).unwrap();
+        files.into_iter().map(|file| file.name).collect::<Vec<_>>()
+    };
+    assert_eq!(names(Some(1)), ["a.txt"]);
+    assert_eq!(names(Some(2)), ["a.txt", "b.yaml"]);
+    assert_eq!(names(None), ["a.txt", "b.yaml", "c.pem"]);
+}
+
+#[test]
+fn walk_leaves_out_the_excluded_names() {
+    let (_dir, root) = tree(&["src/main.rs", ".git/HEAD", "web/node_modules/x/index.js"]);
+    let walk = Walk {
+        exclude: files::VCS_EXCLUDES
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        ..Walk::default()
+    };
+    let files = files::collect_with(&root, "/srv", &walk, |_| {}).unwrap();
+    let remote: Vec<_> = files.iter().map(|file| file.remote_path()).collect();
+    assert_eq!(remote, ["/srv/app/src/main.rs"]);
+    assert!(walk.excludes("web/node_modules/x/index.js"));
+    assert!(!walk.excludes("web/modules/index.js"));
+    // unless it is the directory copied
+    let files = files::collect_with(&root.join(".git"), "/srv", &walk, |_| {}).unwrap();
+    assert_eq!(files.len(), 1);
+}
+
+#[test]
+fn remote_relative_paths_go_below_the_local_directory() {
+    let dir = PathBuf::from("downloads");
+    assert_eq!(
+        files::local_path(&dir, "logs/./app//today.log").unwrap(),
+        dir.join("logs").join("app").join("today.log")
+    );
+    assert_eq!(files::local_path(&dir, "").unwrap(), dir);
+}
+
+#[test]
+fn preserve_applies_the_mode_and_mtime() {
+    let (_dir, root) = tree(&["run.sh"]);
+    let path = root.join("run.sh");
+    let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+    files::preserve(&path, Some(0o100555), Some(modified)).unwrap();
+
+    let meta = std::fs::metadata(&path).unwrap();
+    assert_eq!(meta.modified().unwrap(), modified);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(meta.permissions().mode() & 0o7777, 0o555);
+    }
+}
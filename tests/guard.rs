@@ -0,0 +1,89 @@
+use k8scp::guard::{self, dangerous, system_path};
+
+#[test]
+fn files_right_in_system_dirs_are_refused() {
+    let denied = guard::system_paths();
+    for (path, dir) in [
+        ("/app.tar", "/"),
+        ("/etc/passwd", "/etc"),
+        ("/etc", "/"),
+        ("/usr/bin/sh", "/usr/bin"),
+        ("/usr/local/app", "/usr/local"),
+        ("/proc/1", "/proc"),
+        ("/var/log/app.log", "/var/log"),
+    ] {
+        assert_eq!(system_path(path, &denied), Some(dir), "{}", path);
+    }
+}
+
+#[test]
+fn files_below_system_dirs_are_fine() {
+    let denied = guard::system_paths();
+    for path in [
+        "/etc/app/app.conf",
+        "/etc/nginx/conf.d/site.conf",
+        "/usr/share/nginx/html/index.html",
+        "/srv/app.tar",
+        "/tmp/app.tar",
+        "etc/passwd",
+        "app.tar",
+    ] {
+        assert_eq!(system_path(path, &denied), None, "{}", path);
+    }
+}
+
+#[test]
+fn paths_are_normalized_before_matching() {
+    let denied = guard::system_paths();
+    assert_eq!(system_path("/srv/../etc/passwd", &denied), Some("/etc"));
+    assert_eq!(system_path("//etc//./passwd", &denied), Some("/etc"));
+    assert_eq!(system_path("/usr/bin/../bin/sh", &denied), Some("/usr/bin"));
+    assert_eq!(system_path("/../../app.tar", &denied), Some("/"));
+    // the parent, not the file, is matched
+    assert_eq!(system_path("/etc/app/", &denied), Some("/etc"));
+}
+
+#[test]
+fn the_list_can_be_replaced() {
+    let denied = ["srv/", "/opt"];
+    assert_eq!(system_path("/srv/app.tar", &denied), Some("srv/"));
+    assert_eq!(system_path("/opt", &denied), Some("/opt"));
+    assert_eq!(system_path("/etc/passwd", &denied), None);
+    assert_eq!(system_path("/app.tar", &denied), None);
+}
+
+#[test]
+fn removals_that_break_the_container_are_refused() {
+    for (path, why) in [
+        ("/", "it is the root directory"),
+        ("//", "it is the root directory"),
+        ("/./", "it is the root directory"),
+        ("/usr", "it is a top-level directory"),
+        ("/etc/", "it is a top-level directory"),
+        ("/home", "it is a top-level directory"),
+        ("/usr/bin", "it is a system directory"),
+        ("/var/lib/", "it is a system directory"),
+        ("/srv/..", "it goes up with `..`"),
+        ("..", "it goes up with `..`"),
+        ("app/../../etc", "it goes up with `..`"),
+        (".", "it is the working directory of the container"),
+        ("", "it is the working directory of the container"),
+    ] {
+        assert_eq!(dangerous(path), Some(why), "{:?}", path);
+    }
+}
+
+#[test]
+fn other_removals_are_fine() {
+    for path in [
+        "/srv/app",
+        "/etc/app/app.conf",
+        "/usr/local/app",
+        "/var/lib/app",
+        "/tmp/app.tar",
+        "app.tar",
+        "./logs",
+    ] {
+        assert_eq!(dangerous(path), None, "{:?}", path);
+    }
+}
@@ -0,0 +1,52 @@
+use k8scp::merge::{action, Action};
+
+#[test]
+fn the_first_sync_copies_what_one_side_has() {
+    // no baseline yet
+    for (local, remote, expected) in [
+        (Some("a"), None, Some(Action::Push)),
+        (None, Some("a"), Some(Action::Pull)),
+        (Some("a"), Some("a"), None),
+        (Some("a"), Some("b"), Some(Action::Conflict)),
+    ] {
+        assert_eq!(
+            action(local, remote, None),
+            expected,
+            "local {:?}, remote {:?}",
+            local,
+            remote
+        );
+    }
+}
+
+#[test]
+fn changes_since_the_last_sync_go_to_the_other_side() {
+    for (local, remote, was, expected) in [
+        // unchanged
+        (Some("a"), Some("a"), Some("a"), None),
+        // changed on one side
+        (Some("b"), Some("a"), Some("a"), Some(Action::Push)),
+        (Some("a"), Some("b"), Some("a"), Some(Action::Pull)),
+        // removed on one side
+        (None, Some("a"), Some("a"), Some(Action::RemoveRemote)),
+        (Some("a"), None, Some("a"), Some(Action::RemoveLocal)),
+        // removed on both
+        (None, None, Some("a"), None),
+        // changed the same way on both
+        (Some("b"), Some("b"), Some("a"), None),
+        // changed on both
+        (Some("b"), Some("c"), Some("a"), Some(Action::Conflict)),
+        // changed on one side and removed on the other
+        (Some("b"), None, Some("a"), Some(Action::Conflict)),
+        (None, Some("b"), Some("a"), Some(Action::Conflict)),
+    ] {
+        assert_eq!(
+            action(local, remote, was),
+            expected,
+            "local {:?}, remote {:?}, was {:?}",
+            local,
+            remote,
+            was
+        );
+    }
+}
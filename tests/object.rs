@@ -0,0 +1,115 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+use common::{Exchange, MockApi};
+use hyper::{server::conn::Http, Body, Method, Request, Response, StatusCode};
+use k8scp::{CopyRequest, Error, Location};
+use tempfile::TempDir;
+
+/// The objects of a store, by path
+type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+/// Serves `GET`, `HEAD` and `PUT` of the objects in memory, returning its
+/// URL
+async fn store(objects: Objects) -> String {
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let objects = objects.clone();
+            let service = hyper::service::service_fn(move |req: Request<Body>| {
+                let objects = objects.clone();
+                async move {
+                    let path = req.uri().path().to_string();
+                    let response = match *req.method() {
+                        Method::PUT => {
+                            let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                            objects.lock().unwrap().insert(path, body.to_vec());
+                            Response::new(Body::empty())
+                        }
+                        _ => match objects.lock().unwrap().get(&path) {
+                            Some(data) => Response::new(Body::from(data.clone())),
+                            None => Response::builder()
+                                .status(StatusCode::NOT_FOUND)
+                                .body(Body::empty())
+                                .unwrap(),
+                        },
+                    };
+                    Ok::<_, Infallible>(response)
+                }
+            });
+            tokio::spawn(
+                Http::new()
+                    .http1_only(true)
+                    .serve_connection(stream, service),
+            );
+        }
+    });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn pod_files_are_written_below_a_prefix_and_read_back() {
+    let objects = Objects::default();
+    let url = store(objects.clone()).await;
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on("cat --", Exchange::stdout("line 1\nline 2\n"));
+
+    CopyRequest::builder()
+        .client(mock.client())
+        .source(Location::pod("web-0", "/var/log/app log"))
+        .destination(Location::url(format!("{}/logs/", url)))
+        .build()
+        .unwrap()
+        .start()
+        .wait()
+        .await
+        .unwrap();
+    assert_eq!(
+        objects.lock().unwrap()["/logs/app%20log"],
+        b"line 1\nline 2\n"
+    );
+
+    let dir = TempDir::new().unwrap();
+    let summary = CopyRequest::builder()
+        .source(Location::url(format!("{}/logs/app%20log", url)))
+        .destination(Location::local(dir.path()))
+        .build()
+        .unwrap()
+        .start()
+        .wait()
+        .await
+        .unwrap();
+    assert_eq!(
+        std::fs::read(dir.path().join("app log")).unwrap(),
+        b"line 1\nline 2\n"
+    );
+    assert_eq!(summary.files[0].bytes, 14);
+}
+
+#[tokio::test]
+async fn missing_object_is_a_storage_error() {
+    let url = store(Objects::default()).await;
+    let dir = TempDir::new().unwrap();
+
+    let res = CopyRequest::builder()
+        .source(Location::url(format!("{}/nope.tar", url)))
+        .destination(Location::local(dir.path()))
+        .build()
+        .unwrap()
+        .start()
+        .wait()
+        .await;
+
+    match res {
+        Err(Error::Storage(message)) => assert!(message.contains("not found"), "{}", message),
+        res => panic!("unexpected result {:?}", res),
+    }
+}
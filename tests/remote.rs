@@ -0,0 +1,437 @@
+mod common;
+
+use common::{Exchange, MockApi};
+use k8s_openapi::api::core::v1::Pod;
+use k8scp::{
+    exec,
+    files::{self, Walk},
+    hash::Sha256,
+    remote, Error,
+};
+use kube::api::Api;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn digests_line_up_with_the_paths() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "sha256sum",
+        Exchange::stdout("0 aaaa  /srv/a\n2 cccc  /srv/c d\n"),
+    );
+    let paths = ["/srv/a", "/srv/b", "/srv/c d"].map(String::from);
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let digests = remote::digests(
+        &pods,
+        "web-0",
+        "",
+        &paths,
+        &Sha256,
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        digests,
+        [Some("aaaa".to_string()), None, Some("cccc".to_string())]
+    );
+    let command = &mock.execs()[0].command;
+    assert!(command.contains("sha256sum -- '/srv/c d'"), "{}", command);
+}
+
+#[tokio::test]
+async fn files_are_relative_and_sorted() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on("find . -type f", Exchange::stdout("./b\n./a/x y\n"));
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let files = remote::files(
+        &pods,
+        "web-0",
+        "",
+        "/srv",
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(files, ["a/x y", "b"]);
+}
+
+#[tokio::test]
+async fn listed_paths_outside_the_local_dir_are_refused() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "find . -type f",
+        Exchange::stdout("./app.conf\n./../escape\n"),
+    );
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let listed = remote::files(
+        &pods,
+        "web-0",
+        "",
+        "/srv",
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(listed, ["../escape", "app.conf"]);
+
+    let dst = std::path::Path::new("/tmp/pulled");
+    let escape = files::local_path(dst, &listed[0]);
+    assert!(matches!(escape, Err(Error::Config(_))), "{:?}", escape);
+    assert_eq!(
+        files::local_path(dst, &listed[1]).unwrap(),
+        dst.join("app.conf")
+    );
+    assert!(files::local_path(dst, "a/../../b").is_err());
+    // absolute paths stay below `dst`
+    assert_eq!(
+        files::local_path(dst, "/etc/passwd").unwrap(),
+        dst.join("etc").join("passwd")
+    );
+}
+
+#[tokio::test]
+async fn find_groups_the_expression() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "find . -type f",
+        Exchange::stdout("./heap/b.hprof\n./a.hprof\n"),
+    );
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let files = remote::find(
+        &pods,
+        "web-0",
+        "",
+        "/srv",
+        "-name '*.hprof' -o -name core",
+        &Walk::default(),
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(files, ["a.hprof", "heap/b.hprof"]);
+    let command = &mock.execs()[0].command;
+    assert!(
+        command
+            .contains("cd '/srv' && find . -type f \\( -name '*.hprof' -o -name core \\) -print"),
+        "{}",
+        command
+    );
+}
+
+#[tokio::test]
+async fn find_walks_as_told() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on("find .", Exchange::stdout("./app.yaml\n"));
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    remote::find(
+        &pods,
+        "web-0",
+        "",
+        "/etc/app",
+        "-true",
+        &Walk {
+            max_depth: Some(1),
+            one_file_system: true,
+            ..Walk::default()
+        },
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+
+    let command = &mock.execs()[0].command;
+    assert!(
+        command.contains("find . -maxdepth 1 -xdev -type f \\( -true \\)"),
+        "{}",
+        command
+    );
+}
+
+#[tokio::test]
+async fn stat_reads_mode_size_and_mtime() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "stat -c",
+        Exchange::stdout("f 81a4 3 1700000000 /srv/a b\n"),
+    );
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let entry = remote::stat(
+        &pods,
+        "web-0",
+        "",
+        "/srv/a b",
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(entry.name, "/srv/a b");
+    assert_eq!(entry.kind, remote::Kind::File);
+    assert_eq!(entry.size, Some(3));
+    assert_eq!(entry.mode, Some(0o100644));
+    assert_eq!(
+        entry.modified,
+        Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000))
+    );
+}
+
+#[tokio::test]
+async fn stats_line_up_with_the_paths() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "stat -c",
+        Exchange::stdout("0 f 81ed 3 1700000000 /srv/a\n1 \n2 d 41ed 4096 1700000000 /srv/c\n"),
+    );
+    let paths = ["/srv/a", "/srv/b", "/srv/c"].map(String::from);
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let entries = remote::stats(
+        &pods,
+        "web-0",
+        "",
+        &paths,
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].as_ref().unwrap().mode, Some(0o100755));
+    assert!(entries[1].is_none());
+    assert_eq!(entries[2].as_ref().unwrap().kind, remote::Kind::Dir);
+    // the size of a file is counted where there is no `stat`
+    let command = &mock.execs()[0].command;
+    assert!(command.contains("wc -c < \"$f\""), "{}", command);
+}
+
+#[tokio::test]
+async fn df_reads_the_posix_output() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "df -Pk",
+        Exchange::stdout("/dev/sda1 1000 250 700 27% /var/lib/my data\n"),
+    );
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let usage = remote::df(
+        &pods,
+        "web-0",
+        "",
+        "/var/lib/my data/x",
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(
+        usage,
+        remote::Usage {
+            filesystem: "/dev/sda1".to_string(),
+            size: 1000 * 1024,
+            used: 250 * 1024,
+            available: 700 * 1024,
+            mount: "/var/lib/my data".to_string(),
+        }
+    );
+}
+
+#[tokio::test]
+async fn du_keeps_the_directories_down_to_the_depth() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "du -k",
+        Exchange::stdout("du\n8\t/srv/a/b\n12\t/srv/a\n4\t/srv/c d\n20\t/srv\n"),
+    );
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let usage = remote::du(
+        &pods,
+        "web-0",
+        "",
+        "/srv/",
+        1,
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+
+    let usage: Vec<_> = usage.iter().map(|u| (u.path.as_str(), u.bytes)).collect();
+    assert_eq!(
+        usage,
+        [
+            ("/srv", 20 * 1024),
+            ("/srv/a", 12 * 1024),
+            ("/srv/c d", 4 * 1024)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn du_without_du_adds_up_the_files() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "du -k",
+        Exchange::stdout("find\n  100 /srv/a/b/f\n   10 /srv/a/g\n    1 /srv/h\n  111 total\n"),
+    );
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let usage = remote::du(
+        &pods,
+        "web-0",
+        "",
+        "/srv",
+        1,
+        std::time::Duration::from_secs(5),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+
+    let usage: Vec<_> = usage.iter().map(|u| (u.path.as_str(), u.bytes)).collect();
+    assert_eq!(usage, [("/srv", 111), ("/srv/a", 110)]);
+}
+
+#[tokio::test]
+async fn silent_command_stalls_at_the_idle_timeout() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "sleep",
+        Exchange::success().after(std::time::Duration::from_secs(30)),
+    );
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let started = std::time::Instant::now();
+    let err = exec::run(
+        &pods,
+        "web-0",
+        "",
+        "sleep 3600",
+        std::time::Duration::from_secs(60),
+        std::time::Duration::from_millis(200),
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap_err();
+
+    assert!(matches!(err, Error::Timeout(_)), "{:?}", err);
+    assert!(err.to_string().contains("stalled"), "{}", err);
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[tokio::test]
+async fn stream_forwards_the_output_and_fails_with_the_status() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on("cat", Exchange::stdout("a\nb\n").eager());
+    mock.on("tail", Exchange::failure(1, "tail: gone\n").eager());
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let stream = |command: &'static str| {
+        let pods = pods.clone();
+        async move {
+            let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+            let res = exec::stream(
+                &pods,
+                "web-0",
+                "",
+                command,
+                &mut stdout,
+                &mut stderr,
+                std::time::Duration::from_secs(5),
+                &CancellationToken::new(),
+            )
+            .await;
+            (res, stdout, stderr)
+        }
+    };
+
+    let (res, stdout, _) = stream("exec cat -- /var/log/app.log").await;
+    res.unwrap();
+    assert_eq!(stdout, b"a\nb\n");
+
+    let (res, _, stderr) = stream("exec tail -n 2 -- /var/log/app.log").await;
+    assert_eq!(stderr, b"tail: gone\n");
+    let err = res.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            k8scp::Error::RemoteCommand {
+                exit_code: Some(1),
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn expand_leaves_all_but_tilde_and_variables_quoted() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on("printf", Exchange::stdout("/home/app/cfg $(id)\n"));
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let cancel = CancellationToken::new();
+    let expand = |path: &'static str| {
+        remote::expand(
+            &pods,
+            "web-0",
+            "",
+            path,
+            std::time::Duration::from_secs(5),
+            &cancel,
+        )
+    };
+    assert_eq!(expand("/srv/app").await.unwrap(), "/srv/app");
+    assert!(mock.execs().is_empty());
+    assert_eq!(
+        expand("~/${APP}_cfg $(id)").await.unwrap(),
+        "/home/app/cfg $(id)"
+    );
+
+    let command = &mock.execs()[0].command;
+    assert!(
+        command.contains(r#"set -u; printf '%s\n' ~/"${APP}_cfg \$(id)""#),
+        "{}",
+        command
+    );
+}
+
+#[tokio::test]
+async fn relative_paths_are_below_the_working_dir() {
+    let mock = MockApi::start(["web-0"]).await;
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let timeout = std::time::Duration::from_secs(5);
+    let dir = remote::working_dir(&pods, "web-0", "", timeout)
+        .await
+        .unwrap();
+    assert_eq!(dir.as_deref(), Some("/srv"));
+    let other = remote::working_dir(&pods, "web-0", "sidecar", timeout)
+        .await
+        .unwrap();
+    assert_eq!(other, None);
+
+    assert_eq!(remote::resolve("/srv", "./app/conf"), "/srv/app/conf");
+    assert_eq!(remote::resolve("/srv/", "."), "/srv/");
+    assert_eq!(remote::resolve("/srv", "/etc/app"), "/etc/app");
+}
@@ -0,0 +1,693 @@
+mod common;
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use common::{Exchange, MockApi};
+use k8s_openapi::api::core::v1::Pod;
+use k8scp::{
+    copy_from_pod, copy_split_to_pod, copy_to_pod,
+    progress::{JsonObserver, Progress},
+    text::TextMode,
+    transport::{AgeDecrypt, Base64, Exec, Extract, Id, IdMap, Owners, Tar},
+    verify_from_pod, verify_to_pod, CopyRequest, Error, ExecOptions, Location,
+};
+use kube::api::Api;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// A local file with `data` in a fresh directory
+fn local_file(name: &str, data: &[u8]) -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(name);
+    std::fs::write(&path, data).unwrap();
+    (dir, path)
+}
+
+async fn upload(
+    mock: &MockApi,
+    src: &Path,
+    dst: &str,
+    opts: &ExecOptions<'_>,
+) -> k8scp::Result<String> {
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let progress = Arc::new(Progress::new());
+    let item = progress.item("src", "web-0", dst, 0);
+    copy_to_pod(&pods, "web-0", src, dst, opts, item).await
+}
+
+#[tokio::test]
+async fn upload_sends_the_file_to_cat() {
+    let mock = MockApi::start(["web-0"]).await;
+    let data = b"hello pod\n".repeat(1000);
+    let (_dir, src) = local_file("app.conf", &data);
+
+    let digest = upload(&mock, &src, "/etc/app/app.conf", &ExecOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(digest, sha256(&data));
+    mock.settled().await;
+    let execs = mock.execs();
+    assert_eq!(execs.len(), 1);
+    assert_eq!(
+        execs[0].command,
+        "mkdir -p '/etc/app' && cd '/etc/app' && cat > 'app.conf'"
+    );
+    assert_eq!(execs[0].stdin, data);
+}
+
+/// A named pipe in a fresh directory that `data` is written to once it is
+/// opened
+fn local_pipe(name: &str, data: &[u8]) -> (TempDir, PathBuf) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(name);
+    let made = std::process::Command::new("mkfifo")
+        .arg(&path)
+        .status()
+        .unwrap();
+    assert!(made.success());
+    let (pipe, data) = (path.clone(), data.to_vec());
+    std::thread::spawn(move || {
+        // fails once the reader is gone early
+        let _ = std::fs::write(pipe, data);
+    });
+    (dir, path)
+}
+
+#[tokio::test]
+async fn upload_streams_a_pipe_of_unknown_size() {
+    let mock = MockApi::start(["web-0"]).await;
+    let data = b"row\n".repeat(5000);
+    let (_dir, src) = local_pipe("rows", &data);
+
+    let digest = upload(&mock, &src, "/srv/rows", &ExecOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(digest, sha256(&data));
+    mock.settled().await;
+    assert_eq!(mock.execs()[0].stdin, data);
+
+    // the size goes into the archive up front
+    let (_dir, src) = local_pipe("rows", &data);
+    let opts = ExecOptions {
+        transport: &Exec(Tar),
+        ..ExecOptions::default()
+    };
+    let err = upload(&mock, &src, "/srv/rows", &opts).await.unwrap_err();
+    assert!(matches!(err, Error::Config(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn upload_sets_the_umask_first() {
+    let mock = MockApi::start(["web-0"]).await;
+    let (_dir, src) = local_file("app.conf", b"mode=strict\n");
+    let opts = ExecOptions {
+        umask: Some(0o027),
+        ..ExecOptions::default()
+    };
+
+    upload(&mock, &src, "/etc/app/app.conf", &opts)
+        .await
+        .unwrap();
+
+    mock.settled().await;
+    assert_eq!(
+        mock.execs()[0].command,
+        "umask 027 && mkdir -p '/etc/app' && cd '/etc/app' && cat > 'app.conf'"
+    );
+}
+
+#[tokio::test]
+async fn upload_through_tar_sends_an_archive() {
+    let mock = MockApi::start(["web-0"]).await;
+    let data = vec![7u8; 1500];
+    let (_dir, src) = local_file("blob", &data);
+    let opts = ExecOptions {
+        transport: &Exec(Tar),
+        ..ExecOptions::default()
+    };
+
+    upload(&mock, &src, "/data/blob", &opts).await.unwrap();
+
+    let exec = &mock.execs_of("tar -xof")[0];
+    // a header, the data padded to 3 blocks and the end of the archive
+    assert_eq!(exec.stdin.len(), 512 + 1536 + 1024);
+    assert_eq!(&exec.stdin[..4], b"blob");
+    assert_eq!(&exec.stdin[512..2012], &data[..]);
+}
+
+#[tokio::test]
+async fn upload_through_age_decrypts_in_the_pod() {
+    let mock = MockApi::start(["web-0"]).await;
+    let (_dir, src) = local_file("secrets.age", b"age-encryption.org/v1");
+    let opts = ExecOptions {
+        transport: &Exec(AgeDecrypt::new("/keys/identity.txt")),
+        ..ExecOptions::default()
+    };
+
+    upload(&mock, &src, "/etc/app/secrets", &opts)
+        .await
+        .unwrap();
+
+    let exec = &mock.execs_of("age --decrypt")[0];
+    assert!(
+        exec.command
+            .contains("head -c 21 | age --decrypt -i '/keys/identity.txt' > 'secrets'"),
+        "{}",
+        exec.command
+    );
+    assert_eq!(exec.stdin, b"age-encryption.org/v1");
+}
+
+#[tokio::test]
+async fn upload_through_extract_unpacks_in_the_directory() {
+    let mock = MockApi::start(["web-0"]).await;
+    let (_dir, src) = local_file("bundle.tgz", b"\x1f\x8b");
+    let opts = ExecOptions {
+        transport: &Exec(Extract::default()),
+        ..ExecOptions::default()
+    };
+
+    upload(&mock, &src, "/srv/www/bundle.tgz", &opts)
+        .await
+        .unwrap();
+
+    let exec = &mock.execs_of("tar -xzof")[0];
+    assert_eq!(
+        exec.command,
+        "mkdir -p '/srv/www' && cd '/srv/www' && head -c 2 | tar -xzof -"
+    );
+    assert!(Extract::unpacks("bundle.zip"));
+    assert!(!Extract::unpacks("bundle.gz"));
+}
+
+#[tokio::test]
+async fn extract_restores_the_attributes_asked_for() {
+    let mock = MockApi::start(["web-0"]).await;
+    let (_dir, src) = local_file("rootfs.tar", b"ustar");
+    let opts = ExecOptions {
+        transport: &Exec(Extract::default().xattrs(true).acls(true)),
+        ..ExecOptions::default()
+    };
+
+    upload(&mock, &src, "/srv/rootfs.tar", &opts).await.unwrap();
+
+    let exec = &mock.execs_of("tar ")[0];
+    assert!(
+        exec.command
+            .ends_with("head -c 5 | tar --xattrs --xattrs-include='*' --selinux --acls -xof -"),
+        "{}",
+        exec.command
+    );
+}
+
+#[tokio::test]
+async fn extract_maps_the_owners_of_the_archive() {
+    let mock = MockApi::start(["web-0"]).await;
+    let (_dir, src) = local_file("rootfs.tar.gz", b"\x1f\x8b");
+    let id = |name: &str, id| Id {
+        name: name.to_string(),
+        id,
+    };
+    let owners = Owners {
+        numeric: false,
+        users: vec![IdMap {
+            local: id("me", 1000),
+            remote: id("app", 1001),
+        }],
+        groups: Vec::new(),
+    };
+    let opts = ExecOptions {
+        transport: &Exec(Extract::default().owners(owners)),
+        ..ExecOptions::default()
+    };
+
+    upload(&mock, &src, "/srv/rootfs.tar.gz", &opts)
+        .await
+        .unwrap();
+
+    let exec = &mock.execs_of("tar ")[0];
+    assert!(
+        exec.command
+            .contains("head -c 2 | tar --same-owner --numeric-owner "),
+        "{}",
+        exec.command
+    );
+    assert!(exec.command.contains("-xvzf -"), "{}", exec.command);
+    assert!(exec.command.contains("in 1000) echo 1001;; *) ;; esac"));
+    assert!(exec.command.contains("chown -h"));
+}
+
+#[tokio::test]
+async fn split_upload_sends_checked_parts_and_joins_them() {
+    let mock = MockApi::start(["web-0"]).await;
+    let data: Vec<u8> = (0..25).collect();
+    let (_dir, src) = local_file("blob", &data);
+    // the second part fails once and is sent again
+    mock.on("tar -xof", Exchange::success())
+        .on("tar -xof", Exchange::failure(1, "tar: write error\n"));
+    for (i, part) in data.chunks(10).enumerate() {
+        mock.on(
+            &format!("sha256sum -- '/data/.blob.k8scp-parts/{}'", i),
+            Exchange::stdout(format!("{}  x\n", sha256(part))),
+        );
+    }
+    mock.on(
+        "sha256sum -- '/data/.blob.k8scp-part'",
+        Exchange::stdout(format!("{}  x\n", sha256(&data))),
+    );
+    let opts = ExecOptions {
+        transport: &Exec(Tar),
+        ..ExecOptions::default()
+    };
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let progress = Arc::new(Progress::new());
+    let item = progress.item("src", "web-0", "/data/blob", 25);
+
+    let digest = copy_split_to_pod(&pods, "web-0", &src, "/data/blob", 10, 1, &opts, item)
+        .await
+        .unwrap();
+
+    assert_eq!(digest, sha256(&data));
+    let sent = mock.execs_of("tar -xof");
+    assert_eq!(sent.len(), 4);
+    assert!(sent
+        .iter()
+        .all(|exec| exec.command.contains("cd '/data/.blob.k8scp-parts'")));
+    assert_eq!(
+        mock.execs_of("cat -- * > '/data/.blob.k8scp-part'").len(),
+        1
+    );
+    assert_eq!(
+        mock.execs_of("mv -f -- '/data/.blob.k8scp-part' '/data/blob'")
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn failing_remote_command_reports_stderr_and_exit_code() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "tar -xof",
+        Exchange::failure(2, "tar: blob: Cannot open: Read-only file system\n"),
+    );
+    let (_dir, src) = local_file("blob", b"data");
+    let opts = ExecOptions {
+        transport: &Exec(Tar),
+        ..ExecOptions::default()
+    };
+
+    let err = upload(&mock, &src, "/data/blob", &opts).await.unwrap_err();
+
+    match err {
+        Error::RemoteCommand {
+            stderr, exit_code, ..
+        } => {
+            assert_eq!(exit_code, Some(2));
+            assert!(stderr.contains("Read-only file system"), "{}", stderr);
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[tokio::test]
+async fn download_decodes_base64() {
+    let mock = MockApi::start(["web-0"]).await;
+    let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+    let mut encoded = String::new();
+    for line in data.chunks(57) {
+        encoded.push_str(&base64::encode(line));
+        encoded.push('\n');
+    }
+    mock.on("base64 <", Exchange::stdout(encoded));
+    let dir = TempDir::new().unwrap();
+    let dst = dir.path().join("dump.bin");
+    let opts = ExecOptions {
+        transport: &Exec(Base64),
+        ..ExecOptions::default()
+    };
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let progress = Arc::new(Progress::new());
+    let item = progress.item("/var/dump.bin", "web-0", "dump.bin", 0);
+    copy_from_pod(&pods, "web-0", "/var/dump.bin", &dst, &opts, item.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(std::fs::read(&dst).unwrap(), data);
+    assert_eq!(item.position(), data.len() as u64);
+    assert_eq!(mock.execs()[0].command, "base64 < '/var/dump.bin'");
+}
+
+#[tokio::test]
+async fn text_mode_converts_line_endings_both_ways() {
+    let mock = MockApi::start(["web-0"]).await;
+    let (_dir, src) = local_file("app.yaml", b"a: 1\r\nb: \"\r\"\r\n");
+    let opts = ExecOptions {
+        transport: &Exec(Base64),
+        text_mode: Some(TextMode::CrlfToLf),
+        ..ExecOptions::default()
+    };
+
+    let digest = upload(&mock, &src, "/srv/app.yaml", &opts).await.unwrap();
+
+    let sent = b"a: 1\nb: \"\r\"\n";
+    assert_eq!(digest, sha256(sent));
+    mock.settled().await;
+    // the size of the converted file is told up front
+    let exec = &mock.execs()[0];
+    assert!(exec.command.contains("head -c 17 |"), "{}", exec.command);
+    assert_eq!(exec.stdin, format!("{}\n", base64::encode(sent)).as_bytes());
+
+    mock.on("cat --", Exchange::stdout("a: 1\nb: 2\r\n"));
+    let dir = TempDir::new().unwrap();
+    let dst = dir.path().join("app.yaml");
+    let opts = ExecOptions {
+        text_mode: Some(TextMode::LfToCrlf),
+        ..ExecOptions::default()
+    };
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let progress = Arc::new(Progress::new());
+    let item = progress.item("/etc/app.yaml", "web-0", "app.yaml", 0);
+    copy_from_pod(&pods, "web-0", "/etc/app.yaml", &dst, &opts, item)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&dst).unwrap(), b"a: 1\r\nb: 2\r\n");
+}
+
+#[tokio::test]
+async fn failed_download_removes_the_local_file() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "cat --",
+        Exchange::failure(1, "cat: /nope: No such file or directory\n"),
+    );
+    let dir = TempDir::new().unwrap();
+    let dst = dir.path().join("nope");
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let progress = Arc::new(Progress::new());
+    let item = progress.item("/nope", "web-0", "nope", 0);
+    let err = copy_from_pod(&pods, "web-0", "/nope", &dst, &ExecOptions::default(), item)
+        .await
+        .unwrap_err();
+
+    assert!(
+        matches!(
+            err,
+            Error::RemoteCommand {
+                exit_code: Some(1),
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+    assert!(!dst.exists());
+}
+
+#[tokio::test]
+async fn forbidden_exec_is_an_auth_error() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.forbid_exec();
+    let (_dir, src) = local_file("a", b"a");
+
+    let err = upload(&mock, &src, "/tmp/a", &ExecOptions::default())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, Error::Auth(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn missing_pod_names_the_closest_ones() {
+    let mock = MockApi::start(["web-0", "web-1", "db-0"]).await;
+    let (_dir, src) = local_file("a", b"a");
+
+    let err = CopyRequest::builder()
+        .client(mock.client())
+        .source(Location::local(&src))
+        .destination(Location::pod("web-7", "/tmp"))
+        .build()
+        .unwrap()
+        .start()
+        .wait()
+        .await
+        .unwrap_err();
+
+    match err {
+        Error::PodResolution(message) => assert!(message.contains("web-0"), "{}", message),
+        err => panic!("unexpected error {:?}", err),
+    }
+    assert!(mock.execs().is_empty());
+}
+
+#[tokio::test]
+async fn failed_copy_is_retried() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on(
+        "cat --",
+        Exchange::failure(1, "cat: read error: I/O error\n"),
+    )
+    .on("cat --", Exchange::stdout("second try"));
+    let dir = TempDir::new().unwrap();
+    let dst = dir.path().join("log");
+
+    let summary = CopyRequest::builder()
+        .client(mock.client())
+        .source(Location::pod("web-0", "/var/log/app.log"))
+        .destination(Location::local(&dst))
+        .retries(1)
+        .build()
+        .unwrap()
+        .start()
+        .wait()
+        .await
+        .unwrap();
+
+    assert_eq!(std::fs::read(&dst).unwrap(), b"second try");
+    assert_eq!(summary.files[0].bytes, 10);
+    assert_eq!(mock.execs_of("cat --").len(), 2);
+    assert_eq!(summary.stats.retries(), 1);
+    assert_eq!(summary.stats.to_json()["retries"], 1);
+}
+
+#[tokio::test]
+async fn failed_upload_is_sent_again_after_the_backoff() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on("tar -xof", Exchange::failure(1, "tar: write error\n"));
+    let (_dir, src) = local_file("app.conf", b"hello pod\n");
+    let opts = ExecOptions {
+        transport: &Exec(Tar),
+        retries: 2,
+        retry_backoff: std::time::Duration::from_millis(50),
+        ..ExecOptions::default()
+    };
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let progress = Arc::new(Progress::new());
+    let item = progress.item("src", "web-0", "/etc/app/app.conf", 10);
+    let started = std::time::Instant::now();
+
+    let digest = copy_to_pod(&pods, "web-0", &src, "/etc/app/app.conf", &opts, item)
+        .await
+        .unwrap();
+
+    assert_eq!(digest, sha256(b"hello pod\n"));
+    assert!(started.elapsed() >= std::time::Duration::from_millis(50));
+    assert_eq!(mock.execs_of("tar -xof").len(), 2);
+    assert_eq!(progress.stats().retries(), 1);
+}
+
+#[tokio::test]
+async fn verification_compares_with_sha256sum() {
+    let mock = MockApi::start(["web-0"]).await;
+    let data = b"release 42";
+    // the check is repeated a few times before it is considered different
+    for _ in 0..5 {
+        mock.on(
+            "sha256sum",
+            Exchange::stdout(format!("{}  x\n", sha256(b"other"))),
+        );
+    }
+    let (_dir, src) = local_file("VERSION", data);
+
+    let res = CopyRequest::builder()
+        .client(mock.client())
+        .source(Location::local(&src))
+        .destination(Location::pod("web-0", "/srv"))
+        .transport(Arc::new(Exec(Tar)))
+        .verify(true)
+        .build()
+        .unwrap()
+        .start()
+        .wait()
+        .await;
+
+    match res {
+        Err(Error::Verification { local, remote, .. }) => {
+            assert_eq!(local, sha256(data));
+            assert_eq!(remote, sha256(b"other"));
+        }
+        res => panic!("unexpected result {:?}", res),
+    }
+    // what didn't check out is removed again
+    assert_eq!(mock.execs_of("rm -f -- '/srv/VERSION'").len(), 1);
+}
+
+#[tokio::test]
+async fn verified_copy_reports_the_digest() {
+    let mock = MockApi::start(["web-0", "web-1"]).await;
+    let data = b"release 42";
+    for _ in 0..2 {
+        mock.on(
+            "sha256sum",
+            Exchange::stdout(format!("{}  x\n", sha256(data))),
+        );
+    }
+    let (_dir, src) = local_file("VERSION", data);
+
+    let summary = CopyRequest::builder()
+        .client(mock.client())
+        .source(Location::local(&src))
+        .destination(Location::pods(["web-0", "web-1"], "/srv"))
+        .transport(Arc::new(Exec(Tar)))
+        .verify(true)
+        .build()
+        .unwrap()
+        .start()
+        .wait()
+        .await
+        .unwrap();
+
+    assert_eq!(summary.files.len(), 2);
+    for file in &summary.files {
+        assert_eq!(file.dst, "/srv/VERSION");
+        assert_eq!(file.sha256.as_deref(), Some(&*sha256(data)));
+    }
+    assert_eq!(mock.execs_of("sha256sum -- '/srv/VERSION'").len(), 2);
+    assert_eq!(summary.stats.verified(), 2);
+    assert_eq!(summary.stats.mismatched(), 0);
+}
+
+#[tokio::test]
+async fn download_is_verified_against_the_pod() {
+    let mock = MockApi::start(["web-0"]).await;
+    mock.on("cat --", Exchange::stdout("a: 1\n"));
+    mock.on(
+        "sha256sum --",
+        Exchange::stdout(format!("{}  /etc/app.yaml\n", sha256(b"a: 2\n"))),
+    );
+    let dir = TempDir::new().unwrap();
+    let dst = dir.path().join("app.yaml");
+    let opts = ExecOptions::default();
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let progress = Arc::new(Progress::new());
+    let item = progress.item("/etc/app.yaml", "web-0", "app.yaml", 0);
+    let digest = copy_from_pod(&pods, "web-0", "/etc/app.yaml", &dst, &opts, item)
+        .await
+        .unwrap();
+    assert_eq!(digest, sha256(b"a: 1\n"));
+
+    let err = verify_from_pod(&pods, "web-0", "/etc/app.yaml", &digest, &opts)
+        .await
+        .unwrap_err();
+    match err {
+        Error::Verification { local, remote, .. } => {
+            assert_eq!(local, sha256(b"a: 1\n"));
+            assert_eq!(remote, sha256(b"a: 2\n"));
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+    assert!(mock
+        .execs()
+        .iter()
+        .any(|exec| exec.command == "sha256sum -- '/etc/app.yaml'"));
+}
+
+#[tokio::test]
+async fn uploads_right_into_system_dirs_are_refused() {
+    let mock = MockApi::start(["web-0"]).await;
+    let (_dir, src) = local_file("passwd", b"root::0:0::/:/bin/sh\n");
+
+    let err = upload(&mock, &src, "/etc/passwd", &ExecOptions::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Config(_)), "{:?}", err);
+    assert!(mock.execs().is_empty());
+
+    let denied = vec!["/srv".to_string()];
+    let opts = ExecOptions {
+        system_paths: Some(&denied),
+        ..ExecOptions::default()
+    };
+    assert!(upload(&mock, &src, "/srv/passwd", &opts).await.is_err());
+    upload(&mock, &src, "/etc/passwd", &opts).await.unwrap();
+    let opts = ExecOptions {
+        allow_system_paths: true,
+        ..ExecOptions::default()
+    };
+    upload(&mock, &src, "/etc/passwd", &opts).await.unwrap();
+}
+
+/// What a [`JsonObserver`] wrote, shared with the test
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn verified_uploads_show_up_as_json_events() {
+    let mock = MockApi::start(["web-0"]).await;
+    let data = b"a: 1\n";
+    let (_dir, src) = local_file("app.yaml", data);
+    mock.on(
+        "sha256sum --",
+        Exchange::stdout(format!("{}  /srv/app.yaml\n", sha256(data))),
+    );
+    let out = Captured::default();
+    let opts = ExecOptions::default();
+
+    let pods: Api<Pod> = Api::default_namespaced(mock.client());
+    let progress = Arc::new(Progress::new().observe(JsonObserver::writer(out.clone())));
+    let item = progress.item("app.yaml", "web-0", "/srv/app.yaml", data.len() as u64);
+    let digest = copy_to_pod(&pods, "web-0", &src, "/srv/app.yaml", &opts, item.clone())
+        .await
+        .unwrap();
+    verify_to_pod(&pods, "web-0", "/srv/app.yaml", &digest, &opts)
+        .await
+        .unwrap();
+    item.verified(&digest);
+
+    let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    let events: Vec<serde_json::Value> = out
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let verified = events
+        .iter()
+        .find(|event| event["event"] == "verified")
+        .expect("no verified event");
+    assert_eq!(verified["pod"], "web-0");
+    assert_eq!(verified["dst"], "/srv/app.yaml");
+    assert_eq!(verified["sha256"], sha256(data));
+}